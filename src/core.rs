@@ -1,54 +1,176 @@
 //! Core trait and structs for dealing with docufort format.
 use std::fmt::Debug;
 
-use crate::{*, ecc::calc_ecc_data_len, recovery::BlockReadSummary};
+use crate::{*, ecc::{calc_ecc_data_len, calculate_ecc_chunk}, recovery::{BlockReadSummary, RecoveryPolicy}, read::hash_range, retry_writer::TailState};
 
 
 
-#[derive(Copy,Debug,Clone,PartialEq,Eq,PartialOrd,Ord)]
-pub struct ComponentHeader([u8;HEADER_LEN],u64);
+/// Decodes the header data field (the content length).
+///
+/// All on disk integers are fixed by the file format and never depend on the host:
+/// - The header data field is **little** endian, use [read_u32]/[write_u32]. It has been since `V1`, so changing it would strand existing files.
+/// - Header timestamps are **big** endian, see [BlockInputs::current_timestamp].
+/// - The original length prefixed to compressed content is **big** endian, use [read_comp_len]/[write_comp_len].
+/// - The optional content type field is **big** endian.
+/// - The optional [BatchMarker] fields are **big** endian.
+#[inline(always)]
+pub fn read_u32(bytes:[u8;4])->u32{
+    u32::from_le_bytes(bytes)
+}
+/// Encodes the header data field (the content length), see [read_u32].
+#[inline(always)]
+pub fn write_u32(val:u32)->[u8;4]{
+    val.to_le_bytes()
+}
+/// Decodes the original (uncompressed) length stored in front of compressed content, see [read_u32].
+#[inline(always)]
+pub fn read_comp_len(bytes:[u8;4])->u32{
+    u32::from_be_bytes(bytes)
+}
+/// Encodes the original (uncompressed) length stored in front of compressed content, see [read_u32].
+#[inline(always)]
+pub fn write_comp_len(val:u32)->[u8;4]{
+    val.to_be_bytes()
+}
+
+#[derive(Copy,Debug,Clone)]
+pub struct ComponentHeader([u8;HEADER_LEN],u64,[u8;ECC_LEN]);
+
+//Compared by header bytes and position only, so a header read with a damaged ECC equals its corrected copy.
+impl PartialEq for ComponentHeader {
+    fn eq(&self, other: &Self) -> bool {
+        (self.0,self.1) == (other.0,other.1)
+    }
+}
+impl Eq for ComponentHeader {}
+impl PartialOrd for ComponentHeader {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for ComponentHeader {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.0,self.1).cmp(&(other.0,other.1))
+    }
+}
+
+fn header_ecc(header:&[u8;HEADER_LEN])->[u8;ECC_LEN]{
+    let mut ecc = [0u8;ECC_LEN];
+    calculate_ecc_chunk(header, &mut &mut ecc[..]).expect("ECC_LEN bytes fit");
+    ecc
+}
 
 impl ComponentHeader {
+    ///The ECC is computed from `slice`.
     pub fn new(slice:&[u8],start_offset:u64) -> Self {
         assert_eq!(slice.len(),HEADER_LEN);
-        Self(slice.try_into().unwrap(),start_offset)
+        let header = slice.try_into().unwrap();
+        Self(header,start_offset,header_ecc(&header))
+    }
+    ///`slice` is the header followed by its ECC as read from the file, which is kept as is (see [verify_self](Self::verify_self)).
+    pub fn new_with_ecc(slice:&[u8],start_offset:u64) -> Self {
+        assert_eq!(slice.len(),HEADER_LEN+ECC_LEN);
+        Self(slice[..HEADER_LEN].try_into().unwrap(),start_offset,slice[HEADER_LEN..].try_into().unwrap())
     }
     pub fn new_from_parts(tag:u8,time_stamp:[u8;8],content_len:Option<u32>) -> Self {
         let mut arr = [0u8;HEADER_LEN];
         arr[0] = tag;
         arr[1..9].copy_from_slice(&time_stamp);
         if let Some(data) = content_len {
-            arr[9..13].copy_from_slice(&data.to_le_bytes());
+            arr[9..13].copy_from_slice(&write_u32(data));
         }
-        Self(arr,0)
+        Self(arr,0,header_ecc(&arr))
+    }
+    ///The ECC that followed this header in the file, or the computed ECC if it was not read from a file.
+    pub fn ecc_bytes(&self)->&[u8;ECC_LEN]{
+        &self.2
+    }
+    ///Recomputes the ECC of the header bytes and compares it to [ecc_bytes](Self::ecc_bytes).
+    ///False if either was damaged on disk (and read without correction), or the header was changed through [as_mut_slice](Self::as_mut_slice).
+    pub fn verify_self(&self)->bool{
+        header_ecc(&self.0) == self.2
     }
     ///This is a bit like a transmute
     ///We interpret the header regardless of tag type as carrying content info
     ///The header doesn't carry the uncompressed info, so that must be added later. Some/None used as boolean
     pub fn as_content(&self)->HeaderAsContent{
-        let data_len = u32::from_le_bytes(self.data());
+        let data_len = read_u32(self.data());
         let tag = self.tag();
         let has_ecc = tag.has_ecc();
         let compressed = tag.is_comp();
+        let has_type = self.has_content_type();
+        let has_level = self.has_compression_level();
+        let has_hash = self.has_component_hash();
         let end_pos = self.1 + (HEADER_LEN + ECC_LEN) as u64;
-        let data_start = if has_ecc {calc_ecc_data_len(data_len as usize) as u64+end_pos}else{end_pos};
-        HeaderAsContent{ data_len, data_start, ecc:has_ecc, compressed}
+        let end_pos = if has_type {end_pos + (CONTENT_TYPE_LEN + ECC_LEN) as u64}else{end_pos};
+        let end_pos = if has_level {end_pos + (COMP_LEVEL_LEN + ECC_LEN) as u64}else{end_pos};
+        let end_pos = if has_hash {end_pos + HASH_AND_ECC_LEN as u64}else{end_pos};
+        let end_pos = if self.has_batch_marker() {end_pos + (BATCH_MARKER_LEN + ECC_LEN) as u64}else{end_pos};
+        let trailing_ecc = has_ecc && self.has_trailing_ecc();
+        let data_start = if has_ecc && !trailing_ecc {calc_ecc_data_len(data_len as usize) as u64+end_pos}else{end_pos};
+        HeaderAsContent{ data_len, data_start, ecc:has_ecc, compressed, has_type, has_level, has_hash, trailing_ecc, seekable:self.is_seekable()}
     }
     pub fn tag(&self)->HeaderTag{
         self.0[0].into()
     }
+    ///True if this is a BlockStart header with the [CHAINED] flag set.
+    pub fn is_chained(&self)->bool{
+        self.0[0] & CHAINED == CHAINED && self.0[0] & CON_TAG == 0
+    }
+    ///True if this is a Content header with the [HAS_TYPE] flag set.
+    pub fn has_content_type(&self)->bool{
+        self.0[0] & HAS_TYPE == HAS_TYPE && self.0[0] & END_TAG == CON_TAG
+    }
+    ///True if this is a Content header with the [HAS_LEVEL] flag set.
+    pub fn has_compression_level(&self)->bool{
+        self.0[0] & HAS_LEVEL == HAS_LEVEL && self.0[0] & END_TAG == CON_TAG
+    }
+    ///True if this is a Content header with the [HAS_HASH] flag set.
+    pub fn has_component_hash(&self)->bool{
+        self.0[0] & HAS_HASH == HAS_HASH && self.0[0] & END_TAG == CON_TAG
+    }
+    ///True if this is a compressed Content header with the [SEEKABLE] flag set.
+    pub fn is_seekable(&self)->bool{
+        self.0[0] & (SEEKABLE|IS_COMP) == SEEKABLE|IS_COMP && self.0[0] & END_TAG == CON_TAG
+    }
+    ///True if this is a BlockStart header with the [TRAILING_ECC] flag set.
+    pub fn has_trailing_ecc(&self)->bool{
+        self.0[0] & TRAILING_ECC == TRAILING_ECC && self.0[0] & CON_TAG == 0
+    }
+    ///True if this is a BlockStart header with the [COMMITTED] flag set.
+    pub fn is_committed(&self)->bool{
+        self.0[0] & COMMITTED == COMMITTED && self.0[0] & CON_TAG == 0
+    }
+    ///True if this is a BlockEnd header with the [LOGICAL_HASH] flag set.
+    pub fn is_logical_hash(&self)->bool{
+        self.0[0] & LOGICAL_HASH == LOGICAL_HASH && self.0[0] & END_TAG == END_TAG
+    }
+    ///True if this is a BlockEnd header with the [OFFSET_BOUND] flag set.
+    pub fn is_offset_bound(&self)->bool{
+        self.0[0] & OFFSET_BOUND == OFFSET_BOUND && self.0[0] & END_TAG == END_TAG
+    }
+    ///True if this is a BlockStart header with the [HAS_BATCH] flag set.
+    pub fn has_batch_marker(&self)->bool{
+        self.0[0] & HAS_BATCH == HAS_BATCH && self.0[0] & CON_TAG == 0
+    }
     pub fn start_pos(&self)->u64{
         self.1
     }
     pub fn time_stamp(&self)->[u8;8]{
         self.0[1..9].try_into().unwrap()
     }
+    ///The timestamp as a SystemTime, assuming it was written as microseconds since the Unix epoch (see [microseconds_since_epoch](crate::util::microseconds_since_epoch)).
+    ///Timestamps written with another convention come back as nonsense. Writers convert with [systemtime_to_micros](crate::util::systemtime_to_micros).
+    pub fn timestamp_systemtime(&self)->Option<std::time::SystemTime>{
+        crate::util::micros_to_systemtime(u64::from_be_bytes(self.time_stamp()))
+    }
     pub fn data(&self)->[u8;4]{
         self.0[9..13].try_into().unwrap()
     }
     pub fn as_slice(&self)->&[u8]{
         &self.0[..]
     }
+    ///Does not update [ecc_bytes](Self::ecc_bytes).
     pub fn as_mut_slice(&mut self)->&mut [u8]{
         &mut self.0[..]
     }
@@ -87,14 +209,178 @@ pub struct HeaderAsContent {
     pub data_len: u32,
     pub data_start:u64,
     pub ecc: bool,
-    pub compressed: bool
+    pub compressed: bool,
+    ///A content type field (plus ECC) sits between the header and the content.
+    pub has_type: bool,
+    ///A compression level field (plus ECC) sits between the header (or content type) and the content.
+    pub has_level: bool,
+    ///A component hash field (plus ECC) sits between the header (or content type, or compression level) and the content.
+    pub has_hash: bool,
+    ///Each ECC chunk follows its data chunk, so `data_start` is the first data byte and the data is not contiguous.
+    pub trailing_ecc: bool,
+    ///The compressed content is split into frames behind a frame table, see [SEEKABLE].
+    pub seekable: bool
+}
+impl HeaderAsContent {
+    ///The position just past the content (data and ECC, in either layout).
+    pub fn end(&self)->u64{
+        let trailing = if self.trailing_ecc {calc_ecc_data_len(self.data_len as usize) as u64}else{0};
+        self.data_start + self.data_len as u64 + trailing
+    }
+}
+/// Converts between absolute file offsets and offsets relative to a block start (right after its magic number).
+#[derive(Copy,Debug,Clone,PartialEq,Eq,PartialOrd,Ord,Hash)]
+pub struct BlockCoords{
+    pub block_start:u64
+}
+impl BlockCoords {
+    pub fn new(block_start:u64)->Self{
+        Self { block_start }
+    }
+    pub fn to_absolute(&self,rel:u64)->u64{
+        self.block_start + rel
+    }
+    ///None if `abs` is before the block start.
+    pub fn to_relative(&self,abs:u64)->Option<u64>{
+        abs.checked_sub(self.block_start)
+    }
+    ///Where the magic number (+ ECC) of this block starts, which is where to truncate to drop the whole block.
+    pub fn magic_number_start(&self)->u64{
+        self.block_start - MN_ECC_LEN as u64
+    }
+    ///The first byte after the BlockStart header (+ ECC), where the block hash starts.
+    pub fn body_start(&self)->u64{
+        self.to_absolute((HEADER_LEN + ECC_LEN) as u64)
+    }
+}
+/// Tags a block as one step of a logical write spanning many blocks.
+///
+/// Recovery reports the marker of the last complete block, so a crashed batch can be resumed at `index + 1`.
+#[derive(Copy,Debug,Clone,PartialEq,Eq,PartialOrd,Ord,Hash,Default)]
+pub struct BatchMarker{
+    ///Identifies the batch.
+    pub seq:u32,
+    ///Position of this block within the batch.
+    pub index:u32
+}
+impl BatchMarker {
+    pub fn to_bytes(&self)->[u8;BATCH_MARKER_LEN]{
+        let mut bytes = [0u8;BATCH_MARKER_LEN];
+        bytes[..4].copy_from_slice(&self.seq.to_be_bytes());
+        bytes[4..].copy_from_slice(&self.index.to_be_bytes());
+        bytes
+    }
+    pub fn from_bytes(bytes:[u8;BATCH_MARKER_LEN])->Self{
+        let seq = u32::from_be_bytes(bytes[..4].try_into().unwrap());
+        let index = u32::from_be_bytes(bytes[4..].try_into().unwrap());
+        Self{seq,index}
+    }
+}
+/// One content component listed in a content directory, see [write_content_directory](crate::write::write_content_directory).
+#[derive(Copy,Debug,Clone,PartialEq,Eq)]
+pub struct ContentDirEntry{
+    ///Position of the component's Content header, relative to the block start (just after the magic number).
+    pub offset:u64,
+    ///Stored length of the content.
+    pub len:StoredLen,
+    pub content_type:Option<u16>
+}
+impl ContentDirEntry {
+    pub fn to_bytes(&self)->[u8;CONTENT_DIR_ENTRY_LEN]{
+        let mut bytes = [0u8;CONTENT_DIR_ENTRY_LEN];
+        bytes[..8].copy_from_slice(&self.offset.to_be_bytes());
+        bytes[8..12].copy_from_slice(&self.len.0.to_be_bytes());
+        if let Some(t) = self.content_type {
+            bytes[12] = 1;
+            bytes[13..].copy_from_slice(&t.to_be_bytes());
+        }
+        bytes
+    }
+    pub fn from_bytes(bytes:[u8;CONTENT_DIR_ENTRY_LEN])->Self{
+        let offset = u64::from_be_bytes(bytes[..8].try_into().unwrap());
+        let len = StoredLen(u32::from_be_bytes(bytes[8..12].try_into().unwrap()));
+        let content_type = (bytes[12] != 0).then(||u16::from_be_bytes(bytes[13..].try_into().unwrap()));
+        Self{offset,len,content_type}
+    }
+    ///None if `bytes` is not a whole number of entries.
+    pub fn parse_directory(bytes:&[u8])->Option<Vec<Self>>{
+        if !bytes.len().is_multiple_of(CONTENT_DIR_ENTRY_LEN) {return None}
+        Some(bytes.chunks_exact(CONTENT_DIR_ENTRY_LEN).map(|c|Self::from_bytes(c.try_into().unwrap())).collect())
+    }
+}
+/// A reference to data stored outside the file (e.g. in an object store), the content of a thin block.
+///
+/// Stored as offset (u64) + len (u64) + hash + location (the rest, UTF-8), all big endian.
+/// The reference itself is protected like any content, the data it points to is not checked by this crate.
+/// See [write_thin_block](crate::write::write_thin_block) and [read_thin_block](crate::read::read_thin_block).
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct ExternalRef{
+    ///Where the data lives, e.g. a URL or object key.
+    pub location:String,
+    ///Offset of the data within `location`.
+    pub offset:u64,
+    ///Length of the data.
+    pub len:u64,
+    ///The expected hash of the data, for the application to check when it fetches it.
+    pub hash:[u8;HASH_LEN]
+}
+impl ExternalRef {
+    pub fn to_bytes(&self)->Vec<u8>{
+        let mut bytes = Vec::with_capacity(16 + HASH_LEN + self.location.len());
+        bytes.extend_from_slice(&self.offset.to_be_bytes());
+        bytes.extend_from_slice(&self.len.to_be_bytes());
+        bytes.extend_from_slice(&self.hash);
+        bytes.extend_from_slice(self.location.as_bytes());
+        bytes
+    }
+    ///None if `bytes` is too short or the location is not UTF-8.
+    pub fn from_bytes(bytes:&[u8])->Option<Self>{
+        if bytes.len() < 16 + HASH_LEN {return None}
+        let offset = u64::from_be_bytes(bytes[..8].try_into().unwrap());
+        let len = u64::from_be_bytes(bytes[8..16].try_into().unwrap());
+        let hash = bytes[16..16+HASH_LEN].try_into().unwrap();
+        let location = String::from_utf8(bytes[16+HASH_LEN..].to_vec()).ok()?;
+        Some(Self{location,offset,len,hash})
+    }
 }
+/// The number of content bytes as they sit on disk (compressed, if the content is compressed).
+///
+/// Kept distinct from [OriginalLen] so one can not be passed where the other is expected:
+/// ```compile_fail
+/// use docufort::core::{StoredLen, OriginalLen};
+/// fn decompressed_size(len:OriginalLen)->u32{len.0}
+/// decompressed_size(StoredLen(10));
+/// ```
+#[derive(Copy,Debug,Clone,PartialEq,Eq,PartialOrd,Ord,Hash)]
+pub struct StoredLen(pub u32);
+/// The length of compressed content once decompressed.
+#[derive(Copy,Debug,Clone,PartialEq,Eq,PartialOrd,Ord,Hash)]
+pub struct OriginalLen(pub u32);
+
 #[derive(Copy,Debug,Clone,PartialEq,Eq,PartialOrd,Ord)]
 pub struct Content {
-    pub data_len: u32,
+    pub data_len: StoredLen,
     pub data_start:u64,
     pub ecc: bool,
-    pub compressed: Option<u32>
+    ///The original length, if the content is stored compressed.
+    pub compressed: Option<OriginalLen>,
+    ///The user defined type of this content, if it was written with one.
+    pub content_type: Option<u16>,
+    ///The compression level byte, if one was stored. See [Content::compression_level].
+    pub comp_level: Option<u8>,
+    ///The hash of just this component's stored content, if it was written with one. See [verify_component](crate::read::verify_component).
+    pub component_hash: Option<[u8;HASH_LEN]>,
+    ///The data is stored in `DATA_SIZE` chunks each followed by its ECC, see [TRAILING_ECC].
+    pub trailing_ecc: bool,
+    ///The compressed content is split into frames behind a frame table, see [SEEKABLE] and [SeekableContentReader](crate::read::SeekableContentReader).
+    pub seekable: bool
+}
+impl Content {
+    ///The level this content was compressed at, as given by [BlockInputs::comp_level_byte] when written.
+    ///None for uncompressed content, and for content written before levels were stored.
+    pub fn compression_level(&self)->Option<u8>{
+        self.comp_level
+    }
 }
 /// A structure representing the end of a block in the data storage.
 #[derive(Copy,Debug,Clone,PartialEq,Eq,PartialOrd,Ord)]
@@ -153,10 +439,31 @@ pub enum BlockState{
     ProbablyNotStartHeader{start_from:u64},
     ///Data Corruption, ECC cannot recover original data.
     ///This hopefully never happens, as 'recovery' from here is complicated and not dealt with in this lib.
-    DataCorruption{component_start:u64,is_b_block:bool,component_tag:ComponentTag}
+    DataCorruption{component_start:u64,is_b_block:bool,component_tag:ComponentTag},
+    ///A header passed ECC, but its content length runs past the end of the file *and* the file still ends in a valid BlockEnd.
+    ///A torn write would simply end inside the content, so this is a corrupted length field and not a truncation.
+    ///component_start is the BlockStart header for an A block, or the Content header within a B block.
+    CorruptContentLength{component_start:u64,is_b_block:bool},
+    ///A BlockEnd directly after the magic number, with no BlockStart before it (e.g. left over from a partial rewrite).
+    ///There is no block to recover here. Look for the next magic number from `skip_to` (just past its hash), see [find_next_block_start](crate::recovery::find_next_block_start).
+    StrayBlockEnd{block_start:u64,skip_to:u64}
 }
 
 impl BlockState {
+    ///The variant name, for logs.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BlockState::Closed(_) => "Closed",
+            BlockState::InvalidBlockStructure { .. } => "InvalidBlockStructure",
+            BlockState::OpenABlock { .. } => "OpenABlock",
+            BlockState::OpenBBlock { .. } => "OpenBBlock",
+            BlockState::IncompleteStartHeader { .. } => "IncompleteStartHeader",
+            BlockState::ProbablyNotStartHeader { .. } => "ProbablyNotStartHeader",
+            BlockState::DataCorruption { .. } => "DataCorruption",
+            BlockState::CorruptContentLength { .. } => "CorruptContentLength",
+            BlockState::StrayBlockEnd { .. } => "StrayBlockEnd",
+        }
+    }
     pub fn is_closed(&self) -> bool {
         matches!(self, BlockState::Closed(_))
     }
@@ -168,6 +475,48 @@ impl BlockState {
     pub fn is_open_b(&self) -> bool {
         matches!(self, BlockState::OpenBBlock { .. })
     }
+    ///The offset [recover_tail_with_policy](crate::recovery::recover_tail_with_policy) would truncate the file to for this state under `policy`, without touching the file.
+    ///
+    ///`block_start` is where this block was read from (the position [try_read_block](crate::recovery::try_read_block) was called at).
+    ///An open B block is truncated after its last good component (a BlockEnd is written there next), unless
+    ///[close_open_b_blocks](RecoveryPolicy::close_open_b_blocks) is off, then the whole block goes, like an open A block.
+    ///None for a closed block, and for a [ProbablyNotStartHeader](BlockState::ProbablyNotStartHeader), where recovery keeps looking before it.
+    ///A closed block can still be dropped by a [RecoveryPolicy](crate::recovery::RecoveryPolicy) (e.g. one that is not committed), that is not a property of the block state.
+    pub fn truncation_target(&self, block_start:u64, policy:&RecoveryPolicy) -> Option<u64> {
+        match self {
+            BlockState::Closed(_) |
+            BlockState::ProbablyNotStartHeader { .. } => None,
+            BlockState::OpenBBlock { .. } if !policy.close_open_b_blocks => Some(BlockCoords::new(block_start).magic_number_start()),
+            BlockState::OpenABlock { truncate_at } |
+            BlockState::OpenBBlock { truncate_at, .. } |
+            BlockState::IncompleteStartHeader { truncate_at } => Some(*truncate_at),
+            BlockState::InvalidBlockStructure { end_of_last_good_component, .. } => Some(*end_of_last_good_component),
+            BlockState::DataCorruption { component_start, .. } => Some(*component_start),
+            BlockState::CorruptContentLength { component_start, is_b_block:true } => Some(*component_start),
+            BlockState::CorruptContentLength { is_b_block:false, .. } => Some(BlockCoords::new(block_start).magic_number_start()),
+            BlockState::StrayBlockEnd { block_start, .. } => Some(BlockCoords::new(*block_start).magic_number_start()),
+        }
+    }
+    ///Converts this (tail) block state in to the [TailState] that [perform_file_op](crate::retry_writer::perform_file_op) needs to resume writing.
+    ///
+    ///`block_start` is where this block was read from (the position [try_read_block](crate::recovery::try_read_block) was called at).
+    ///For an open B block the hasher is rebuilt by re-hashing the good components from the file, after the previous block's hash if it is [CHAINED].
+    ///The returned state is only valid once the file is truncated at `truncate_at` (if the state has one).
+    ///Returns None if the tail needs [recover_tail](crate::recovery::recover_tail) first.
+    pub fn to_tail_state<R:std::io::Read + std::io::Seek,B:BlockInputs>(&self,file:&mut R,block_start:u64)->Result<Option<TailState<B>>,ReadWriteError>{
+        match self {
+            BlockState::Closed(_) |
+            BlockState::OpenABlock { .. } |
+            BlockState::IncompleteStartHeader { .. } => Ok(Some(TailState::ClosedBlock)),
+            BlockState::OpenBBlock { truncate_at, .. } => {
+                let content_start = BlockCoords::new(block_start).body_start();
+                let mut hasher = crate::recovery::block_hasher::<_,B>(file, block_start)?;
+                hash_range(file, content_start, *truncate_at, &mut hasher)?;
+                Ok(Some(TailState::OpenBBlock { hasher }))
+            },
+            _ => Ok(None)
+        }
+    }
 }
 
 /// A trait for implementers to choose a hash fn and compression algorithm.
@@ -184,6 +533,17 @@ pub trait BlockInputs:Clone {
     ///This is stored as big endian in headers so direct byte comparison works.
     fn current_timestamp() -> u64;
 
+    /// The byte stored with compressed content so the level can be read back with [Content::compression_level].
+    /// Return None (the default) to not store a level.
+    fn comp_level_byte(_comp_level: &Self::CompLevel) -> Option<u8> {
+        None
+    }
+
+    /// The bytes every compressed frame starts with (e.g. zstd's `[0x28, 0xB5, 0x2F, 0xFD]`), checked before decompressing.
+    /// Content flagged compressed that does not start with them is reported as [ReadWriteError::DataCorruption], rather than decompressing plain data.
+    /// Empty (the default) skips the check.
+    const COMPRESSED_MAGIC: &'static [u8] = &[];
+
     /// Compress data to writer with the given compression level.
     /// Returns the number of bytes written.
     fn compress<W:std::io::Write>(data: &[u8], writer: &mut W, comp_level: &Self::CompLevel) -> std::io::Result<usize>;
@@ -2,7 +2,9 @@
 //! You shouldn't need to use any of these functions directly.
 use crate::DATA_SIZE;
 use crate::ECC_LEN;
-use reed_solomon::{Encoder,Decoder, DecoderError};
+#[cfg(not(feature = "crc_only"))]
+use reed_solomon::{Encoder,Decoder};
+use reed_solomon::DecoderError;
 
 
 
@@ -20,8 +22,29 @@ pub fn ceiling_division(numerator: usize, denominator: usize) -> usize {
 pub fn calc_ecc_data_len(raw_data_len:usize)->usize{
     ceiling_division(raw_data_len, DATA_SIZE)*ECC_LEN
 }
+///Same as [calc_ecc_data_len], for a file written with a different ECC length than this build.
+#[inline(always)]
+pub fn calc_ecc_data_len_for(raw_data_len:usize,ecc_len:usize)->usize{
+    ceiling_division(raw_data_len, 255 - ecc_len)*ecc_len
+}
+///Same as [calculate_ecc_chunk], for a file written with a different ECC length than this build.
+/// data must be less than or equal to `255 - ecc_len`
+#[cfg(not(feature = "crc_only"))]
+pub fn calculate_ecc_chunk_with_len<W: std::io::Write>(data: &[u8],ecc_len:usize,writer:&mut W) -> std::io::Result<()> {
+    writer.write_all(Encoder::new(ecc_len).encode(data).ecc())
+}
+///Same as [apply_ecc], for a file written with a different ECC length than this build.
+#[cfg(not(feature = "crc_only"))]
+pub fn apply_ecc_with_len(ecc_data: &mut[u8],ecc_len:usize) -> Result<usize,DecoderError> {
+    let decoder = Decoder::new(ecc_len);
+    if !decoder.is_corrupted(ecc_data) {return Ok(0)}
+    let (buffer,errors) = decoder.correct_err_count(ecc_data,None)?;
+    ecc_data.copy_from_slice(&buffer[..]);
+    Ok(errors)
+}
 ///Write the ecc value (of ECC_LEN) to the writer for the given data.
 /// data must be less than or equal to DATA_SIZE
+#[cfg(not(feature = "crc_only"))]
 #[inline]
 pub fn calculate_ecc_chunk<W: std::io::Write>(data: &[u8],writer:&mut W) -> std::io::Result<()> {
     //let bytes: &[u8] = data.as_ref();
@@ -30,6 +53,20 @@ pub fn calculate_ecc_chunk<W: std::io::Write>(data: &[u8],writer:&mut W) -> std:
     writer.write_all(ecc_data.ecc())?;
     Ok(())
 }
+///The ECC for a magic number (normally [MN_ECC_LEN](crate::MN_ECC_LEN) - ECC_LEN bytes), computed at runtime.
+///For [MAGIC_NUMBER](crate::MAGIC_NUMBER) this is the precomputed [MN_ECC](crate::MN_ECC).
+pub fn magic_number_ecc(magic:&[u8])->[u8;ECC_LEN]{
+    let mut ecc = [0u8;ECC_LEN];
+    calculate_ecc_chunk(magic, &mut &mut ecc[..]).expect("ECC_LEN bytes fit");
+    ecc
+}
+///Write the big endian CRC32 (of ECC_LEN) to the writer for the given data.
+/// data must be less than or equal to DATA_SIZE
+#[cfg(feature = "crc_only")]
+#[inline]
+pub fn calculate_ecc_chunk<W: std::io::Write>(data: &[u8],writer:&mut W) -> std::io::Result<()> {
+    writer.write_all(&crate::util::crc32(data).to_be_bytes())
+}
 #[cfg(feature = "parallel")]
 pub fn calculate_ecc_chunk_par(data: &[u8]) -> [u8;ECC_LEN] {
     //let bytes: &[u8] = data.as_ref();
@@ -39,6 +76,11 @@ pub fn calculate_ecc_chunk_par(data: &[u8]) -> [u8;ECC_LEN] {
 }
 #[cfg(not(feature = "parallel"))]
 pub fn calculate_ecc_for_chunks<W: std::io::Write>(data: &[u8], writer: &mut W) -> std::io::Result<()> {
+    calculate_ecc_for_chunks_serial(data, writer)
+}
+///Single threaded [calculate_ecc_for_chunks], available with or without the `parallel` feature.
+///Both produce byte identical output, so files are interchangeable between builds.
+pub fn calculate_ecc_for_chunks_serial<W: std::io::Write>(data: &[u8], writer: &mut W) -> std::io::Result<()> {
     data.chunks(DATA_SIZE).try_for_each(|chunk_data| {
         calculate_ecc_chunk(chunk_data, writer)
     })
@@ -60,25 +102,83 @@ pub fn calculate_ecc_for_chunks<W: std::io::Write>(data: &[u8], writer: &mut W)
     Ok(())
 }
 
+///The most symbol errors a single chunk (of DATA_SIZE + ECC_LEN) can have and still be corrected.
+#[cfg(not(feature = "crc_only"))]
+pub const MAX_CORRECTABLE_ERRORS:usize = ECC_LEN/2;
+///The most symbol errors a single chunk (of DATA_SIZE + ECC_LEN) can have and still be corrected.
+#[cfg(feature = "crc_only")]
+pub const MAX_CORRECTABLE_ERRORS:usize = 0;
+
+///Same as [apply_ecc], but also returns how many more errors this chunk could have had and still been corrected.
+///A headroom of 0 means one more bad symbol in this chunk would have been data loss.
+///
+///Returns Ok((errors_corrected, headroom))
+#[inline]
+pub fn apply_ecc_with_headroom(ecc_data: &mut[u8]) -> Result<(usize,usize),DecoderError> {
+    let errors = apply_ecc(ecc_data)?;
+    Ok((errors,MAX_CORRECTABLE_ERRORS.saturating_sub(errors)))
+}
+
+///Corrects a chunk (data followed by its ECC) in place and returns how many errors were corrected.
+///With `crc_only` the trailing CRC32 is only checked, nothing is ever corrected, so this returns Ok(0) or Err.
 #[inline]
 pub fn apply_ecc(ecc_data: &mut[u8]) -> Result<usize,DecoderError> {
-    let decoder = Decoder::new(ECC_LEN);
-    if decoder.is_corrupted(&ecc_data) {
-        let (buffer,errors) = decoder.correct_err_count(&ecc_data,None)?;
-        (&mut ecc_data[..]).copy_from_slice(&buffer[..]);
-        // {
-        //     let (data,ecc) = ecc_data.split_at_mut(buffer.data().len());
-        //     data.copy_from_slice(buffer.data());
-        //     ecc.copy_from_slice(buffer.ecc());
-        // }
-        Ok(errors)
-    }else{
-        Ok(0)
+    ChunkDecoder::new().apply(ecc_data)
+}
+
+///Applies the ECC of many chunks with one decoder, built once and reused for each chunk.
+///The decoder holds no per chunk state, so the corrections are the same as [apply_ecc].
+///With `crc_only` there is no decoder, and this only checks the CRC32.
+pub struct ChunkDecoder{
+    #[cfg(not(feature = "crc_only"))]
+    decoder:Decoder
+}
+impl ChunkDecoder {
+    pub fn new()->Self{
+        Self{
+            #[cfg(not(feature = "crc_only"))]
+            decoder:Decoder::new(ECC_LEN)
+        }
+    }
+    ///See [apply_ecc].
+    #[cfg(feature = "crc_only")]
+    #[inline]
+    pub fn apply(&self,ecc_data: &mut[u8]) -> Result<usize,DecoderError> {
+        let (data,crc) = ecc_data.split_at(ecc_data.len() - ECC_LEN);
+        if crate::util::crc32(data).to_be_bytes() == crc {Ok(0)}else{Err(DecoderError::TooManyErrors)}
+    }
+    ///See [apply_ecc].
+    #[cfg(not(feature = "crc_only"))]
+    #[inline]
+    pub fn apply(&self,ecc_data: &mut[u8]) -> Result<usize,DecoderError> {
+        if self.decoder.is_corrupted(ecc_data) {
+            let (buffer,errors) = self.decoder.correct_err_count(ecc_data,None)?;
+            (&mut ecc_data[..]).copy_from_slice(&buffer[..]);
+            Ok(errors)
+        }else{
+            Ok(0)
+        }
+    }
+    ///See [apply_ecc_with_headroom].
+    #[inline]
+    pub fn apply_with_headroom(&self,ecc_data: &mut[u8]) -> Result<(usize,usize),DecoderError> {
+        let errors = self.apply(ecc_data)?;
+        Ok((errors,MAX_CORRECTABLE_ERRORS.saturating_sub(errors)))
+    }
+}
+impl Default for ChunkDecoder {
+    fn default() -> Self {
+        Self::new()
     }
 }
 ///This assumes the ecc_data is before the msg_data, as the case for the 'content'
 #[cfg(not(feature = "parallel"))]
 pub fn apply_ecc_for_chunks(raw_data: &mut [u8]) -> Result<usize, DecoderError> {
+    apply_ecc_for_chunks_serial(raw_data)
+}
+///Single threaded [apply_ecc_for_chunks], available with or without the `parallel` feature.
+///Both correct the buffer to the same bytes, so files are interchangeable between builds.
+pub fn apply_ecc_for_chunks_serial(raw_data: &mut [u8]) -> Result<usize, DecoderError> {
     let len = raw_data.len();
     let msg_len = calculate_msg_len(len);
     let ecc_len = len - msg_len;
@@ -86,6 +186,7 @@ pub fn apply_ecc_for_chunks(raw_data: &mut [u8]) -> Result<usize, DecoderError>
     assert_eq!((len - msg_len) % ECC_LEN, 0);
     let mut tot_errors = 0;
     let mut chunk_data = [0u8;255];
+    let decoder = ChunkDecoder::new();
     for i in 0..num_chunks {
         let data_start = (i * DATA_SIZE) + ecc_len;
         let data_end = (((i + 1) * DATA_SIZE) + ecc_len).min(len);
@@ -96,10 +197,10 @@ pub fn apply_ecc_for_chunks(raw_data: &mut [u8]) -> Result<usize, DecoderError>
         chunk_data[chunk_data_len..chunk_len].copy_from_slice(&raw_data[ecc_start..ecc_start+ECC_LEN]);
         //dbg!(data_start,data_end,chunk_data_len,ecc_start,chunk_len);
 
-        let errors = apply_ecc(&mut chunk_data[..chunk_len])?;
+        let errors = decoder.apply(&mut chunk_data[..chunk_len])?;
         if errors > 0{
             // split out and copy the chunk and ecc back to the raw_data if there is an error
-            let (chunk, ecc) = chunk_data.split_at(chunk_data_len);
+            let (chunk, ecc) = chunk_data[..chunk_len].split_at(chunk_data_len);
             raw_data[data_start..data_end].copy_from_slice(chunk);
             raw_data[ecc_start..ecc_start+ECC_LEN].copy_from_slice(ecc);
         }
@@ -129,6 +230,7 @@ pub fn apply_ecc_par(ecc_data: &[u8]) -> Result<Result<(),([u8;255],usize,usize)
     }
 }
 ///This assumes the ecc_data is before the msg_data, as the case for the 'content'
+///The ecc and data portions are split into disjoint mutable chunks and corrected in place, so no per chunk buffers are allocated.
 #[cfg(feature = "parallel")]
 pub fn apply_ecc_for_chunks(raw_data: &mut [u8]) -> Result<usize, DecoderError> {
     use rayon::prelude::*;
@@ -136,45 +238,25 @@ pub fn apply_ecc_for_chunks(raw_data: &mut [u8]) -> Result<usize, DecoderError>
     let len = raw_data.len();
     let msg_len = calculate_msg_len(len);
     let ecc_len = len - msg_len;
-    let num_chunks = (len - msg_len) / ECC_LEN;
-    assert_eq!((len - msg_len) % ECC_LEN, 0);
-    let chunk_pairs: Vec<(usize,[u8; 255], usize)> = (0..num_chunks)
-        .into_par_iter()
-        .map(|i| {
-            let mut chunk_data = [0u8; 255];
-            let data_start = (i * DATA_SIZE) + ecc_len;
-            let data_end = (((i + 1) * DATA_SIZE) + ecc_len).min(len);
-            let chunk_data_len = data_end-data_start;
-            let ecc_start = i * ECC_LEN;
+    assert_eq!(ecc_len % ECC_LEN, 0);
+    let (ecc_data, msg_data) = raw_data.split_at_mut(ecc_len);
+    ecc_data
+        .par_chunks_mut(ECC_LEN)
+        .zip(msg_data.par_chunks_mut(DATA_SIZE))
+        .map_init(ChunkDecoder::new, |decoder, (ecc, chunk)| {
+            let mut chunk_data = [0u8;255];
+            let chunk_data_len = chunk.len();
             let chunk_len = chunk_data_len+ECC_LEN;
-            chunk_data[..chunk_data_len].copy_from_slice(&raw_data[data_start..data_end]);
-            chunk_data[chunk_data_len..chunk_len].copy_from_slice(&raw_data[ecc_start..ecc_start+ECC_LEN]);
-            (i,chunk_data, chunk_len)
+            chunk_data[..chunk_data_len].copy_from_slice(chunk);
+            chunk_data[chunk_data_len..chunk_len].copy_from_slice(ecc);
+            let errors = decoder.apply(&mut chunk_data[..chunk_len])?;
+            if errors > 0 {
+                chunk.copy_from_slice(&chunk_data[..chunk_data_len]);
+                ecc.copy_from_slice(&chunk_data[chunk_data_len..chunk_len]);
+            }
+            Ok(errors)
         })
-        .collect();
-
-    let results = chunk_pairs
-        .into_par_iter()
-        .map(|(i,chunk, chunk_len)| (i,apply_ecc_par(&chunk[..chunk_len]))) // Apply ECC correction to each chunk
-        .collect_vec_list();
-
-    let mut tot_errors = 0;
-    for (i,res) in results.into_iter().flatten() {
-        let inner_res = res?;
-        if let Err((chunk, chunk_len,errors)) = inner_res {
-            debug_assert!(errors > 0);
-            let data_start = (i * DATA_SIZE) + ecc_len;
-            let data_end = (((i + 1) * DATA_SIZE) + ecc_len).min(len);
-            let chunk_data_len = data_end-data_start;
-            let ecc_start = i * ECC_LEN;
-            debug_assert_eq!(chunk_data_len+ECC_LEN,chunk_len,"Chunk: {} Data Start: {} Data End: {} ECC Start: {} Chunk Len: {}",i,data_start,data_end,ecc_start,chunk_len);
-            let (chunk, ecc) = &chunk[..chunk_len].split_at(chunk_data_len);
-            raw_data[data_start..data_end].copy_from_slice(chunk);
-            raw_data[ecc_start..ecc_start+ECC_LEN].copy_from_slice(ecc);
-            tot_errors += errors;
-        }
-    }
-    Ok(tot_errors)
+        .try_reduce(|| 0, |a, b| Ok(a + b))
 }
 
 #[inline(always)]
@@ -238,6 +320,28 @@ mod tests {
         assert_eq!(combined, corrupted);
     }
 
+    #[test]
+    fn test_chunk_decoder_matches_apply_ecc() {
+        let decoder = ChunkDecoder::new();
+        for n in 0..40usize {
+            let data: Vec<u8> = (0..DATA_SIZE).map(|i|(i * 7 + n * 13) as u8).collect();
+            let mut chunk = data.clone();
+            calculate_ecc_chunk(&data, &mut chunk).unwrap();
+            //0 up to one past MAX_CORRECTABLE_ERRORS bad symbols, so some chunks are uncorrectable
+            for e in 0..n % (MAX_CORRECTABLE_ERRORS + 2) {
+                chunk[(n * 5 + e * 17) % (DATA_SIZE + ECC_LEN)] ^= 0xA5;
+            }
+            let (mut shared,mut fresh) = (chunk.clone(),chunk);
+            let shared_res = decoder.apply(&mut shared);
+            let fresh_res = apply_ecc(&mut fresh);
+            assert_eq!(shared_res.is_ok(), fresh_res.is_ok(), "chunk {}", n);
+            if let (Ok(a),Ok(b)) = (shared_res,fresh_res) {
+                assert_eq!(a, b, "chunk {}", n);
+            }
+            assert_eq!(shared, fresh, "chunk {}", n);
+        }
+    }
+
     #[test]
     fn test_calculate_ecc_for_chunks() {
         let data: Vec<u8> = vec![128;500]; // Two chunks
@@ -272,4 +376,82 @@ mod tests {
             Err(_) => panic!("DecoderError"),
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_apply_ecc_for_chunks_1mb() {
+        let len = 1024*1024;
+        let mut seed = 0x2545_F491u32;
+        let data: Vec<u8> = (0..len).map(|_|{seed ^= seed << 13; seed ^= seed >> 17; seed ^= seed << 5; seed as u8}).collect();
+        let mut ecc = Cursor::new(Vec::new());
+        calculate_ecc_for_chunks(data.as_slice(),&mut ecc).unwrap();
+        let mut all_data = ecc.into_inner();
+        let ecc_len = all_data.len();
+        all_data.extend_from_slice(data.as_slice());
+        let orig = all_data.clone();
+        //scatter up to ECC_LEN/2 errors in every 7th chunk, in both the ecc and the data portions
+        let mut expected_errors = 0;
+        for chunk in (0..ecc_len/ECC_LEN).step_by(7) {
+            all_data[chunk*ECC_LEN] ^= 0xFF;
+            let data_pos = ecc_len + chunk*DATA_SIZE + (chunk % DATA_SIZE).min(len - chunk*DATA_SIZE - 1);
+            all_data[data_pos] ^= 0x0F;
+            expected_errors += 2;
+        }
+        assert_ne!(orig,all_data);
+        let errors = apply_ecc_for_chunks(&mut all_data).unwrap();
+        assert_eq!(errors,expected_errors);
+        assert_eq!(orig,all_data);
+    }
+    ///Runs with and without the `parallel` feature, so both builds read and write the same bytes.
+    #[cfg(not(feature = "crc_only"))]
+    #[test]
+    fn test_serial_matches_apply_ecc_for_chunks() {
+        let len = 5000;
+        let data: Vec<u8> = (0..len).map(|i|(i * 31 % 256) as u8).collect();
+        let mut ecc = Vec::new();
+        calculate_ecc_for_chunks(data.as_slice(),&mut ecc).unwrap();
+        let mut serial_ecc = Vec::new();
+        calculate_ecc_for_chunks_serial(data.as_slice(),&mut serial_ecc).unwrap();
+        assert_eq!(ecc,serial_ecc);
+        let ecc_len = ecc.len();
+        let mut orig = ecc;
+        orig.extend_from_slice(data.as_slice());
+        let mut corrupted = orig.clone();
+        //first, a middle one, and the short last chunk
+        let num_chunks = ecc_len/ECC_LEN;
+        for chunk in [0,num_chunks/2,num_chunks-1] {
+            corrupted[chunk*ECC_LEN+1] ^= 0xFF;
+            corrupted[ecc_len + chunk*DATA_SIZE + (len - chunk*DATA_SIZE).min(DATA_SIZE) - 1] ^= 0x0F;
+        }
+        let mut serial = corrupted.clone();
+        let mut other = corrupted;
+        let serial_errors = apply_ecc_for_chunks_serial(&mut serial).unwrap();
+        let other_errors = apply_ecc_for_chunks(&mut other).unwrap();
+        assert_eq!(serial_errors,6);
+        assert_eq!(serial_errors,other_errors);
+        assert_eq!(serial,other);
+        assert_eq!(serial,orig);
+    }
+    #[cfg(feature = "crc_only")]
+    #[test]
+    fn test_crc_only_detects_header_corruption() {
+        let header = [7u8;crate::HEADER_LEN];
+        let mut chunk = header.to_vec();
+        calculate_ecc_chunk(&header, &mut chunk).unwrap();
+        assert_eq!(&chunk[crate::HEADER_LEN..],&crate::util::crc32(&header).to_be_bytes());
+        assert_eq!(apply_ecc(&mut chunk).unwrap(),0);
+        chunk[3] ^= 1;
+        let corrupted = chunk.clone();
+        assert!(apply_ecc(&mut chunk).is_err());
+        assert_eq!(chunk,corrupted,"detect only, nothing is changed");
+    }
+    #[test]
+    #[cfg(all(feature = "ecc_len_4", not(feature = "crc_only")))]
+    fn test_default_ecc_len() {
+        //the default features select ecc_len_4, and its magic number ECC must match what we compute
+        assert_eq!(ECC_LEN,4);
+        assert_eq!(crate::FILE_ECC_LEN_BYTE,4);
+        let mut mn_ecc = Vec::new();
+        calculate_ecc_chunk(&crate::MAGIC_NUMBER, &mut mn_ecc).unwrap();
+        assert_eq!(mn_ecc,crate::MN_ECC);
+    }
+}
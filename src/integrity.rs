@@ -5,7 +5,12 @@
 
 use std::io::SeekFrom;
 
-use crate::{core::{Block, BlockInputs, BlockState}, read::{read_magic_number, verify_configs}, recovery::{try_read_block, BlockReadSummary}, ComponentTag, CorruptDataSegment, FileLike, ReadWriteError};
+use std::io::Seek;
+
+use crate::{core::{Block, BlockInputs, BlockState, Content}, read::{read_magic_number, check_configs, ConfigMatch, VersionPolicy, catalog_block_parts, hash_range, DeferredPatches, PositionedFile}, recovery::{try_read_block, find_next_block_start, BlockReadSummary}, write::chained_hasher, ComponentTag, CorruptDataSegment, FileLike, ReadWriteError, EXTERNAL_REF_TYPE, FILE_HEADER_LEN, HASH_LEN, HASH_AND_ECC_LEN, HEADER_LEN, ECC_LEN, MN_ECC_LEN, MAX_ALIGNMENT};
+
+///Length of a BlockEnd header and hash, with their ECC.
+const BLOCK_END_LEN:usize = HEADER_LEN + ECC_LEN + HASH_AND_ECC_LEN;
 
 
 /// The struct returned when we were able to recover the file.
@@ -23,6 +28,10 @@ pub struct IntegrityCheckOk{
     pub data_contents: u64,
     ///Number of bytes of 'Content' in the compressed form (no ECC counted).
     pub data_size_on_disk: u64,
+    ///Bytes of 'Content' stored with ECC (as on disk, no ECC counted).
+    pub protected_content_bytes: u64,
+    ///Bytes of 'Content' stored without ECC, where corruption can only be detected by the block hash.
+    pub unprotected_content_bytes: u64,
     ///Number of Blocks in file
     pub num_blocks:usize,
     ///This is the index up to which we checked
@@ -33,13 +42,85 @@ pub struct IntegrityCheckOk{
     ///they can be corrupted beyond what ECC can do.
     pub corrupted_segments: Vec<CorruptDataSegment>,
     ///Contains the block start position and the time stamp found there
-    pub block_times: Vec<(u64,u64)>
-
+    pub block_times: Vec<(u64,u64)>,
+    ///The block start position of each BlockEnd found without a BlockStart. These are skipped, and the check carries on from the next magic number.
+    pub stray_block_ends: Vec<u64>,
+    ///The block start position of each thin block (see [write_thin_block](crate::write::write_thin_block)).
+    ///Their [ExternalRef](crate::core::ExternalRef) is checked with the block, the external data it points to is not.
+    pub unchecked_external_refs: Vec<u64>,
+    ///Where to pass `resume_from` to [integrity_check_file_resume] to carry on, if the check stopped at its block limit.
+    ///None if the check got to the end of what it could check.
+    pub resume_at: Option<u64>,
+    ///One entry per file, in order, if this is a [merge](Self::merge) of the checks of several files. Empty for a single file.
+    pub merged_files: Vec<MergedCheck>,
+    ///The file's version, if it is newer than this build and was accepted (see [integrity_check_file_with_policy]).
+    ///Only its `V1` blocks were understood, warn the user.
+    pub newer_version: Option<u8>
+}
+/// One file's share of a merged [IntegrityCheckOk].
+///
+/// Its index in [merged_files](IntegrityCheckOk::merged_files) identifies the file. Its entries in the merged lists follow those of the files before it.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MergedCheck{
+    pub file_len_checked:u64,
+    pub num_blocks:usize,
+    ///Number of [corrupted_segments](IntegrityCheckOk::corrupted_segments) from this file.
+    pub corrupted_segments:usize,
+    ///Number of [block_times](IntegrityCheckOk::block_times) from this file.
+    pub block_times:usize,
+    ///Number of [stray_block_ends](IntegrityCheckOk::stray_block_ends) from this file.
+    pub stray_block_ends:usize,
+    ///Number of [unchecked_external_refs](IntegrityCheckOk::unchecked_external_refs) from this file.
+    pub unchecked_external_refs:usize,
+}
+impl IntegrityCheckOk {
+    ///Adds the results of a check resumed at this one's [resume_at](Self::resume_at), so the totals read as one check over both ranges.
+    pub fn append(&mut self, next:IntegrityCheckOk){
+        self.last_block_state = next.last_block_state;
+        self.errors_corrected += next.errors_corrected;
+        self.data_contents += next.data_contents;
+        self.data_size_on_disk += next.data_size_on_disk;
+        self.protected_content_bytes += next.protected_content_bytes;
+        self.unprotected_content_bytes += next.unprotected_content_bytes;
+        self.num_blocks += next.num_blocks;
+        self.file_len_checked = next.file_len_checked;
+        self.corrupted_segments.extend(next.corrupted_segments);
+        self.block_times.extend(next.block_times);
+        self.stray_block_ends.extend(next.stray_block_ends);
+        self.unchecked_external_refs.extend(next.unchecked_external_refs);
+        self.resume_at = next.resume_at;
+        self.newer_version = self.newer_version.max(next.newer_version);
+    }
+    ///Combines the checks of two different files (e.g. the rolled files of one log), `other` being the later file.
+    ///
+    ///Unlike [append](Self::append), `file_len_checked` is summed, and [merged_files](Self::merged_files) records which list entries came from which file.
+    ///The last block state and `resume_at` are the later file's. Merging a merged check keeps its per file entries.
+    pub fn merge(mut self, other:IntegrityCheckOk)->IntegrityCheckOk{
+        let parts = |c:&IntegrityCheckOk|if c.merged_files.is_empty() {
+            vec![MergedCheck {
+                file_len_checked: c.file_len_checked,
+                num_blocks: c.num_blocks,
+                corrupted_segments: c.corrupted_segments.len(),
+                block_times: c.block_times.len(),
+                stray_block_ends: c.stray_block_ends.len(),
+                unchecked_external_refs: c.unchecked_external_refs.len()
+            }]
+        }else{
+            c.merged_files.clone()
+        };
+        let mut merged_files = parts(&self);
+        merged_files.extend(parts(&other));
+        let file_len_checked = self.file_len_checked + other.file_len_checked;
+        self.append(other);
+        self.file_len_checked = file_len_checked;
+        self.merged_files = merged_files;
+        self
+    }
 }
 #[derive(Debug)]
 pub enum IntegrityErr{
     Other(ReadWriteError),
-    ///This only returns if a Component Header (or hash) is corrupted.
+    ///This only returns if a Component Header (or hash, or the magic number in front of a block) is corrupted.
     ///We cannot process the file any farther. We only read Front to Back so the position is all the farther we checked the file.
     ///The file may still be able to succeed at tail recovery if this corruption is earlier than the second to last block.
     ///If found in the last block, then a tail recovery would truncate this block.
@@ -95,6 +176,10 @@ impl std::error::Error for IntegrityErr {
 /// Note: May return Ok if content is corrupted beyond ECC repair (or no ECC enabled). Check the `corrupted_segments` for details.
 /// This is because we can still read past the corruption and find the next block, and recover other data.
 /// This is not fatal to docufort, but it is a problem for the user's data.
+///
+/// A BlockEnd with no BlockStart before it is skipped (see `stray_block_ends`), and the check carries on from the next magic number.
+///
+/// For [CHAINED](crate::CHAINED) blocks, a block that was replaced shows up as the *next* block's content in `corrupted_segments`, since its hash covers the replaced block's hash.
 /// ## Err
 /// - File is not a docufort file
 /// - File is not written with the same configuration as this compiled program (ECC_LEN or version mismatch)
@@ -102,19 +187,98 @@ impl std::error::Error for IntegrityErr {
 /// - The block structure is invalid
 /// - An IO error occurred
 pub fn integrity_check_file<RW:FileLike, B: BlockInputs>(file: &mut RW) -> Result<IntegrityCheckOk, IntegrityErr> {
-    let mut file_len = file.len()?;
+    integrity_check_file_resume::<_,B>(file, None, None)
+}
+/// Same as [integrity_check_file], but starts at `resume_from` and stops after `max_blocks` blocks, so a check of a huge file can be split across runs.
+///
+/// `resume_from` is the [resume_at](IntegrityCheckOk::resume_at) of a prior run (the magic number of the next block to check), None starts at the first block.
+/// The returned counters only cover the blocks checked in this run, [append](IntegrityCheckOk::append) them to the prior run's for file totals.
+/// When the limit is hit, `resume_at` is set to where the next run should start.
+///
+/// This assumes the file was not modified between runs, other than appended to. If the file carries a generation (see [file_generation](crate::read::file_generation)),
+/// compare it before resuming and start over if it changed.
+pub fn integrity_check_file_resume<RW:FileLike, B: BlockInputs>(file: &mut RW, resume_from:Option<u64>, max_blocks:Option<usize>) -> Result<IntegrityCheckOk, IntegrityErr> {
+    check_file::<_,B>(file, resume_from, max_blocks, VersionPolicy::Strict)
+}
+/// Same as [integrity_check_file_resume], with a [VersionPolicy] like [integrity_check_file_with_policy].
+pub fn integrity_check_file_resume_with_policy<RW:FileLike, B: BlockInputs>(file: &mut RW, resume_from:Option<u64>, max_blocks:Option<usize>, policy:VersionPolicy) -> Result<IntegrityCheckOk, IntegrityErr> {
+    check_file::<_,B>(file, resume_from, max_blocks, policy)
+}
+/// Same as [integrity_check_file], but with [VersionPolicy::AllowNewer] a file of a newer version is checked as if it were `V1`.
+///
+/// [newer_version](IntegrityCheckOk::newer_version) is then set, so the caller can warn that only the `V1` subset was understood.
+/// A block using anything `V1` does not know fails the check like a corrupt one would.
+pub fn integrity_check_file_with_policy<RW:FileLike, B: BlockInputs>(file: &mut RW, policy:VersionPolicy) -> Result<IntegrityCheckOk, IntegrityErr> {
+    check_file::<_,B>(file, None, None, policy)
+}
+fn check_file<RW:FileLike, B: BlockInputs>(file: &mut RW, resume_from:Option<u64>, max_blocks:Option<usize>, policy:VersionPolicy) -> Result<IntegrityCheckOk, IntegrityErr> {
+    let file = &mut PositionedFile::new(file);
+    let file_len = file.len()?;
+    file.seek(SeekFrom::Start(0))?;
+    let newer_version = newer_version(file, policy)?;
+    if let Some(offset) = resume_from {
+        if offset < FILE_HEADER_LEN as u64 {
+            return Err(ReadWriteError::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, "resume_from is inside the file header")).into())
+        }
+        file.seek(SeekFrom::Start(offset))?;
+    }
+    let ok = check_blocks::<_,B>(file, file_len, max_blocks, |_,_|())?;
+    Ok(IntegrityCheckOk { newer_version, ..ok })
+}
+///Checks the file header under `policy`, returning the version if it is a newer one.
+fn newer_version<R:std::io::Read>(file: &mut R, policy:VersionPolicy) -> Result<Option<u8>, IntegrityErr> {
+    match check_configs(file, policy)? {
+        ConfigMatch::Exact => Ok(None),
+        ConfigMatch::Newer(v) => Ok(Some(v)),
+        ConfigMatch::Mismatch => Err(IntegrityErr::FileConfigMisMatch)
+    }
+}
+/// Same as [integrity_check_file], but for a stream that can not seek (e.g. a file arriving over the network), read strictly forward.
+///
+/// Only the block being checked is held in memory, along with the [MAX_ALIGNMENT](crate::MAX_ALIGNMENT) bytes before it ([CHAINED](crate::CHAINED) blocks need the hash before them).
+/// ECC corrections are made to that copy only, and counted in `errors_corrected`, the stream is never written to.
+/// The stream is read until it ends, and `file_len_checked` is how far it checked out, like [integrity_check_file] does for an open tail.
+///
+/// A [stray BlockEnd](IntegrityCheckOk::stray_block_ends) is skipped by searching the rest of the stream, which reads all of it into memory.
+pub fn verify_stream<R:std::io::Read, B: BlockInputs>(reader: R) -> Result<IntegrityCheckOk, IntegrityErr> {
+    verify_stream_with_policy::<_,B>(reader, VersionPolicy::Strict)
+}
+/// Same as [verify_stream], with a [VersionPolicy] like [integrity_check_file_with_policy].
+pub fn verify_stream_with_policy<R:std::io::Read, B: BlockInputs>(reader: R, policy:VersionPolicy) -> Result<IntegrityCheckOk, IntegrityErr> {
+    let mut stream = StreamWindow::new(reader);
+    let newer_version = newer_version(&mut stream, policy)?;
+    //the length is unknown, so check until the stream runs out
+    let ok = check_blocks::<_,B>(&mut stream, u64::MAX, None, |stream,block| stream.release_before(block.saturating_sub(MAX_ALIGNMENT + BLOCK_END_LEN as u64)))?;
+    Ok(IntegrityCheckOk { newer_version, ..ok })
+}
+///Checks blocks from the current position of `file` up to `file_len`. `at_block` is called with the position of each magic number before it is read.
+fn check_blocks<RW:std::io::Read + std::io::Write + std::io::Seek, B: BlockInputs>(file: &mut RW, mut file_len:u64, max_blocks:Option<usize>, mut at_block:impl FnMut(&mut RW,u64)) -> Result<IntegrityCheckOk, IntegrityErr> {
     let mut errors_corrected = 0;
     let mut data_contents = 0;
     let mut data_size_on_disk = 0;
+    let mut protected_content_bytes = 0;
+    let mut unprotected_content_bytes = 0;
     let mut num_blocks = 0;
     let mut corrupted_segments = Vec::new();
     let mut block_times = Vec::new();
+    let mut stray_block_ends = Vec::new();
+    let mut unchecked_external_refs = Vec::new();
+
+    let mut resume_at = None;
 
-    if !verify_configs(file)?{return Err(IntegrityErr::FileConfigMisMatch)}
     let mut last_state= None;
     loop {
         let cur_pos = file.seek(SeekFrom::Current(0))?;
+        if max_blocks.is_some_and(|max|num_blocks >= max) && cur_pos < file_len {
+            resume_at = Some(cur_pos);
+            file_len = cur_pos;
+            break;
+        }
+        at_block(file, cur_pos);
         let res = read_magic_number(file, true);
+        if let Err(ReadWriteError::EccTooManyErrors) = res {
+            return Err(IntegrityErr::Corruption(cur_pos,ComponentTag::MagicNumber))
+        }
         let after_read_pos = file.seek(SeekFrom::Current(0))?;
         if cur_pos > file_len || after_read_pos > file_len || res.is_err() {//we read too far from when the fn was originally called.
             //We set the file_len to reflect how far we have integrity checked
@@ -128,23 +292,27 @@ pub fn integrity_check_file<RW:FileLike, B: BlockInputs>(file: &mut RW) -> Resul
             BlockState::Closed(BlockReadSummary { errors_corrected: e, block,  corrupted_content_blocks, block_start, block_start_timestamp, .. }) => {
                 errors_corrected += e;
                 corrupted_segments.extend_from_slice(corrupted_content_blocks.as_slice());
+                let mut count_protection = |c:&Content|if c.ecc {protected_content_bytes += c.data_len.0 as u64}else{unprotected_content_bytes += c.data_len.0 as u64};
                 match block {
                     Block::A { middle, .. } => {
+                        count_protection(middle);
                         if let Some(decomp_len) = middle.compressed {
-                            data_contents += decomp_len as u64;
-                            data_size_on_disk += middle.data_len as u64;
+                            data_contents += decomp_len.0 as u64;
+                            data_size_on_disk += middle.data_len.0 as u64;
                         }else{
-                            data_contents += middle.data_len as u64;
-                            data_size_on_disk += middle.data_len as u64;
+                            data_contents += middle.data_len.0 as u64;
+                            data_size_on_disk += middle.data_len.0 as u64;
                         }
                     },
                     Block::B { middle, .. } => middle.iter().for_each(|(_,c)|{
+                        count_protection(c);
+                        if c.content_type == Some(EXTERNAL_REF_TYPE) {unchecked_external_refs.push(*block_start)}
                         if let Some(decomp_len) = c.compressed {
-                            data_contents += decomp_len as u64;
-                            data_size_on_disk += c.data_len as u64;
+                            data_contents += decomp_len.0 as u64;
+                            data_size_on_disk += c.data_len.0 as u64;
                         }else{
-                            data_contents += c.data_len as u64;
-                            data_size_on_disk += c.data_len as u64;
+                            data_contents += c.data_len.0 as u64;
+                            data_size_on_disk += c.data_len.0 as u64;
                         }
                     }),
                 }
@@ -173,6 +341,20 @@ pub fn integrity_check_file<RW:FileLike, B: BlockInputs>(file: &mut RW) -> Resul
             BlockState::DataCorruption { component_start, component_tag,.. } => {
                 return Err(IntegrityErr::Corruption(*component_start,*component_tag))
             },
+            BlockState::StrayBlockEnd { block_start, skip_to } => {
+                stray_block_ends.push(*block_start);
+                match find_next_block_start(file, *skip_to)? {
+                    Some(next) => {file.seek(SeekFrom::Start(next - MN_ECC_LEN as u64))?;},
+                    None => {
+                        file_len = (*skip_to).min(file_len);
+                        break;
+                    }
+                }
+            },
+            BlockState::CorruptContentLength { component_start, is_b_block } => {
+                let tag = if *is_b_block {ComponentTag::ContentHeader}else{ComponentTag::StartHeader};
+                return Err(IntegrityErr::Corruption(*component_start,tag))
+            },
         }
     }
     Ok(IntegrityCheckOk {
@@ -180,9 +362,202 @@ pub fn integrity_check_file<RW:FileLike, B: BlockInputs>(file: &mut RW) -> Resul
         errors_corrected,
         data_contents,
         data_size_on_disk,
+        protected_content_bytes,
+        unprotected_content_bytes,
         num_blocks,
         file_len_checked: file_len,
         corrupted_segments,
-        block_times
+        block_times,
+        stray_block_ends,
+        unchecked_external_refs,
+        resume_at,
+        merged_files: Vec::new(),
+        newer_version: None
     })
 }
+
+/// Buffers a forward only stream so the read functions can seek within the part of it still held, see [verify_stream].
+///
+/// Writes (ECC corrections) change the buffered copy. Seeking to the end reads the rest of the stream into memory.
+struct StreamWindow<R>{
+    inner:R,
+    ///Stream position of `buf[0]`.
+    base:u64,
+    buf:Vec<u8>,
+    pos:u64,
+    eof:bool
+}
+impl<R:std::io::Read> StreamWindow<R> {
+    fn new(inner:R)->Self{
+        Self { inner, base: 0, buf: Vec::new(), pos: 0, eof: false }
+    }
+    ///Reads from the stream until `end` is buffered, or the stream ends.
+    fn fill_to(&mut self,end:u64)->std::io::Result<()>{
+        const CHUNK:usize = 64 * 1024;
+        while !self.eof && self.base + (self.buf.len() as u64) < end {
+            let len = self.buf.len();
+            self.buf.resize(len + CHUNK, 0);
+            match self.inner.read(&mut self.buf[len..]) {
+                Ok(n) => {
+                    self.buf.truncate(len + n);
+                    self.eof = n == 0;
+                },
+                Err(e) => {
+                    self.buf.truncate(len);
+                    if e.kind() != std::io::ErrorKind::Interrupted {return Err(e)}
+                }
+            }
+        }
+        Ok(())
+    }
+    ///Drops the buffered bytes before `offset`, they can not be read again.
+    fn release_before(&mut self,offset:u64){
+        let n = (offset.saturating_sub(self.base) as usize).min(self.buf.len());
+        self.buf.drain(..n);
+        self.base += n as u64;
+    }
+    ///Fills the buffer for `len` bytes at the position, and returns the buffered part of them.
+    fn window(&mut self,len:usize)->std::io::Result<std::ops::Range<usize>>{
+        if self.pos < self.base {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "position was already released from the stream window"))
+        }
+        self.fill_to(self.pos + len as u64)?;
+        let start = ((self.pos - self.base) as usize).min(self.buf.len());
+        Ok(start..(start + len).min(self.buf.len()))
+    }
+}
+impl<R:std::io::Read> std::io::Read for StreamWindow<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let range = self.window(buf.len())?;
+        let n = range.len();
+        buf[..n].copy_from_slice(&self.buf[range]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+impl<R:std::io::Read> std::io::Write for StreamWindow<R> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let range = self.window(buf.len())?;
+        let n = range.len();
+        self.buf[range].copy_from_slice(&buf[..n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+impl<R:std::io::Read> std::io::Seek for StreamWindow<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let invalid = ||std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position");
+        self.pos = match pos {
+            SeekFrom::Start(p) => p,
+            SeekFrom::Current(d) => self.pos.checked_add_signed(d).ok_or_else(invalid)?,
+            SeekFrom::End(d) => {
+                self.fill_to(u64::MAX)?;
+                (self.base + self.buf.len() as u64).checked_add_signed(d).ok_or_else(invalid)?
+            },
+        };
+        Ok(self.pos)
+    }
+}
+
+/// One block's result from [verify_against_manifest].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BlockVerifyResult{
+    ///Start of the block (after its magic number).
+    pub offset:u64,
+    ///The block's hash equals the manifest entry at the same index.
+    pub matched:bool,
+    ///Where this block's hash was found in the manifest, if anywhere.
+    ///A Some that differs from this result's index means a block was added or removed before it.
+    pub manifest_index:Option<usize>
+}
+
+/// Recomputes the hash of every closed block and compares it to a trusted list of block hashes, in file order.
+///
+/// ECC is applied in memory only (see [DeferredPatches]), so correctable errors still match and the file is never written.
+/// Stops at the first block that is not closed, so an open tail is not listed.
+/// If the returned Vec is not the same length as `expected`, blocks were added or removed (see [BlockVerifyResult::manifest_index] for where).
+pub fn verify_against_manifest<R:std::io::Read + std::io::Seek, B: BlockInputs>(file: &mut R, expected:&[[u8;HASH_LEN]]) -> Result<Vec<BlockVerifyResult>, IntegrityErr> {
+    verify_against_manifest_with_policy::<_,B>(file, expected, VersionPolicy::Strict)
+}
+/// Same as [verify_against_manifest], but with [VersionPolicy::AllowNewer] the blocks of a newer version file are verified as if it were `V1`.
+pub fn verify_against_manifest_with_policy<R:std::io::Read + std::io::Seek, B: BlockInputs>(file: &mut R, expected:&[[u8;HASH_LEN]], policy:VersionPolicy) -> Result<Vec<BlockVerifyResult>, IntegrityErr> {
+    file.seek(SeekFrom::Start(0))?;
+    newer_version(file, policy)?;
+    let mut file = DeferredPatches::new(file);
+    let mut results = Vec::new();
+    let mut next = FILE_HEADER_LEN as u64;
+    loop {
+        file.seek(SeekFrom::Start(next))?;
+        match read_magic_number(&mut file, true) {
+            Ok(_) => (),
+            Err(ReadWriteError::EndOfFile) => break,
+            Err(e) => return Err(e.into()),
+        }
+        let offset = file.stream_position()?;
+        let BlockState::Closed(BlockReadSummary { hash_as_read, .. }) = try_read_block::<_, B>(&mut file, true, true)? else {break};
+        let i = results.len();
+        let matched = expected.get(i) == Some(&hash_as_read);
+        let manifest_index = if matched {Some(i)}else{expected.iter().position(|h|h == &hash_as_read)};
+        results.push(BlockVerifyResult { offset, matched, manifest_index });
+        next = file.stream_position()?;
+    }
+    Ok(results)
+}
+
+/// One block's entry from [generate_manifest].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ManifestEntry{
+    ///Start of the block (after its magic number).
+    pub offset:u64,
+    ///The hash of the block, as computed (and found to match the stored one).
+    pub hash:[u8;HASH_LEN],
+    ///Timestamp of the BlockStart header.
+    pub timestamp:u64,
+}
+
+/// Hashes every closed block in a single forward pass and lists their hashes, e.g. to feed [verify_against_manifest] later.
+///
+/// Unlike [try_read_block], content is only streamed through the hasher: it is not ECC checked or decompressed
+/// (except for [LOGICAL_HASH](crate::LOGICAL_HASH) blocks, whose hash is over the decompressed content). Header ECC is applied in memory only.
+/// This is meant for clean files, run an [integrity check](integrity_check_file) first to correct errors.
+/// Stops at the first block that is incomplete or does not decode, so an open tail is not listed.
+///
+/// # Errors
+/// - [ReadWriteError::NotADocuFortFile] if the file header does not match this build.
+/// - [ReadWriteError::HashMismatch] if a block's hash does not match its stored hash.
+pub fn generate_manifest<R:std::io::Read + std::io::Seek, B: BlockInputs>(file: &mut R) -> Result<Vec<ManifestEntry>, ReadWriteError> {
+    generate_manifest_with_policy::<_,B>(file, VersionPolicy::Strict)
+}
+/// Same as [generate_manifest], but with [VersionPolicy::AllowNewer] the blocks of a newer version file are listed as if it were `V1`.
+pub fn generate_manifest_with_policy<R:std::io::Read + std::io::Seek, B: BlockInputs>(file: &mut R, policy:VersionPolicy) -> Result<Vec<ManifestEntry>, ReadWriteError> {
+    file.seek(SeekFrom::Start(0))?;
+    if check_configs(file, policy)? == ConfigMatch::Mismatch {return Err(ReadWriteError::NotADocuFortFile)}
+    let mut file = PositionedFile::new(file);
+    let mut entries:Vec<ManifestEntry> = Vec::new();
+    file.seek(SeekFrom::Start(FILE_HEADER_LEN as u64))?;
+    loop {
+        let (entry,start,end,stored) = match catalog_block_parts(&mut file) {
+            Ok(Some(parts)) => parts,
+            Ok(None) | Err(ReadWriteError::EndOfFile) => break,
+            Err(e) => return Err(e),
+        };
+        let block_end = file.stream_position()?;
+        let hash = if end.is_logical_hash() {
+            file.seek(SeekFrom::Start(entry.offset))?;
+            let BlockState::Closed(brs) = try_read_block::<_, B>(&mut DeferredPatches::new(&mut file), false, false)? else {break};
+            brs.hash_as_read
+        }else{
+            let mut hasher = if start.is_chained() {chained_hasher::<B>(entries.last().map(|e|&e.hash))}else{B::new()};
+            hash_range(&mut file, entry.offset + (HEADER_LEN + ECC_LEN) as u64, end.start_pos(), &mut hasher)?;
+            if end.is_offset_bound() {hasher.update(&entry.offset.to_be_bytes())}
+            hasher.finalize()
+        };
+        if hash != stored {return Err(ReadWriteError::HashMismatch { block_start: entry.offset })}
+        entries.push(ManifestEntry { offset: entry.offset, hash, timestamp: entry.start_ts });
+        file.seek(SeekFrom::Start(block_end))?;
+    }
+    Ok(entries)
+}
@@ -13,6 +13,11 @@ This allows for recovery and consistency of partially written data (due to power
 
 The error correction is used as both a checksum and self-healing corruption protection in the header portions of the file, and is optional for content stored.
 The default allows for 2 errors every 251 bytes of data. Set the proper feature to change this.
+Exactly one of `ecc_len_2`, `ecc_len_4` (the default), `ecc_len_6`, `ecc_len_8`, `ecc_len_16` or `ecc_len_32` must be enabled,
+so to pick another length turn off default features: `docufort = { version = "*", default-features = false, features = ["ecc_len_8"] }`.
+Anything else fails to build with a message saying so.
+The `crc_only` feature swaps the ECC for a CRC32, so corruption is detected but never corrected (the file header stores an ECC length of 0).
+It stands in for `ecc_len_4`, so it may be used with or without the default features.
 
 This library provides a trait that handles all the hashing, compression and decompression for the implementer, making it transparent for usage.
 
@@ -50,6 +55,8 @@ pub mod recovery;
 pub mod integrity;
 pub mod retry_writer;
 pub mod content_reader;
+pub mod prelude;
+pub mod util;
 
 ///Magic Number for the file format: "docufort"
 pub const MAGIC_NUMBER: [u8; 8] = [0x64, 0x6F, 0x63, 0x75, 0x66, 0x6F, 0x72, 0x74]; //b"docufort"
@@ -60,11 +67,26 @@ pub const ECC_LEN: usize = 2;
 #[cfg(feature = "ecc_len_2")]
 pub const MN_ECC: [u8;ECC_LEN] = [97, 115];
 
-#[cfg(feature = "ecc_len_4")]
+#[cfg(all(feature = "ecc_len_4", not(feature = "crc_only")))]
 pub const ECC_LEN: usize = 4;
-#[cfg(feature = "ecc_len_4")]
+#[cfg(all(feature = "ecc_len_4", not(feature = "crc_only")))]
 pub const MN_ECC: [u8;ECC_LEN] = [14, 182, 66, 232];
 
+///With `crc_only` the ECC slots hold a big endian CRC32 instead, so every error is detected and none corrected.
+#[cfg(feature = "crc_only")]
+pub const ECC_LEN: usize = 4;
+#[cfg(feature = "crc_only")]
+pub const MN_ECC: [u8;ECC_LEN] = util::crc32(&MAGIC_NUMBER).to_be_bytes();
+#[cfg(all(feature = "crc_only", feature = "parallel"))]
+compile_error!("The crc_only feature does not support the parallel feature");
+
+///The ECC_LEN value stored in the file header. `crc_only` files store 0 as they have no error correction.
+#[cfg(not(feature = "crc_only"))]
+pub const FILE_ECC_LEN_BYTE: u8 = ECC_LEN as u8;
+///The ECC_LEN value stored in the file header. `crc_only` files store 0 as they have no error correction.
+#[cfg(feature = "crc_only")]
+pub const FILE_ECC_LEN_BYTE: u8 = 0;
+
 #[cfg(feature = "ecc_len_6")]
 pub const ECC_LEN: usize = 6;
 #[cfg(feature = "ecc_len_6")]
@@ -85,11 +107,38 @@ pub const ECC_LEN: usize = 32;
 #[cfg(feature = "ecc_len_32")]
 pub const MN_ECC: [u8;ECC_LEN] = [83, 167, 242, 14, 210, 222, 207, 128, 220, 246, 44, 99, 124, 84, 131, 64, 179, 22, 142, 190, 162, 181, 70, 110, 139, 197, 88, 22, 116, 21, 212, 200];
 
+#[cfg(not(any(feature = "ecc_len_2", feature = "ecc_len_4", feature = "ecc_len_6", feature = "ecc_len_8", feature = "ecc_len_16", feature = "ecc_len_32", feature = "crc_only")))]
+compile_error!("No ECC length selected. Enable exactly one ecc_len_* feature (the default is ecc_len_4), or crc_only.");
+//Stand ins so the error above is the only one reported.
+#[cfg(not(any(feature = "ecc_len_2", feature = "ecc_len_4", feature = "ecc_len_6", feature = "ecc_len_8", feature = "ecc_len_16", feature = "ecc_len_32", feature = "crc_only")))]
+pub const ECC_LEN: usize = 4;
+#[cfg(not(any(feature = "ecc_len_2", feature = "ecc_len_4", feature = "ecc_len_6", feature = "ecc_len_8", feature = "ecc_len_16", feature = "ecc_len_32", feature = "crc_only")))]
+pub const MN_ECC: [u8;ECC_LEN] = [14, 182, 66, 232];
+
+///Emits a compile_error for every pair of ECC length features that are both enabled.
+macro_rules! exclusive_ecc_len_features {
+    ($first:literal $(, $rest:literal)*) => {
+        $(
+            #[cfg(all(feature = $first, feature = $rest))]
+            compile_error!(concat!("Both ", $first, " and ", $rest, " are enabled. Enable exactly one ecc_len_* feature (set default-features = false to use one other than ecc_len_4)."));
+        )*
+        exclusive_ecc_len_features!($($rest),*);
+    };
+    () => {};
+}
+exclusive_ecc_len_features!("ecc_len_2", "ecc_len_4", "ecc_len_6", "ecc_len_8", "ecc_len_16", "ecc_len_32");
+#[cfg(all(feature = "crc_only", any(feature = "ecc_len_2", feature = "ecc_len_6", feature = "ecc_len_8", feature = "ecc_len_16", feature = "ecc_len_32")))]
+compile_error!("crc_only always uses a 4 byte CRC32, it can only be combined with ecc_len_4.");
+
 pub const DATA_SIZE:usize = (255 - ECC_LEN) as usize;
 
 ///MAGIC_NUMBER(8) + Ver(2) + ECC_LEN(1)
 pub const FILE_HEADER_LEN:u8 = 11;
 
+///The largest alignment [write_aligned_magic_number](crate::write::write_aligned_magic_number) pads to.
+///Readers skip at most this many zero bytes of padding in front of a magic number.
+pub const MAX_ALIGNMENT:u64 = 64 * 1024;
+
 ///TYPE(1) + TS(8) + DATA(4)
 pub const HEADER_LEN:usize = 13;
 ///HASH(20)
@@ -110,6 +159,57 @@ pub const END_TAG:u8 = 0b0110_0000;
 pub const HAS_ECC:u8 = 0b0000_1000;
 /// Bit flag indicating the content is compressed.
 pub const IS_COMP:u8 = 0b0000_0100;
+/// Bit flag on a Content tag indicating a 2 byte content type (plus its own ECC) follows the header.
+/// See [write_typed_content_component](crate::write::write_typed_content_component).
+pub const HAS_TYPE:u8 = 0b0000_0001;
+///Length of the content type field (u16, big endian) that follows a Content header with [HAS_TYPE] set.
+pub const CONTENT_TYPE_LEN:usize = 2;
+/// Bit flag on a Content tag indicating a 1 byte compression level (plus its own ECC) follows the header (and content type, if any).
+/// Only set on compressed content whose [BlockInputs::comp_level_byte] returns Some.
+pub const HAS_LEVEL:u8 = 0b0000_0010;
+///Length of the compression level field that follows a Content header with [HAS_LEVEL] set.
+pub const COMP_LEVEL_LEN:usize = 1;
+/// Bit flag on a Content tag indicating a hash of just this component's stored content (plus its own ECC) follows the header (and content type and compression level, if any).
+/// See [write_hashed_content_component](crate::write::write_hashed_content_component).
+pub const HAS_HASH:u8 = 0b0001_0000;
+/// Bit flag on a compressed Content tag indicating the content is stored as independently compressed frames behind a frame table, so it can be read from any offset.
+/// See [write_seekable_content_component](crate::write::write_seekable_content_component) and [SeekableContentReader](crate::read::SeekableContentReader).
+pub const SEEKABLE:u8 = 0b1000_0000;
+///Length of the fixed part of a [SEEKABLE] frame table (original length, frame length and number of frames, u32 each, big endian). A u32 compressed length per frame follows.
+pub const SEEK_TABLE_HEADER_LEN:usize = 12;
+///Set on a BlockStart header (A or B) when a [BatchMarker](crate::core::BatchMarker) (+ its ECC) follows the header.
+pub const HAS_BATCH:u8 = 0b0000_0010;
+///Length of the batch marker field (u32 seq + u32 index, big endian) that follows a BlockStart header with [HAS_BATCH] set.
+pub const BATCH_MARKER_LEN:usize = 8;
+///How many Content components [try_read_block](crate::recovery::try_read_block) reads from one B block before giving up with [BlockState::DataCorruption](crate::core::BlockState::DataCorruption).
+///Bounds the work a corrupted block (e.g. one whose BlockEnd was lost) can cause, see [try_read_block_with_limit](crate::recovery::try_read_block_with_limit).
+pub const MAX_COMPONENTS_PER_BLOCK:usize = 1_000_000;
+///The content type reserved for a content directory, the last content component of a B block listing the ones before it.
+///See [write_content_directory](crate::write::write_content_directory).
+pub const CONTENT_DIRECTORY_TYPE:u16 = u16::MAX;
+///The content type reserved for a generation record, a content component holding the file's generation (u64, big endian).
+///See [bump_generation](crate::write::bump_generation).
+pub const GENERATION_TYPE:u16 = u16::MAX - 1;
+///The content type reserved for an external reference, the content component of a thin block describing data stored outside the file.
+///See [write_thin_block](crate::write::write_thin_block) and [ExternalRef](crate::core::ExternalRef).
+pub const EXTERNAL_REF_TYPE:u16 = u16::MAX - 2;
+///Length of one [ContentDirEntry](crate::core::ContentDirEntry) in a content directory (u64 offset + u32 length + type flag + u16 type, big endian).
+pub const CONTENT_DIR_ENTRY_LEN:usize = 15;
+///Set on an A BlockStart tag (with [HAS_ECC]) when each content ECC chunk directly follows its data chunk, instead of all the ECC preceding the content.
+///See [write_streamed_atomic_block](crate::write::write_streamed_atomic_block).
+pub const TRAILING_ECC:u8 = 0b0000_0001;
+/// Bit flag on a BlockStart tag indicating the block hash is prefixed with the previous block's hash.
+/// See [write_chained_atomic_block](crate::write::write_chained_atomic_block).
+pub const CHAINED:u8 = 0b0001_0000;
+/// Bit flag on a BlockStart tag (A or B) marking the block as a commit point.
+/// See [RecoveryPolicy::rollback_uncommitted](crate::recovery::RecoveryPolicy::rollback_uncommitted).
+pub const COMMITTED:u8 = 0b1000_0000;
+/// Bit flag on the BlockEnd tag of an A block indicating the block hash covers the logical (decompressed) content instead of the stored bytes and their ECC.
+/// See [write_logical_hash_atomic_block](crate::write::write_logical_hash_atomic_block).
+pub const LOGICAL_HASH:u8 = 0b0000_0100;
+/// Bit flag on the BlockEnd tag (A or B) indicating the block's start offset (u64, big endian) was fed to the hasher last, just before finalizing.
+/// A block copied or swapped to a different offset then fails its hash check. See [write_offset_bound_atomic_block](crate::write::write_offset_bound_atomic_block).
+pub const OFFSET_BOUND:u8 = 0b0000_1000;
 
 
 ///Represents our different block types for matching against.
@@ -149,6 +249,14 @@ impl HeaderTag {
 
 impl From<u8> for HeaderTag {
     fn from(val: u8) -> Self {
+        //CHAINED only changes how the block is hashed
+        let val = if val & CHAINED == CHAINED && val & CON_TAG == 0 {val & !CHAINED}else{val};
+        //HAS_TYPE, HAS_LEVEL and HAS_HASH only add a field after the header, SEEKABLE only changes how the compressed content is framed
+        let val = if val & CON_TAG == CON_TAG {val & !(HAS_TYPE|HAS_LEVEL|HAS_HASH|SEEKABLE)}else{val};
+        //HAS_BATCH only adds a field after the header, TRAILING_ECC only changes where the content ECC sits, COMMITTED only matters to recovery
+        let val = if val & CON_TAG == 0 {val & !(HAS_BATCH|TRAILING_ECC|COMMITTED)}else{val};
+        //LOGICAL_HASH and OFFSET_BOUND only change what the block hash covers
+        let val = if val & END_TAG == END_TAG {val & !(LOGICAL_HASH|OFFSET_BOUND)}else{val};
         match val {
             B_BLOCK => HeaderTag::StartBBlock,
             END_TAG => HeaderTag::EndBlock,
@@ -169,6 +277,8 @@ impl From<u8> for HeaderTag {
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ComponentTag {
+    ///The magic number (and its ECC) in front of a BlockStart.
+    MagicNumber,
     StartHeader,
     EndHeader,
     Header,
@@ -178,17 +288,68 @@ pub enum ComponentTag {
 }
 
 
+///The file does not start with a DocuFort header, and no magic number was found near its end.
+///
+///Returned (inside an [std::io::Error]) by [find_block_start](crate::recovery::find_block_start) instead of scanning a whole foreign file.
+///Converts to [ReadWriteError::NotADocuFortFile].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NotADocuFortFile;
+impl std::fmt::Display for NotADocuFortFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Not a DocuFort file")
+    }
+}
+impl std::error::Error for NotADocuFortFile {}
+
 ///A ReadWriterError for problems occurring during operations.
 #[derive(Debug)]
 pub enum ReadWriteError{
     Io(std::io::Error),
     EndOfFile,
-    EccTooManyErrors
+    EccTooManyErrors,
+    ///Another handle holds the recovery lock, see `recovery::with_recovery_lock` (`recovery_lock` feature).
+    Locked,
+    ///The block (starting after the magic number) does not match its stored hash.
+    HashMismatch{block_start:u64},
+    ///Recovery corrected more errors than [RecoveryPolicy::max_corrections](crate::recovery::RecoveryPolicy::max_corrections) allows, carries the running total.
+    TooMuchCorruption(usize),
+    ///See [NotADocuFortFile].
+    NotADocuFortFile,
+    ///The content starting at `data_start` does not match its header,
+    ///e.g. flagged compressed but missing [COMPRESSED_MAGIC](crate::core::BlockInputs::COMPRESSED_MAGIC) because a corrupted tag still passed its ECC.
+    DataCorruption{data_start:u64},
+    ///The compressed content starting at `data_start` could not be decompressed, e.g. corrupted past what its ECC could correct.
+    ///See [DecompressPolicy](crate::read::DecompressPolicy).
+    DecompressionFailed{data_start:u64},
+    ///The file length changed under [recover_tail](crate::recovery::recover_tail), e.g. another process appended. Recovery stopped, run it again once writers are stopped.
+    LengthChanged{expected:u64,found:u64},
+    ///An I/O operation did not finish within its budget, see [TimeoutFile](crate::read::TimeoutFile). This is transient, the operation can be retried.
+    Timeout,
+    ///The content starting at `data_start` decoded, but was rejected by the caller's check, see [read_content_verified](crate::read::read_content_verified).
+    ContentVerificationFailed{data_start:u64}
+}
+impl ReadWriteError {
+    ///True if the same operation may succeed if simply attempted again: an I/O error that was [Interrupted](std::io::ErrorKind::Interrupted),
+    ///[WouldBlock](std::io::ErrorKind::WouldBlock) (EAGAIN) or [TimedOut](std::io::ErrorKind::TimedOut), a [Timeout](Self::Timeout),
+    ///the recovery lock being held ([Locked](Self::Locked)) or the file changing under recovery ([LengthChanged](Self::LengthChanged)).
+    ///
+    ///Everything else (corruption, a foreign file, other I/O errors) fails the same way again.
+    pub fn is_transient(&self)->bool{
+        match self {
+            ReadWriteError::Io(e) => matches!(e.kind(), std::io::ErrorKind::Interrupted | std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut),
+            ReadWriteError::Timeout |
+            ReadWriteError::Locked |
+            ReadWriteError::LengthChanged { .. } => true,
+            _ => false
+        }
+    }
 }
 impl From<std::io::Error> for ReadWriteError{
     fn from(value: std::io::Error) -> Self {
         match value.kind() {
             std::io::ErrorKind::UnexpectedEof => Self::EndOfFile,
+            std::io::ErrorKind::TimedOut => Self::Timeout,
+            _ if value.get_ref().is_some_and(|e|e.is::<NotADocuFortFile>()) => Self::NotADocuFortFile,
             _ => Self::Io(value),
         }
     }
@@ -204,6 +365,15 @@ impl std::fmt::Display for ReadWriteError {
             ReadWriteError::Io(err) => write!(f, "I/O error: {}", err),
             ReadWriteError::EndOfFile => write!(f, "Unexpected end of file"),
             ReadWriteError::EccTooManyErrors => write!(f, "Too many ECC errors"),
+            ReadWriteError::Locked => write!(f, "File is locked by another recovery"),
+            ReadWriteError::HashMismatch { block_start } => write!(f, "Block at {} does not match its hash", block_start),
+            ReadWriteError::TooMuchCorruption(count) => write!(f, "Corrected {} errors, more than the recovery limit", count),
+            ReadWriteError::NotADocuFortFile => write!(f, "{}", NotADocuFortFile),
+            ReadWriteError::DataCorruption { data_start } => write!(f, "Content at {} does not match its header", data_start),
+            ReadWriteError::DecompressionFailed { data_start } => write!(f, "Content at {} failed to decompress", data_start),
+            ReadWriteError::LengthChanged { expected, found } => write!(f, "File length changed during recovery, expected {} found {}", expected, found),
+            ReadWriteError::Timeout => write!(f, "I/O operation timed out"),
+            ReadWriteError::ContentVerificationFailed { data_start } => write!(f, "Content at {} failed verification", data_start),
         }
     }
 }
@@ -217,31 +387,36 @@ impl std::error::Error for ReadWriteError {
     }
 }
 
+///Hashes the bytes passing through it: everything written to (or read from) `inner` is also fed to `hasher`.
+///
+///Only the bytes the inner call actually wrote or read are hashed, so short writes and partial reads hash correctly.
 pub struct HashAdapter<'a,RW,B:BlockInputs> {
     pub hasher:&'a mut B,
-    pub writer:&'a mut RW,
+    ///The writer or reader being wrapped.
+    pub inner:&'a mut RW,
 }
 
-impl<'a,W: std::io::Write,B:BlockInputs> HashAdapter<'a,W,B> {
-    pub fn new(writer: &'a mut W,hasher:&'a mut B) -> Self {
-        Self { writer,hasher }
+impl<'a,RW,B:BlockInputs> HashAdapter<'a,RW,B> {
+    ///`inner` may be a writer or a reader.
+    pub fn new(inner: &'a mut RW,hasher:&'a mut B) -> Self {
+        Self { inner,hasher }
     }
 }
 
 impl<'a,W: std::io::Write,B:BlockInputs> std::io::Write for HashAdapter<'a,W,B> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        let bytes_written = self.writer.write(buf)?;
+        let bytes_written = self.inner.write(buf)?;
         self.hasher.update(&buf[..bytes_written]);
         Ok(bytes_written)
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
-        self.writer.flush()
+        self.inner.flush()
     }
 }
 impl<'a, R: std::io::Read, B: BlockInputs> std::io::Read for HashAdapter<'a, R, B> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        let bytes_read = self.writer.read(buf)?;
+        let bytes_read = self.inner.read(buf)?;
         if bytes_read > 0 {
             self.hasher.update(&buf[..bytes_read]);
         }
@@ -274,8 +449,25 @@ pub trait FileLike:std::io::Read+std::io::Write+std::io::Seek {
     fn truncate(&mut self, len: u64)->std::io::Result<()>;
     /// Returns the length of the underlying data.
     fn len(&self)->std::io::Result<u64>;
+    /// Makes everything written so far durable (an fsync for a [File](std::fs::File)). Defaults to [flush](std::io::Write::flush).
+    fn sync(&mut self)->std::io::Result<()>{
+        self.flush()
+    }
 }
 
+impl<F:FileLike + ?Sized> FileLike for &mut F{
+    fn truncate(&mut self, len: u64)->std::io::Result<()>{
+        (**self).truncate(len)
+    }
+
+    fn len(&self)->std::io::Result<u64> {
+        (**self).len()
+    }
+
+    fn sync(&mut self)->std::io::Result<()> {
+        (**self).sync()
+    }
+}
 impl FileLike for std::io::Cursor<Vec<u8>>{
     fn truncate(&mut self, len: u64)->std::io::Result<()>{
         let data = self.get_mut();
@@ -295,6 +487,77 @@ impl FileLike for std::fs::File{
     fn len(&self)->std::io::Result<u64> {
         self.metadata().map(|m|m.len())
     }
+
+    fn sync(&mut self)->std::io::Result<()> {
+        self.sync_all()
+    }
+}
+
+///The ECC parameters this build was compiled with, see [ecc_info].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EccInfo{
+    ///[ECC_LEN], the ECC symbols (bytes) per chunk.
+    pub ecc_len:usize,
+    ///[DATA_SIZE], the data bytes per chunk.
+    pub data_size:usize,
+    ///[MAX_CORRECTABLE_ERRORS](crate::ecc::MAX_CORRECTABLE_ERRORS) per chunk, 0 with `crc_only`.
+    pub max_correctable_errors:usize,
+    ///ECC bytes stored per byte of data, e.g. 4/251 for the default.
+    pub overhead_ratio:f64,
+}
+impl std::fmt::Display for EccInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "ECC: {} symbols, corrects up to {} errors per {} bytes, ~{:.1}% overhead", self.ecc_len, self.max_correctable_errors, self.ecc_len + self.data_size, self.overhead_ratio * 100.0)
+    }
+}
+///Returns the ECC parameters selected by this build's features, e.g. to log the configuration at startup.
+pub fn ecc_info()->EccInfo{
+    EccInfo { ecc_len: ECC_LEN, data_size: DATA_SIZE, max_correctable_errors: ecc::MAX_CORRECTABLE_ERRORS, overhead_ratio: ECC_LEN as f64 / DATA_SIZE as f64 }
+}
+
+///What [open_and_verify] found and did.
+#[derive(Debug)]
+pub struct OpenResult<B>{
+    ///The file length after [recover_tail](crate::recovery::recover_tail), this is where the next write goes.
+    pub recovered_len:u64,
+    ///The state to hand to [perform_file_op](crate::retry_writer::perform_file_op) to resume writing.
+    pub tail_state:retry_writer::TailState<B>,
+    pub recovery:recovery::TailRecoverySummary,
+    ///Present if a full integrity check was requested.
+    pub integrity:Option<integrity::IntegrityCheckOk>
+}
+
+///The startup routine for most applications: opens the file at `path` for reading and writing, verifies the file header, recovers the tail,
+///then runs [integrity_check_file](crate::integrity::integrity_check_file) over the whole file if `full_integrity` is set.
+///
+///A header that does not match this build returns [ReadWriteError::NotADocuFortFile], and nothing is written.
+///An integrity check that can not get past a corrupted header returns an [std::io::ErrorKind::InvalidData] error wrapping the [IntegrityErr](crate::integrity::IntegrityErr).
+///Any other error is whatever the failing step returned.
+pub fn open_and_verify<B:BlockInputs>(path:&std::path::Path,full_integrity:bool)->Result<OpenResult<B>,ReadWriteError>{
+    open_and_verify_with_policy::<B>(path, full_integrity, read::VersionPolicy::Strict)
+}
+///Same as [open_and_verify], but with [VersionPolicy::AllowNewer](read::VersionPolicy::AllowNewer) a file of a newer version is opened,
+///recovered and checked as if it were `V1`. The integrity check's [newer_version](integrity::IntegrityCheckOk::newer_version) then reports the version.
+pub fn open_and_verify_with_policy<B:BlockInputs>(path:&std::path::Path,full_integrity:bool,policy:read::VersionPolicy)->Result<OpenResult<B>,ReadWriteError>{
+    use std::io::Seek;
+    let mut file = std::fs::OpenOptions::new().read(true).write(true).open(path)?;
+    if read::check_configs(&mut file, policy)? == read::ConfigMatch::Mismatch {return Err(ReadWriteError::NotADocuFortFile)}
+    let recovery = recovery::recover_tail_with_policy::<_,B>(&mut file, recovery::RecoveryPolicy{ version_policy: policy, ..Default::default() })?;
+    let tail_state = match recovery.file_ops.last() {
+        Some((block_start,state)) if recovery.has_blocks => state.to_tail_state(&mut file, *block_start)?.unwrap_or(retry_writer::TailState::ClosedBlock),
+        _ => retry_writer::TailState::ClosedBlock,
+    };
+    let integrity = if full_integrity {
+        file.seek(std::io::SeekFrom::Start(0))?;
+        match integrity::integrity_check_file_with_policy::<_,B>(&mut file, policy) {
+            Ok(check) => Some(check),
+            Err(integrity::IntegrityErr::Other(e)) => return Err(e),
+            Err(integrity::IntegrityErr::FileConfigMisMatch) => return Err(ReadWriteError::NotADocuFortFile),
+            Err(e) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e).into()),
+        }
+    }else{None};
+    file.seek(std::io::SeekFrom::Start(recovery.recovered_file_len))?;
+    Ok(OpenResult { recovered_len: recovery.recovered_file_len, tail_state, recovery, integrity })
 }
 
 #[cfg(test)]
@@ -314,4 +577,14 @@ mod test_super {
         // Verify the writer contains the expected ECC data
         assert_eq!(writer.into_inner(), MN_ECC);
     }
+    #[test]
+    fn test_ecc_info() {
+        let info = ecc_info();
+        assert_eq!((info.ecc_len,info.data_size),(ECC_LEN,DATA_SIZE));
+        assert_eq!(info.ecc_len + info.data_size,255);
+        assert_eq!(info.max_correctable_errors,crate::ecc::MAX_CORRECTABLE_ERRORS);
+        assert_eq!(info.overhead_ratio,ECC_LEN as f64 / DATA_SIZE as f64);
+        #[cfg(all(feature = "ecc_len_4", not(feature = "crc_only")))]
+        assert_eq!(info.to_string(),"ECC: 4 symbols, corrects up to 2 errors per 255 bytes, ~1.6% overhead");
+    }
 }
\ No newline at end of file
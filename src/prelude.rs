@@ -0,0 +1,16 @@
+//! The commonly needed types and functions, so a single `use docufort::prelude::*;` is enough to write and recover a file.
+//!
+//! Everything here is still available at its original path.
+
+pub use crate::{
+    core::{BlockInputs, BlockState, Block, BlockEnd, ComponentHeader, Content, StoredLen, OriginalLen, BatchMarker, ContentDirEntry, BlockCoords, ExternalRef},
+    recovery::{BlockReadSummary, OwnedBlock, TailRecoverySummary, RecoveryEstimate, MergedRecovery, estimate_recovery, needs_recovery, repair_header_config, recover_tail, recover_tail_with_policy, atomic_replace, RecoveryPolicy, UncorrectableContent, VerifyStrategy, try_read_block_verified, RecoveryStep, RecoveryAction, try_read_block, try_read_block_with_limit, find_next_block_start, verify_after_patch, refresh_block_ecc, merge_b_block_components},
+    integrity::{IntegrityCheckOk, MergedCheck, IntegrityErr, integrity_check_file, integrity_check_file_resume, integrity_check_file_with_policy, integrity_check_file_resume_with_policy, verify_stream, verify_stream_with_policy, verify_against_manifest, verify_against_manifest_with_policy, generate_manifest, generate_manifest_with_policy, ManifestEntry, BlockVerifyResult},
+    write::{init_file, write_magic_number, write_aligned_magic_number, write_header, write_content_component, write_typed_content_component, write_hashed_content_component, write_seekable_content_component, prehash_content_component, write_content_precomputed_hash, PrehashedContent, write_atomic_block, write_committed_atomic_block, write_streamed_atomic_block, write_atomic_block_streaming, write_batch_atomic_block, write_batch_block_start, write_block_end, write_block_end_with_body_len, write_verified, write_cached, write_heartbeat, bump_generation, write_content_directory, write_thin_block, write_large_content, write_large_content_with_policy, FlushPolicy, WrittenBlock, validate_content_ecc, EccPlan, ShardedWriter, write_chained_atomic_block, write_logical_hash_atomic_block, write_offset_bound_atomic_block, write_offset_bound_block_end, chained_hasher},
+    read::{check_configs, VersionPolicy, ConfigMatch, read_content, read_content_verified, read_content_ecc_only, read_content_prefetched, read_content_with_policy, read_content_tail, load_content_decoded, LazyContentReader, SeekableContentReader, DecompressPolicy, DecodedContent, content_chunks, read_content_slice, content_cow, verify_component, verify_configs, verify_block_start_slice, catalog, logical_size, BlockCatalogEntry, content_fingerprint, DeferredPatches, apply_patches, ReadAheadReader, PositionedFile, TimeoutFile, Source, TailingBlockIter, replay, all_content, file_generation, GlobalContent, hash_range, locate_offset, OffsetLocation, recorded_block_end, copy_block_raw, BlockHashCache, verify_block_hash, verify_atomic_block, read_thin_block, extract_block_content, read_large_content, byte_classification, ByteClass},
+    retry_writer::{Op, Operation, TailState, perform_file_op, perform_file_op_with_policy, RetryingFile, spawn_writer, spawn_writer_with_policy, WriterHandle},
+    content_reader::find_content,
+    HeaderTag, CorruptDataSegment, ReadWriteError, NotADocuFortFile, FileLike, HASH_LEN, open_and_verify, open_and_verify_with_policy, OpenResult, ecc_info, EccInfo,
+};
+#[cfg(feature = "recovery_lock")]
+pub use crate::recovery::with_recovery_lock;
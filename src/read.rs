@@ -4,10 +4,12 @@ We *always* write to the file if we find errors reading system messages.
 Hence the Read + Write trait bounds for the RW generic that represents the docufort file.
 
 *Content* error correction happens at a higher level.
+
+To avoid many small scattered writes during a large scan, wrap the file in [DeferredPatches] and [apply_patches] once at the end.
 */
 
 
-use crate::{FILE_HEADER_LEN, MAGIC_NUMBER, ECC_LEN, core::{ComponentHeader, HeaderAsContent, BlockHash, BlockInputs, BlockEnd, Content}, ReadWriteError, HEADER_LEN, ecc::{apply_ecc, calc_ecc_data_len}, HASH_AND_ECC_LEN, DATA_SIZE, HeaderTag, HASH_LEN, ComponentTag, CorruptDataSegment, MN_ECC_LEN, MN_ECC};
+use crate::{FILE_HEADER_LEN, FILE_ECC_LEN_BYTE, MAGIC_NUMBER, ECC_LEN, core::{ComponentHeader, HeaderAsContent, BlockHash, BlockInputs, BlockEnd, Content, StoredLen, OriginalLen, BatchMarker, BlockCoords, ExternalRef, read_comp_len, read_u32}, ReadWriteError, HashAdapter, HEADER_LEN, ecc::{apply_ecc, calc_ecc_data_len, ChunkDecoder, MAX_CORRECTABLE_ERRORS}, HASH_AND_ECC_LEN, DATA_SIZE, HeaderTag, HASH_LEN, ComponentTag, CorruptDataSegment, MN_ECC_LEN, MN_ECC, END_TAG, A_BLOCK, B_BLOCK, CON_TAG, HAS_ECC, IS_COMP, CHAINED, COMMITTED, TRAILING_ECC, HAS_TYPE, CONTENT_TYPE_LEN, HAS_LEVEL, COMP_LEVEL_LEN, HAS_HASH, SEEKABLE, SEEK_TABLE_HEADER_LEN, HAS_BATCH, BATCH_MARKER_LEN, MAX_COMPONENTS_PER_BLOCK, MAX_ALIGNMENT, EXTERNAL_REF_TYPE, LOGICAL_HASH, OFFSET_BOUND};
 
 
 
@@ -22,6 +24,32 @@ use crate::{FILE_HEADER_LEN, MAGIC_NUMBER, ECC_LEN, core::{ComponentHeader, Head
 ///
 /// Return Ok(true) if everything matches, and Ok(false) if something mis-matches
 pub fn verify_configs<R:std::io::Read>(file: &mut R) -> std::io::Result<bool> {
+    Ok(check_configs(file, VersionPolicy::Strict)? == ConfigMatch::Exact)
+}
+
+/// Which file versions [check_configs] accepts.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VersionPolicy{
+    ///Only `V1` files, like [verify_configs].
+    Strict,
+    ///Also files of a newer version (`V2` to `V9`), read as if they were `V1`.
+    ///This is only safe for versions whose layout is a superset of `V1`: their `V1` blocks read as usual, anything else is reported as corrupt.
+    AllowNewer,
+}
+/// The outcome of [check_configs].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConfigMatch{
+    ///The header matches this build.
+    Exact,
+    ///The file is the given (newer) version, accepted by [VersionPolicy::AllowNewer]. Callers should warn that only the `V1` subset is understood.
+    Newer(u8),
+    ///Not a DocuFort file, or not one this build can read.
+    Mismatch,
+}
+/// Same as [verify_configs], but a file of a newer version is accepted under [VersionPolicy::AllowNewer].
+///
+/// The magic number and ECC_LEN must always match, as blocks can not be read without them.
+pub fn check_configs<R:std::io::Read>(file: &mut R, policy:VersionPolicy) -> std::io::Result<ConfigMatch> {
     // Create a buffer large enough for all data
     let mut buffer = [0; FILE_HEADER_LEN as usize];
     file.read_exact(&mut buffer)?;
@@ -29,36 +57,74 @@ pub fn verify_configs<R:std::io::Read>(file: &mut R) -> std::io::Result<bool> {
     // Split the buffer into the magic number and the constants
     let (magic_number, constants) = buffer.split_at(MAGIC_NUMBER.len());
     // Convert the magic number slice to an array
-    let magic_number_arr: [u8; 8] = magic_number.try_into().expect("Wrong size for magic number");
+    let magic_number_arr: [u8; MAGIC_NUMBER.len()] = magic_number.try_into().expect("Wrong size for magic number");
 
     if magic_number_arr != MAGIC_NUMBER {
-        return Ok(false);
+        return Ok(ConfigMatch::Mismatch);
     }
-    if &constants[0..2] != &[b'V',b'1'] {
-        return Ok(false);
+    if constants[2] != FILE_ECC_LEN_BYTE {
+        return Ok(ConfigMatch::Mismatch);
     }
-    if constants[2] != ECC_LEN as u8 {
-        return Ok(false);
+    match (constants[0],constants[1],policy) {
+        (b'V',b'1',_) => Ok(ConfigMatch::Exact),
+        (b'V',v @ b'2'..=b'9',VersionPolicy::AllowNewer) => Ok(ConfigMatch::Newer(v - b'0')),
+        _ => Ok(ConfigMatch::Mismatch)
     }
-
-    Ok(true)
 }
 
 /// Attempts to read the magic number from the reader.
+///
+/// The bytes are always checked: if they do not decode (ECC applied in memory) to the magic number this returns [ReadWriteError::EccTooManyErrors],
+/// so walking forward never silently steps over a damaged separator between blocks.
+/// The number of corrected bytes is returned either way, but only with `error_correct` are they written back.
+///
+/// Zero bytes of alignment padding in front of the magic number (see [write_aligned_magic_number](crate::write::write_aligned_magic_number)) are skipped.
 pub fn read_magic_number<RW:std::io::Write + std::io::Read + std::io::Seek>(reader_writer:&mut RW,error_correct:bool)->Result<usize,ReadWriteError>{
     let mut buf = [0u8;MN_ECC_LEN];
     let start = reader_writer.seek(std::io::SeekFrom::Current(0))?;
     reader_writer.read_exact(&mut buf)?;
-    let errors = if error_correct && (&buf[..MAGIC_NUMBER.len()] != &MAGIC_NUMBER || &buf[MAGIC_NUMBER.len()..] != MN_ECC) {
-        let errors = apply_ecc(&mut buf)?;
-        assert!(errors > 0);
-        reader_writer.seek(std::io::SeekFrom::Start(start))?;
-        reader_writer.write_all(&buf)?;
-        errors
-    }else{0};
+    if buf[..MAGIC_NUMBER.len()] == MAGIC_NUMBER && buf[MAGIC_NUMBER.len()..] == MN_ECC {return Ok(0)}
+    let errors = match apply_ecc(&mut buf) {
+        //a valid codeword for something else is not a damaged magic number
+        Ok(errors) if errors > 0 && buf[..MAGIC_NUMBER.len()] == MAGIC_NUMBER => errors,
+        _ if buf[0] == 0 && skip_alignment_padding(reader_writer, start)? => return read_magic_number(reader_writer, error_correct),
+        Ok(_) => return Err(ReadWriteError::EccTooManyErrors),
+        Err(e) => return Err(e.into()),
+    };
+    if !error_correct {return Ok(errors)}
+    reader_writer.seek(std::io::SeekFrom::Start(start))?;
+    reader_writer.write_all(&buf)?;
     Ok(errors)
 }
 
+/// If the bytes at `start` are a run of zeros (at most [MAX_ALIGNMENT] long) followed by something else, seeks past them and returns true.
+/// Otherwise the reader is left at `start`.
+fn skip_alignment_padding<R:std::io::Read + std::io::Seek>(reader:&mut R,start:u64)->Result<bool,ReadWriteError>{
+    reader.seek(std::io::SeekFrom::Start(start))?;
+    let mut buf = [0u8;4096];
+    let mut zeros = 0u64;
+    let skip = loop {
+        let n = match reader.read(&mut buf) {
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        if n == 0 {break None}
+        match buf[..n].iter().position(|b|*b != 0) {
+            Some(i) => {
+                zeros += i as u64;
+                break if zeros > 0 && zeros <= MAX_ALIGNMENT {Some(zeros)}else{None}
+            },
+            None => {
+                zeros += n as u64;
+                if zeros > MAX_ALIGNMENT {break None}
+            }
+        }
+    };
+    reader.seek(std::io::SeekFrom::Start(start + skip.unwrap_or(0)))?;
+    Ok(skip.is_some())
+}
+
 /// Attempts to read a header from the reader.
 ///
 /// Returns Ok(errors_corrected, ComponentHeader)
@@ -74,7 +140,7 @@ pub fn read_header<RW:std::io::Write + std::io::Read + std::io::Seek>(reader_wri
         }
         errors
     }else{0};
-    Ok((errors,ComponentHeader::new(&header[0..HEADER_LEN],start)))
+    Ok((errors,ComponentHeader::new_with_ecc(&header,start)))
 }
 /// Attempts to read a content header from the reader.
 ///
@@ -92,7 +158,27 @@ pub fn read_content_header<RW:std::io::Write + std::io::Read + std::io::Seek, B:
         errors
     }else{0};
     hasher.update(&header);
-    Ok((errors,ComponentHeader::new(&header[0..HEADER_LEN],start)))
+    Ok((errors,ComponentHeader::new_with_ecc(&header,start)))
+}
+
+/// Reads the [BatchMarker] (and its ECC) that follows a BlockStart header with [HAS_BATCH](crate::HAS_BATCH) set, hashing it.
+///
+/// Reader should be positioned directly after the BlockStart header.
+/// Returns Ok(errors_corrected, BatchMarker)
+pub fn read_batch_marker<RW:std::io::Write + std::io::Read + std::io::Seek, B:BlockInputs>(reader_writer:&mut RW,error_correct:bool,hasher:&mut B)->Result<(usize,BatchMarker),ReadWriteError>{
+    let mut field = [0u8;BATCH_MARKER_LEN+ECC_LEN];
+    let start = reader_writer.stream_position()?;
+    reader_writer.read_exact(&mut field[..])?;
+    let errors = if error_correct {
+        let errors = apply_ecc(&mut field)?;
+        if errors > 0 {
+            reader_writer.seek(std::io::SeekFrom::Start(start))?;
+            reader_writer.write_all(&field)?;
+        }
+        errors
+    }else{0};
+    hasher.update(&field);
+    Ok((errors,BatchMarker::from_bytes(field[..BATCH_MARKER_LEN].try_into().unwrap())))
 }
 
 /// Attempts to read the hash from the reader.
@@ -118,42 +204,78 @@ pub fn read_hash<RW:  std::io::Write + std::io::Read + std::io::Seek>(reader_wri
 ///
 /// This is used to during block verification.
 /// Reader should be position at the start of the content portion (ecc bytes if present, else the data bytes).
-pub fn check_read_content<RW:std::io::Write + std::io::Read + std::io::Seek, B:BlockInputs>(reader_writer:&mut RW,content_info:&HeaderAsContent,error_correct:bool,hasher:&mut B)->Result<(usize,Vec<CorruptDataSegment>,Content),ReadWriteError>{
-    let HeaderAsContent { data_len, data_start, ecc, compressed } = *content_info;
+///
+/// Returns Ok((errors_corrected, min_correction_headroom, corrupted_segments, Content))
+/// The headroom is the least [apply_ecc_with_headroom](crate::ecc::apply_ecc_with_headroom) reported across all the chunks (0 for an uncorrectable chunk).
+/// If no ECC was applied it is [MAX_CORRECTABLE_ERRORS].
+pub fn check_read_content<RW:std::io::Write + std::io::Read + std::io::Seek, B:BlockInputs>(reader_writer:&mut RW,content_info:&HeaderAsContent,error_correct:bool,hasher:&mut B)->Result<(usize,usize,Vec<CorruptDataSegment>,Content),ReadWriteError>{
+    let HeaderAsContent { data_len, data_start, ecc, compressed, has_type, has_level, has_hash, trailing_ecc, seekable } = *content_info;
     let ecc_len = if ecc{calc_ecc_data_len(data_len as usize)}else{0};
     let to_read = data_len as usize + ecc_len;
-    let cursor_start = data_start - ecc_len as u64;
+    let cursor_start = if trailing_ecc {data_start}else{data_start - ecc_len as u64};
     let mut corruption = Vec::new();
+    let hash_start = cursor_start - if has_hash {HASH_AND_ECC_LEN as u64}else{0};
+    let level_start = hash_start - if has_level {(COMP_LEVEL_LEN + ECC_LEN) as u64}else{0};
+    let (type_errors,content_type) = if has_type {
+        let (errors,[a,b]) = read_header_field(reader_writer, level_start - (CONTENT_TYPE_LEN + ECC_LEN) as u64, error_correct, hasher)?;
+        (errors,Some(u16::from_be_bytes([a,b])))
+    }else{(0,None)};
+    let (level_errors,comp_level) = if has_level {
+        let (errors,[level]) = read_header_field::<_,_,COMP_LEVEL_LEN>(reader_writer, level_start, error_correct, hasher)?;
+        (errors,Some(level))
+    }else{(0,None)};
+    let (hash_errors,component_hash) = if has_hash {
+        let (errors,hash) = read_header_field::<_,_,HASH_LEN>(reader_writer, hash_start, error_correct, hasher)?;
+        (errors,Some(hash))
+    }else{(0,None)};
+    let type_headroom = if error_correct {MAX_CORRECTABLE_ERRORS - type_errors.max(level_errors).max(hash_errors)}else{MAX_CORRECTABLE_ERRORS};
+    let type_errors = type_errors + level_errors + hash_errors;
     if !ecc || (ecc && !error_correct) {
         let content = if compressed{
             reader_writer.seek(std::io::SeekFrom::Start(data_start))?;
             let mut len = [0u8;4];
             reader_writer.read_exact(&mut len)?;
-            Content{ data_len, data_start, ecc, compressed: Some(u32::from_be_bytes(len)) }
-        }else{Content{ data_len, data_start, ecc, compressed: None }};
+            Content{ data_len: StoredLen(data_len), data_start, ecc, compressed: Some(OriginalLen(read_comp_len(len))), content_type, comp_level, component_hash, trailing_ecc, seekable }
+        }else{Content{ data_len: StoredLen(data_len), data_start, ecc, compressed: None, content_type, comp_level, component_hash, trailing_ecc, seekable }};
 
         reader_writer.seek(std::io::SeekFrom::Start(cursor_start))?;
         buffer_hash(reader_writer, to_read as usize, hasher)?;
-        return Ok((0,corruption,content))
+        return Ok((type_errors,type_headroom,corruption,content))
     }
     let num_chunks = ecc_len/ECC_LEN;
-    let mut ecc_data = vec![0u8;ecc_len];
-    reader_writer.read_exact(&mut ecc_data[..])?;
+    let mut tot_errors = type_errors;
+    let mut min_headroom = type_headroom;
+    if trailing_ecc {
+        let (errors,headroom) = correct_trailing_ecc(reader_writer, data_start, data_len, &mut corruption)?;
+        tot_errors += errors;
+        min_headroom = min_headroom.min(headroom);
+    }
+    //The ECC is read a batch at a time, so memory use does not grow with the content.
+    const ECC_BATCH_CHUNKS:usize = 128;
+    let mut ecc_data = [0u8;ECC_BATCH_CHUNKS*ECC_LEN];
     let mut data = [0u8;DATA_SIZE+ECC_LEN];
-    let mut tot_errors = 0;
+    let decoder = ChunkDecoder::new();
 
-    for i in 0..num_chunks {
-        let data_chunk_end = if i+1 < num_chunks{DATA_SIZE}else{data_len as usize%DATA_SIZE};
+    for i in (0..num_chunks).filter(|_|!trailing_ecc) {
+        let batch_i = i % ECC_BATCH_CHUNKS;
+        if batch_i == 0 {
+            let batch_len = (num_chunks - i).min(ECC_BATCH_CHUNKS)*ECC_LEN;
+            reader_writer.seek(std::io::SeekFrom::Start(cursor_start + (i*ECC_LEN) as u64))?;
+            reader_writer.read_exact(&mut ecc_data[..batch_len])?;
+            reader_writer.seek(std::io::SeekFrom::Start(cursor_start + (ecc_len + (i*DATA_SIZE)) as u64))?;
+        }
+        let data_chunk_end = (data_len as usize - i*DATA_SIZE).min(DATA_SIZE);
         let chunk_end = data_chunk_end + ECC_LEN;
-        let (e_s,e_e) = (i*ECC_LEN,(i*ECC_LEN)+ECC_LEN);
+        let (e_s,e_e) = (batch_i*ECC_LEN,(batch_i*ECC_LEN)+ECC_LEN);
         {
             let (d,e) = data.split_at_mut(data_chunk_end);
             reader_writer.read_exact(d)?;
             e[..ECC_LEN].copy_from_slice(&ecc_data[e_s..e_e])
         }
         let (crsr_e,crsr_d) = (cursor_start + (i*ECC_LEN) as u64, cursor_start + (ecc_len + (i*DATA_SIZE)) as u64);
-        match apply_ecc(&mut data[..chunk_end]) {
-            Ok(errors) => {
+        match decoder.apply_with_headroom(&mut data[..chunk_end]) {
+            Ok((errors,headroom)) => {
+                min_headroom = min_headroom.min(headroom);
                 if errors == 0 {continue;}
                 //seek to ecc slot, write
                 reader_writer.seek(std::io::SeekFrom::Start(crsr_e))?;
@@ -164,6 +286,7 @@ pub fn check_read_content<RW:std::io::Write + std::io::Read + std::io::Seek, B:B
                 tot_errors += errors;
             },
             Err(_) => {
+                min_headroom = 0;
                 corruption.push(CorruptDataSegment::EccChunk{ chunk_start: crsr_d, chunk_ecc_start: crsr_e, ecc_start: cursor_start, data_start, data_len })
             },
         }
@@ -172,28 +295,1993 @@ pub fn check_read_content<RW:std::io::Write + std::io::Read + std::io::Seek, B:B
         reader_writer.seek(std::io::SeekFrom::Start(data_start))?;
         let mut len = [0u8;4];
         reader_writer.read_exact(&mut len)?;
-        Content{ data_len, data_start, ecc, compressed: Some(u32::from_be_bytes(len)) }
-    }else{Content{ data_len, data_start, ecc, compressed: None }};
+        Content{ data_len: StoredLen(data_len), data_start, ecc, compressed: Some(OriginalLen(read_comp_len(len))), content_type, comp_level, component_hash, trailing_ecc, seekable }
+    }else{Content{ data_len: StoredLen(data_len), data_start, ecc, compressed: None, content_type, comp_level, component_hash, trailing_ecc, seekable }};
     reader_writer.seek(std::io::SeekFrom::Start(cursor_start))?;
     buffer_hash(reader_writer, to_read, hasher)?;
-    Ok((tot_errors, corruption,content))
+    Ok((tot_errors, min_headroom, corruption,content))
+}
+
+/// Applies the ECC of content written with [TRAILING_ECC](crate::TRAILING_ECC), one chunk (data then its ECC) at a time.
+///
+/// Returns Ok((errors_corrected, min_correction_headroom)), uncorrectable chunks are pushed to `corruption`.
+fn correct_trailing_ecc<RW:std::io::Write + std::io::Read + std::io::Seek>(reader_writer:&mut RW,data_start:u64,data_len:u32,corruption:&mut Vec<CorruptDataSegment>)->Result<(usize,usize),ReadWriteError>{
+    let mut data = [0u8;DATA_SIZE+ECC_LEN];
+    let (mut tot_errors,mut min_headroom) = (0,MAX_CORRECTABLE_ERRORS);
+    let mut remaining = data_len as usize;
+    let mut chunk_start = data_start;
+    let decoder = ChunkDecoder::new();
+    reader_writer.seek(std::io::SeekFrom::Start(chunk_start))?;
+    while remaining > 0 {
+        let data_chunk_end = remaining.min(DATA_SIZE);
+        let chunk_end = data_chunk_end + ECC_LEN;
+        reader_writer.read_exact(&mut data[..chunk_end])?;
+        match decoder.apply_with_headroom(&mut data[..chunk_end]) {
+            Ok((errors,headroom)) => {
+                min_headroom = min_headroom.min(headroom);
+                if errors > 0 {
+                    reader_writer.seek(std::io::SeekFrom::Start(chunk_start))?;
+                    reader_writer.write_all(&data[..chunk_end])?;
+                    tot_errors += errors;
+                }
+            },
+            Err(_) => {
+                min_headroom = 0;
+                corruption.push(CorruptDataSegment::EccChunk{ chunk_start, chunk_ecc_start: chunk_start + data_chunk_end as u64, ecc_start: data_start, data_start, data_len })
+            },
+        }
+        remaining -= data_chunk_end;
+        chunk_start += chunk_end as u64;
+    }
+    Ok((tot_errors,min_headroom))
+}
+
+/// Reads only the data bytes of content written with [TRAILING_ECC](crate::TRAILING_ECC), skipping the ECC after each chunk.
+struct SkipTrailingEcc<'a,R>{
+    src:&'a mut R,
+    ///Data bytes left before the next ECC chunk
+    left_in_chunk:usize,
+    ///Data bytes left in the content
+    remaining:usize
+}
+impl<'a,R:std::io::Read + std::io::Seek> SkipTrailingEcc<'a,R> {
+    fn new(src:&'a mut R,data_start:u64,data_len:u32)->std::io::Result<Self>{
+        src.seek(std::io::SeekFrom::Start(data_start))?;
+        let remaining = data_len as usize;
+        Ok(Self { src, left_in_chunk: remaining.min(DATA_SIZE), remaining })
+    }
+}
+impl<R:std::io::Read + std::io::Seek> std::io::Read for SkipTrailingEcc<'_,R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.remaining == 0 {return Ok(0)}
+        if self.left_in_chunk == 0 {
+            self.src.seek(std::io::SeekFrom::Current(ECC_LEN as i64))?;
+            self.left_in_chunk = self.remaining.min(DATA_SIZE);
+        }
+        let max = buf.len().min(self.left_in_chunk);
+        let n = self.src.read(&mut buf[..max])?;
+        self.left_in_chunk -= n;
+        self.remaining -= n;
+        Ok(n)
+    }
 }
 
+/// Reads a field that follows a Content header (content type or compression level) and its ECC at `start`, hashing it.
+/// Leaves the reader positioned at the end of the field.
+///
+/// Returns Ok((errors_corrected, field))
+fn read_header_field<RW:std::io::Write + std::io::Read + std::io::Seek, B:BlockInputs, const N:usize>(reader_writer:&mut RW,start:u64,error_correct:bool,hasher:&mut B)->Result<(usize,[u8;N]),ReadWriteError>{
+    let mut field = vec![0u8;N+ECC_LEN];
+    reader_writer.seek(std::io::SeekFrom::Start(start))?;
+    reader_writer.read_exact(&mut field)?;
+    let errors = if error_correct {
+        let errors = apply_ecc(&mut field)?;
+        if errors > 0 {
+            reader_writer.seek(std::io::SeekFrom::Start(start))?;
+            reader_writer.write_all(&field)?;
+        }
+        errors
+    }else{0};
+    hasher.update(&field);
+    Ok((errors,field[..N].try_into().unwrap()))
+}
+
+///Reads the original length prefix and [COMPRESSED_MAGIC](BlockInputs::COMPRESSED_MAGIC) of compressed content, and returns the magic bytes read so they can be chained back in front of the frame.
+fn check_compressed_magic<R:std::io::Read, B:BlockInputs>(data:&mut R,content_info:&Content)->Result<Vec<u8>,ReadWriteError>{
+    let mut magic = vec![0u8;B::COMPRESSED_MAGIC.len()];
+    if (content_info.data_len.0 as usize) < 4 + magic.len() {return Err(ReadWriteError::DataCorruption { data_start: content_info.data_start })}
+    data.read_exact(&mut [0u8;4])?;
+    data.read_exact(&mut magic)?;
+    if magic != B::COMPRESSED_MAGIC {return Err(ReadWriteError::DataCorruption { data_start: content_info.data_start })}
+    Ok(magic)
+}
 /// Reads the content from the reader and writes it to the sink.
 ///
 /// Handles the decompression if the content is compressed.
 /// Returns the number of bytes written to the sink.
+/// Compressed content that does not start with [COMPRESSED_MAGIC](BlockInputs::COMPRESSED_MAGIC) returns [ReadWriteError::DataCorruption],
+/// and content that then fails to decompress returns [ReadWriteError::DecompressionFailed] (see [read_content_with_policy]).
 pub fn read_content<W:std::io::Write, R:std::io::Read + std::io::Seek, B:BlockInputs>(src:&mut R,sink:&mut W,content_info:&Content)->Result<usize,ReadWriteError>{
-    let Content { data_len, data_start, compressed, .. } = *content_info;
-    if let Some(decomp_len) = compressed{
-        src.seek(std::io::SeekFrom::Start(data_start+4))?;
-        let _compressed_len = B::decompress(src,sink,decomp_len)?;
-        Ok(decomp_len as usize)
+    let Content { data_len, data_start, trailing_ecc, .. } = *content_info;
+    if trailing_ecc {
+        return decode_content::<_,_,B>(&mut SkipTrailingEcc::new(src, data_start, data_len.0)?, sink, content_info)
+    }
+    src.seek(std::io::SeekFrom::Start(data_start))?;
+    decode_content::<_,_,B>(src, sink, content_info)
+}
+
+/// Same as [read_content], but the decoded content is passed to `verify` first, and only written to `dest` if it returns true.
+///
+/// For content that carries its own check (a checksum, a Merkle leaf, ...), on top of the block hash.
+/// The whole content is decoded into memory. Returns [ReadWriteError::ContentVerificationFailed] if `verify` returns false, with nothing written.
+pub fn read_content_verified<RW:std::io::Read + std::io::Seek, W:std::io::Write, B:BlockInputs>(file:&mut RW,dest:&mut W,content:&Content,verify:impl FnOnce(&[u8])->bool)->Result<usize,ReadWriteError>{
+    let mut decoded = Vec::new();
+    read_content::<_,_,B>(file, &mut decoded, content)?;
+    if !verify(&decoded) {return Err(ReadWriteError::ContentVerificationFailed { data_start: content.data_start })}
+    dest.write_all(&decoded)?;
+    Ok(decoded.len())
+}
+
+/// Reads content that has ECC but no block hash to check it against, like the last components of an open B block (see [BlockMiddleState::UnexpectedEof]).
+///
+/// The ECC is the only check: each chunk is corrected in memory (nothing is written back), then the content is decompressed to `dest`, as [load_content_decoded] does.
+/// Returns Ok((errors_corrected, bytes written to `dest`)), and [ReadWriteError::EccTooManyErrors] if a chunk can't be corrected.
+/// Content without ECC has nothing to check it with, and returns an [InvalidInput](std::io::ErrorKind::InvalidInput) error.
+pub fn read_content_ecc_only<RW:std::io::Read + std::io::Seek, W:std::io::Write, B:BlockInputs>(file:&mut RW,dest:&mut W,content:&Content)->Result<(usize,usize),ReadWriteError>{
+    if !content.ecc {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "read_content_ecc_only needs content written with ECC").into())
+    }
+    load_content_decoded::<_,_,B>(file, dest, content)
+}
+
+/// Same as [read_content], but the stored bytes are read `chunk` bytes at a time through a pair of buffers.
+///
+/// The next chunk is always loaded before the current one is handed to the decompressor, so every decode step works from memory
+/// and storage sees a steady run of `chunk` sized reads instead of the small reads a decompressor tends to make.
+/// A `chunk` of 0 is treated as 1.
+pub fn read_content_prefetched<W:std::io::Write, R:std::io::Read + std::io::Seek, B:BlockInputs>(src:&mut R,sink:&mut W,content_info:&Content,chunk:usize)->Result<usize,ReadWriteError>{
+    let Content { data_len:StoredLen(data_len), data_start, trailing_ecc, .. } = *content_info;
+    if trailing_ecc {
+        let mut data = PrefetchReader::new(SkipTrailingEcc::new(src, data_start, data_len)?, chunk)?;
+        return decode_content::<_,_,B>(&mut data, sink, content_info)
+    }
+    src.seek(std::io::SeekFrom::Start(data_start))?;
+    let mut data = PrefetchReader::new(std::io::Read::take(src, data_len as u64), chunk)?;
+    decode_content::<_,_,B>(&mut data, sink, content_info)
+}
+
+/// Writes the last `tail_bytes` of the content to `dest` (all of it if the content is shorter), and returns how many bytes were written.
+///
+/// Content without ECC is read from `data_start + data_len - tail_bytes` on. Content with ECC reads only the ECC chunks covering the tail, and corrects them in memory
+/// (nothing is written back), returning [ReadWriteError::EccTooManyErrors] for an uncorrectable chunk.
+/// Compressed content can not be read from the middle, and returns an [InvalidInput](std::io::ErrorKind::InvalidInput) error.
+pub fn read_content_tail<R:std::io::Read + std::io::Seek, W:std::io::Write, B:BlockInputs>(file:&mut R,dest:&mut W,content:&Content,tail_bytes:u64)->Result<usize,ReadWriteError>{
+    let Content { data_len:StoredLen(data_len), data_start, ecc, compressed, .. } = *content;
+    if compressed.is_some() {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "read_content_tail can not read compressed content").into())
+    }
+    let data_len = data_len as usize;
+    let tail = (tail_bytes.min(data_len as u64)) as usize;
+    let tail_start = data_len - tail;
+    if !ecc {
+        file.seek(std::io::SeekFrom::Start(data_start + tail_start as u64))?;
+        copy_n(file, dest, tail)?;
+        return Ok(tail)
+    }
+    let decoder = ChunkDecoder::new();
+    let mut chunk = [0u8;DATA_SIZE+ECC_LEN];
+    for i in tail_start/DATA_SIZE..data_len.div_ceil(DATA_SIZE) {
+        let chunk_data_len = read_ecc_chunk(file, content, i, &mut chunk)?;
+        decoder.apply(&mut chunk[..chunk_data_len+ECC_LEN])?;
+        let skip = tail_start.saturating_sub(i*DATA_SIZE);
+        dest.write_all(&chunk[skip..chunk_data_len])?;
+    }
+    Ok(tail)
+}
+
+///Reads ECC chunk `i` of `content` (which must have ECC) into `chunk` as a codeword, the data followed by its ECC, in either layout.
+///Returns the length of the chunk's data.
+fn read_ecc_chunk<R:std::io::Read + std::io::Seek>(file:&mut R,content:&Content,i:usize,chunk:&mut [u8;DATA_SIZE+ECC_LEN])->Result<usize,ReadWriteError>{
+    let Content { data_len:StoredLen(data_len), data_start, trailing_ecc, .. } = *content;
+    let data_len = data_len as usize;
+    let chunk_data_len = (data_len - i*DATA_SIZE).min(DATA_SIZE);
+    if trailing_ecc {
+        file.seek(std::io::SeekFrom::Start(data_start + (i*(DATA_SIZE+ECC_LEN)) as u64))?;
+        file.read_exact(&mut chunk[..chunk_data_len+ECC_LEN])?;
     }else{
-        src.seek(std::io::SeekFrom::Start(data_start))?;
-        copy_n(src, sink, data_len as usize)?;
-        Ok(data_len as usize)
+        let ecc_start = data_start.checked_sub(calc_ecc_data_len(data_len) as u64)
+            .ok_or_else(||std::io::Error::new(std::io::ErrorKind::InvalidInput, "Content ECC would start before the file"))?;
+        file.seek(std::io::SeekFrom::Start(data_start + (i*DATA_SIZE) as u64))?;
+        file.read_exact(&mut chunk[..chunk_data_len])?;
+        file.seek(std::io::SeekFrom::Start(ecc_start + (i*ECC_LEN) as u64))?;
+        file.read_exact(&mut chunk[chunk_data_len..chunk_data_len+ECC_LEN])?;
     }
+    Ok(chunk_data_len)
+}
+
+/// Same as [read_content], but the content ECC is applied first: each stored (compressed, if it is) chunk is corrected in memory, then streamed to the decompressor.
+///
+/// This is the reverse of the write path, which compresses and then computes the ECC over the compressed bytes.
+/// Nothing is written back, so the same corrections are made on every load until the block is recovered or integrity checked.
+/// Returns Ok((errors_corrected, bytes written to the sink)), and [ReadWriteError::EccTooManyErrors] if a chunk can't be corrected.
+/// Content without ECC is read as [read_content] does.
+pub fn load_content_decoded<W:std::io::Write, R:std::io::Read + std::io::Seek, B:BlockInputs>(src:&mut R,sink:&mut W,content_info:&Content)->Result<(usize,usize),ReadWriteError>{
+    if !content_info.ecc {
+        return read_content::<_,_,B>(src, sink, content_info).map(|n|(0,n))
+    }
+    let mut data = LazyContentReader::<_,B>::stored(src, content_info);
+    match decode_content::<_,_,B>(&mut data, sink, content_info) {
+        _ if data.uncorrectable => Err(ReadWriteError::EccTooManyErrors),
+        res => res.map(|n|(data.errors,n)),
+    }
+}
+
+/// [Read](std::io::Read) over uncompressed content that applies the content ECC one `DATA_SIZE` chunk at a time, as the bytes are consumed.
+///
+/// A consumer that stops early (e.g. after parsing a header at the front of the content) never loads or corrects the chunks after it.
+/// Corrections are made in memory only, nothing is written back. An uncorrectable chunk is an [InvalidData](std::io::ErrorKind::InvalidData) error from `read`.
+/// Content without ECC is read as it is.
+pub struct LazyContentReader<'a,R,B>{
+    file:&'a mut R,
+    content:Content,
+    decoder:ChunkDecoder,
+    chunk:[u8;DATA_SIZE+ECC_LEN],
+    ///The next chunk to load.
+    next:usize,
+    ///The unread data is `chunk[pos..len]`.
+    pos:usize,
+    len:usize,
+    chunks_checked:usize,
+    errors:usize,
+    uncorrectable:bool,
+    _b:std::marker::PhantomData<B>
+}
+impl<'a,R:std::io::Read + std::io::Seek,B:BlockInputs> LazyContentReader<'a,R,B> {
+    ///Errors with InvalidInput if `content` is compressed, as the decompressor needs all of it (see [load_content_decoded]).
+    pub fn new(file:&'a mut R,content:&Content)->Result<Self,ReadWriteError>{
+        if content.compressed.is_some() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "LazyContentReader can not read compressed content").into())
+        }
+        Ok(Self::stored(file, content))
+    }
+    ///Reads the stored bytes, compressed or not.
+    fn stored(file:&'a mut R,content:&Content)->Self{
+        Self { file, content: *content, decoder: ChunkDecoder::new(), chunk: [0u8;DATA_SIZE+ECC_LEN], next: 0, pos: 0, len: 0, chunks_checked: 0, errors: 0, uncorrectable: false, _b: std::marker::PhantomData }
+    }
+    ///How many chunks have been loaded and checked against their ECC so far (0 for content without ECC).
+    pub fn chunks_checked(&self)->usize{
+        self.chunks_checked
+    }
+    ///How many errors the ECC corrected in the chunks loaded so far.
+    pub fn errors_corrected(&self)->usize{
+        self.errors
+    }
+    fn load_chunk(&mut self)->Result<usize,ReadWriteError>{
+        let Content { data_len:StoredLen(data_len), data_start, ecc, .. } = self.content;
+        if !ecc {
+            let n = (data_len as usize - self.next*DATA_SIZE).min(DATA_SIZE);
+            self.file.seek(std::io::SeekFrom::Start(data_start + (self.next*DATA_SIZE) as u64))?;
+            self.file.read_exact(&mut self.chunk[..n])?;
+            return Ok(n)
+        }
+        let chunk_data_len = read_ecc_chunk(self.file, &self.content, self.next, &mut self.chunk)?;
+        self.chunks_checked += 1;
+        match self.decoder.apply(&mut self.chunk[..chunk_data_len+ECC_LEN]) {
+            Ok(e) => self.errors += e,
+            Err(_) => {
+                self.uncorrectable = true;
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("ECC chunk {} is uncorrectable",self.next)).into())
+            },
+        }
+        Ok(chunk_data_len)
+    }
+}
+impl<R:std::io::Read + std::io::Seek,B:BlockInputs> std::io::Read for LazyContentReader<'_,R,B> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos == self.len {
+            if self.next*DATA_SIZE >= self.content.data_len.0 as usize {return Ok(0)}
+            let chunk_data_len = self.load_chunk().map_err(|e|match e {
+                ReadWriteError::Io(e) => e,
+                ReadWriteError::EndOfFile => std::io::ErrorKind::UnexpectedEof.into(),
+                e => std::io::Error::other(e.to_string()),
+            })?;
+            (self.next,self.pos,self.len) = (self.next + 1,0,chunk_data_len);
+        }
+        let n = buf.len().min(self.len - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos+n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Yields the content (decompressed if need be) in `chunk_size` pieces, each with its offset in the content (not the file).
+///
+/// Stored content is read a chunk at a time. Compressed content is decompressed into memory first, as the decompressor writes to a sink.
+/// The content ECC is not applied. After the first error nothing more is yielded. A `chunk_size` of 0 is treated as 1.
+pub fn content_chunks<'a, RW:std::io::Read + std::io::Seek, B:BlockInputs>(file:&'a mut RW,content:&Content,chunk_size:usize)->impl Iterator<Item = Result<(u64,Vec<u8>),ReadWriteError>> + 'a{
+    let chunk_size = chunk_size.max(1);
+    let mut state = Some(content_reader::<_,B>(file, content));
+    let mut offset = 0u64;
+    std::iter::from_fn(move ||{
+        let reader = match state.as_mut()? {
+            Ok(reader) => reader,
+            Err(_) => return state.take().and_then(Result::err).map(Err),
+        };
+        let mut buf = vec![0u8;chunk_size];
+        let mut n = 0;
+        while n < chunk_size {
+            match reader.read(&mut buf[n..]) {
+                Ok(0) => break,
+                Ok(m) => n += m,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    state = None;
+                    return Some(Err(e.into()))
+                },
+            }
+        }
+        if n == 0 {
+            state = None;
+            return None
+        }
+        buf.truncate(n);
+        let chunk_offset = offset;
+        offset += n as u64;
+        Some(Ok((chunk_offset,buf)))
+    })
+}
+fn content_reader<'a, RW:std::io::Read + std::io::Seek, B:BlockInputs>(file:&'a mut RW,content:&Content)->Result<Box<dyn std::io::Read + 'a>,ReadWriteError>{
+    let Content { data_len:StoredLen(data_len), data_start, trailing_ecc, compressed, .. } = *content;
+    if compressed.is_some() {
+        let mut decoded = Vec::new();
+        read_content::<_,_,B>(file, &mut decoded, content)?;
+        return Ok(Box::new(std::io::Cursor::new(decoded)))
+    }
+    if trailing_ecc {
+        return Ok(Box::new(SkipTrailingEcc::new(file, data_start, data_len)?))
+    }
+    file.seek(std::io::SeekFrom::Start(data_start))?;
+    Ok(Box::new(std::io::Read::take(file, data_len as u64)))
+}
+
+/// Decompresses (if need be) the stored bytes of the content from `data`, which must be positioned at the first data byte.
+fn decode_content<D:std::io::Read, W:std::io::Write, B:BlockInputs>(data:&mut D,sink:&mut W,content_info:&Content)->Result<usize,ReadWriteError>{
+    match content_info.compressed {
+        Some(_) if content_info.seekable => decode_frames::<_,_,B>(data, sink, content_info),
+        Some(OriginalLen(decomp_len)) => {
+            let magic = check_compressed_magic::<_,B>(data, content_info)?;
+            let (mut src,mut out) = (Tracked::new(std::io::Read::chain(&magic[..], data)),Tracked::new(sink));
+            if let Err(e) = B::decompress(&mut src,&mut out,decomp_len) {
+                //only blame the data if neither side of the decompressor failed
+                if src.failed || out.failed {return Err(e.into())}
+                return Err(ReadWriteError::DecompressionFailed { data_start: content_info.data_start })
+            }
+            Ok(decomp_len as usize)
+        },
+        None => {
+            let StoredLen(data_len) = content_info.data_len;
+            copy_n(data, sink, data_len as usize)?;
+            Ok(data_len as usize)
+        }
+    }
+}
+
+/// Decompresses [SEEKABLE] content frame by frame, `data` positioned at the first data byte.
+fn decode_frames<D:std::io::Read, W:std::io::Write, B:BlockInputs>(data:&mut D,sink:&mut W,content_info:&Content)->Result<usize,ReadWriteError>{
+    let table = SeekTable::read(data, content_info)?;
+    let mut out = Tracked::new(sink);
+    for (i,&stored) in table.frames.iter().enumerate() {
+        let mut src = Tracked::new(std::io::Read::take(&mut *data, stored as u64));
+        if let Err(e) = B::decompress(&mut src,&mut out,table.frame_out_len(i)) {
+            if src.failed || out.failed {return Err(e.into())}
+            return Err(ReadWriteError::DecompressionFailed { data_start: content_info.data_start })
+        }
+        //the decompressor may stop short of the end of its frame
+        std::io::copy(&mut src.inner, &mut std::io::sink())?;
+    }
+    Ok(table.orig_len as usize)
+}
+
+/// The frame table at the start of [SEEKABLE] content, see [write_seekable_content_component](crate::write::write_seekable_content_component).
+struct SeekTable{
+    orig_len:u32,
+    frame_len:u32,
+    ///Compressed length of each frame, in order.
+    frames:Vec<u32>
+}
+impl SeekTable {
+    ///Reads the table from `data`, positioned at the first data byte, leaving it at the first frame.
+    ///Returns [ReadWriteError::DataCorruption] if the table does not add up to the stored length.
+    fn read<R:std::io::Read>(data:&mut R,content_info:&Content)->Result<Self,ReadWriteError>{
+        let corrupt = ||ReadWriteError::DataCorruption { data_start: content_info.data_start };
+        let stored = content_info.data_len.0 as u64;
+        if stored < SEEK_TABLE_HEADER_LEN as u64 {return Err(corrupt())}
+        let mut head = [0u8;SEEK_TABLE_HEADER_LEN];
+        data.read_exact(&mut head)?;
+        let [orig_len,frame_len,num_frames] = [0,4,8].map(|i|u32::from_be_bytes(head[i..i+4].try_into().unwrap()));
+        if frame_len == 0 || num_frames != orig_len.div_ceil(frame_len) {return Err(corrupt())}
+        let table_len = (SEEK_TABLE_HEADER_LEN + num_frames as usize*4) as u64;
+        if table_len > stored {return Err(corrupt())}
+        let mut entries = vec![0u8;num_frames as usize*4];
+        data.read_exact(&mut entries)?;
+        let frames:Vec<u32> = entries.chunks_exact(4).map(|e|u32::from_be_bytes(e.try_into().unwrap())).collect();
+        if table_len + frames.iter().map(|&f|f as u64).sum::<u64>() != stored {return Err(corrupt())}
+        Ok(Self { orig_len, frame_len, frames })
+    }
+    ///Decompressed length of frame `i`, only the last may be short.
+    fn frame_out_len(&self,i:usize)->u32{
+        self.frame_len.min(self.orig_len - i as u32*self.frame_len)
+    }
+}
+
+/// [Read](std::io::Read) + [Seek](std::io::Seek) over the decompressed view of [SEEKABLE] content, decompressing only the frames a read touches.
+///
+/// The last frame decompressed is kept, so sequential reads decompress each frame once.
+/// The stored bytes are read as they are, without ECC, so check the block first (e.g. [try_read_block](crate::recovery::try_read_block) with error correction).
+/// A frame that fails to decompress is an [InvalidData](std::io::ErrorKind::InvalidData) error from `read`.
+pub struct SeekableContentReader<'a,R,B>{
+    file:&'a mut R,
+    table:SeekTable,
+    ///Position of each frame in the file.
+    frame_starts:Vec<u64>,
+    pos:u64,
+    frame:Option<(usize,Vec<u8>)>,
+    _b:std::marker::PhantomData<B>
+}
+impl<'a,R:std::io::Read + std::io::Seek,B:BlockInputs> SeekableContentReader<'a,R,B> {
+    ///Reads the frame table of `content`. Errors with InvalidInput if it is not [SEEKABLE], and [ReadWriteError::DataCorruption] if its table does not add up.
+    pub fn new(file:&'a mut R,content:&Content)->Result<Self,ReadWriteError>{
+        if !content.seekable || content.compressed.is_none() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "content is not seekable").into())
+        }
+        file.seek(std::io::SeekFrom::Start(content.data_start))?;
+        let table = SeekTable::read(file, content)?;
+        let first = content.data_start + (SEEK_TABLE_HEADER_LEN + table.frames.len()*4) as u64;
+        let frame_starts = table.frames.iter().scan(first, |next,&f|{let start = *next; *next += f as u64; Some(start)}).collect();
+        Ok(Self { file, table, frame_starts, pos: 0, frame: None, _b: std::marker::PhantomData })
+    }
+    ///The decompressed length.
+    pub fn len(&self)->u64{
+        self.table.orig_len as u64
+    }
+    pub fn is_empty(&self)->bool{
+        self.len() == 0
+    }
+    fn load_frame(&mut self,i:usize)->std::io::Result<&[u8]>{
+        if self.frame.as_ref().map(|(f,_)|*f) != Some(i) {
+            let out_len = self.table.frame_out_len(i);
+            self.file.seek(std::io::SeekFrom::Start(self.frame_starts[i]))?;
+            let mut src = Tracked::new(std::io::Read::take(&mut *self.file, self.table.frames[i] as u64));
+            let mut out = Vec::with_capacity(out_len as usize);
+            match B::decompress(&mut src, &mut out, out_len) {
+                Err(e) if src.failed => return Err(e),
+                Ok(_) if out.len() == out_len as usize => (),
+                _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Frame {} failed to decompress",i))),
+            }
+            self.frame = Some((i,out));
+        }
+        Ok(&self.frame.as_ref().unwrap().1)
+    }
+}
+impl<'a,R:std::io::Read + std::io::Seek,B:BlockInputs> std::io::Read for SeekableContentReader<'a,R,B> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos >= self.len() || buf.is_empty() {return Ok(0)}
+        let frame_len = self.table.frame_len as u64;
+        let (i,offset) = ((self.pos / frame_len) as usize,(self.pos % frame_len) as usize);
+        let frame = self.load_frame(i)?;
+        let n = buf.len().min(frame.len() - offset);
+        buf[..n].copy_from_slice(&frame[offset..offset+n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+impl<'a,R:std::io::Read + std::io::Seek,B:BlockInputs> std::io::Seek for SeekableContentReader<'a,R,B> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let new_pos = match pos {
+            std::io::SeekFrom::Start(n) => Some(n),
+            std::io::SeekFrom::End(d) => self.len().checked_add_signed(d),
+            std::io::SeekFrom::Current(d) => self.pos.checked_add_signed(d),
+        };
+        let Some(new_pos) = new_pos else {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position"))
+        };
+        self.pos = new_pos;
+        Ok(new_pos)
+    }
+}
+
+/// Passes reads or writes through, counting the bytes and remembering if the inner side ever returned an error.
+struct Tracked<T>{
+    inner:T,
+    count:usize,
+    failed:bool
+}
+impl<T> Tracked<T> {
+    fn new(inner:T)->Self{
+        Self { inner, count: 0, failed: false }
+    }
+    fn track(&mut self,res:std::io::Result<usize>)->std::io::Result<usize>{
+        match &res {
+            Ok(n) => self.count += n,
+            Err(_) => self.failed = true,
+        }
+        res
+    }
+}
+impl<T:std::io::Read> std::io::Read for Tracked<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let res = self.inner.read(buf);
+        self.track(res)
+    }
+}
+impl<T:std::io::Write> std::io::Write for Tracked<T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let res = self.inner.write(buf);
+        self.track(res)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush().inspect_err(|_|self.failed = true)
+    }
+}
+
+/// What [read_content_with_policy] does with compressed content that fails to decompress,
+/// either [ReadWriteError::DecompressionFailed] or [ReadWriteError::DataCorruption] (missing [COMPRESSED_MAGIC](BlockInputs::COMPRESSED_MAGIC)).
+#[derive(Copy,Clone,Debug,Default,PartialEq,Eq)]
+pub enum DecompressPolicy{
+    /// Return the error, as [read_content] does. The sink may hold part of the output.
+    #[default]
+    Error,
+    /// Write the stored bytes (the original length prefix and the compressed frame) to the sink instead.
+    /// Decompressed output is buffered, so nothing partial reaches the sink first.
+    Raw,
+    /// Keep whatever decompressed before the failure in the sink.
+    Partial,
+}
+/// How [read_content_with_policy] filled the sink, with the number of bytes written.
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub enum DecodedContent{
+    Complete(usize),
+    ///Decompression failed, the sink holds the stored bytes. See [DecompressPolicy::Raw].
+    Raw(usize),
+    ///Decompression failed, the sink holds what decompressed before the failure. See [DecompressPolicy::Partial].
+    Partial(usize),
+}
+impl DecodedContent {
+    pub fn len(&self)->usize{
+        match self {
+            DecodedContent::Complete(n) | DecodedContent::Raw(n) | DecodedContent::Partial(n) => *n,
+        }
+    }
+    pub fn is_empty(&self)->bool{
+        self.len() == 0
+    }
+    pub fn is_complete(&self)->bool{
+        matches!(self,DecodedContent::Complete(_))
+    }
+}
+/// Same as [read_content], but compressed content that fails to decompress is handled as `policy` says.
+///
+/// Uncompressed content always reads as [DecodedContent::Complete].
+pub fn read_content_with_policy<W:std::io::Write, R:std::io::Read + std::io::Seek, B:BlockInputs>(src:&mut R,sink:&mut W,content_info:&Content,policy:DecompressPolicy)->Result<DecodedContent,ReadWriteError>{
+    match policy {
+        DecompressPolicy::Error => read_content::<_,_,B>(src, sink, content_info).map(DecodedContent::Complete),
+        DecompressPolicy::Partial => {
+            let mut out = Tracked::new(sink);
+            match read_content::<_,_,B>(src, &mut out, content_info) {
+                Err(ReadWriteError::DecompressionFailed { .. } | ReadWriteError::DataCorruption { .. }) => Ok(DecodedContent::Partial(out.count)),
+                res => res.map(DecodedContent::Complete),
+            }
+        },
+        DecompressPolicy::Raw => {
+            let mut buf = Vec::new();
+            match read_content::<_,_,B>(src, &mut buf, content_info) {
+                Ok(n) => {
+                    sink.write_all(&buf)?;
+                    Ok(DecodedContent::Complete(n))
+                },
+                Err(ReadWriteError::DecompressionFailed { .. } | ReadWriteError::DataCorruption { .. }) => {
+                    let stored = Content { compressed: None, ..*content_info };
+                    read_content::<_,_,B>(src, sink, &stored).map(DecodedContent::Raw)
+                },
+                Err(e) => Err(e),
+            }
+        },
+    }
+}
+
+/// Double buffered reader for [read_content_prefetched]: one buffer is read from while the other already holds the next chunk.
+struct PrefetchReader<R>{
+    inner:R,
+    bufs:[Vec<u8>;2],
+    filled:[usize;2],
+    ///Index of the buffer being read from
+    cur:usize,
+    pos:usize
+}
+impl<R:std::io::Read> PrefetchReader<R> {
+    fn new(inner:R,chunk:usize)->std::io::Result<Self>{
+        let chunk = chunk.max(1);
+        let mut s = Self { inner, bufs: [vec![0u8;chunk],vec![0u8;chunk]], filled: [0,0], cur: 0, pos: 0 };
+        s.fill(0)?;
+        s.fill(1)?;
+        Ok(s)
+    }
+    fn fill(&mut self,i:usize)->std::io::Result<()>{
+        let mut n = 0;
+        while n < self.bufs[i].len() {
+            match self.inner.read(&mut self.bufs[i][n..]) {
+                Ok(0) => break,
+                Ok(m) => n += m,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        self.filled[i] = n;
+        Ok(())
+    }
+}
+impl<R:std::io::Read> std::io::Read for PrefetchReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos == self.filled[self.cur] {
+            if self.filled[self.cur] == 0 {return Ok(0)}
+            //hand over the prefetched chunk, then load the one after it into the spent buffer
+            let spent = self.cur;
+            self.cur ^= 1;
+            self.pos = 0;
+            self.fill(spent)?;
+        }
+        let avail = &self.bufs[self.cur][self.pos..self.filled[self.cur]];
+        let n = avail.len().min(buf.len());
+        buf[..n].copy_from_slice(&avail[..n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Checks the content against its own [component_hash](Content::component_hash), without reading any other component of the block.
+///
+/// The hash covers the stored bytes (compressed, if compressed), not the content ECC. Apply the ECC first (e.g. [check_read_content]) to verify corrected data.
+/// Returns Ok(false) if the content does not match, or was written without a component hash.
+pub fn verify_component<RW:std::io::Read + std::io::Seek, B:BlockInputs>(file:&mut RW,content:&Content)->Result<bool,ReadWriteError>{
+    let Content { data_len:StoredLen(data_len), data_start, trailing_ecc, component_hash, .. } = *content;
+    let Some(component_hash) = component_hash else {return Ok(false)};
+    let mut hasher = B::new();
+    let mut sink = std::io::sink();
+    let mut ha = HashAdapter::new(&mut sink, &mut hasher);
+    if trailing_ecc {
+        copy_n(&mut SkipTrailingEcc::new(file, data_start, data_len)?, &mut ha, data_len as usize)?;
+    }else{
+        file.seek(std::io::SeekFrom::Start(data_start))?;
+        copy_n(file, &mut ha, data_len as usize)?;
+    }
+    Ok(hasher.finalize() == component_hash)
+}
+
+/// Same as [read_content], but `file` is the whole file in memory (e.g. a writable mmap), and the content ECC is applied first.
+///
+/// Chunks are corrected in place by indexing into `file`, nothing seeks, so this is the fastest correction path for mmap'd files.
+/// Returns Ok((errors_corrected, bytes written to the sink)).
+/// Returns EccTooManyErrors if a chunk can't be corrected (chunks before it stay corrected), and EndOfFile if the content runs past `file`.
+pub fn read_content_slice<W:std::io::Write, B:BlockInputs>(file:&mut [u8],sink:&mut W,content_info:&Content)->Result<(usize,usize),ReadWriteError>{
+    let errors = if content_info.ecc {correct_content_slice(file, content_info)?}else{0};
+    let written = read_content::<_,_,B>(&mut std::io::Cursor::new(&*file), sink, content_info)?;
+    Ok((errors,written))
+}
+
+/// Returns the content of an in-memory file, borrowing it when the stored bytes already are the content.
+///
+/// Content that is neither compressed nor has ECC is returned as [Cow::Borrowed](std::borrow::Cow::Borrowed) straight out of `src`.
+/// Anything else is loaded with [load_content_decoded] (corrected in memory, then decompressed) and returned as [Cow::Owned](std::borrow::Cow::Owned).
+/// Returns EndOfFile if the content runs past `src`.
+pub fn content_cow<'a, B:BlockInputs>(src:&'a [u8],content_info:&Content)->Result<std::borrow::Cow<'a,[u8]>,ReadWriteError>{
+    let Content { data_len:StoredLen(data_len), data_start, ecc, compressed, .. } = *content_info;
+    if !ecc && compressed.is_none() {
+        let bytes = usize::try_from(data_start).ok().and_then(|start|src.get(start..start.checked_add(data_len as usize)?)).ok_or(ReadWriteError::EndOfFile)?;
+        return Ok(std::borrow::Cow::Borrowed(bytes))
+    }
+    let mut out = Vec::new();
+    load_content_decoded::<_,_,B>(&mut std::io::Cursor::new(src), &mut out, content_info)?;
+    Ok(std::borrow::Cow::Owned(out))
+}
+
+fn correct_content_slice(file:&mut [u8],content_info:&Content)->Result<usize,ReadWriteError>{
+    let Content { data_len:StoredLen(data_len), data_start, trailing_ecc, .. } = *content_info;
+    let (data_len,data_start) = (data_len as usize,data_start as usize);
+    let ecc_len = calc_ecc_data_len(data_len);
+    let region_start = if trailing_ecc {data_start}else{data_start.checked_sub(ecc_len).ok_or(ReadWriteError::EccTooManyErrors)?};
+    let region = file.get_mut(region_start..region_start + ecc_len + data_len).ok_or(ReadWriteError::EndOfFile)?;
+    let mut chunk = [0u8;DATA_SIZE+ECC_LEN];
+    let mut errors = 0;
+    let decoder = ChunkDecoder::new();
+    for i in 0..ecc_len/ECC_LEN {
+        let chunk_data_len = (data_len - i*DATA_SIZE).min(DATA_SIZE);
+        if trailing_ecc {
+            //data then its ECC, already a codeword
+            errors += decoder.apply(&mut region[i*(DATA_SIZE+ECC_LEN)..][..chunk_data_len+ECC_LEN])?;
+            continue;
+        }
+        let (ecc,data) = region.split_at_mut(ecc_len);
+        let (data,ecc) = (&mut data[i*DATA_SIZE..][..chunk_data_len],&mut ecc[i*ECC_LEN..][..ECC_LEN]);
+        chunk[..chunk_data_len].copy_from_slice(data);
+        chunk[chunk_data_len..chunk_data_len+ECC_LEN].copy_from_slice(ecc);
+        let e = decoder.apply(&mut chunk[..chunk_data_len+ECC_LEN])?;
+        if e > 0 {
+            data.copy_from_slice(&chunk[..chunk_data_len]);
+            ecc.copy_from_slice(&chunk[chunk_data_len..chunk_data_len+ECC_LEN]);
+            errors += e;
+        }
+    }
+    Ok(errors)
+}
+
+/// Hashes just the stored bytes of the given content with a fresh hasher and returns the digest.
+///
+/// This does not include the header or ECC data, and does not decompress, so it is cheap enough for delta/dedup tooling.
+/// This is **not** the block hash, and does no ECC (you should have integrity checked already).
+pub fn content_fingerprint<R:std::io::Read + std::io::Seek, B:BlockInputs>(file:&mut R,content:&Content)->Result<[u8;HASH_LEN],ReadWriteError>{
+    let Content { data_len, data_start, trailing_ecc, .. } = content;
+    let mut hasher = B::new();
+    if *trailing_ecc {
+        buffer_hash(&mut SkipTrailingEcc::new(file, *data_start, data_len.0)?, data_len.0 as usize, &mut hasher)?;
+    }else{
+        file.seek(std::io::SeekFrom::Start(*data_start))?;
+        buffer_hash(file, data_len.0 as usize, &mut hasher)?;
+    }
+    Ok(hasher.finalize())
+}
+
+/// Wraps a reader so that any error corrections written back by the read functions are collected instead of written.
+///
+/// Seeks go to the inner reader, a write records `(position, bytes)` and moves the position past them.
+/// Reads see the collected patches laid over the inner bytes, so the read functions behave the same as with inline write backs.
+/// Call [into_patches](DeferredPatches::into_patches) when done and hand them to [apply_patches].
+#[derive(Debug)]
+pub struct DeferredPatches<'a,R>{
+    inner:&'a mut R,
+    patches:Vec<(u64,Vec<u8>)>
+}
+impl<'a,R:std::io::Read + std::io::Seek> DeferredPatches<'a,R> {
+    pub fn new(inner:&'a mut R)->Self{
+        Self { inner, patches: Vec::new() }
+    }
+    pub fn patches(&self)->&[(u64,Vec<u8>)]{
+        &self.patches
+    }
+    pub fn into_patches(self)->Vec<(u64,Vec<u8>)>{
+        self.patches
+    }
+}
+impl<R:std::io::Read + std::io::Seek> std::io::Read for DeferredPatches<'_,R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let start = self.inner.stream_position()?;
+        let n = self.inner.read(buf)?;
+        let end = start + n as u64;
+        for (pos,bytes) in self.patches.iter() {
+            let p_end = pos + bytes.len() as u64;
+            if *pos >= end || p_end <= start {continue}
+            let (from,to) = ((*pos).max(start),p_end.min(end));
+            buf[(from-start) as usize..(to-start) as usize].copy_from_slice(&bytes[(from-pos) as usize..(to-pos) as usize]);
+        }
+        Ok(n)
+    }
+}
+impl<R:std::io::Read + std::io::Seek> std::io::Seek for DeferredPatches<'_,R> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+impl<R:std::io::Read + std::io::Seek> std::io::Write for DeferredPatches<'_,R> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let pos = self.inner.stream_position()?;
+        self.patches.push((pos,buf.to_vec()));
+        self.inner.seek(std::io::SeekFrom::Current(buf.len() as i64))?;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes all the patches in a single pass, sorted by file offset.
+///
+/// Overlapping patches only come from correcting the same region more than once, so they carry the same bytes.
+/// Returns the number of patches written.
+pub fn apply_patches<W:std::io::Write + std::io::Seek>(file:&mut W,mut patches:Vec<(u64,Vec<u8>)>)->Result<usize,ReadWriteError>{
+    //stable sort keeps collection order for the same offset
+    patches.sort_by_key(|(pos,_)|*pos);
+    for (pos,bytes) in patches.iter() {
+        file.seek(std::io::SeekFrom::Start(*pos))?;
+        file.write_all(bytes)?;
+    }
+    Ok(patches.len())
+}
+
+/// Prefetches a window of bytes so the many small reads of a forward scan are served from memory.
+///
+/// A read outside the window (after a backward seek or past its end) refills it from the current position.
+/// Writes go straight through to the inner file and drop the window, so ECC write backs are never hidden by stale bytes.
+/// Reads larger than the window skip the buffer.
+#[derive(Debug)]
+pub struct ReadAheadReader<R>{
+    inner:R,
+    buf:Vec<u8>,
+    ///File offset of `buf[0]`
+    buf_start:u64,
+    window:usize,
+    ///Logical position of this reader
+    pos:u64,
+    ///Position of the inner reader, if known
+    inner_pos:Option<u64>
+}
+impl<R:std::io::Read + std::io::Seek> ReadAheadReader<R> {
+    /// `window` is the number of bytes to prefetch on each refill.
+    pub fn new(mut inner:R,window:usize)->std::io::Result<Self>{
+        let pos = inner.stream_position()?;
+        Ok(Self { inner, buf: Vec::with_capacity(window), buf_start: pos, window:window.max(1), pos, inner_pos: Some(pos) })
+    }
+    pub fn get_ref(&self)->&R{
+        &self.inner
+    }
+    /// Drops the window, since the caller may change the inner file.
+    pub fn get_mut(&mut self)->&mut R{
+        self.invalidate();
+        &mut self.inner
+    }
+    pub fn into_inner(self)->R{
+        self.inner
+    }
+    pub fn invalidate(&mut self){
+        self.buf.clear();
+        self.inner_pos = None;
+    }
+    fn seek_inner(&mut self,pos:u64)->std::io::Result<()>{
+        if self.inner_pos != Some(pos) {
+            self.inner.seek(std::io::SeekFrom::Start(pos))?;
+            self.inner_pos = Some(pos);
+        }
+        Ok(())
+    }
+    fn fill(&mut self)->std::io::Result<()>{
+        self.seek_inner(self.pos)?;
+        self.buf.resize(self.window, 0);
+        let mut filled = 0;
+        while filled < self.window {
+            match self.inner.read(&mut self.buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => {self.invalidate(); return Err(e)},
+            }
+        }
+        self.buf.truncate(filled);
+        self.buf_start = self.pos;
+        self.inner_pos = Some(self.pos + filled as u64);
+        Ok(())
+    }
+}
+impl<R:std::io::Read + std::io::Seek> std::io::Read for ReadAheadReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let in_window = self.pos >= self.buf_start && self.pos < self.buf_start + self.buf.len() as u64;
+        if !in_window {
+            if buf.len() >= self.window {
+                self.seek_inner(self.pos)?;
+                let n = self.inner.read(buf)?;
+                self.pos += n as u64;
+                self.inner_pos = Some(self.pos);
+                return Ok(n)
+            }
+            self.fill()?;
+        }
+        let from = (self.pos - self.buf_start) as usize;
+        let n = buf.len().min(self.buf.len() - from);
+        buf[..n].copy_from_slice(&self.buf[from..from+n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+impl<R:std::io::Read + std::io::Seek> std::io::Seek for ReadAheadReader<R> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let new_pos = match pos {
+            std::io::SeekFrom::Start(p) => Some(p),
+            std::io::SeekFrom::Current(off) => self.pos.checked_add_signed(off),
+            std::io::SeekFrom::End(_) => {
+                let p = self.inner.seek(pos)?;
+                self.inner_pos = Some(p);
+                Some(p)
+            },
+        };
+        let new_pos = new_pos.ok_or_else(||std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position"))?;
+        if new_pos < self.buf_start {self.buf.clear()}
+        self.pos = new_pos;
+        Ok(new_pos)
+    }
+}
+impl<R:std::io::Read + std::io::Seek + std::io::Write> std::io::Write for ReadAheadReader<R> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.clear();
+        self.seek_inner(self.pos)?;
+        let n = match self.inner.write(buf) {
+            Ok(n) => n,
+            Err(e) => {self.invalidate(); return Err(e)},
+        };
+        self.pos += n as u64;
+        self.inner_pos = Some(self.pos);
+        Ok(n)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+impl<R:crate::FileLike> crate::FileLike for ReadAheadReader<R> {
+    fn truncate(&mut self, len: u64)->std::io::Result<()> {
+        self.invalidate();
+        self.inner.truncate(len)
+    }
+    fn len(&self)->std::io::Result<u64> {
+        self.inner.len()
+    }
+    fn sync(&mut self)->std::io::Result<()> {
+        self.inner.sync()
+    }
+}
+
+/// Caches the logical position of a file so position queries (`seek(Current(0))`) and seeks to where the file already is never reach the inner file.
+///
+/// The read helpers ask for the position constantly, and seek back after ECC write backs, which serializes badly on a shared handle.
+/// Wrap the handle once and pass the wrapper to [read_header], [read_content], [read_hash], etc.
+/// After a failed read or write the position is unknown, and the next seek asks the inner file.
+#[derive(Debug)]
+pub struct PositionedFile<RW>{
+    inner:RW,
+    pos:Option<u64>
+}
+impl<RW:std::io::Seek> PositionedFile<RW> {
+    pub fn new(inner:RW)->Self{
+        Self { inner, pos: None }
+    }
+    pub fn get_ref(&self)->&RW{
+        &self.inner
+    }
+    /// Forgets the cached position, since the caller may move the inner file.
+    pub fn get_mut(&mut self)->&mut RW{
+        self.pos = None;
+        &mut self.inner
+    }
+    pub fn into_inner(self)->RW{
+        self.inner
+    }
+    fn track<T>(&mut self,res:std::io::Result<T>,advanced:impl Fn(&T)->u64)->std::io::Result<T>{
+        match &res {
+            Ok(t) => self.pos = self.pos.map(|p|p + advanced(t)),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(_) => self.pos = None,
+        }
+        res
+    }
+}
+impl<RW:std::io::Read + std::io::Seek> std::io::Read for PositionedFile<RW> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let res = self.inner.read(buf);
+        self.track(res, |n|*n as u64)
+    }
+}
+impl<RW:std::io::Write + std::io::Seek> std::io::Write for PositionedFile<RW> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let res = self.inner.write(buf);
+        self.track(res, |n|*n as u64)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+impl<RW:std::io::Seek> std::io::Seek for PositionedFile<RW> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let target = match (pos,self.pos) {
+            (std::io::SeekFrom::Start(p),_) => Some(p),
+            (std::io::SeekFrom::Current(off),Some(cur)) => cur.checked_add_signed(off),
+            _ => None,
+        };
+        if let (Some(target),Some(cur)) = (target,self.pos) {
+            if target == cur {return Ok(cur)}
+        }
+        let pos = target.map(std::io::SeekFrom::Start).unwrap_or(pos);
+        let res = self.inner.seek(pos);
+        self.pos = res.as_ref().ok().copied();
+        res
+    }
+}
+impl<RW:crate::FileLike> crate::FileLike for PositionedFile<RW> {
+    fn truncate(&mut self, len: u64)->std::io::Result<()> {
+        self.inner.truncate(len)
+    }
+    fn len(&self)->std::io::Result<u64> {
+        self.inner.len()
+    }
+    fn sync(&mut self)->std::io::Result<()> {
+        self.inner.sync()
+    }
+}
+
+enum TimeoutOp{
+    Read{pos:u64,len:usize},
+    Write{pos:u64,data:Vec<u8>},
+    Flush,
+    Truncate(u64),
+    Len,
+}
+enum TimeoutReply{
+    Data(Vec<u8>),
+    Count(usize),
+    Len(u64),
+    Done,
+}
+/// Runs every operation on a [FileLike] on a helper thread, so a read that stalls (e.g. on a hung network mount) fails with [TimedOut](std::io::ErrorKind::TimedOut) instead of hanging.
+///
+/// Only reads are bounded by `read_timeout` (None waits forever), other operations wait for the helper thread.
+/// A timed out read is still in flight, and the inner file stays busy until it returns. Its result is discarded and the position does not move,
+/// so the read can simply be retried (see [RetryingFile::with_read_attempts](crate::retry_writer::RetryingFile::with_read_attempts)).
+/// Timeouts convert to [ReadWriteError::Timeout].
+#[derive(Debug)]
+pub struct TimeoutFile{
+    pub read_timeout:Option<std::time::Duration>,
+    //in a RefCell so FileLike::len can ask the helper thread through &self
+    channel:std::cell::RefCell<TimeoutChannel>,
+    pos:u64,
+}
+#[derive(Debug)]
+struct TimeoutChannel{
+    ops:std::sync::mpsc::Sender<TimeoutOp>,
+    replies:std::sync::mpsc::Receiver<std::io::Result<TimeoutReply>>,
+    ///Replies still owed for reads that timed out.
+    stale:usize,
+}
+impl TimeoutChannel {
+    /// Sends `op` and waits for its reply, skipping the replies of reads that timed out earlier.
+    fn call(&mut self,op:TimeoutOp,timeout:Option<std::time::Duration>)->std::io::Result<TimeoutReply>{
+        let gone = ||std::io::Error::new(std::io::ErrorKind::BrokenPipe, "TimeoutFile helper thread exited");
+        self.ops.send(op).map_err(|_|gone())?;
+        let deadline = timeout.map(|t|std::time::Instant::now() + t);
+        loop {
+            let reply = match deadline {
+                Some(deadline) => match self.replies.recv_timeout(deadline.saturating_duration_since(std::time::Instant::now())) {
+                    Ok(reply) => reply,
+                    Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                        self.stale += 1;
+                        return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "read timed out"))
+                    },
+                    Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => return Err(gone()),
+                },
+                None => self.replies.recv().map_err(|_|gone())?,
+            };
+            if self.stale > 0 {
+                self.stale -= 1;
+                continue;
+            }
+            return reply
+        }
+    }
+}
+impl TimeoutFile {
+    pub fn new<F:crate::FileLike + Send + 'static>(mut inner:F,read_timeout:Option<std::time::Duration>)->Self{
+        let (ops,op_rx) = std::sync::mpsc::channel::<TimeoutOp>();
+        let (reply_tx,replies) = std::sync::mpsc::channel();
+        std::thread::spawn(move||{
+            for op in op_rx {
+                let reply = match op {
+                    TimeoutOp::Read { pos, len } => inner.seek(std::io::SeekFrom::Start(pos)).and_then(|_|{
+                        let mut buf = vec![0u8;len];
+                        let n = inner.read(&mut buf)?;
+                        buf.truncate(n);
+                        Ok(TimeoutReply::Data(buf))
+                    }),
+                    TimeoutOp::Write { pos, data } => inner.seek(std::io::SeekFrom::Start(pos)).and_then(|_|inner.write(&data)).map(TimeoutReply::Count),
+                    TimeoutOp::Flush => inner.flush().map(|_|TimeoutReply::Done),
+                    TimeoutOp::Truncate(len) => inner.truncate(len).map(|_|TimeoutReply::Done),
+                    TimeoutOp::Len => inner.len().map(TimeoutReply::Len),
+                };
+                if reply_tx.send(reply).is_err() {break}
+            }
+        });
+        Self { read_timeout, channel: std::cell::RefCell::new(TimeoutChannel { ops, replies, stale: 0 }), pos: 0 }
+    }
+    fn call(&self,op:TimeoutOp,timeout:Option<std::time::Duration>)->std::io::Result<TimeoutReply>{
+        self.channel.borrow_mut().call(op, timeout)
+    }
+}
+impl std::io::Read for TimeoutFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let timeout = self.read_timeout;
+        match self.call(TimeoutOp::Read { pos: self.pos, len: buf.len() }, timeout)? {
+            TimeoutReply::Data(data) => {
+                buf[..data.len()].copy_from_slice(&data);
+                self.pos += data.len() as u64;
+                Ok(data.len())
+            },
+            _ => unreachable!("Read always replies with Data"),
+        }
+    }
+}
+impl std::io::Write for TimeoutFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.call(TimeoutOp::Write { pos: self.pos, data: buf.to_vec() }, None)? {
+            TimeoutReply::Count(n) => {
+                self.pos += n as u64;
+                Ok(n)
+            },
+            _ => unreachable!("Write always replies with Count"),
+        }
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.call(TimeoutOp::Flush, None).map(|_|())
+    }
+}
+impl std::io::Seek for TimeoutFile {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let target = match pos {
+            std::io::SeekFrom::Start(p) => Some(p),
+            std::io::SeekFrom::Current(off) => self.pos.checked_add_signed(off),
+            std::io::SeekFrom::End(off) => crate::FileLike::len(self)?.checked_add_signed(off),
+        };
+        self.pos = target.ok_or_else(||std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position"))?;
+        Ok(self.pos)
+    }
+}
+impl crate::FileLike for TimeoutFile {
+    fn truncate(&mut self, len: u64)->std::io::Result<()> {
+        self.call(TimeoutOp::Truncate(len), None).map(|_|())
+    }
+    fn len(&self)->std::io::Result<u64> {
+        match self.call(TimeoutOp::Len, None)? {
+            TimeoutReply::Len(len) => Ok(len),
+            _ => unreachable!("Len always replies with Len"),
+        }
+    }
+}
+
+/// A read only view of a docufort file, either already in memory (e.g. an mmap) or an open [File](std::fs::File).
+///
+/// File reads are positioned (`pread`), so seeking is free and the underlying file cursor is never moved.
+/// Implements [Read](std::io::Read) + [Seek](std::io::Seek), so it can be handed to [catalog], [locate_offset], [find_block_start](crate::recovery::find_block_start), etc.
+#[derive(Debug)]
+pub enum Source<'a>{
+    Slice{data:&'a [u8],pos:u64},
+    File{file:&'a std::fs::File,pos:u64},
+}
+impl<'a> Source<'a> {
+    pub fn from_slice(data:&'a [u8])->Self{
+        Source::Slice { data, pos: 0 }
+    }
+    pub fn from_file(file:&'a std::fs::File)->Self{
+        Source::File { file, pos: 0 }
+    }
+    /// Reads from `offset` without changing the position of this source.
+    pub fn read_at(&self,offset:u64,buf:&mut [u8])->std::io::Result<usize>{
+        match self {
+            Source::Slice { data, .. } => {
+                let start = offset.min(data.len() as u64) as usize;
+                let n = buf.len().min(data.len() - start);
+                buf[..n].copy_from_slice(&data[start..start+n]);
+                Ok(n)
+            },
+            Source::File { file, .. } => file_read_at(file, offset, buf),
+        }
+    }
+    pub fn len(&self)->std::io::Result<u64>{
+        match self {
+            Source::Slice { data, .. } => Ok(data.len() as u64),
+            Source::File { file, .. } => Ok(file.metadata()?.len()),
+        }
+    }
+    pub fn is_empty(&self)->std::io::Result<bool>{
+        Ok(self.len()? == 0)
+    }
+    fn pos_mut(&mut self)->&mut u64{
+        match self {
+            Source::Slice { pos, .. } | Source::File { pos, .. } => pos,
+        }
+    }
+}
+#[cfg(unix)]
+fn file_read_at(file:&std::fs::File,offset:u64,buf:&mut [u8])->std::io::Result<usize>{
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+#[cfg(windows)]
+fn file_read_at(file:&std::fs::File,offset:u64,buf:&mut [u8])->std::io::Result<usize>{
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+#[cfg(not(any(unix,windows)))]
+fn file_read_at(mut file:&std::fs::File,offset:u64,buf:&mut [u8])->std::io::Result<usize>{
+    use std::io::{Read, Seek};
+    file.seek(std::io::SeekFrom::Start(offset))?;
+    file.read(buf)
+}
+impl std::io::Read for Source<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let pos = *self.pos_mut();
+        let n = self.read_at(pos, buf)?;
+        *self.pos_mut() += n as u64;
+        Ok(n)
+    }
+}
+impl std::io::Seek for Source<'_> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let new_pos = match pos {
+            std::io::SeekFrom::Start(p) => Some(p),
+            std::io::SeekFrom::Current(off) => self.pos_mut().checked_add_signed(off),
+            std::io::SeekFrom::End(off) => self.len()?.checked_add_signed(off),
+        };
+        let new_pos = new_pos.ok_or_else(||std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position"))?;
+        *self.pos_mut() = new_pos;
+        Ok(new_pos)
+    }
+}
+
+/// Copies the stored bytes of `content` (ECC + data) to `dst`, correcting with `old_ecc_len` and writing ECC of `new_ecc_len`, so migrating a file to a new ECC length takes one pass.
+///
+/// The old ECC must sit right before `data_start` (or after each chunk for [TRAILING_ECC] content), the new ECC is always prepended.
+/// Content without ECC is copied as is. Nothing is written back to `src`, and the headers are up to the caller.
+/// Returns (errors corrected, bytes written), or EccTooManyErrors before writing anything if a chunk can't be corrected.
+#[cfg(not(feature = "crc_only"))]
+pub fn transcode_content_ecc<R:std::io::Read + std::io::Seek, W:std::io::Write>(src:&mut R,dst:&mut W,content:&Content,old_ecc_len:usize,new_ecc_len:usize)->Result<(usize,usize),ReadWriteError>{
+    if !(1..255).contains(&old_ecc_len) || !(1..255).contains(&new_ecc_len) {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "ECC length must be between 1 and 254").into())
+    }
+    use crate::ecc::{apply_ecc_with_len, calc_ecc_data_len_for, calculate_ecc_chunk_with_len};
+    let data_len = content.data_len.0 as usize;
+    let mut data = vec![0u8;data_len];
+    if !content.ecc {
+        src.seek(std::io::SeekFrom::Start(content.data_start))?;
+        src.read_exact(&mut data)?;
+        dst.write_all(&data)?;
+        return Ok((0,data_len))
+    }
+    let old_size = 255 - old_ecc_len;
+    let mut chunk = [0u8;255];
+    let mut errors = 0;
+    if content.trailing_ecc {
+        src.seek(std::io::SeekFrom::Start(content.data_start))?;
+        for data_chunk in data.chunks_mut(old_size) {
+            let chunk_len = data_chunk.len() + old_ecc_len;
+            src.read_exact(&mut chunk[..chunk_len])?;
+            errors += apply_ecc_with_len(&mut chunk[..chunk_len], old_ecc_len)?;
+            data_chunk.copy_from_slice(&chunk[..data_chunk.len()]);
+        }
+    }else{
+        let mut old_ecc = vec![0u8;calc_ecc_data_len_for(data_len, old_ecc_len)];
+        let ecc_start = content.data_start.checked_sub(old_ecc.len() as u64)
+            .ok_or_else(||std::io::Error::new(std::io::ErrorKind::InvalidInput, "Content ECC would start before the file"))?;
+        src.seek(std::io::SeekFrom::Start(ecc_start))?;
+        src.read_exact(&mut old_ecc)?;
+        src.read_exact(&mut data)?;
+        for (data_chunk,ecc) in data.chunks_mut(old_size).zip(old_ecc.chunks(old_ecc_len)) {
+            let n = data_chunk.len();
+            chunk[..n].copy_from_slice(data_chunk);
+            chunk[n..n+old_ecc_len].copy_from_slice(ecc);
+            errors += apply_ecc_with_len(&mut chunk[..n+old_ecc_len], old_ecc_len)?;
+            data_chunk.copy_from_slice(&chunk[..n]);
+        }
+    }
+    let mut new_ecc = Vec::with_capacity(calc_ecc_data_len_for(data_len, new_ecc_len));
+    data.chunks(255 - new_ecc_len).try_for_each(|c|calculate_ecc_chunk_with_len(c, new_ecc_len, &mut new_ecc))?;
+    dst.write_all(&new_ecc)?;
+    dst.write_all(&data)?;
+    Ok((errors,new_ecc.len() + data_len))
+}
+
+/// Used after hitting an unexpected EOF while reading content.
+///
+/// A torn write leaves the file ending somewhere inside the content.
+/// A corrupted length field (that still passes header ECC) runs past the end of the file, but the rest of the block is still there.
+/// Returns true if the file ends in a valid BlockEnd header + hash, meaning the length is corrupt and the file is not truncated.
+pub fn ends_with_block_end<R:std::io::Read + std::io::Seek>(reader:&mut R)->Result<bool,ReadWriteError>{
+    let file_len = reader.seek(std::io::SeekFrom::End(0))?;
+    if file_len < BLOCK_END_LEN as u64 {return Ok(false)}
+    reader.seek(std::io::SeekFrom::End(-(BLOCK_END_LEN as i64)))?;
+    let mut buf = [0u8;BLOCK_END_LEN];
+    reader.read_exact(&mut buf)?;
+    Ok(is_block_end(&buf))
+}
+/// Used after hitting an unexpected EOF while reading `content`, to tell a corrupted length field from a torn write.
+///
+/// The declared content must run past the end of the file, as an EOF hit while reading anything else (e.g. the content type field) says nothing about the length.
+/// [ends_with_block_end] is then only a second check, that the rest of the block is still there.
+pub(crate) fn content_len_is_corrupt<R:std::io::Read + std::io::Seek>(reader:&mut R,content:&HeaderAsContent)->Result<bool,ReadWriteError>{
+    let file_len = reader.seek(std::io::SeekFrom::End(0))?;
+    Ok(content.end() > file_len && ends_with_block_end(reader)?)
+}
+const BLOCK_END_LEN:usize = HEADER_LEN + ECC_LEN + HASH_AND_ECC_LEN;
+///Whether `buf` is a BlockEnd header + hash, ECC applied in memory.
+fn is_block_end(buf:&[u8])->bool{
+    let mut buf:[u8;BLOCK_END_LEN] = buf.try_into().unwrap();
+    let (header,hash) = buf.split_at_mut(HEADER_LEN+ECC_LEN);
+    apply_ecc(header).is_ok() && is_end_tag(header[0]) && apply_ecc(hash).is_ok()
+}
+///Where the block before the magic number at `mn_start` ends, stepping back over any alignment padding (see [skip_alignment_padding]).
+///Returns FILE_HEADER_LEN if there is only padding between the file header and `mn_start`.
+fn prev_block_end<R:std::io::Read + std::io::Seek>(reader:&mut R,mn_start:u64)->Result<u64,ReadWriteError>{
+    let window_start = mn_start.saturating_sub(MAX_ALIGNMENT + BLOCK_END_LEN as u64).max(FILE_HEADER_LEN as u64);
+    reader.seek(std::io::SeekFrom::Start(window_start))?;
+    let mut buf = vec![0u8;(mn_start - window_start) as usize];
+    reader.read_exact(&mut buf)?;
+    //a hash whose ECC ends in a zero byte is not padding
+    if buf.last() != Some(&0) || (buf.len() >= BLOCK_END_LEN && is_block_end(&buf[buf.len()-BLOCK_END_LEN..])) {return Ok(mn_start)}
+    let zeros = buf.iter().rev().take_while(|b|**b == 0).count();
+    if zeros == buf.len() && window_start == FILE_HEADER_LEN as u64 {return Ok(window_start)}
+    for end in (buf.len()-zeros..buf.len()).rev() {
+        if end >= BLOCK_END_LEN && is_block_end(&buf[end-BLOCK_END_LEN..end]) {return Ok(window_start + end as u64)}
+    }
+    Ok(mn_start)
+}
+
+/// Reads the hash of the block that ends directly before the block starting at `block_start` (after the magic number).
+///
+/// ECC is applied in memory only. Returns None if `block_start` is the first block in the file.
+/// Returns [ReadWriteError::EccTooManyErrors] if the hash does not decode, or there is no room for one before `block_start`.
+/// Alignment padding between the two blocks (see [write_aligned_magic_number](crate::write::write_aligned_magic_number)) is stepped over.
+/// Used to verify [CHAINED] blocks.
+pub fn read_prev_hash<R:std::io::Read + std::io::Seek>(reader:&mut R,block_start:u64)->Result<Option<[u8;HASH_LEN]>,ReadWriteError>{
+    let first_block = FILE_HEADER_LEN as u64 + MN_ECC_LEN as u64;
+    if block_start <= first_block {return Ok(None)}
+    let return_to = reader.stream_position()?;
+    let prev_end = prev_block_end(reader, block_start - MN_ECC_LEN as u64)?;
+    if prev_end <= FILE_HEADER_LEN as u64 {
+        reader.seek(std::io::SeekFrom::Start(return_to))?;
+        return Ok(None)
+    }
+    //too close to the file header to hold a hash, so `block_start` is not where a block can start
+    let Some(hash_start) = prev_end.checked_sub(HASH_AND_ECC_LEN as u64).filter(|s|*s >= FILE_HEADER_LEN as u64) else {
+        reader.seek(std::io::SeekFrom::Start(return_to))?;
+        return Err(ReadWriteError::EccTooManyErrors)
+    };
+    reader.seek(std::io::SeekFrom::Start(hash_start))?;
+    let mut hash = [0u8;HASH_AND_ECC_LEN];
+    reader.read_exact(&mut hash)?;
+    apply_ecc(&mut hash)?;
+    reader.seek(std::io::SeekFrom::Start(return_to))?;
+    Ok(Some(hash[..HASH_LEN].try_into().unwrap()))
+}
+
+/// Iterates the complete blocks of a file that a writer may still be appending to.
+///
+/// Each block is read with **no** ECC (so nothing is written to the file), and yielded as a [BlockReadSummary](crate::recovery::BlockReadSummary).
+/// When the next block is not complete (or does not read as a closed block) this returns None and remembers where that block starts.
+/// Call [refresh](TailingBlockIter::refresh) later to try again from that position and pick up newly appended blocks.
+pub struct TailingBlockIter<'a,RW,B>{
+    file:&'a mut RW,
+    next_block:u64,
+    done:bool,
+    _b:std::marker::PhantomData<B>
+}
+impl<'a,RW:std::io::Write + std::io::Read + std::io::Seek,B:BlockInputs> TailingBlockIter<'a,RW,B> {
+    /// Starts from the first block in the file.
+    pub fn new(file:&'a mut RW)->Self{
+        Self { file, next_block: FILE_HEADER_LEN as u64, done: false, _b: std::marker::PhantomData }
+    }
+    /// Clears the end of iteration, so the next call will re-read from the last incomplete position.
+    pub fn refresh(&mut self){
+        self.done = false;
+    }
+    /// Position of the magic number for the next block we will try to read.
+    pub fn next_block(&self)->u64{
+        self.next_block
+    }
+    pub fn get_mut(&mut self)->&mut RW{
+        self.file
+    }
+    fn read_next(&mut self)->Result<crate::core::BlockState,ReadWriteError>{
+        self.file.seek(std::io::SeekFrom::Start(self.next_block))?;
+        read_magic_number(self.file, false)?;
+        crate::recovery::try_read_block::<_,B>(self.file, false, false)
+    }
+}
+impl<RW:std::io::Write + std::io::Read + std::io::Seek,B:BlockInputs> Iterator for TailingBlockIter<'_,RW,B> {
+    type Item = Result<crate::recovery::BlockReadSummary,ReadWriteError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {return None}
+        match self.read_next() {
+            Ok(crate::core::BlockState::Closed(brs)) => {
+                match self.file.stream_position() {
+                    Ok(pos) => self.next_block = pos,
+                    Err(e) => return Some(Err(e.into())),
+                }
+                Some(Ok(brs))
+            },
+            Ok(_) | Err(ReadWriteError::EndOfFile) => {
+                self.done = true;
+                None
+            },
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Copies the exact on disk bytes of a closed block, from its magic number to the end of its hash, to `dest`.
+///
+/// `block_start` is the position after the magic number (as everywhere else). ECC is applied in memory only (see [DeferredPatches]), so nothing is written to `file`.
+/// If `verify` is true the block must match its hash, after correcting its content if it does not match as read, so a replica only ever receives blocks that verify.
+/// The bytes copied are those on disk, so correctable errors are copied too. Run an [integrity check](crate::integrity::integrity_check_file) first to fix them.
+/// Returns the number of bytes copied.
+///
+/// # Errors
+/// - [ReadWriteError::HashMismatch] if verifying and the block does not match its hash.
+/// - [ReadWriteError::EndOfFile] if the block is incomplete, [ReadWriteError::EccTooManyErrors] if it can't be read as a closed block.
+pub fn copy_block_raw<R:std::io::Read + std::io::Seek, W:std::io::Write, B:BlockInputs>(file:&mut R,block_start:u64,dest:&mut W,verify:bool)->Result<u64,ReadWriteError>{
+    use crate::core::BlockState;
+    let mut reader = DeferredPatches::new(file);
+    //Ok((matches its hash, block end))
+    let read_block = |reader:&mut DeferredPatches<R>,error_correct_content:bool|->Result<(bool,u64),ReadWriteError>{
+        std::io::Seek::seek(reader, std::io::SeekFrom::Start(block_start))?;
+        match crate::recovery::try_read_block::<_,B>(reader, true, error_correct_content)? {
+            BlockState::Closed(brs) => Ok((brs.block.clone().take_end().hash.hash() == &brs.hash_as_read[..],std::io::Seek::stream_position(reader)?)),
+            BlockState::OpenABlock { .. } | BlockState::OpenBBlock { .. } | BlockState::IncompleteStartHeader { .. } => Err(ReadWriteError::EndOfFile),
+            _ => Err(ReadWriteError::EccTooManyErrors),
+        }
+    };
+    let (mut verified,mut block_end) = read_block(&mut reader, false)?;
+    if verify && !verified {(verified,block_end) = read_block(&mut reader, true)?}
+    if verify && !verified {
+        return Err(ReadWriteError::HashMismatch { block_start })
+    }
+    let span_start = BlockCoords::new(block_start).magic_number_start();
+    file.seek(std::io::SeekFrom::Start(span_start))?;
+    let copied = std::io::copy(&mut std::io::Read::take(&mut *file, block_end - span_start), dest)?;
+    Ok(copied)
+}
+
+/// A small LRU of `block_start -> block hash` for [verify_block_hash], filled as blocks are written with [write_cached](crate::write::write_cached).
+///
+/// A hit skips re-reading and re-hashing the block, so it trusts that what was written is still what is on disk.
+/// That holds for our own writes, but not against bit rot or anyone else touching the file. Set `reread_on_hit` to still re-hash on a hit.
+#[derive(Clone, Debug)]
+pub struct BlockHashCache{
+    capacity:usize,
+    ///Least recently used first
+    entries:std::collections::VecDeque<(u64,[u8;HASH_LEN])>,
+    ///If true a hit re-reads the block too, and it must match the cached hash.
+    pub reread_on_hit:bool
+}
+impl BlockHashCache {
+    /// A `capacity` of 0 caches nothing.
+    pub fn new(capacity:usize)->Self{
+        Self { capacity, entries: std::collections::VecDeque::with_capacity(capacity), reread_on_hit: false }
+    }
+    ///Returns the cached hash of the block at `block_start` (after its magic number), marking it as recently used.
+    pub fn get(&mut self,block_start:u64)->Option<[u8;HASH_LEN]>{
+        let i = self.entries.iter().position(|(s,_)|*s == block_start)?;
+        let entry = self.entries.remove(i)?;
+        self.entries.push_back(entry);
+        Some(entry.1)
+    }
+    ///Caches the hash, evicting the least recently used entry if full.
+    pub fn insert(&mut self,block_start:u64,hash:[u8;HASH_LEN]){
+        if self.capacity == 0 {return}
+        self.entries.retain(|(s,_)|*s != block_start);
+        if self.entries.len() == self.capacity {self.entries.pop_front();}
+        self.entries.push_back((block_start,hash));
+    }
+    pub fn len(&self)->usize{
+        self.entries.len()
+    }
+    pub fn is_empty(&self)->bool{
+        self.entries.is_empty()
+    }
+}
+
+/// Returns the hash of the closed block at `block_start` (after its magic number), if the block matches it.
+///
+/// A [BlockHashCache] hit returns the cached hash without reading (unless [reread_on_hit](BlockHashCache::reread_on_hit) is set).
+/// A miss re-reads and re-hashes the block with no error correction, like [verify_after_patch](crate::recovery::verify_after_patch), and caches the result.
+/// Returns Ok(None) if the block is not closed or does not match its hash (or the cached hash, on a re-read hit).
+pub fn verify_block_hash<R:std::io::Read + std::io::Seek, B:BlockInputs>(file:&mut R,block_start:u64,cache:&mut BlockHashCache)->Result<Option<[u8;HASH_LEN]>,ReadWriteError>{
+    use crate::core::BlockState;
+    let cached = cache.get(block_start);
+    if let (Some(hash),false) = (cached,cache.reread_on_hit) {return Ok(Some(hash))}
+    file.seek(std::io::SeekFrom::Start(block_start))?;
+    let mut reader = DeferredPatches::new(file);
+    let BlockState::Closed(brs) = crate::recovery::try_read_block::<_,B>(&mut reader, false, false)? else {return Ok(None)};
+    let hash = brs.hash_as_read;
+    if brs.block.take_end().hash.hash() != &hash[..] || cached.is_some_and(|c|c != hash) {return Ok(None)}
+    cache.insert(block_start, hash);
+    Ok(Some(hash))
+}
+
+/// Returns true if the A block at `block_start` (after its magic number) is closed and its content matches the BlockEnd hash.
+///
+/// Meant for A blocks without content ECC, where the hash is the only check. Nothing is corrected or written, headers included,
+/// and no [CorruptDataSegment] is built, a mismatch is just false. An open or truncated block is false too.
+/// Errors with InvalidInput if the block is a B block.
+pub fn verify_atomic_block<R:std::io::Read + std::io::Seek, B:BlockInputs>(file:&mut R,block_start:u64)->Result<bool,ReadWriteError>{
+    use crate::core::{Block, BlockState};
+    file.seek(std::io::SeekFrom::Start(block_start))?;
+    let mut reader = DeferredPatches::new(file);
+    let BlockState::Closed(brs) = crate::recovery::try_read_block::<_,B>(&mut reader, false, false)? else {return Ok(false)};
+    let Block::A { end, .. } = &brs.block else {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "not an A block").into())
+    };
+    Ok(end.hash.hash() == &brs.hash_as_read[..])
+}
+
+/// Returns the [ExternalRef] held by the thin block at `block_start` (after its magic number), see [write_thin_block](crate::write::write_thin_block).
+///
+/// Errors are corrected with ECC in memory only (see [DeferredPatches]), so nothing is written to `file`.
+/// Returns None if the block is not closed or is not a thin block, [ReadWriteError::HashMismatch] if it does not match its hash,
+/// and [ReadWriteError::DataCorruption] if it matches but does not hold a valid reference. The external data itself is not checked.
+pub fn read_thin_block<R:std::io::Read + std::io::Seek, B:BlockInputs>(file:&mut R,block_start:u64)->Result<Option<ExternalRef>,ReadWriteError>{
+    use crate::core::{Block, BlockState};
+    file.seek(std::io::SeekFrom::Start(block_start))?;
+    let mut reader = DeferredPatches::new(file);
+    let BlockState::Closed(brs) = crate::recovery::try_read_block::<_,B>(&mut reader, true, true)? else {return Ok(None)};
+    let Block::B { middle, end, .. } = &brs.block else {return Ok(None)};
+    let [(_,content)] = middle.as_slice() else {return Ok(None)};
+    if content.content_type != Some(EXTERNAL_REF_TYPE) {return Ok(None)}
+    if end.hash.hash() != &brs.hash_as_read[..] {return Err(ReadWriteError::HashMismatch { block_start })}
+    let mut bytes = Vec::new();
+    read_content::<_,_,B>(&mut reader, &mut bytes, content)?;
+    ExternalRef::from_bytes(&bytes).map(Some).ok_or(ReadWriteError::DataCorruption { data_start: content.data_start })
+}
+
+/// Writes the content of the block at `block_index` (0 is the first block in the file) to `out`, and returns the number of bytes written.
+///
+/// The block is verified against its hash first, with ECC applied in memory only (see [DeferredPatches]), so nothing is written to `file`.
+/// Content is decompressed, and the components of a B block are concatenated in order (a content directory is left out).
+///
+/// # Errors
+/// - NotFound (as [ReadWriteError::Io]) if the file has fewer closed blocks.
+/// - [ReadWriteError::HashMismatch] if the block does not match its hash.
+pub fn extract_block_content<R:std::io::Read + std::io::Seek, W:std::io::Write, B:BlockInputs>(file:&mut R,block_index:usize,out:&mut W)->Result<u64,ReadWriteError>{
+    let not_found = ||ReadWriteError::from(std::io::Error::new(std::io::ErrorKind::NotFound, "no block at that index"));
+    let block_start = catalog(file)?.get(block_index).ok_or_else(not_found)?.offset;
+    read_large_content::<_,_,B>(file, block_start, out)
+}
+
+/// Same as [extract_block_content], but for the block at `block_start` (after its magic number), e.g. one from [write_large_content](crate::write::write_large_content).
+///
+/// The pieces the content was split into are reassembled into `out` in order. Returns NotFound (as [ReadWriteError::Io]) if the block is not closed.
+pub fn read_large_content<R:std::io::Read + std::io::Seek, W:std::io::Write, B:BlockInputs>(file:&mut R,block_start:u64,out:&mut W)->Result<u64,ReadWriteError>{
+    use crate::core::{Block, BlockState};
+    file.seek(std::io::SeekFrom::Start(block_start))?;
+    let mut reader = DeferredPatches::new(file);
+    let BlockState::Closed(brs) = crate::recovery::try_read_block::<_,B>(&mut reader, true, true)? else {
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no closed block at that position").into())
+    };
+    let (contents,end) = match brs.block {
+        Block::A { middle, end, .. } => (vec![middle],end),
+        Block::B { middle, end, .. } => (middle.into_iter().map(|(_,c)|c).filter(|c|c.content_type != Some(crate::CONTENT_DIRECTORY_TYPE)).collect(),end),
+    };
+    if end.hash.hash() != &brs.hash_as_read[..] {return Err(ReadWriteError::HashMismatch { block_start })}
+    let mut written = 0;
+    for content in contents.iter() {
+        written += read_content::<_,_,B>(&mut reader, out, content)? as u64;
+    }
+    Ok(written)
+}
+
+/// What a byte of a DocuFort file is, as returned by [byte_classification].
+#[derive(Copy,Debug,Clone,PartialEq,Eq,Hash)]
+pub enum ByteClass{
+    ///A magic number and its ECC, or the magic number in the file header.
+    Magic,
+    ///A header, or a field following one (file version + ECC length, batch marker, content type, compression level).
+    Header,
+    ///The ECC of a header or header field.
+    HeaderEcc,
+    ///Stored user content (compressed, if it was written compressed).
+    Content,
+    ///The ECC of content.
+    ContentEcc,
+    ///A block hash, or the hash of a single component.
+    Hash,
+    HashEcc,
+    ///Zero bytes in front of an aligned magic number, see [write_aligned_magic_number](crate::write::write_aligned_magic_number).
+    Padding,
+}
+
+/// Walks the file and labels every byte range with the [ByteClass] of what is stored there, in file order.
+///
+/// Meant for targeting mutations (fuzzing) or drawing a map of the file, nothing is corrected, hashed or written.
+/// The walk stops at the first block that is not closed, so an open or damaged tail is left out:
+/// the ranges then end before the end of the file.
+pub fn byte_classification<RW:std::io::Read + std::io::Seek, B:BlockInputs>(file:&mut RW)->Result<Vec<(std::ops::Range<u64>,ByteClass)>,ReadWriteError>{
+    use crate::core::BlockState;
+    let file_len = file.seek(std::io::SeekFrom::End(0))?;
+    if file_len < FILE_HEADER_LEN as u64 {return Err(ReadWriteError::EndOfFile)}
+    let mut ranges = vec![(0..MAGIC_NUMBER.len() as u64,ByteClass::Magic),(MAGIC_NUMBER.len() as u64..FILE_HEADER_LEN as u64,ByteClass::Header)];
+    let mut reader = DeferredPatches::new(file);
+    let mut pos = FILE_HEADER_LEN as u64;
+    while pos < file_len {
+        std::io::Seek::seek(&mut reader, std::io::SeekFrom::Start(pos))?;
+        match read_magic_number(&mut reader, false) {
+            Ok(_) => (),
+            Err(ReadWriteError::EndOfFile | ReadWriteError::EccTooManyErrors) => break,
+            Err(e) => return Err(e),
+        }
+        let block_start = std::io::Seek::stream_position(&mut reader)?;
+        let BlockState::Closed(brs) = crate::recovery::try_read_block::<_,B>(&mut reader, false, false)? else {break};
+        let magic_start = block_start - MN_ECC_LEN as u64;
+        if magic_start > pos {ranges.push((pos..magic_start,ByteClass::Padding))}
+        ranges.push((magic_start..block_start,ByteClass::Magic));
+        ranges.extend(classify_block(&brs));
+        pos = ranges.last().map(|(r,_)|r.end).unwrap_or(pos);
+    }
+    Ok(ranges)
+}
+/// The [ByteClass] ranges of a closed block, from its BlockStart header to the end of its hash ECC (the magic number is not included).
+pub(crate) fn classify_block(brs:&crate::recovery::BlockReadSummary)->Vec<(std::ops::Range<u64>,ByteClass)>{
+    use crate::core::Block;
+    let mut ranges = Vec::new();
+    let push = |ranges:&mut Vec<(std::ops::Range<u64>,ByteClass)>,start:u64,len:usize,class:ByteClass|{
+        if len > 0 {ranges.push((start..start+len as u64,class))}
+        start + len as u64
+    };
+    //a header or field of `len` bytes followed by its ECC
+    let push_field = |ranges:&mut Vec<(std::ops::Range<u64>,ByteClass)>,start:u64,len:usize,class:ByteClass,ecc_class:ByteClass|{
+        let at = push(ranges, start, len, class);
+        push(ranges, at, ECC_LEN, ecc_class)
+    };
+    let push_content = |ranges:&mut Vec<(std::ops::Range<u64>,ByteClass)>,mut at:u64,content:&Content|{
+        let Content { data_len:StoredLen(data_len), data_start, ecc, trailing_ecc, .. } = *content;
+        if !ecc {
+            push(ranges, at, data_len as usize, ByteClass::Content);
+            return
+        }
+        if !trailing_ecc {
+            at = push(ranges, at, (data_start - at) as usize, ByteClass::ContentEcc);
+            push(ranges, at, data_len as usize, ByteClass::Content);
+            return
+        }
+        let mut remaining = data_len as usize;
+        while remaining > 0 {
+            let chunk = remaining.min(DATA_SIZE);
+            at = push_field(ranges, at, chunk, ByteClass::Content, ByteClass::ContentEcc);
+            remaining -= chunk;
+        }
+    };
+    let (start,end) = match &brs.block {
+        Block::A { start, end, .. } | Block::B { start, end, .. } => (start,end),
+    };
+    let mut at = push_field(&mut ranges, brs.block_start, HEADER_LEN, ByteClass::Header, ByteClass::HeaderEcc);
+    if start.has_batch_marker() {
+        at = push_field(&mut ranges, at, BATCH_MARKER_LEN, ByteClass::Header, ByteClass::HeaderEcc);
+    }
+    match &brs.block {
+        Block::A { middle, .. } => push_content(&mut ranges, at, middle),
+        Block::B { middle, .. } => for (header,content) in middle {
+            let mut at = push_field(&mut ranges, header.start_pos(), HEADER_LEN, ByteClass::Header, ByteClass::HeaderEcc);
+            if header.has_content_type() {at = push_field(&mut ranges, at, CONTENT_TYPE_LEN, ByteClass::Header, ByteClass::HeaderEcc)}
+            if header.has_compression_level() {at = push_field(&mut ranges, at, COMP_LEVEL_LEN, ByteClass::Header, ByteClass::HeaderEcc)}
+            if header.has_component_hash() {at = push_field(&mut ranges, at, HASH_LEN, ByteClass::Hash, ByteClass::HashEcc)}
+            push_content(&mut ranges, at, content);
+        },
+    }
+    let at = push_field(&mut ranges, end.header.start_pos(), HEADER_LEN, ByteClass::Header, ByteClass::HeaderEcc);
+    push_field(&mut ranges, at, HASH_LEN, ByteClass::Hash, ByteClass::HashEcc);
+    ranges
+}
+
+/// Walks every closed block in order and decodes each content component into an event.
+///
+/// Content is decompressed before it is given to `decode`. Each block's hash is checked before any of its content is decoded.
+/// No ECC is applied (you should have integrity checked already). Iteration stops at the first block that is not closed (an open tail).
+///
+/// # Errors
+/// - [ReadWriteError::HashMismatch] if a block does not match its hash.
+/// - Any error returned from `decode` is returned as [ReadWriteError::Io].
+pub fn replay<RW:std::io::Write + std::io::Read + std::io::Seek, B:BlockInputs, E>(file:&mut RW,mut decode:impl FnMut(&[u8])->std::io::Result<E>)->Result<Vec<E>,ReadWriteError>{
+    let mut events = Vec::new();
+    let mut buf = Vec::new();
+    let mut blocks = TailingBlockIter::<_,B>::new(file);
+    while let Some(brs) = blocks.next() {
+        let crate::recovery::BlockReadSummary { block, hash_as_read, block_start, .. } = brs?;
+        let contents = match block {
+            crate::core::Block::A { middle, end, .. } => {
+                if end.hash.hash() != hash_as_read {return Err(ReadWriteError::HashMismatch { block_start })}
+                vec![middle]
+            },
+            crate::core::Block::B { middle, end, .. } => {
+                if end.hash.hash() != hash_as_read {return Err(ReadWriteError::HashMismatch { block_start })}
+                middle.into_iter().map(|(_,c)|c).collect()
+            },
+        };
+        for content in contents {
+            buf.clear();
+            read_content::<_,_,B>(blocks.get_mut(), &mut buf, &content)?;
+            events.push(decode(&buf)?);
+        }
+    }
+    Ok(events)
+}
+
+/// A content component located in the file as a whole. See [all_content].
+#[derive(Copy,Debug,Clone,PartialEq,Eq)]
+pub struct GlobalContent{
+    ///Index of the block in the file, counting from 0.
+    pub block_index:usize,
+    ///Index of the component within its block, always 0 in an A block.
+    pub component_index:usize,
+    pub content:Content,
+    ///Timestamp of the content header in a B block, or of the BlockStart in an A block.
+    pub timestamp:u64,
+}
+/// Walks every closed block in order and flattens their content components into one list.
+///
+/// Block start and end headers are skipped. No ECC is applied and nothing is written, any corrections are read through [DeferredPatches] and dropped.
+/// Iteration stops at the first block that is not closed (an open tail).
+pub fn all_content<RW:std::io::Read + std::io::Seek, B:BlockInputs>(file:&mut RW)->Result<Vec<GlobalContent>,ReadWriteError>{
+    let mut reader = DeferredPatches::new(file);
+    let mut out = Vec::new();
+    for (block_index,brs) in TailingBlockIter::<_,B>::new(&mut reader).enumerate() {
+        match brs?.block {
+            crate::core::Block::A { start, middle, .. } => {
+                out.push(GlobalContent { block_index, component_index: 0, content: middle, timestamp: u64::from_be_bytes(start.time_stamp()) });
+            },
+            crate::core::Block::B { middle, .. } => {
+                for (component_index,(header,content)) in middle.into_iter().enumerate() {
+                    out.push(GlobalContent { block_index, component_index, content, timestamp: u64::from_be_bytes(header.time_stamp()) });
+                }
+            },
+        }
+    }
+    Ok(out)
+}
+
+/// The file's generation: the value in its last generation record, or 0 if it has none. See [bump_generation](crate::write::bump_generation).
+///
+/// Walks every closed block like [all_content], so the cost grows with the file.
+pub fn file_generation<R:std::io::Read + std::io::Seek, B:BlockInputs>(file:&mut R)->Result<u64,ReadWriteError>{
+    let Some(last) = all_content::<_,B>(file)?.into_iter().rev().find(|g|g.content.content_type == Some(crate::GENERATION_TYPE)) else {return Ok(0)};
+    let mut buf = Vec::new();
+    read_content::<_,_,B>(file, &mut buf, &last.content)?;
+    let bytes:[u8;8] = buf.try_into().map_err(|_|ReadWriteError::DataCorruption { data_start: last.content.data_start })?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+/// A lightweight summary of a single block, built from its headers only. See [catalog].
+#[derive(Copy,Debug,Clone,PartialEq,Eq)]
+pub struct BlockCatalogEntry{
+    ///Position of the BlockStart header (just after the magic number).
+    pub offset:u64,
+    pub is_atomic:bool,
+    ///Timestamp of the BlockStart header.
+    pub start_ts:u64,
+    ///Timestamp of the BlockEnd header.
+    pub end_ts:u64,
+    ///Sum of the content lengths as stored on disk (compressed size if compressed, ECC data excluded).
+    pub total_content_bytes:u64,
+    ///Sum of the content lengths once decompressed (ECC data excluded).
+    pub logical_content_bytes:u64,
+}
+
+/// Reads only the headers of every block to build a quick summary of the file.
+///
+/// Header ECC is applied in memory (nothing is written back), nothing is hashed, and content is skipped
+/// (except the 4 byte original length at the start of compressed content, which is not ECC checked).
+/// This stops at the first block that is incomplete or does not decode, so an open tail block is not listed.
+/// Run [recover_tail](crate::recovery::recover_tail) or an [integrity check](crate::integrity::integrity_check_file) first if you need to know why.
+pub fn catalog<R:std::io::Read + std::io::Seek>(file:&mut R)->Result<Vec<BlockCatalogEntry>,ReadWriteError>{
+    let file = &mut PositionedFile::new(file);
+    let mut entries = Vec::new();
+    std::io::Seek::seek(file, std::io::SeekFrom::Start(FILE_HEADER_LEN as u64))?;
+    loop {
+        match catalog_block(file) {
+            Ok(Some(entry)) => entries.push(entry),
+            Ok(None) | Err(ReadWriteError::EndOfFile) => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(entries)
+}
+
+fn catalog_block<R:std::io::Read + std::io::Seek>(reader:&mut R)->Result<Option<BlockCatalogEntry>,ReadWriteError>{
+    Ok(catalog_block_parts(reader)?.map(|(entry,..)|entry))
+}
+/// Same as [catalog_block], but also returns the BlockStart and BlockEnd headers and the stored hash (as read, no ECC applied).
+/// The reader is left at the end of the block.
+#[allow(clippy::type_complexity)]
+pub(crate) fn catalog_block_parts<R:std::io::Read + std::io::Seek>(reader:&mut R)->Result<Option<(BlockCatalogEntry,ComponentHeader,ComponentHeader,[u8;HASH_LEN])>,ReadWriteError>{
+    let mut mn = [0u8;MN_ECC_LEN];
+    let start = reader.stream_position()?;
+    reader.read_exact(&mut mn)?;
+    if mn[0] == 0 && skip_alignment_padding(reader, start)? {
+        reader.read_exact(&mut mn)?;
+    }
+    if (mn[..MAGIC_NUMBER.len()] != MAGIC_NUMBER || mn[MAGIC_NUMBER.len()..] != MN_ECC) && (apply_ecc(&mut mn).is_err() || mn[..MAGIC_NUMBER.len()] != MAGIC_NUMBER) {
+        return Ok(None)
+    }
+    let Some(start) = peek_header(reader)? else {return Ok(None)};
+    let tag = start.as_slice()[0];
+    let Some(is_atomic) = start_tag_is_atomic(tag) else {return Ok(None)};
+    if !is_atomic && start.has_batch_marker() {
+        reader.seek(std::io::SeekFrom::Current((BATCH_MARKER_LEN+ECC_LEN) as i64))?;
+    }
+    let mut total_content_bytes = 0;
+    let mut logical_content_bytes = 0;
+    let end = if is_atomic {
+        let content = start.as_content();
+        total_content_bytes += content.data_len as u64;
+        logical_content_bytes += logical_len(reader, &content)?;
+        reader.seek(std::io::SeekFrom::Start(content.end()))?;
+        let Some(end) = peek_header(reader)? else {return Ok(None)};
+        end
+    }else{
+        loop {
+            let Some(header) = peek_header(reader)? else {return Ok(None)};
+            let tag = header.as_slice()[0];
+            if is_end_tag(tag) {break header}
+            if tag & !(HAS_ECC|IS_COMP|HAS_TYPE|HAS_LEVEL|HAS_HASH|SEEKABLE) != CON_TAG {return Ok(None)}
+            let content = header.as_content();
+            total_content_bytes += content.data_len as u64;
+            logical_content_bytes += logical_len(reader, &content)?;
+            reader.seek(std::io::SeekFrom::Start(content.end()))?;
+        }
+    };
+    if !is_end_tag(end.as_slice()[0]) {return Ok(None)}
+    //make sure the hash is all there, but we don't need to check it.
+    let mut hash = [0u8;HASH_AND_ECC_LEN];
+    reader.read_exact(&mut hash)?;
+    let entry = BlockCatalogEntry{
+        offset: start.start_pos(),
+        is_atomic,
+        start_ts: u64::from_be_bytes(start.time_stamp()),
+        end_ts: u64::from_be_bytes(end.time_stamp()),
+        total_content_bytes,
+        logical_content_bytes,
+    };
+    Ok(Some((entry,start,end,hash[..HASH_LEN].try_into().unwrap())))
+}
+
+///The original length of compressed content (read from its first 4 bytes), else the stored length.
+fn logical_len<R:std::io::Read + std::io::Seek>(reader:&mut R,content:&HeaderAsContent)->Result<u64,ReadWriteError>{
+    if !content.compressed {return Ok(content.data_len as u64)}
+    let mut len = [0u8;4];
+    reader.seek(std::io::SeekFrom::Start(content.data_start))?;
+    reader.read_exact(&mut len)?;
+    Ok(read_comp_len(len) as u64)
+}
+
+/// The number of content bytes in the file once decompressed, without ECC, from headers only (see [catalog]).
+///
+/// Matches [IntegrityCheckOk::data_contents](crate::integrity::IntegrityCheckOk::data_contents) for a file with no corruption, without hashing or reading the content.
+/// Like [catalog], it stops at the first incomplete block.
+pub fn logical_size<R:std::io::Read + std::io::Seek>(file:&mut R)->Result<u64,ReadWriteError>{
+    Ok(catalog(file)?.iter().map(|e|e.logical_content_bytes).sum())
+}
+
+/// Where a file offset falls, see [locate_offset].
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub struct OffsetLocation{
+    ///Start of the block (after its magic number) containing the offset.
+    pub block_start:u64,
+    ///The magic number, any batch marker and the content of an A block belong to [StartHeader](ComponentTag::StartHeader).
+    ///Within a B block, the content type and content belong to their [ContentHeader](ComponentTag::ContentHeader).
+    pub component_tag:ComponentTag,
+    ///True if the offset is a content data byte (not a header or content ECC byte).
+    pub within_content:bool,
+    ///Offset from the first content data byte, only meaningful if within_content.
+    pub content_relative_offset:u64,
+}
+
+/// Walks the blocks from the start of the file until one brackets `offset`, and reports which component it falls in.
+///
+/// Like [catalog], only headers are read (ECC applied in memory), nothing is hashed or written.
+/// Returns EndOfFile if the offset is past the last complete block, and EccTooManyErrors if a block before it does not decode.
+pub fn locate_offset<R:std::io::Read + std::io::Seek>(file:&mut R,offset:u64)->Result<OffsetLocation,ReadWriteError>{
+    if offset < FILE_HEADER_LEN as u64 {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Offset is within the file header").into())
+    }
+    file.seek(std::io::SeekFrom::Start(FILE_HEADER_LEN as u64))?;
+    loop {
+        let mut mn = [0u8;MN_ECC_LEN];
+        file.read_exact(&mut mn)?;
+        if (mn[..MAGIC_NUMBER.len()] != MAGIC_NUMBER || mn[MAGIC_NUMBER.len()..] != MN_ECC) && (apply_ecc(&mut mn).is_err() || mn[..MAGIC_NUMBER.len()] != MAGIC_NUMBER) {
+            return Err(ReadWriteError::EccTooManyErrors)
+        }
+        let Some(start) = peek_header(file)? else {return Err(ReadWriteError::EccTooManyErrors)};
+        let block_start = start.start_pos();
+        let at = |component_tag,within_content,content_relative_offset|Ok(OffsetLocation{block_start,component_tag,within_content,content_relative_offset});
+        //returns Some if the offset is in this content, else moves past it
+        let in_content = |file:&mut R,content:HeaderAsContent,component_tag:ComponentTag|->Result<Option<OffsetLocation>,ReadWriteError>{
+            let data_end = content.end();
+            if offset < content.data_start {return Ok(Some(OffsetLocation{block_start,component_tag,within_content:false,content_relative_offset:0}))}
+            if offset < data_end && content.trailing_ecc {
+                //each DATA_SIZE chunk is followed by its ECC
+                let rel = offset - content.data_start;
+                let (chunk,within) = (rel / (DATA_SIZE + ECC_LEN) as u64, rel % (DATA_SIZE + ECC_LEN) as u64);
+                let chunk_len = (content.data_len as u64 - chunk * DATA_SIZE as u64).min(DATA_SIZE as u64);
+                let within_content = within < chunk_len;
+                let content_relative_offset = if within_content {chunk * DATA_SIZE as u64 + within}else{0};
+                return Ok(Some(OffsetLocation{block_start,component_tag,within_content,content_relative_offset}))
+            }
+            if offset < data_end {return Ok(Some(OffsetLocation{block_start,component_tag,within_content:true,content_relative_offset:offset - content.data_start}))}
+            file.seek(std::io::SeekFrom::Start(data_end))?;
+            Ok(None)
+        };
+        let tag = start.as_slice()[0];
+        let Some(is_atomic) = start_tag_is_atomic(tag) else {return Err(ReadWriteError::EccTooManyErrors)};
+        if !is_atomic {
+            let mut body_start = BlockCoords::new(block_start).body_start();
+            if start.has_batch_marker() {body_start += (BATCH_MARKER_LEN + ECC_LEN) as u64}
+            if offset < body_start {return at(ComponentTag::StartHeader,false,0)}
+            file.seek(std::io::SeekFrom::Start(body_start))?;
+            loop {
+                let Some(header) = peek_header(file)? else {return Err(ReadWriteError::EccTooManyErrors)};
+                let tag = header.as_slice()[0];
+                if is_end_tag(tag) {
+                    file.seek(std::io::SeekFrom::Start(header.start_pos()))?;
+                    break
+                }
+                if tag & !(HAS_ECC|IS_COMP|HAS_TYPE|HAS_LEVEL|HAS_HASH|SEEKABLE) != CON_TAG {return Err(ReadWriteError::EccTooManyErrors)}
+                if let Some(loc) = in_content(file,header.as_content(),ComponentTag::ContentHeader)? {return Ok(loc)}
+            }
+        }else if let Some(loc) = in_content(file,start.as_content(),ComponentTag::StartHeader)? {
+            return Ok(loc)
+        }
+        let Some(end) = peek_header(file)? else {return Err(ReadWriteError::EccTooManyErrors)};
+        if !is_end_tag(end.as_slice()[0]) {return Err(ReadWriteError::EccTooManyErrors)}
+        let hash_start = end.start_pos() + (HEADER_LEN + ECC_LEN) as u64;
+        if offset < hash_start {return at(ComponentTag::EndHeader,false,0)}
+        if offset < hash_start + HASH_AND_ECC_LEN as u64 {return at(ComponentTag::Hash,false,0)}
+        file.seek(std::io::SeekFrom::Start(hash_start + HASH_AND_ECC_LEN as u64))?;
+    }
+}
+
+/// Offset of the BlockEnd header of the B block at `block_start` (right after its magic number), if its BlockStart recorded the body length.
+///
+/// See [write_block_end_with_body_len](crate::write::write_block_end_with_body_len). Headers are corrected in memory only.
+/// Returns None if the block is atomic, no length was recorded, or there is no BlockEnd where it points, so the caller can fall back to reading each Content.
+pub fn recorded_block_end<R:std::io::Read + std::io::Seek>(file:&mut R,block_start:u64)->Result<Option<u64>,ReadWriteError>{
+    file.seek(std::io::SeekFrom::Start(block_start))?;
+    let Some(start) = peek_header(file)? else {return Ok(None)};
+    if start_tag_is_atomic(start.as_slice()[0]) != Some(false) {return Ok(None)}
+    let body_len = read_u32(start.data());
+    if body_len == 0 {return Ok(None)}
+    let end_start = BlockCoords::new(block_start).body_start() + body_len as u64;
+    file.seek(std::io::SeekFrom::Start(end_start))?;
+    match peek_header(file) {
+        Ok(Some(end)) if is_end_tag(end.as_slice()[0]) => Ok(Some(end_start)),
+        Ok(_) | Err(ReadWriteError::EndOfFile) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+///Checks the raw tag byte (so noise can't panic [HeaderTag::from]). Returns Some(true) for an A BlockStart, Some(false) for a B BlockStart, else None.
+fn start_tag_is_atomic(tag:u8)->Option<bool>{
+    if tag & !(HAS_ECC|IS_COMP|CHAINED|HAS_BATCH|TRAILING_ECC|COMMITTED) == A_BLOCK {Some(true)}
+    else if tag & !(CHAINED|HAS_BATCH|COMMITTED) == B_BLOCK {Some(false)}
+    else {None}
+}
+
+///Checks the raw tag byte like [start_tag_is_atomic]. True for a BlockEnd, with or without [LOGICAL_HASH] or [OFFSET_BOUND], the same as [HeaderTag::EndBlock].
+fn is_end_tag(tag:u8)->bool{
+    tag & !(LOGICAL_HASH|OFFSET_BOUND) == END_TAG
+}
+
+/// Checks that `slice` starts with a BlockStart header (+ its ECC), without a file, for scanning memory or an mmap.
+///
+/// `slice` should begin right after a magic number. ECC is applied to a copy, `slice` is not changed.
+/// Returns Some(true) if the header is clean, Some(false) if it needed correcting,
+/// and None if it does not decode, is not a BlockStart, or `slice` is too short.
+pub fn verify_block_start_slice(slice:&[u8])->Option<bool>{
+    let mut header:[u8;HEADER_LEN+ECC_LEN] = slice.get(..HEADER_LEN+ECC_LEN)?.try_into().unwrap();
+    let errors = apply_ecc(&mut header).ok()?;
+    start_tag_is_atomic(header[0])?;
+    Some(errors == 0)
+}
+
+///Reads a header and applies ECC in memory only. Returns None if the header does not decode.
+fn peek_header<R:std::io::Read + std::io::Seek>(reader:&mut R)->Result<Option<ComponentHeader>,ReadWriteError>{
+    let mut header = [0u8;HEADER_LEN+ECC_LEN];
+    let start = reader.stream_position()?;
+    reader.read_exact(&mut header[..])?;
+    if apply_ecc(&mut header).is_err() {return Ok(None)}
+    Ok(Some(ComponentHeader::new_with_ecc(&header,start)))
+}
+
+///Streams the bytes in `start..end` through the hasher, 4 KiB at a time, so huge ranges never need a big allocation.
+pub fn hash_range<R:std::io::Read + std::io::Seek, B:BlockInputs>(reader:&mut R,start:u64,end:u64,hasher:&mut B)->Result<(),ReadWriteError>{
+    reader.seek(std::io::SeekFrom::Start(start))?;
+    buffer_hash(reader, end.saturating_sub(start) as usize, hasher)?;
+    Ok(())
 }
 
 fn buffer_hash<R:std::io::Read, B:BlockInputs>(reader:&mut R,mut num_bytes:usize,hasher:&mut B)->std::io::Result<()>{
@@ -217,22 +2305,42 @@ pub enum BlockMiddleState{
     InvalidBlockStructure{last_good_component_end:u64},
     UnexpectedEof{last_good_component_end:u64,hash_at_last_good_component:[u8;HASH_LEN],content:Vec<(ComponentHeader,Content)>},
     DataCorruption{component_start:u64,component_tag:ComponentTag},
-    BBlock { middle: Vec<(ComponentHeader,Content)>, end: BlockEnd, errors_corrected: usize , hash:[u8;HASH_LEN],corrupted_content_blocks:Vec<CorruptDataSegment>}
+    ///See [BlockState::CorruptContentLength](crate::core::BlockState::CorruptContentLength)
+    CorruptContentLength{component_start:u64},
+    BBlock { middle: Vec<(ComponentHeader,Content)>, end: BlockEnd, errors_corrected: usize, min_correction_headroom: usize, hash:[u8;HASH_LEN],corrupted_content_blocks:Vec<CorruptDataSegment>}
 }
 
 /// This is a wrapper to just keep reading all the content.
 /// The reader should be positioned after reading a BBlockStart header
 pub fn read_block_middle<RW:std::io::Write + std::io::Read + std::io::Seek, B:BlockInputs>(reader_writer:&mut RW,error_correct_header:bool,error_correct_content:bool)->Result<BlockMiddleState,ReadWriteError>{
+    read_block_middle_with_hasher(reader_writer, error_correct_header, error_correct_content, B::new())
+}
+/// Same as [read_block_middle], but starts from the given hasher state (see [chained_hasher](crate::write::chained_hasher)).
+pub fn read_block_middle_with_hasher<RW:std::io::Write + std::io::Read + std::io::Seek, B:BlockInputs>(reader_writer:&mut RW,error_correct_header:bool,error_correct_content:bool,hasher:B)->Result<BlockMiddleState,ReadWriteError>{
+    read_block_middle_with_limit(reader_writer, error_correct_header, error_correct_content, hasher, MAX_COMPONENTS_PER_BLOCK)
+}
+/// Same as [read_block_middle_with_hasher], but a Content header after `max_components` of them returns [BlockMiddleState::DataCorruption] at that header.
+///
+/// The block start is not known here, so the hash of an [OFFSET_BOUND](crate::OFFSET_BOUND) block does not include it, and will not match. Use [try_read_block](crate::recovery::try_read_block) for those.
+pub fn read_block_middle_with_limit<RW:std::io::Write + std::io::Read + std::io::Seek, B:BlockInputs>(reader_writer:&mut RW,error_correct_header:bool,error_correct_content:bool,hasher:B,max_components:usize)->Result<BlockMiddleState,ReadWriteError>{
+    read_block_middle_at(reader_writer, error_correct_header, error_correct_content, hasher, max_components, None)
+}
+///`block_start` is hashed last if the BlockEnd is [OFFSET_BOUND](crate::OFFSET_BOUND).
+pub(crate) fn read_block_middle_at<RW:std::io::Write + std::io::Read + std::io::Seek, B:BlockInputs>(reader_writer:&mut RW,error_correct_header:bool,error_correct_content:bool,mut hasher:B,max_components:usize,block_start:Option<u64>)->Result<BlockMiddleState,ReadWriteError>{
     let mut middle = Vec::new();
     let mut errors_corrected = 0;
-    let mut hasher = B::new();
+    let mut min_correction_headroom = MAX_CORRECTABLE_ERRORS;
     let mut corrupted_content_blocks = Vec::new();
     loop{
         let last_good_component_end = reader_writer.seek(std::io::SeekFrom::Current(0))?;
         let hash_at_last_good_component = hasher.finalize();
+        //the state an OFFSET_BOUND BlockEnd continues from
+        let before_header = block_start.map(|_|hasher.clone());
         let (errs,header) = match read_content_header(reader_writer,error_correct_header,&mut hasher){
             Ok(a) => a,
             Err(ReadWriteError::EndOfFile) => {
+                //Also covers a file ending exactly on a component boundary (zero bytes of the next header):
+                //every component read so far is complete, so the block closes right here.
                 return Ok(BlockMiddleState::UnexpectedEof { last_good_component_end,hash_at_last_good_component,content:middle })
             },
             Err(ReadWriteError::EccTooManyErrors) => {
@@ -241,6 +2349,7 @@ pub fn read_block_middle<RW:std::io::Write + std::io::Read + std::io::Seek, B:Bl
             Err(e)=>return Err(e)
         };
         errors_corrected += errs;
+        if error_correct_header {min_correction_headroom = min_correction_headroom.min(MAX_CORRECTABLE_ERRORS - errs)}
         match header.tag() {
             HeaderTag::StartABlock |
             HeaderTag::StartACBlock |
@@ -253,18 +2362,29 @@ pub fn read_block_middle<RW:std::io::Write + std::io::Read + std::io::Seek, B:Bl
             HeaderTag::CCComponent |
             HeaderTag::CECComponent |
             HeaderTag::CEComponent => {
+                if middle.len() >= max_components {
+                    return Ok(BlockMiddleState::DataCorruption { component_start: last_good_component_end,component_tag:ComponentTag::ContentHeader})
+                }
                 let h_content = header.as_content();
                 let content = match check_read_content(reader_writer, &h_content, error_correct_content,&mut hasher) {
-                    Ok((errs,cc,content)) => {
+                    Ok((errs,headroom,cc,content)) => {
                         let Content { data_len, data_start, ecc, .. } = content.clone();
                         errors_corrected += errs;
+                        min_correction_headroom = min_correction_headroom.min(headroom);
                         if !ecc && error_correct_content {
-                            corrupted_content_blocks.push(CorruptDataSegment::MaybeCorrupt { data_start, data_len })
+                            corrupted_content_blocks.push(CorruptDataSegment::MaybeCorrupt { data_start, data_len:data_len.0 })
                         }else{
                             corrupted_content_blocks.extend_from_slice(cc.as_slice());
                         }
                         content
                     },
+                    Err(ReadWriteError::EndOfFile) if content_len_is_corrupt(reader_writer, &h_content)? => {
+                        return Ok(BlockMiddleState::CorruptContentLength { component_start: last_good_component_end })
+                    },
+                    //only the content type field can fail like this, content ECC failures are reported as corrupted segments
+                    Err(ReadWriteError::EccTooManyErrors) => {
+                        return Ok(BlockMiddleState::DataCorruption { component_start: last_good_component_end,component_tag:ComponentTag::ContentHeader})
+                    },
                     Err(ReadWriteError::EndOfFile) => {
                         return Ok(BlockMiddleState::UnexpectedEof { last_good_component_end,hash_at_last_good_component,content:middle})
                     },
@@ -284,11 +2404,18 @@ pub fn read_block_middle<RW:std::io::Write + std::io::Read + std::io::Seek, B:Bl
                     Err(e)=>return Err(e)
                 };
                 errors_corrected += errs;
+                let hash_at_last_good_component = match (block_start,before_header) {
+                    (Some(block_start),Some(mut h)) if header.is_offset_bound() => {
+                        h.update(&block_start.to_be_bytes());
+                        h.finalize()
+                    },
+                    _ => hash_at_last_good_component
+                };
                 if hash.hash() == hash_at_last_good_component && error_correct_content{
                     corrupted_content_blocks.clear();//we loaded up all the non ecc Contents to this vec in case hash didn't check out
                 }
                 let end = BlockEnd{ header, hash };
-                return Ok(BlockMiddleState::BBlock { middle, end, errors_corrected,hash:hash_at_last_good_component,corrupted_content_blocks })
+                return Ok(BlockMiddleState::BBlock { middle, end, errors_corrected, min_correction_headroom, hash:hash_at_last_good_component,corrupted_content_blocks })
             },
         }
     }
@@ -6,27 +6,125 @@ This is used at startup to determine a new end of the file and keep the semantic
 use std::io::SeekFrom;
 
 use crate::core::HeaderAsContent;
-use crate::read::{read_header, check_read_content, read_hash, read_block_middle, BlockMiddleState};
-use crate::write::write_block_end;
+use crate::read::{check_configs, ConfigMatch, VersionPolicy, read_content, read_header, check_read_content, read_hash, read_block_middle_at, content_len_is_corrupt, read_prev_hash, hash_range, read_batch_marker, load_content_decoded, catalog_block_parts, PositionedFile, BlockMiddleState};
+use crate::write::{write_header, write_block_end, write_block_end_with_body_len, write_batch_marker, write_typed_content_component, chained_hasher, WrittenBlock};
 //use write::{WriteError, FILE_HEADER_LEN};
 
 use crate::*;
 
-use crate::{core::{ComponentHeader,Block,BlockInputs,BlockState, BlockEnd}, ecc::apply_ecc};
+use crate::{core::{ComponentHeader,Block,BlockInputs,BlockState, BlockEnd, BatchMarker, BlockCoords, Content, ContentDirEntry}, ecc::{apply_ecc, MAX_CORRECTABLE_ERRORS}};
 
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct BlockReadSummary{
     pub errors_corrected:usize,
+    ///The fewest additional errors any single ECC chunk in this block could have absorbed (see [MAX_CORRECTABLE_ERRORS](crate::ecc::MAX_CORRECTABLE_ERRORS)).
+    ///0 means at least one chunk was corrected at its limit (or was uncorrectable), so it is one bit flip away from data loss.
+    ///Only chunks read with error correction count towards this.
+    pub min_correction_headroom:usize,
     pub block:Block,
     pub block_start:u64,
     pub block_start_timestamp:u64,
     pub hash_as_read:[u8;HASH_LEN],
-    pub corrupted_content_blocks:Vec<CorruptDataSegment>
+    pub corrupted_content_blocks:Vec<CorruptDataSegment>,
+    ///Present if the BlockStart carried a [BatchMarker](crate::core::BatchMarker).
+    pub batch_marker:Option<BatchMarker>,
+    ///The entries of the B block's content directory, if its last content component is one (see [write_content_directory](crate::write::write_content_directory)).
+    ///The directory component itself stays in the block's `middle`.
+    pub directory:Option<Vec<ContentDirEntry>>
 }
+impl BlockReadSummary {
+    ///Loads every content component of the block (decompressed, if stored compressed), paired with the first of `corrupted_content_blocks` that falls in it.
+    ///
+    ///An A block returns a single entry. A corrupt component that can no longer be decompressed is returned as its stored bytes, so it can still be inspected and repaired.
+    #[allow(clippy::type_complexity)]
+    pub fn load_content_with_report<RW:std::io::Read + std::io::Seek,B:BlockInputs>(&self,file:&mut RW)->Result<Vec<(Vec<u8>,Option<CorruptDataSegment>)>,ReadWriteError>{
+        let contents:Vec<&Content> = match &self.block {
+            Block::A { middle, .. } => vec![middle],
+            Block::B { middle, .. } => middle.iter().map(|(_,c)|c).collect(),
+        };
+        let mut out = Vec::with_capacity(contents.len());
+        for content in contents {
+            let report = self.corrupted_content_blocks.iter().find(|seg|{
+                let (CorruptDataSegment::EccChunk { data_start, .. } | CorruptDataSegment::MaybeCorrupt { data_start, .. } | CorruptDataSegment::Corrupt { data_start, .. }) = seg;
+                *data_start == content.data_start
+            }).copied();
+            let mut bytes = Vec::new();
+            match read_content::<_,_,B>(file, &mut bytes, content) {
+                Ok(_) => (),
+                Err(ReadWriteError::DataCorruption { .. } | ReadWriteError::DecompressionFailed { .. }) if report.is_some() => {
+                    bytes.clear();
+                    read_content::<_,_,B>(file, &mut bytes, &Content{compressed:None,..*content})?;
+                },
+                Err(e) => return Err(e)
+            }
+            out.push((bytes,report));
+        }
+        Ok(out)
+    }
+    ///Loads and decodes all of the block's content, so the result no longer needs `file` (e.g. to send it to another thread).
+    ///
+    ///Content with ECC is corrected in memory as it is loaded (see [load_content_decoded]), nothing is written back.
+    ///Returns [ReadWriteError::EccTooManyErrors] if a chunk can't be corrected. Use [load_content_with_report](Self::load_content_with_report) for a corrupt block.
+    pub fn into_owned<RW:std::io::Read + std::io::Seek,B:BlockInputs>(self,file:&mut RW)->Result<OwnedBlock,ReadWriteError>{
+        let BlockReadSummary { block, block_start, block_start_timestamp, hash_as_read, batch_marker, .. } = self;
+        let (start,parts,end) = match block {
+            Block::A { start, middle, end } => (start,vec![(None,middle)],end),
+            Block::B { start, middle, end } => (start,middle.into_iter().map(|(h,c)|(Some(h),c)).collect(),end),
+        };
+        let mut content = Vec::with_capacity(parts.len());
+        for (header,c) in parts {
+            let mut bytes = Vec::new();
+            load_content_decoded::<_,_,B>(file, &mut bytes, &c)?;
+            content.push((header,bytes));
+        }
+        Ok(OwnedBlock { block_start, block_start_timestamp, start, content, end, hash_as_read, batch_marker })
+    }
+}
+/// A block with all of its content loaded, see [BlockReadSummary::into_owned].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OwnedBlock{
+    pub block_start:u64,
+    pub block_start_timestamp:u64,
+    pub start:ComponentHeader,
+    ///The decoded content of each component, in order. An A block has a single entry, with no content header of its own.
+    pub content:Vec<(Option<ComponentHeader>,Vec<u8>)>,
+    pub end:BlockEnd,
+    pub hash_as_read:[u8;HASH_LEN],
+    pub batch_marker:Option<BatchMarker>,
+}
+impl OwnedBlock {
+    pub fn is_atomic(&self)->bool{
+        self.content.first().is_some_and(|(h,_)|h.is_none())
+    }
+}
+///Reads the content directory if `middle` ends with one, leaving the reader where it was.
+///A directory that can not be read or parsed is None, the block itself is still fine.
+fn read_content_directory<R:std::io::Read + std::io::Seek,B:BlockInputs>(reader:&mut R,middle:&[(ComponentHeader,Content)])->Result<Option<Vec<ContentDirEntry>>,ReadWriteError>{
+    let Some((_,dir)) = middle.last().filter(|(_,c)|c.content_type == Some(CONTENT_DIRECTORY_TYPE)) else {return Ok(None)};
+    let pos = reader.stream_position()?;
+    let mut bytes = Vec::new();
+    let res = read_content::<_,_,B>(reader, &mut bytes, dir);
+    reader.seek(SeekFrom::Start(pos))?;
+    Ok(res.ok().and_then(|_|ContentDirEntry::parse_directory(&bytes)))
+}
+
+/// How far back [find_block_start] looks in a file that does not start with a valid DocuFort header before giving up.
+pub const FOREIGN_FILE_SCAN_WINDOW:u64 = 64 * 1024;
 
 /// Attempts to find a MAGIC_NUMBER, starting from the given position of the reader.
-pub fn find_block_start<RW: std::io::Read + std::io::Write + std::io::Seek>(file: &mut RW)-> std::io::Result<u64> {
+///
+/// If the file does not start with a valid header (see [verify_configs](crate::read::verify_configs)), only the last [FOREIGN_FILE_SCAN_WINDOW] bytes are scanned,
+/// and finding nothing returns an error wrapping [NotADocuFortFile], rather than scanning all of a file that is probably the wrong type.
+/// The file header has no ECC, so a header with the magic number but other config bytes (or the config bytes but not the magic number) is still scanned in full.
+pub fn find_block_start<RW: std::io::Read + std::io::Seek>(file: &mut RW)-> std::io::Result<u64> {
+    scan_block_start(file, VersionPolicy::Strict)
+}
+/// Same as [find_block_start], but a file of a newer version is scanned in full under [VersionPolicy::AllowNewer], rather than treated as foreign.
+pub fn find_block_start_with_policy<RW: std::io::Read + std::io::Seek>(file: &mut RW,policy:VersionPolicy)-> std::io::Result<u64> {
+    scan_block_start(file, policy)
+}
+fn scan_block_start<RW: std::io::Read + std::io::Seek>(file: &mut RW,policy:VersionPolicy)-> std::io::Result<u64> {
     const MN_SIZE:usize = MAGIC_NUMBER.len();
 
     // Ensure the file is large enough to contain the magic number
@@ -39,8 +137,16 @@ pub fn find_block_start<RW: std::io::Read + std::io::Write + std::io::Seek>(file
     }
     let mut buff = [0u8;MN_ECC_LEN];
     let end_index = start_pos - MN_ECC_LEN as u64;
+    file.seek(SeekFrom::Start(0))?;
+    let mut header = [0u8;FILE_HEADER_LEN as usize];
+    file.read_exact(&mut header)?;
+    //one damaged field must not make a whole file look foreign
+    let mut with_magic = header;
+    with_magic[..MN_SIZE].copy_from_slice(&MAGIC_NUMBER);
+    let is_docufort = header[..MN_SIZE] == MAGIC_NUMBER || check_configs(&mut &with_magic[..], policy)? != ConfigMatch::Mismatch;
+    let scan_floor = if is_docufort {FILE_HEADER_LEN as u64}else{end_index.saturating_sub(FOREIGN_FILE_SCAN_WINDOW).max(FILE_HEADER_LEN as u64)};
     // Iterate over the file in reverse, one byte at a time
-    for start_index in (FILE_HEADER_LEN as u64..=end_index).rev() {
+    for start_index in (scan_floor..=end_index).rev() {
         file.seek(SeekFrom::Start(start_index))?;
 
         file.read_exact(&mut buff)?;
@@ -55,33 +161,98 @@ pub fn find_block_start<RW: std::io::Read + std::io::Write + std::io::Seek>(file
             },
         }
     }
+    if !is_docufort {return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, NotADocuFortFile))}
     Ok(0)
 }
 
+/// The forward counterpart of [find_block_start]: finds the first MAGIC_NUMBER at or after `from`.
+///
+/// Returns the position after the magic number (where [try_read_block] reads from), or None if there is no complete magic number left in the file.
+pub fn find_next_block_start<RW: std::io::Read + std::io::Seek>(file: &mut RW,from:u64)-> std::io::Result<Option<u64>> {
+    let file_len = file.seek(SeekFrom::End(0))?;
+    let Some(last_index) = file_len.checked_sub(MN_ECC_LEN as u64) else {return Ok(None)};
+    let mut buff = [0u8;MN_ECC_LEN];
+    for start_index in from..=last_index {
+        file.seek(SeekFrom::Start(start_index))?;
+        file.read_exact(&mut buff)?;
+        match apply_ecc(&mut buff) {
+            Ok(_errors) if buff[..MAGIC_NUMBER.len()] == MAGIC_NUMBER => return Ok(Some(start_index + MN_ECC_LEN as u64)),
+            _ => continue,
+        }
+    }
+    Ok(None)
+}
+
+///A hasher to re-hash the block at `block_start` (after its magic number) from its body, started the way [try_read_block] starts it:
+///fed the previous block's hash if the BlockStart is [CHAINED]. A BlockStart or previous hash that can not be read leaves it unchained.
+pub(crate) fn block_hasher<R:std::io::Read + std::io::Seek,B:BlockInputs>(file:&mut R,block_start:u64)->Result<B,ReadWriteError>{
+    let mut header = [0u8;HEADER_LEN+ECC_LEN];
+    file.seek(SeekFrom::Start(block_start))?;
+    file.read_exact(&mut header)?;
+    if apply_ecc(&mut header).is_err() || !ComponentHeader::new(&header[..HEADER_LEN], block_start).is_chained() {return Ok(B::new())}
+    match read_prev_hash(file, block_start) {
+        Ok(prev) => Ok(chained_hasher(prev.as_ref())),
+        Err(ReadWriteError::EccTooManyErrors) => Ok(B::new()),
+        Err(e) => Err(e),
+    }
+}
+
 
 /// Reader should be positioned at the start of a header (after the magic number).
 /// This function will hash, and optionally it will ecc the headers and or the content.
 /// This function will intercept any relevant IO or decode Errors and return them as part of the Ok(BlockState)
 pub fn try_read_block<RW:std::io::Write + std::io::Read + std::io::Seek,B:BlockInputs>(reader_writer:&mut RW,error_correct_header:bool,error_correct_content:bool)->Result<BlockState,ReadWriteError>{
+    try_read_block_with_limit::<_,B>(reader_writer, error_correct_header, error_correct_content, MAX_COMPONENTS_PER_BLOCK)
+}
+/// Same as [try_read_block], but a B block with more than `max_components` Content components is [BlockState::DataCorruption] at the first one past the limit.
+///
+/// [try_read_block] uses [MAX_COMPONENTS_PER_BLOCK].
+pub fn try_read_block_with_limit<RW:std::io::Write + std::io::Read + std::io::Seek,B:BlockInputs>(reader_writer:&mut RW,error_correct_header:bool,error_correct_content:bool,max_components:usize)->Result<BlockState,ReadWriteError>{
     let block_start = reader_writer.seek(std::io::SeekFrom::Current(0))?;
+    let coords = BlockCoords::new(block_start);
     let mut hasher = B::new();
     let (mut errors_corrected,start) = match read_header(reader_writer,error_correct_header){
         Ok(a) => a,
-        Err(ReadWriteError::EndOfFile) => return  Ok(BlockState::IncompleteStartHeader { truncate_at: block_start - MN_ECC_LEN as u64 }),
+        Err(ReadWriteError::EndOfFile) => return  Ok(BlockState::IncompleteStartHeader { truncate_at: coords.magic_number_start() }),
         Err(ReadWriteError::EccTooManyErrors) => return Ok(BlockState::ProbablyNotStartHeader{start_from:block_start}) ,//return Ok(BlockState::DataCorruption { component_start:block_start, is_b_block: false, component_tag: ComponentTag::StartHeader }),
         Err(e) => return Err(e)
     };
+    let mut min_correction_headroom = if error_correct_header {MAX_CORRECTABLE_ERRORS - errors_corrected}else{MAX_CORRECTABLE_ERRORS};
+    if start.is_chained() {
+        match read_prev_hash(reader_writer, block_start) {
+            Ok(prev) => hasher = chained_hasher(prev.as_ref()),
+            //the hash before us is damaged, so this block can't verify. Its own integrity check will report it.
+            Err(ReadWriteError::EccTooManyErrors) => (),
+            Err(e) => return Err(e),
+        }
+    }
+    let batch_marker = if start.has_batch_marker() {
+        match read_batch_marker(reader_writer, error_correct_header, &mut hasher) {
+            Ok((e,marker)) => {
+                errors_corrected += e;
+                if error_correct_header {min_correction_headroom = min_correction_headroom.min(MAX_CORRECTABLE_ERRORS - e)}
+                Some(marker)
+            },
+            Err(ReadWriteError::EndOfFile) => return  Ok(BlockState::IncompleteStartHeader { truncate_at: coords.magic_number_start() }),
+            Err(ReadWriteError::EccTooManyErrors) => return Ok(BlockState::DataCorruption { component_start:block_start, is_b_block: start.tag() == HeaderTag::StartBBlock, component_tag: ComponentTag::StartHeader }),
+            Err(e) => return Err(e)
+        }
+    }else{None};
     match start.tag() {
         HeaderTag::StartACBlock |
         HeaderTag::StartAECBlock |
         HeaderTag::StartABlock |
         HeaderTag::StartAEBlock => {
             let h_content = start.as_content();
+            //the state a LOGICAL_HASH BlockEnd continues from, fed the decompressed content instead
+            let mut logical_hasher = hasher.clone();
             let (mut corrupted_content_blocks, content) = match check_read_content(reader_writer, &h_content, error_correct_content,&mut hasher) {
-                Ok((errs,cc,content)) => {
+                Ok((errs,headroom,cc,content)) => {
                     errors_corrected+=errs;
+                    min_correction_headroom = min_correction_headroom.min(headroom);
                     (cc,content)
                 },
+                Err(ReadWriteError::EndOfFile) if content_len_is_corrupt(reader_writer, &h_content)? => return Ok(BlockState::CorruptContentLength { component_start: block_start, is_b_block: false }),
                 Err(ReadWriteError::EndOfFile) => return Ok(BlockState::OpenABlock { truncate_at: block_start-(MN_ECC_LEN) as u64 }),
                 Err(e)=>return Err(e)
             };
@@ -101,6 +272,21 @@ pub fn try_read_block<RW:std::io::Write + std::io::Read + std::io::Seek,B:BlockI
                     Err(e)=>return Err(e)
                 };
                 errors_corrected += e1+e2;
+                if error_correct_header {min_correction_headroom = min_correction_headroom.min(MAX_CORRECTABLE_ERRORS - e1.max(e2))}
+                let mut hasher = if header.is_logical_hash() {
+                    let end_of_block = reader_writer.stream_position()?;
+                    let mut sink = std::io::sink();
+                    match read_content::<_,_,B>(reader_writer, &mut HashAdapter::new(&mut sink, &mut logical_hasher), &content) {
+                        //content that can't be decoded can't match, the hash of what did decode is as good as any
+                        Ok(_) | Err(ReadWriteError::DataCorruption { .. } | ReadWriteError::DecompressionFailed { .. }) => (),
+                        Err(e) => return Err(e)
+                    }
+                    reader_writer.seek(SeekFrom::Start(end_of_block))?;
+                    logical_hasher
+                }else{
+                    hasher
+                };
+                if header.is_offset_bound() {hasher.update(&block_start.to_be_bytes())}
                 let hash_as_read = hasher.finalize();
 
                 if !content.ecc && hash_as_read != hash.hash() && error_correct_content{
@@ -109,17 +295,19 @@ pub fn try_read_block<RW:std::io::Write + std::io::Read + std::io::Seek,B:BlockI
                     corrupted_content_blocks.push(CorruptDataSegment::Corrupt{ data_start, data_len });
                 }
                 let end = BlockEnd{ header, hash };
-                let brs = BlockReadSummary { hash_as_read,errors_corrected, block_start,block_start_timestamp:u64::from_be_bytes(start.time_stamp()),corrupted_content_blocks, block: Block::A { start, middle: content, end }};
+                let brs = BlockReadSummary { hash_as_read,errors_corrected, min_correction_headroom, block_start,block_start_timestamp:u64::from_be_bytes(start.time_stamp()),corrupted_content_blocks, block: Block::A { start, middle: content, end }, batch_marker, directory: None};
                 Ok(BlockState::Closed(brs))
             }else{
                 Ok(BlockState::InvalidBlockStructure {end_of_last_good_component:block_start, info: "Did not find BlockEnd at correct position".to_string() })
             }
         }
         HeaderTag::StartBBlock => {
-            match read_block_middle::<_,B>(reader_writer,error_correct_header,error_correct_content){
-                Ok(BlockMiddleState::BBlock { middle, end, errors_corrected:ec, hash, corrupted_content_blocks }) => {
+            match read_block_middle_at(reader_writer,error_correct_header,error_correct_content,hasher,max_components,Some(block_start)){
+                Ok(BlockMiddleState::BBlock { middle, end, errors_corrected:ec, min_correction_headroom:headroom, hash, corrupted_content_blocks }) => {
                     errors_corrected += ec;
-                    let brs = BlockReadSummary { hash_as_read:hash,errors_corrected, block_start, block_start_timestamp:u64::from_be_bytes(start.time_stamp()), block: Block::B { start, middle, end }, corrupted_content_blocks };
+                    min_correction_headroom = min_correction_headroom.min(headroom);
+                    let directory = read_content_directory::<_,B>(reader_writer, &middle)?;
+                    let brs = BlockReadSummary { hash_as_read:hash,errors_corrected, min_correction_headroom, block_start, block_start_timestamp:u64::from_be_bytes(start.time_stamp()), block: Block::B { start, middle, end }, corrupted_content_blocks, batch_marker, directory };
                     Ok(BlockState::Closed(brs))
                 },
                 Ok(BlockMiddleState::InvalidBlockStructure { last_good_component_end }) => {
@@ -132,6 +320,9 @@ pub fn try_read_block<RW:std::io::Write + std::io::Read + std::io::Seek,B:BlockI
                 Ok(BlockMiddleState::DataCorruption { component_start, component_tag  }) => {
                     Ok(BlockState::DataCorruption { component_start, is_b_block: true,component_tag})
                 }
+                Ok(BlockMiddleState::CorruptContentLength { component_start }) => {
+                    Ok(BlockState::CorruptContentLength { component_start, is_b_block: true })
+                }
                 Err(e) => return Err(e),
             }
         },
@@ -140,8 +331,342 @@ pub fn try_read_block<RW:std::io::Write + std::io::Read + std::io::Seek,B:BlockI
         HeaderTag::CECComponent |
         HeaderTag::CComponent |
         HeaderTag::CEComponent => return Ok(BlockState::InvalidBlockStructure {end_of_last_good_component:block_start, info: "Found a Content Component, Expected BlockStart".to_string()}),
-        HeaderTag::EndBlock => return Ok(BlockState::InvalidBlockStructure {end_of_last_good_component:block_start, info: "Found a BlockEnd, expected BlockStart".to_string() }),
+        HeaderTag::EndBlock => return Ok(BlockState::StrayBlockEnd { block_start, skip_to: coords.body_start() + HASH_AND_ECC_LEN as u64 }),
+    }
+}
+
+/// Re-reads the block starting at `block_start` (after the magic number) with **no** error correction.
+/// Returns Ok(true) if the block is closed and the stored hash matches the bytes as they are on disk.
+///
+/// Use this as a post-condition after *carefully* writing corrected bytes back for a [CorruptDataSegment].
+/// Any other block state (open, invalid, etc) returns Ok(false).
+pub fn verify_after_patch<RW:std::io::Write + std::io::Read + std::io::Seek,B:BlockInputs>(file:&mut RW,block_start:u64)->Result<bool,ReadWriteError>{
+    file.seek(SeekFrom::Start(block_start))?;
+    match try_read_block::<_,B>(file, false, false)? {
+        BlockState::Closed(BlockReadSummary { block, hash_as_read, .. }) => {
+            let BlockEnd { hash,.. } = block.take_end();
+            Ok(&hash_as_read[..] == hash.hash())
+        },
+        _ => Ok(false)
+    }
+}
+
+/// Recomputes every ECC field of the block at `block_start` (after the magic number) and writes back the ones that changed, restoring full correction headroom.
+///
+/// The block is read with error correction in memory first, and must be closed and match its hash, otherwise [ReadWriteError::HashMismatch] is returned and nothing is written.
+/// The fresh ECC is computed from the corrected headers, content and hash, but only ECC bytes are written: damage in the data they protect is left for
+/// [recover_tail] or an [integrity check](crate::integrity::integrity_check_file) to correct. The magic number's ECC is refreshed too.
+/// Returns the number of ECC bytes rewritten.
+pub fn refresh_block_ecc<RW:FileLike, B:BlockInputs>(file:&mut RW,block_start:u64)->Result<usize,ReadWriteError>{
+    use crate::read::{classify_block, ByteClass, DeferredPatches};
+    use crate::ecc::{calculate_ecc_chunk, calculate_ecc_for_chunks};
+    use std::io::{Read, Seek};
+    file.seek(SeekFrom::Start(block_start))?;
+    let mut reader = DeferredPatches::new(file);
+    let brs = match try_read_block::<_,B>(&mut reader, true, true)? {
+        BlockState::Closed(brs) if brs.block.clone().take_end().hash.hash() == &brs.hash_as_read[..] => brs,
+        _ => return Err(ReadWriteError::HashMismatch { block_start }),
+    };
+    let ranges = classify_block(&brs);
+    let magic_start = block_start - MN_ECC_LEN as u64;
+    let block_end = ranges.last().map(|(r,_)|r.end).unwrap_or(block_start);
+    let at = |pos:u64|(pos - magic_start) as usize;
+    let mut corrected = vec![0u8;at(block_end)];
+    reader.seek(SeekFrom::Start(magic_start))?;
+    reader.read_exact(&mut corrected)?;
+    drop(reader);
+    let mut on_disk = vec![0u8;corrected.len()];
+    file.seek(SeekFrom::Start(magic_start))?;
+    file.read_exact(&mut on_disk)?;
+
+    //every ECC field paired with the bytes it covers
+    let mut fresh:Vec<(u64,Vec<u8>)> = Vec::new();
+    if corrected[..MAGIC_NUMBER.len()] == MAGIC_NUMBER {fresh.push((magic_start + MAGIC_NUMBER.len() as u64,MN_ECC.to_vec()))}
+    for pair in ranges.windows(2) {
+        let [(data,class),(ecc,ecc_class)] = pair else {unreachable!()};
+        let mut ecc_bytes = Vec::new();
+        match (class,ecc_class) {
+            (ByteClass::Header,ByteClass::HeaderEcc) | (ByteClass::Content,ByteClass::ContentEcc) | (ByteClass::Hash,ByteClass::HashEcc) => {
+                calculate_ecc_chunk(&corrected[at(data.start)..at(data.end)], &mut ecc_bytes)?;
+                fresh.push((ecc.start,ecc_bytes));
+            },
+            //content with its ECC in front
+            (ByteClass::ContentEcc,ByteClass::Content) => {
+                calculate_ecc_for_chunks(&corrected[at(ecc.start)..at(ecc.end)], &mut ecc_bytes)?;
+                fresh.push((data.start,ecc_bytes));
+            },
+            _ => (),
+        }
+    }
+    let mut rewritten = 0;
+    for (pos,ecc_bytes) in fresh {
+        if on_disk[at(pos)..at(pos)+ecc_bytes.len()] == ecc_bytes[..] {continue}
+        file.seek(SeekFrom::Start(pos))?;
+        file.write_all(&ecc_bytes)?;
+        rewritten += ecc_bytes.len();
+    }
+    file.seek(SeekFrom::Start(block_end))?;
+    Ok(rewritten)
+}
+
+/// Rewrites the B block at `block_start` (after the magic number) with all of its content in a single content component, dropping the per-component header and ECC overhead.
+///
+/// The block is read with error correction first, and must be closed and match its hash, otherwise [ReadWriteError::HashMismatch] is returned and nothing is written.
+/// The logical content (decompressed, if need be) of each component is concatenated in order and stored uncompressed, with ECC if any component had it.
+/// The content type is kept if all components share one. A trailing content directory is dropped, as the components it lists are gone.
+/// The BlockStart flags, batch marker, recorded body length and BlockEnd header are kept, and the hash is recomputed ([OFFSET_BOUND] included, the block does not move).
+///
+/// Everything after the block is moved to follow the new block, and the file is truncated if it shrank.
+/// This is **not** crash safe: stop writers and keep a backup (or build a copy with [atomic_replace]).
+/// Errors with InvalidInput, before writing anything, if the block is an A block, the next block is [CHAINED] (its hash covers this block's hash),
+/// or a later block that would move is [OFFSET_BOUND] (its hash covers where it starts).
+/// Returns where the new block landed, the file is left positioned at its end.
+pub fn merge_b_block_components<RW:FileLike, B:BlockInputs>(file:&mut RW,block_start:u64)->Result<WrittenBlock,ReadWriteError>{
+    let invalid = |msg:&str|ReadWriteError::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg.to_string()));
+    file.seek(SeekFrom::Start(block_start))?;
+    let (start,middle,end,batch_marker) = match try_read_block::<_,B>(file, true, true)? {
+        BlockState::Closed(BlockReadSummary { block: Block::B { start, middle, end }, hash_as_read, batch_marker, .. }) if end.hash.hash() == &hash_as_read[..] => (start,middle,end,batch_marker),
+        BlockState::Closed(BlockReadSummary { block: Block::A { .. }, .. }) => return Err(invalid("merge_b_block_components needs a B block")),
+        _ => return Err(ReadWriteError::HashMismatch { block_start }),
+    };
+    let old_end = file.stream_position()?;
+    let file_len = file.len()?;
+    if old_end + (MN_ECC_LEN + HEADER_LEN) as u64 <= file_len {
+        let mut next = [0u8;HEADER_LEN];
+        file.seek(SeekFrom::Start(old_end + MN_ECC_LEN as u64))?;
+        file.read_exact(&mut next)?;
+        if ComponentHeader::new(&next, 0).is_chained() {return Err(invalid("the next block is chained to this one"))}
+    }
+    let components:Vec<&Content> = middle.iter().map(|(_,c)|c).filter(|c|c.content_type != Some(CONTENT_DIRECTORY_TYPE)).collect();
+    let mut merged = Vec::new();
+    for content in components.iter() {
+        read_content::<_,_,B>(file, &mut merged, content)?;
+    }
+    let calc_ecc = components.iter().any(|c|c.ecc);
+    let content_type = components.first().and_then(|c|c.content_type).filter(|t|components.iter().all(|c|c.content_type == Some(*t)));
+    let time_stamp = middle.first().map(|(h,_)|u64::from_be_bytes(h.time_stamp()));
+
+    //the new block is built relative to its own start, so it can be written over the old one in one go
+    let mut hasher = if start.is_chained() {chained_hasher::<B>(read_prev_hash(file, block_start)?.as_ref())}else{B::new()};
+    let mut block = std::io::Cursor::new(Vec::new());
+    let new_start = ComponentHeader::new_from_parts(start.as_slice()[0], start.time_stamp(), None);
+    write_header(&mut block, &new_start)?;
+    if let Some(marker) = batch_marker {write_batch_marker(&mut block, &marker, &mut hasher)?}
+    write_typed_content_component(&mut block, calc_ecc, None, time_stamp, content_type, &merged, &mut hasher)?;
+    if end.header.is_offset_bound() {hasher.update(&block_start.to_be_bytes())}
+    let hash = hasher.finalize();
+    if start.data() != [0u8;4] {
+        write_block_end_with_body_len(&mut block, 0, &new_start, &end.header, &hash)?;
+    }else{
+        write_block_end(&mut block, &end.header, &hash)?;
+    }
+    let block = block.into_inner();
+
+    let new_end = block_start + block.len() as u64;
+    if new_end != old_end && offset_bound_block_from(file, old_end)? {return Err(invalid("a block after this one is offset bound and can not move"))}
+    move_range(file, old_end, new_end, file_len - old_end)?;
+    file.seek(SeekFrom::Start(block_start))?;
+    file.write_all(&block)?;
+    if new_end < old_end {file.truncate(file_len - (old_end - new_end))?}
+    file.flush()?;
+    file.seek(SeekFrom::Start(new_end))?;
+    Ok(WrittenBlock { start_offset: block_start, end_offset: new_end, hash, timestamp: u64::from_be_bytes(start.time_stamp()) })
+}
+///True if a block from `from` on has an [OFFSET_BOUND] BlockEnd, so it would stop verifying if it moved.
+fn offset_bound_block_from<R:std::io::Read + std::io::Seek>(file:&mut R,from:u64)->Result<bool,ReadWriteError>{
+    let file = &mut PositionedFile::new(file);
+    file.seek(SeekFrom::Start(from))?;
+    loop {
+        match catalog_block_parts(file) {
+            Ok(Some((_,_,end,_))) if end.is_offset_bound() => return Ok(true),
+            Ok(Some(_)) => continue,
+            Ok(None) | Err(ReadWriteError::EndOfFile) => return Ok(false),
+            Err(e) => return Err(e),
+        }
+    }
+}
+///Moves `len` bytes at `from` to `to`, copying in the direction that never overwrites bytes before they are moved.
+fn move_range<RW:std::io::Read + std::io::Write + std::io::Seek>(file:&mut RW,from:u64,to:u64,len:u64)->Result<(),ReadWriteError>{
+    const CHUNK:u64 = 64 * 1024;
+    if from == to {return Ok(())}
+    let mut buf = vec![0u8;CHUNK.min(len) as usize];
+    let mut moved = 0;
+    while moved < len {
+        let n = CHUNK.min(len - moved);
+        //moving down copies from the front, moving up from the back
+        let offset = if to < from {moved}else{len - moved - n};
+        file.seek(SeekFrom::Start(from + offset))?;
+        file.read_exact(&mut buf[..n as usize])?;
+        file.seek(SeekFrom::Start(to + offset))?;
+        file.write_all(&buf[..n as usize])?;
+        moved += n;
+    }
+    Ok(())
+}
+
+/// Runs `f` (normally [recover_tail]) while holding an exclusive advisory lock on the file, so only one recovery runs at a time.
+///
+/// If another handle holds the lock, this returns [ReadWriteError::Locked] right away without calling `f`.
+/// The lock is released when `f` returns.
+///
+/// Platform behavior (see [std::fs::File::try_lock]):
+/// - Unix: `flock(LOCK_EX | LOCK_NB)`. It is advisory, so it only keeps out other callers of this fn (or other flock users).
+///   Each `File::open` gets its own lock, even within one process.
+/// - Windows: `LockFileEx`, which is mandatory, so other handles can't write while we recover.
+/// - Other platforms return an error from the lock call, which is passed through as [ReadWriteError::Io].
+///
+/// Needs the `recovery_lock` feature, as [std::fs::File::try_lock] is only stable since Rust 1.89.
+/// An error releasing the lock is only returned if `f` succeeded, it never hides the error from `f`.
+#[cfg(feature = "recovery_lock")]
+pub fn with_recovery_lock<T>(file:&mut std::fs::File,f:impl FnOnce(&mut std::fs::File)->Result<T,ReadWriteError>)->Result<T,ReadWriteError>{
+    match file.try_lock() {
+        Ok(()) => (),
+        Err(std::fs::TryLockError::WouldBlock) => return Err(ReadWriteError::Locked),
+        Err(std::fs::TryLockError::Error(e)) => return Err(e.into()),
+    }
+    let res = f(file);
+    let unlocked = file.unlock();
+    match (res,unlocked) {
+        (Ok(_),Err(e)) => Err(e.into()),
+        (res,_) => res,
+    }
+}
+
+/// Replaces the file at `path` with a new DocuFort file written by `build`, so readers see either the old file or the new one, never a mix.
+///
+/// `build` writes the whole new file (starting with [init_file](crate::write::init_file)) to `path` + `.tmp`.
+/// The temp file is synced and must pass an [integrity check](crate::integrity::integrity_check_file) with every block closed and no corrupted content,
+/// then it is renamed over `path`. On Unix the parent directory is synced too, so the rename itself is durable.
+/// If `build` or the check fails, the temp file is removed and `path` is left untouched. A failed check returns [ReadWriteError::Io] with `InvalidData`.
+pub fn atomic_replace<B:BlockInputs>(path:&std::path::Path,build:impl FnOnce(&mut std::fs::File)->Result<(),ReadWriteError>)->Result<(),ReadWriteError>{
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = std::path::PathBuf::from(tmp_path);
+    let res = (||{
+        let mut tmp = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&tmp_path)?;
+        build(&mut tmp)?;
+        tmp.sync_all()?;
+        std::io::Seek::seek(&mut tmp, SeekFrom::Start(0))?;
+        let invalid = |msg:String|ReadWriteError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, msg));
+        let check = match crate::integrity::integrity_check_file::<_,B>(&mut tmp) {
+            Ok(check) => check,
+            Err(crate::integrity::IntegrityErr::Other(e)) => return Err(e),
+            Err(e) => return Err(invalid(format!("replacement failed its integrity check: {}",e))),
+        };
+        if check.file_len_checked != tmp.len()? || !check.corrupted_segments.is_empty() {
+            return Err(invalid("replacement has an open block or corrupted content".to_string()))
+        }
+        //the check may have written corrections
+        tmp.sync_all()?;
+        Ok(())
+    })();
+    if let Err(e) = res {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(e)
+    }
+    std::fs::rename(&tmp_path, path)?;
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        let dir = if dir.as_os_str().is_empty() {std::path::Path::new(".")}else{dir};
+        std::fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// What [recover_tail] is expected to touch, see [estimate_recovery].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RecoveryEstimate{
+    pub file_len:u64,
+    ///Start of the last block (after its magic number), None if the file has no block yet.
+    pub last_block_start:Option<u64>,
+    ///Bytes from the last block's magic number (or the file header) to EOF.
+    ///This is what recovery will read, and at most truncate, unless the last block start header is damaged.
+    pub tail_bytes:u64,
+    ///The tail is not a cleanly closed block, so recovery will truncate and/or close it.
+    pub tail_open:bool,
+}
+///Looks at the tail like [recover_tail] would, without changing the file.
+///
+///ECC corrections are collected with [DeferredPatches](crate::read::DeferredPatches) and dropped, nothing is written.
+pub fn estimate_recovery<RW:std::io::Read + std::io::Seek, B:BlockInputs>(file: &mut RW) -> Result<RecoveryEstimate, ReadWriteError> {
+    let file_len = file.seek(SeekFrom::End(0))?;
+    let no_blocks = RecoveryEstimate{ file_len, last_block_start: None, tail_bytes: file_len.saturating_sub(FILE_HEADER_LEN as u64), tail_open: file_len > FILE_HEADER_LEN as u64 };
+    let block_start = match find_block_start(file)? {
+        offset if offset <= FILE_HEADER_LEN as u64 => return Ok(no_blocks),
+        offset => offset,
+    };
+    file.seek(SeekFrom::Start(block_start))?;
+    let mut reader = crate::read::DeferredPatches::new(file);
+    let closed = try_read_block::<_,B>(&mut reader, true, false)?.is_closed();
+    let block_end = std::io::Seek::stream_position(&mut reader)?;
+    let tail_start = BlockCoords::new(block_start).magic_number_start();
+    Ok(RecoveryEstimate{ file_len, last_block_start: Some(block_start), tail_bytes: file_len - tail_start, tail_open: !closed || block_end < file_len })
+}
+///Opens the file at `path` read-only and returns true if [recover_tail] would change it (see [RecoveryEstimate::tail_open]).
+///
+///Works on read-only files and media, as nothing is written.
+pub fn needs_recovery<B:BlockInputs>(path:&std::path::Path) -> Result<bool, ReadWriteError> {
+    let mut file = std::fs::File::open(path)?;
+    Ok(estimate_recovery::<_,B>(&mut file)?.tail_open)
+}
+
+///The ECC_LEN values a file header can record, with the ECC length each implies. 0 is a `crc_only` file (a CRC32 in a 4 byte slot).
+const ECC_LEN_CANDIDATES:[(u8,usize);7] = [(0,4),(2,2),(4,4),(6,6),(8,8),(16,16),(32,32)];
+
+///Whether `ecc` is exactly the ECC (or CRC32 for `ecc_len_byte` 0) that `ecc_len_byte` would produce for `data`.
+fn ecc_matches(data:&[u8],ecc:&[u8],ecc_len_byte:u8)->bool{
+    if ecc_len_byte == 0 {return ecc == crate::util::crc32(data).to_be_bytes()}
+    #[cfg(not(feature = "crc_only"))]
+    {
+        let mut expected = Vec::with_capacity(ecc.len());
+        crate::ecc::calculate_ecc_chunk_with_len(data, ecc.len(), &mut expected).is_ok() && expected == ecc
     }
+    #[cfg(feature = "crc_only")]
+    {false}
+}
+
+/// Repairs a corrupted ECC_LEN byte in the file header by inferring it from the first block.
+///
+/// The magic number and version must be intact. Every ECC length a file can be written with is tried against the first block's magic number ECC and BlockStart header ECC,
+/// and the byte is only rewritten if exactly one length matches both without correction (RS lengths are not checked in a `crc_only` build).
+/// Returns the ECC_LEN byte the header now holds, or None if the file has no block to infer it from or nothing matched.
+/// A file written with another build's ECC length is repaired to that length, so [verify_configs](crate::read::verify_configs) still reports the mismatch afterwards.
+pub fn repair_header_config<RW:std::io::Read + std::io::Write + std::io::Seek>(file:&mut RW) -> Result<Option<u8>, ReadWriteError> {
+    let mut header = [0u8;FILE_HEADER_LEN as usize];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut header)?;
+    if header[..MAGIC_NUMBER.len()] != MAGIC_NUMBER || header[MAGIC_NUMBER.len()..MAGIC_NUMBER.len()+2] != [b'V',b'1'] {
+        return Err(ReadWriteError::NotADocuFortFile);
+    }
+    let stored = header[FILE_HEADER_LEN as usize - 1];
+    let mut first_block = [0u8;MAGIC_NUMBER.len() + 32 + HEADER_LEN + 32];
+    let mut read = 0;
+    while read < first_block.len() {
+        match file.read(&mut first_block[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    let mut found = ECC_LEN_CANDIDATES.iter().filter(|(_,len)|{
+        let header_start = MAGIC_NUMBER.len() + len;
+        let needed = header_start + HEADER_LEN + len;
+        needed <= read && first_block[..MAGIC_NUMBER.len()] == MAGIC_NUMBER
+            && first_block[header_start] & 0x40 == 0 //A or B BlockStart
+    }).filter(|(byte,len)|{
+        let header_start = MAGIC_NUMBER.len() + len;
+        ecc_matches(&MAGIC_NUMBER, &first_block[MAGIC_NUMBER.len()..header_start], *byte)
+            && ecc_matches(&first_block[header_start..header_start+HEADER_LEN], &first_block[header_start+HEADER_LEN..header_start+HEADER_LEN+len], *byte)
+    });
+    let inferred = match (found.next(),found.next()) {
+        (Some((byte,_)),None) => *byte,
+        _ => return Ok(None),
+    };
+    if inferred != stored {
+        file.seek(SeekFrom::Start(FILE_HEADER_LEN as u64 - 1))?;
+        file.write_all(&[inferred])?;
+        file.flush()?;
+    }
+    Ok(Some(inferred))
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
@@ -153,50 +678,420 @@ pub struct TailRecoverySummary{
     pub has_blocks:bool,
     pub tot_errors_corrected:usize,
     ///Corruption exceeds ECC for content in the following file offsets that are DATA_SIZE len
-    pub corrupted_content_blocks:Vec<CorruptDataSegment>
+    pub corrupted_content_blocks:Vec<CorruptDataSegment>,
+    ///The [BatchMarker] of the last complete block that has one, i.e. of the last batch block to land.
+    ///A batch interrupted by the crash can be resumed at `index + 1`.
+    ///If the tail block has none, the block headers before it are walked to find one, like [catalog](crate::read::catalog) does.
+    pub last_batch_marker:Option<BatchMarker>,
+    ///The tail block was kept (see [UncorrectableContent::Keep]) even though it failed its final check, i.e. its hash still mismatches after ECC.
+    ///Its bad segments are in [corrupted_content_blocks](Self::corrupted_content_blocks). Strict startup logic can treat this as an error.
+    pub tail_block_unverified:bool,
+    ///One entry per file, in order, if this is a [merge](Self::merge) of the summaries of several files. Empty for a single file.
+    pub merged_files:Vec<MergedRecovery>
+}
+/// One file's share of a merged [TailRecoverySummary].
+///
+/// Its index in [merged_files](TailRecoverySummary::merged_files) identifies the file. Its entries in the merged lists follow those of the files before it.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MergedRecovery{
+    pub original_file_len:u64,
+    pub recovered_file_len:u64,
+    ///Number of [file_ops](TailRecoverySummary::file_ops) from this file.
+    pub file_ops:usize,
+    ///Number of [corrupted_content_blocks](TailRecoverySummary::corrupted_content_blocks) from this file.
+    pub corrupted_content_blocks:usize,
+}
+impl TailRecoverySummary {
+    ///Combines the summaries of recovering two files (e.g. the rolled files of one log), `other` being the later file.
+    ///
+    ///Counters and lengths are summed, lists are concatenated, and [merged_files](Self::merged_files) records which entries came from which file.
+    ///The batch marker is the later one (if it has one). Merging a merged summary keeps its per file entries.
+    pub fn merge(mut self, other:TailRecoverySummary)->TailRecoverySummary{
+        let parts = |s:&TailRecoverySummary|if s.merged_files.is_empty() {
+            vec![MergedRecovery { original_file_len: s.original_file_len, recovered_file_len: s.recovered_file_len, file_ops: s.file_ops.len(), corrupted_content_blocks: s.corrupted_content_blocks.len() }]
+        }else{
+            s.merged_files.clone()
+        };
+        let mut merged_files = parts(&self);
+        merged_files.extend(parts(&other));
+        self.original_file_len += other.original_file_len;
+        self.recovered_file_len += other.recovered_file_len;
+        self.file_ops.extend(other.file_ops);
+        self.has_blocks |= other.has_blocks;
+        self.tot_errors_corrected += other.tot_errors_corrected;
+        self.corrupted_content_blocks.extend(other.corrupted_content_blocks);
+        self.last_batch_marker = other.last_batch_marker.or(self.last_batch_marker);
+        self.tail_block_unverified |= other.tail_block_unverified;
+        self.merged_files = merged_files;
+        self
+    }
+    ///The [file_ops](Self::file_ops) trace with what recovery did after each read.
+    pub fn steps(&self)->Vec<RecoveryStep>{
+        self.file_ops.iter().enumerate().map(|(i,(offset,state))|{
+            //recovery re-reads the same block after closing it or to apply ECC, anything else moved on to an earlier block
+            let next = self.file_ops.get(i+1).map(|(o,_)|*o);
+            let reread = next == Some(*offset);
+            let mn_start = BlockCoords::new(*offset).magic_number_start();
+            let action = match state {
+                BlockState::Closed(_) if next.is_none() && self.has_blocks => RecoveryAction::Keep { end: self.recovered_file_len },
+                BlockState::Closed(_) if reread => RecoveryAction::RetryWithEcc,
+                BlockState::Closed(_) => RecoveryAction::DropBlock { truncate_at: mn_start },
+                BlockState::OpenBBlock { truncate_at, .. } if reread => RecoveryAction::CloseBBlock { truncate_at: *truncate_at },
+                BlockState::OpenBBlock { .. } |
+                BlockState::StrayBlockEnd { .. } => RecoveryAction::DropBlock { truncate_at: mn_start },
+                BlockState::ProbablyNotStartHeader { .. } => RecoveryAction::SkipFalseMatch,
+                BlockState::OpenABlock { truncate_at } |
+                BlockState::IncompleteStartHeader { truncate_at } => RecoveryAction::Truncate { truncate_at: *truncate_at },
+                BlockState::InvalidBlockStructure { end_of_last_good_component, .. } => RecoveryAction::Truncate { truncate_at: *end_of_last_good_component },
+                BlockState::DataCorruption { component_start, .. } => RecoveryAction::Truncate { truncate_at: *component_start },
+                BlockState::CorruptContentLength { component_start, is_b_block } => {
+                    RecoveryAction::Truncate { truncate_at: if *is_b_block {*component_start}else{*component_start - MN_ECC_LEN as u64} }
+                },
+            };
+            let block_summary = if let BlockState::Closed(brs) = state {Some(brs.clone())}else{None};
+            RecoveryStep { offset: *offset, state: state.name(), action, block_summary }
+        }).collect()
+    }
+    ///[steps](Self::steps) as one line each, e.g. "read block at 268 → OpenABlock → truncate to 256".
+    pub fn render_steps(&self)->String{
+        self.steps().iter().map(|s|s.to_string()).collect::<Vec<_>>().join("\n")
+    }
+}
+///What [recover_tail] did after reading a block, see [TailRecoverySummary::steps].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RecoveryAction{
+    ///The block is complete, and the file now ends at `end`.
+    Keep{end:u64},
+    ///The block did not match its hash, so it was read again applying the content ECC.
+    RetryWithEcc,
+    ///The whole block (from its magic number) was truncated.
+    DropBlock{truncate_at:u64},
+    ///An open B block was truncated at its last complete Content and given a BlockEnd.
+    CloseBBlock{truncate_at:u64},
+    ///The file was truncated after the last good component.
+    Truncate{truncate_at:u64},
+    ///The magic number was not followed by a valid header, so the scan went on before it.
+    SkipFalseMatch,
+}
+impl std::fmt::Display for RecoveryAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecoveryAction::Keep { end } => write!(f, "keep, file ends at {}", end),
+            RecoveryAction::RetryWithEcc => write!(f, "retry with ECC"),
+            RecoveryAction::DropBlock { truncate_at } => write!(f, "drop block, truncate to {}", truncate_at),
+            RecoveryAction::CloseBBlock { truncate_at } => write!(f, "close B block at {}", truncate_at),
+            RecoveryAction::Truncate { truncate_at } => write!(f, "truncate to {}", truncate_at),
+            RecoveryAction::SkipFalseMatch => write!(f, "skip false match"),
+        }
+    }
+}
+///One read of the [recover_tail] trace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecoveryStep{
+    ///Where the block was read from (after its magic number).
+    pub offset:u64,
+    ///The [BlockState] read there, see [BlockState::name].
+    pub state:&'static str,
+    pub action:RecoveryAction,
+    ///Present if the block was closed.
+    pub block_summary:Option<BlockReadSummary>
+}
+impl std::fmt::Display for RecoveryStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "read block at {} → {} → {}", self.offset, self.state, self.action)
+    }
+}
+///What recovery does with a complete tail block whose content does not match its hash, even after ECC.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum UncorrectableContent{
+    ///Consider the block recovered, and report the segments in [TailRecoverySummary::corrupted_content_blocks].
+    #[default]
+    Keep,
+    ///Truncate the whole block, and keep recovering from the block before it.
+    TruncateBlock,
+    ///Return [ReadWriteError::HashMismatch] for the block, leaving it in place.
+    Fail
+}
+///How a complete block is checked, see [try_read_block_verified] and [RecoveryPolicy::verify_via].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum VerifyStrategy{
+    ///Hash the block as it is on disk, and only apply the content ECC if the hash does not match.
+    #[default]
+    HashThenEcc,
+    ///Apply the content ECC to every component and skip hashing, for files where all content has ECC.
+    ///A block counts as corrupt only if an ECC chunk is uncorrectable, so content without ECC is not checked at all.
+    EccOnly,
+    ///Only hash, never apply the content ECC.
+    HashOnly,
+}
+///Skips all hashing for [VerifyStrategy::EccOnly], everything else goes to `B`.
+struct NoHash<B>(std::marker::PhantomData<B>);
+impl<B> Clone for NoHash<B> {
+    fn clone(&self) -> Self {
+        NoHash(std::marker::PhantomData)
+    }
+}
+impl<B:BlockInputs> BlockInputs for NoHash<B> {
+    type CompLevel = B::CompLevel;
+    const COMPRESSED_MAGIC: &'static [u8] = B::COMPRESSED_MAGIC;
+    fn new() -> Self {
+        NoHash(std::marker::PhantomData)
+    }
+    fn update(&mut self, _data: &[u8]) {}
+    fn finalize(&self) -> [u8; HASH_LEN] {
+        [0u8;HASH_LEN]
+    }
+    fn current_timestamp() -> u64 {
+        B::current_timestamp()
+    }
+    fn comp_level_byte(comp_level: &Self::CompLevel) -> Option<u8> {
+        B::comp_level_byte(comp_level)
+    }
+    fn compress<W:std::io::Write>(data: &[u8], writer: &mut W, comp_level: &Self::CompLevel) -> std::io::Result<usize> {
+        B::compress(data, writer, comp_level)
+    }
+    fn decompress<R:std::io::Read,W:std::io::Write>(compressed: &mut R, sink: &mut W, output_size:u32) -> std::io::Result<usize> {
+        B::decompress(compressed, sink, output_size)
+    }
+}
+///True if the closed block checks out under `verify_via`.
+fn block_verified(verify_via:VerifyStrategy,brs:&BlockReadSummary)->bool{
+    match verify_via {
+        VerifyStrategy::EccOnly => !brs.corrupted_content_blocks.iter().any(|c|matches!(c,CorruptDataSegment::EccChunk { .. })),
+        _ => brs.block.clone().take_end().hash.hash() == &brs.hash_as_read[..],
+    }
+}
+/// Same as [try_read_block], but a complete block is checked with `verify_via` (the header ECC is applied if `error_correct_header`).
+///
+/// With [EccOnly](VerifyStrategy::EccOnly) nothing is hashed, so `hash_as_read` is all zeros, and uncorrectable chunks are in `corrupted_content_blocks`.
+/// With [HashThenEcc](VerifyStrategy::HashThenEcc) a block that does not match its hash is read a second time, applying the content ECC.
+pub fn try_read_block_verified<RW:std::io::Write + std::io::Read + std::io::Seek,B:BlockInputs>(reader_writer:&mut RW,error_correct_header:bool,verify_via:VerifyStrategy)->Result<BlockState,ReadWriteError>{
+    match verify_via {
+        VerifyStrategy::EccOnly => try_read_block::<_,NoHash<B>>(reader_writer, error_correct_header, true),
+        VerifyStrategy::HashOnly => try_read_block::<_,B>(reader_writer, error_correct_header, false),
+        VerifyStrategy::HashThenEcc => {
+            let block_start = reader_writer.stream_position()?;
+            match try_read_block::<_,B>(reader_writer, error_correct_header, false)? {
+                BlockState::Closed(brs) if !block_verified(verify_via, &brs) => {
+                    reader_writer.seek(SeekFrom::Start(block_start))?;
+                    try_read_block::<_,B>(reader_writer, error_correct_header, true)
+                },
+                bs => Ok(bs),
+            }
+        },
+    }
+}
+///Options for [recover_tail_with_policy]. The default is what [recover_tail] does.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RecoveryPolicy{
+    pub uncorrectable_content:UncorrectableContent,
+    ///If true (the default) an open B block is closed after its last complete Content, keeping what was written.
+    ///If false it is truncated back to its magic number like an open A block, treating it as an aborted transaction.
+    pub close_open_b_blocks:bool,
+    ///Stop with [ReadWriteError::TooMuchCorruption] once more than this many errors have been corrected (None is unlimited).
+    ///Pervasive bit rot points at failing hardware, which healing would hide.
+    ///Corrections in the block that crossed the limit have already been written back.
+    pub max_corrections:Option<usize>,
+    ///If true, complete blocks after the last [COMMITTED] block are truncated too, as if they were never written (a rolled back transaction).
+    ///With no committed block at all, the file is truncated back to its header.
+    pub rollback_uncommitted:bool,
+    ///How the tail block is checked. With [EccOnly](VerifyStrategy::EccOnly) an uncorrectable ECC chunk is what counts as `uncorrectable_content`,
+    ///and [Fail](UncorrectableContent::Fail) still returns [ReadWriteError::HashMismatch].
+    pub verify_via:VerifyStrategy,
+    ///If true, a complete tail block with the same idempotency key as the block before it is truncated, as a retried write that already landed.
+    ///The key is the [BatchMarker] if the tail block has one (a user chosen id), else the block hash (the content).
+    ///Only the tail is checked, but that repeats, so a run of duplicates collapses to its first block.
+    ///Leave this off if the same content is legitimately written twice in a row (e.g. [heartbeats](crate::write::write_heartbeat)).
+    ///A [CHAINED] block without a marker never matches, as its hash covers the block before it.
+    pub drop_duplicate_tail:bool,
+    ///Which file versions are recovered. Under [VersionPolicy::AllowNewer] a newer version file is scanned for blocks like a `V1` one,
+    ///instead of only its last [FOREIGN_FILE_SCAN_WINDOW] bytes, see [find_block_start_with_policy].
+    pub version_policy:VersionPolicy
+}
+impl Default for RecoveryPolicy {
+    fn default() -> Self {
+        Self { uncorrectable_content: UncorrectableContent::default(), close_open_b_blocks: true, max_corrections: None, rollback_uncommitted: false, verify_via: VerifyStrategy::default(), drop_duplicate_tail: false, version_policy: VersionPolicy::Strict }
+    }
+}
+///The [BatchMarker] of the last block before `tail_start` (after its magic number) that has one, see [TailRecoverySummary::last_batch_marker].
+///The blocks are walked by their headers like [catalog](crate::read::catalog), only the markers are read (ECC applied in memory).
+fn last_batch_marker_before<R:std::io::Read + std::io::Seek>(file:&mut R,tail_start:u64)->Result<Option<BatchMarker>,ReadWriteError>{
+    let file = &mut PositionedFile::new(file);
+    file.seek(SeekFrom::Start(FILE_HEADER_LEN as u64))?;
+    let mut last = None;
+    loop {
+        let (entry,start) = match catalog_block_parts(file) {
+            Ok(Some((entry,start,..))) if entry.offset < tail_start => (entry,start),
+            Ok(_) | Err(ReadWriteError::EndOfFile) => break,
+            Err(e) => return Err(e),
+        };
+        if !start.has_batch_marker() {continue}
+        let block_end = file.stream_position()?;
+        file.seek(SeekFrom::Start(BlockCoords::new(entry.offset).body_start()))?;
+        let mut field = [0u8;BATCH_MARKER_LEN+ECC_LEN];
+        file.read_exact(&mut field)?;
+        if apply_ecc(&mut field).is_ok() {
+            last = Some(BatchMarker::from_bytes(field[..BATCH_MARKER_LEN].try_into().unwrap()));
+        }
+        file.seek(SeekFrom::Start(block_end))?;
+    }
+    Ok(last)
+}
+///True if everything after the file header is a torn first block: alignment padding (at most [MAX_ALIGNMENT] zeros),
+///optionally followed by the start of a magic number, see [write_aligned_magic_number](crate::write::write_aligned_magic_number).
+fn is_torn_first_block<R:std::io::Read + std::io::Seek>(file:&mut R,file_len:u64)->Result<bool,ReadWriteError>{
+    let len = file_len.saturating_sub(FILE_HEADER_LEN as u64);
+    if len > MAX_ALIGNMENT + MN_ECC_LEN as u64 {return Ok(false)}
+    file.seek(SeekFrom::Start(FILE_HEADER_LEN as u64))?;
+    let mut buf = [0u8;4096];
+    let (mut zeros,mut rest) = (0u64,Vec::new());
+    let mut remaining = len;
+    while remaining > 0 {
+        let n = remaining.min(buf.len() as u64) as usize;
+        file.read_exact(&mut buf[..n])?;
+        remaining -= n as u64;
+        let z = if rest.is_empty() {buf[..n].iter().take_while(|b|**b == 0).count()}else{0};
+        zeros += z as u64;
+        rest.extend_from_slice(&buf[z..n]);
+        if rest.len() >= MN_ECC_LEN {return Ok(false)}
+    }
+    Ok(zeros <= MAX_ALIGNMENT && [&MAGIC_NUMBER[..],&MN_ECC[..]].concat().starts_with(&rest))
+}
+///True if the block before `block_start` has the same idempotency key as `brs`, see [RecoveryPolicy::drop_duplicate_tail].
+///The block before is read with no error correction, and one that does not read as a closed block is no match.
+fn duplicates_previous<RW:std::io::Write + std::io::Read + std::io::Seek,B:BlockInputs>(file:&mut RW,block_start:u64,brs:&BlockReadSummary)->Result<bool,ReadWriteError>{
+    let Some(marker) = brs.batch_marker else {
+        let hash = brs.block.clone().take_end().hash;
+        return match read_prev_hash(file, block_start) {
+            Ok(prev) => Ok(prev.is_some_and(|prev|&prev[..] == hash.hash())),
+            Err(ReadWriteError::EccTooManyErrors) => Ok(false),
+            Err(e) => Err(e),
+        }
+    };
+    file.seek(SeekFrom::Start(BlockCoords::new(block_start).magic_number_start()))?;
+    let prev_start = find_block_start(file)?;
+    if prev_start <= FILE_HEADER_LEN as u64 {return Ok(false)}
+    file.seek(SeekFrom::Start(prev_start))?;
+    match try_read_block::<_,B>(file, false, false)? {
+        BlockState::Closed(prev) => Ok(prev.batch_marker == Some(marker)),
+        _ => Ok(false),
+    }
+}
+fn check_correction_limit(policy:&RecoveryPolicy,tot_errors_corrected:usize)->Result<(),ReadWriteError>{
+    match policy.max_corrections {
+        Some(max) if tot_errors_corrected > max => Err(ReadWriteError::TooMuchCorruption(tot_errors_corrected)),
+        _ => Ok(()),
+    }
+}
+///Truncates to `at`, positions the file there and records it as the expected length.
+///Checks the length first, so a truncate never drops bytes someone else appended.
+fn truncate_tracked<RW:FileLike>(file:&mut RW,file_len:&mut u64,at:u64)->Result<(),ReadWriteError>{
+    check_len_unchanged(file, *file_len)?;
+    file.truncate(at)?;
+    file.seek(SeekFrom::Start(at))?;
+    *file_len = at;
+    Ok(())
+}
+///Recovery does not support another writer changing the file under it.
+fn check_len_unchanged<RW:FileLike>(file:&RW,expected:u64)->Result<(),ReadWriteError>{
+    let found = file.len()?;
+    if found != expected {return Err(ReadWriteError::LengthChanged { expected, found })}
+    Ok(())
 }
 ///Recovers the end of the DocuFort file.
 ///As long as the headers have corruption below the error correction ability, this will at most truncate the last block, if it is incomplete.
 ///If headers are corrupted, then it will keep truncating the end of the file until it can read a complete block.
-///This does *not* truncate a block whose *contents* are corrupted beyond repair.
+///This does *not* truncate a block whose *contents* are corrupted beyond repair, see [recover_tail_with_policy] for that.
+///If there are enough bytes after the file header for a magic number, but none can be found, [ReadWriteError::DataCorruption] is returned
+///instead of truncating the file back to its header. The same goes for more than a magic number and [MAX_ALIGNMENT] worth of bytes after the last
+///complete block: `data_start` is where that block ends.
+///
+///Appending to the file while this runs is not supported. The length is taken once at the start, and if it changes other than by recovery's own
+///truncates and writes, [ReadWriteError::LengthChanged] is returned. Hold the recovery lock (`with_recovery_lock`, with the `recovery_lock` feature) and stop writers first.
 pub fn recover_tail<RW:FileLike, B:BlockInputs>(file: &mut RW) -> Result<TailRecoverySummary, ReadWriteError> {
+    recover_tail_with_policy::<_,B>(file, RecoveryPolicy::default())
+}
+///Same as [recover_tail], but the [RecoveryPolicy] decides what happens to a tail block whose content can't be verified, and to an open B block.
+pub fn recover_tail_with_policy<RW:FileLike, B:BlockInputs>(file: &mut RW, policy:RecoveryPolicy) -> Result<TailRecoverySummary, ReadWriteError> {
     let original_file_len = file.len()?;
-    file.seek(SeekFrom::End(0))?;
+    //The length we expect the file to have, only changed by our own truncates and writes.
+    let mut file_len = original_file_len;
+    file.seek(SeekFrom::Start(file_len))?;
     let mut file_ops = Vec::new();
     let mut tot_errors_corrected = 0;
     let mut error_correct_content = false;
     let mut other_start = None;
     loop {
-        let current_file_len = file.len()?;
+        check_len_unchanged(file, file_len)?;
+        let current_file_len = file_len;
         if let Some(offset) = other_start.take() {
             file.seek(SeekFrom::Start(offset))?;
         }
-        let block_start_offset = match find_block_start(file) {
-            Ok(offset) if offset <= FILE_HEADER_LEN as u64 => return Ok(TailRecoverySummary { original_file_len, recovered_file_len: current_file_len, file_ops, has_blocks: false, tot_errors_corrected,corrupted_content_blocks:vec![] }),
+        let block_start_offset = match find_block_start_with_policy(file, policy.version_policy) {
+            Ok(offset) if offset <= FILE_HEADER_LEN as u64 => {
+                //No magic number found. Alignment padding and part of a magic number after the file header are a partially written first block,
+                //anything else should have had one, so it is corruption rather than a torn write and is not ours to drop.
+                if current_file_len >= (FILE_HEADER_LEN + MN_ECC_LEN) as u64 && !is_torn_first_block(file, current_file_len)? {
+                    return Err(ReadWriteError::DataCorruption { data_start: FILE_HEADER_LEN as u64 })
+                }
+                if current_file_len > FILE_HEADER_LEN as u64 {
+                    truncate_tracked(file, &mut file_len, FILE_HEADER_LEN as u64)?;
+                }
+                let recovered_file_len = current_file_len.min(FILE_HEADER_LEN as u64);
+                check_len_unchanged(file, recovered_file_len)?;
+                return Ok(TailRecoverySummary { original_file_len, recovered_file_len, file_ops, has_blocks: false, tot_errors_corrected,corrupted_content_blocks:vec![], last_batch_marker:None, tail_block_unverified:false, merged_files:vec![] })
+            },
             Err(e) => return Err(e.into()),
             Ok(offset) => offset,
         };
         file.seek(SeekFrom::Start(block_start_offset))?;
-        let bs = try_read_block::<_,B>(file, true,error_correct_content)?;
+        let bs = match policy.verify_via {
+            VerifyStrategy::EccOnly => try_read_block::<_,NoHash<B>>(file, true, true)?,
+            _ => try_read_block::<_,B>(file, true,error_correct_content)?,
+        };
         let crsr_pos = file.seek(SeekFrom::Current(0)).unwrap();
         file_ops.push((block_start_offset,bs));
         let (_,bs) = file_ops.last().unwrap();
         match bs {
             BlockState::ProbablyNotStartHeader{ start_from } => {other_start = Some(*start_from)}
-            BlockState::Closed (BlockReadSummary { errors_corrected, block,  hash_as_read, corrupted_content_blocks, .. }) => {
+            BlockState::Closed (brs @ BlockReadSummary { errors_corrected, block, corrupted_content_blocks, batch_marker, .. }) => {
                 tot_errors_corrected += errors_corrected;
-                let BlockEnd { hash,.. } = block.clone().take_end();
-                if !error_correct_content && &hash_as_read[..] != hash.hash() {
+                check_correction_limit(&policy, tot_errors_corrected)?;
+                let verified = block_verified(policy.verify_via, brs);
+                if !error_correct_content && !verified && policy.verify_via == VerifyStrategy::HashThenEcc {
                     error_correct_content = true;
                     continue;//read the same block over, but correct the errors
                 }else{//hash is perfect, skip ecc, clean recovery
+                    if !verified {
+                        match policy.uncorrectable_content {
+                            UncorrectableContent::Keep => (),
+                            UncorrectableContent::TruncateBlock => {
+                                truncate_tracked(file, &mut file_len, BlockCoords::new(block_start_offset).magic_number_start())?;
+                                error_correct_content = false;
+                                continue;
+                            },
+                            UncorrectableContent::Fail => return Err(ReadWriteError::HashMismatch { block_start: block_start_offset }),
+                        }
+                    }
+                    if policy.rollback_uncommitted && !block.clone().take_start().is_committed() {
+                        truncate_tracked(file, &mut file_len, BlockCoords::new(block_start_offset).magic_number_start())?;
+                        error_correct_content = false;
+                        continue;
+                    }
+                    if policy.drop_duplicate_tail && duplicates_previous::<_,B>(file, block_start_offset, brs)? {
+                        truncate_tracked(file, &mut file_len, BlockCoords::new(block_start_offset).magic_number_start())?;
+                        error_correct_content = false;
+                        continue;
+                    }
                     if crsr_pos < current_file_len{
-                        //we must truncate, as their is an incomplete MN+ECC chunk of bytes after
-                        assert!(crsr_pos + MN_ECC_LEN as u64 > current_file_len,"{} !> {}",crsr_pos+MN_ECC_LEN as u64,current_file_len);
-                        file.truncate(crsr_pos)?;
+                        //we must truncate, as their is an incomplete MN+ECC chunk of bytes after (maybe behind alignment padding)
+                        //anything longer is more than a torn write, so leave it for the caller to look at
+                        if crsr_pos + MN_ECC_LEN as u64 + MAX_ALIGNMENT <= current_file_len {return Err(ReadWriteError::DataCorruption { data_start: crsr_pos })}
+                        truncate_tracked(file, &mut file_len, crsr_pos)?;
                     }else{
                         assert_eq!(crsr_pos,current_file_len);
                     }
+                    check_len_unchanged(file, file_len)?;
 
                     //let content_has_uncorrectable_errors = error_correct_content && &hash_as_read[..] != hash.hash();
                     //we could try to recover a b block that has one or more Content components that do not have ecc but has errors
@@ -205,47 +1100,73 @@ pub fn recover_tail<RW:FileLike, B:BlockInputs>(file: &mut RW) -> Result<TailRec
                     //for now, we will consider this 'recovered'
                     //the application using this should also not be able to decode the data properly.
                     let corrupted_content_blocks = corrupted_content_blocks.clone();
+                    let last_batch_marker = match batch_marker {
+                        Some(marker) => Some(*marker),
+                        None => {
+                            let marker = last_batch_marker_before(file, block_start_offset)?;
+                            file.seek(SeekFrom::Start(crsr_pos))?;
+                            marker
+                        },
+                    };
 
-                    return Ok(TailRecoverySummary { original_file_len, recovered_file_len:crsr_pos, file_ops, has_blocks: true, tot_errors_corrected,corrupted_content_blocks })
+                    return Ok(TailRecoverySummary { original_file_len, recovered_file_len:crsr_pos, file_ops, has_blocks: true, tot_errors_corrected,corrupted_content_blocks, last_batch_marker, tail_block_unverified:!verified, merged_files:vec![] })
                 }
             },
-            BlockState::OpenBBlock { truncate_at: truncate_at_then_close_block, errors, hash_for_end, .. } => {
+            BlockState::OpenBBlock { .. } if !policy.close_open_b_blocks => {
+                truncate_tracked(file, &mut file_len, BlockCoords::new(block_start_offset).magic_number_start())?;
+                error_correct_content = false;
+                continue; //should try the next block back
+            },
+            BlockState::OpenBBlock { truncate_at: truncate_at_then_close_block, errors, .. } => {
                 tot_errors_corrected += errors;
-                //let truncation_amt = file.metadata()?.len() - truncate_at_then_close_block;
-                //how do we avoid allocating a really big vec? we would need to know when to start hashing, up to the truncate
-                //then we could just buffer update to get the hash to avoid a large allocation.
-                file.truncate(*truncate_at_then_close_block)?;
-                file.seek(SeekFrom::End(0))?;
+                check_correction_limit(&policy, tot_errors_corrected)?;
+                //Re-hash what survives from disk, so the end hash matches the bytes even if correction rewrote some of them.
+                let mut hasher = block_hasher::<_,B>(file, block_start_offset)?;
+                hash_range(file, BlockCoords::new(block_start_offset).body_start(), *truncate_at_then_close_block, &mut hasher)?;
+                truncate_tracked(file, &mut file_len, *truncate_at_then_close_block)?;
                 let time_stamp = B::current_timestamp();
                 let header = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, time_stamp.to_be_bytes(), None);
-                write_block_end(file, &header, &hash_for_end)?;
+                write_block_end(file, &header, &hasher.finalize())?;
+                //A crash before this leaves the block open (or a torn BlockEnd), which the next recovery closes the same way.
+                file.flush()?;
+                file_len = file.stream_position()?;
                 continue; //should end in a closed block
             },
             BlockState::OpenABlock { truncate_at } => {
-                file.truncate(*truncate_at)?;
-                file.seek(SeekFrom::End(0))?;
+                truncate_tracked(file, &mut file_len, *truncate_at)?;
                 error_correct_content = false;
                 continue; //should try the next block back
             },
             BlockState::InvalidBlockStructure { end_of_last_good_component, .. } => {
-                file.truncate(*end_of_last_good_component)?;
-                file.seek(SeekFrom::End(0))?;
+                truncate_tracked(file, &mut file_len, *end_of_last_good_component)?;
                 error_correct_content = false;
                 continue; //If this is an A block, it will be OpenA next, if B Block, will try to close it next.
             },
             BlockState::DataCorruption { component_start,.. } => {
                 //This should really only occur on headers.
-                file.truncate(*component_start)?;
-                file.seek(SeekFrom::End(0))?;
+                truncate_tracked(file, &mut file_len, *component_start)?;
                 error_correct_content = false;
                 continue; //If this is an A block, it will be OpenA next, if B Block, will try to close it next.
             },
             BlockState::IncompleteStartHeader { truncate_at } => {
-                file.truncate(*truncate_at)?;
-                file.seek(SeekFrom::End(0))?;
+                truncate_tracked(file, &mut file_len, *truncate_at)?;
                 error_correct_content = false;
                 continue; //We don't know what we are, but we just try again after truncation.
             },
+            BlockState::StrayBlockEnd { block_start, .. } => {
+                //Nothing after the magic number belongs to a block, so drop all of it and keep looking back.
+                truncate_tracked(file, &mut file_len, BlockCoords::new(*block_start).magic_number_start())?;
+                error_correct_content = false;
+                continue;
+            },
+            BlockState::CorruptContentLength { component_start, is_b_block } => {
+                //We can't trust anything from this length onward.
+                //An A block is dropped entirely, a B block is truncated at the bad Content and closed next loop.
+                let truncate_at = if *is_b_block {*component_start}else{*component_start - MN_ECC_LEN as u64};
+                truncate_tracked(file, &mut file_len, truncate_at)?;
+                error_correct_content = false;
+                continue;
+            },
         }
     }
 }
@@ -17,7 +17,7 @@ Or you could wrap it in a struct that stores the return values and a file handle
 
 use std::fmt::Debug;
 
-use crate::{core::{BlockInputs, ComponentHeader}, write::{write_magic_number, write_header, write_block_hash, write_atomic_block, write_content_component}, HeaderTag, ReadWriteError};
+use crate::{core::{BlockInputs, ComponentHeader}, write::{write_magic_number, write_header, write_block_hash, write_atomic_block, write_content_component, FlushPolicy}, HeaderTag, ReadWriteError, FileLike};
 
 
 
@@ -34,6 +34,9 @@ pub struct Operation<T:AsRef<[u8]>,C>{
     ///This is basically always the header for the Op.
     ///If the Op is ContentWrite, then this is 'BlockStart'
     pub timestamp:Option<u64>,
+    ///For any BlockEnd this Op writes besides a CloseBlock (which uses `timestamp`): ending an atomic block, or closing the open B block before it.
+    ///If None, [current_timestamp](BlockInputs::current_timestamp) is used. Pin both to write byte for byte reproducible files.
+    pub end_timestamp:Option<u64>,
     pub calc_ecc:bool,
     pub compress:Option<C>
 }
@@ -41,7 +44,7 @@ pub struct Operation<T:AsRef<[u8]>,C>{
 #[derive(Debug)]
 enum InnerOp<T:AsRef<[u8]>,B:BlockInputs> {
     WriteMagicNumber,
-    WriteABlock{time_stamp:u64,content: T, calc_ecc: bool, compress:Option<B::CompLevel> },
+    WriteABlock{time_stamp:u64,end_time_stamp:Option<u64>,content: T, calc_ecc: bool, compress:Option<B::CompLevel> },
     WriteBBlockStart{time_stamp:[u8;8]},
     WriteContentComponent{time_stamp:u64,content: T, calc_ecc: bool, compress:Option<B::CompLevel>,hasher:Option<B>},
     WriteEndHeader{time_stamp:Option<[u8;8]>,hasher:Option<B>},
@@ -92,18 +95,53 @@ impl<B> TailState<B> {
 }
 
 ///The file Read, assumes it is positioned where this function last left it, and the tailstate is the same as what this function returns.
+///
+///Nothing is flushed, see [perform_file_op_with_policy].
 pub fn perform_file_op<RWS, T, B>(
     file: &mut RWS,
     tail: TailState<B>,
     oper: Operation<T,B::CompLevel>,
-    mut write_attempts:usize
+    write_attempts:usize
 ) -> Result<TailState<B>,Vec<ReadWriteError>>//outer error is unrecoverable
 where
     RWS: std::io::Read + std::io::Write + std::io::Seek,
     T: AsRef<[u8]>+Debug,
     B: BlockInputs+Debug,
 {
-    let Operation { op, timestamp, calc_ecc, compress } = oper;
+    file_op(file, tail, oper, write_attempts, &mut |_,_|Ok(()))
+}
+///Same as [perform_file_op], but flushes or syncs `file` after each content component and BlockEnd written, as the [FlushPolicy] says.
+///
+///A flush or sync that fails counts as a failed write attempt, and the part just written is written again.
+pub fn perform_file_op_with_policy<RWS, T, B>(
+    file: &mut RWS,
+    tail: TailState<B>,
+    oper: Operation<T,B::CompLevel>,
+    write_attempts:usize,
+    flush_policy:FlushPolicy
+) -> Result<TailState<B>,Vec<ReadWriteError>>
+where
+    RWS: FileLike,
+    T: AsRef<[u8]>+Debug,
+    B: BlockInputs+Debug,
+{
+    file_op(file, tail, oper, write_attempts, &mut |f,block_end|flush_policy.apply(f, block_end))
+}
+///`flush` is called after each content component (false) and BlockEnd (true) is written.
+fn file_op<RWS, T, B, F>(
+    file: &mut RWS,
+    tail: TailState<B>,
+    oper: Operation<T,B::CompLevel>,
+    mut write_attempts:usize,
+    flush: &mut F
+) -> Result<TailState<B>,Vec<ReadWriteError>>
+where
+    RWS: std::io::Read + std::io::Write + std::io::Seek,
+    T: AsRef<[u8]>+Debug,
+    B: BlockInputs+Debug,
+    F: FnMut(&mut RWS,bool)->std::io::Result<()>,
+{
+    let Operation { op, timestamp, end_timestamp, calc_ecc, compress } = oper;
     //let time_stamp = timestamp.map(|u|u.to_be_bytes());
     let (tail_state,inner_ops) = match (tail,op) {
         (TailState::OpenBBlock { hasher }, Op::CloseBlock) => {
@@ -120,10 +158,10 @@ where
             (
                 TailState::ClosedBlock,
                 vec![
-                    InnerOp::WriteEndHeader { time_stamp:None ,hasher:None },
+                    InnerOp::WriteEndHeader { time_stamp:end_timestamp.map(|u|u.to_be_bytes()) ,hasher:None },
                     InnerOp::WriteHash(Some(hasher)),
                     InnerOp::WriteMagicNumber,
-                    InnerOp::WriteABlock{time_stamp, content: t, calc_ecc, compress },
+                    InnerOp::WriteABlock{time_stamp, end_time_stamp:end_timestamp, content: t, calc_ecc, compress },
                 ]
             )
         },
@@ -145,7 +183,7 @@ where
             let time_stamp = timestamp.unwrap_or_else(B::current_timestamp);
             let ops = vec![
                 if clean.is_closed() { Some(InnerOp::WriteMagicNumber) } else { None },
-                Some(InnerOp::WriteABlock { time_stamp, content: t, calc_ecc, compress  }),
+                Some(InnerOp::WriteABlock { time_stamp, end_time_stamp:end_timestamp, content: t, calc_ecc, compress  }),
             ].into_iter().filter_map(|x| x).collect::<Vec<_>>();
             (TailState::ClosedBlock,ops)
         },
@@ -166,7 +204,7 @@ where
         loop {
             if inner_ops.is_empty(){return Ok(tail_state)}
             let inner = inner_ops.pop().unwrap();
-            match perform_inner_op::<_,T,B>(file,inner){
+            match perform_inner_op::<_,T,B,_>(file,inner,flush){
                 Ok(Some(b)) => if let Some(i) = inner_ops.last_mut() {
                     i.inner.insert_hasher(b)
                 }else{
@@ -186,14 +224,16 @@ where
     }
 }
 
-fn perform_inner_op<RWS, T, B>(
+fn perform_inner_op<RWS, T, B, F>(
     file: &mut RWS,
     oper: InnerOperation<T,B>,
+    flush: &mut F
 ) -> Result<Option<B>,(InnerOperation<T,B>,ReadWriteError)>
 where
     RWS: std::io::Read + std::io::Write + std::io::Seek,
     T: AsRef<[u8]>+Debug,
     B: BlockInputs+Debug,
+    F: FnMut(&mut RWS,bool)->std::io::Result<()>,
 {
     let InnerOperation { inner, start_offset } = oper;
     let start_offset = if let Some(start) = start_offset{
@@ -214,10 +254,10 @@ where
             }
             Ok(None)
         },
-        InnerOp::WriteABlock{ time_stamp, calc_ecc, content, compress } => {
-
-            if let Err(e) = write_atomic_block::<_,B>(file,Some(time_stamp),content.as_ref(),calc_ecc,compress.as_ref(),None) {
-                return Err((InnerOperation{ inner:InnerOp::WriteABlock{ time_stamp, calc_ecc, content, compress }, start_offset:Some(start_offset) },e))
+        InnerOp::WriteABlock{ time_stamp, end_time_stamp, calc_ecc, content, compress } => {
+            let end = end_time_stamp.map(|ts|ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, ts.to_be_bytes(), None));
+            if let Err(e) = write_atomic_block::<_,B>(file,Some(time_stamp),content.as_ref(),calc_ecc,compress.as_ref(),end.as_ref()).and_then(|_|Ok(flush(file,true)?)) {
+                return Err((InnerOperation{ inner:InnerOp::WriteABlock{ time_stamp, end_time_stamp, calc_ecc, content, compress }, start_offset:Some(start_offset) },e))
             }
             Ok(None)
         },
@@ -232,7 +272,7 @@ where
         InnerOp::WriteContentComponent { time_stamp, content, calc_ecc, compress, hasher } => {
             let mut b = if let Some(b) = hasher {b}else{B::new()};
             let hasher = Some(b.clone());//preserve hash state in case of failure
-            if let Err(e) = write_content_component(file,calc_ecc,compress.as_ref(),Some(time_stamp),content.as_ref(),&mut b) {
+            if let Err(e) = write_content_component(file,calc_ecc,compress.as_ref(),Some(time_stamp),content.as_ref(),&mut b).and_then(|_|Ok(flush(file,false)?)) {
                 return Err((InnerOperation{ inner:InnerOp::WriteContentComponent {  time_stamp, content, calc_ecc, compress, hasher}, start_offset:Some(start_offset) },e))
             }
             Ok(Some(b))
@@ -249,7 +289,7 @@ where
         InnerOp::WriteHash(h) => {
             let hasher = h.unwrap();
             let hash = hasher.finalize();
-            if let Err(e) = write_block_hash(file,&hash) {
+            if let Err(e) = write_block_hash(file,&hash).and_then(|_|Ok(flush(file,true)?)) {
                 return Err((InnerOperation{ inner:InnerOp::WriteHash(Some(hasher)), start_offset:Some(start_offset) },e))
             }
             Ok(Some(hasher))
@@ -258,6 +298,159 @@ where
 
 }
 
+/// Wraps a [FileLike] so [truncate](FileLike::truncate) is attempted up to `truncate_attempts` times before giving up.
+///
+/// Truncating to a computed length is safe to repeat, so this can be handed to [recover_tail](crate::recovery::recover_tail) to ride out transient truncation failures.
+/// Reads, writes and seeks are passed straight through, as the recovery logic re-reads after any failure anyway.
+/// Reads that time out (see [TimeoutFile](crate::read::TimeoutFile)) can also be retried, see [with_read_attempts](Self::with_read_attempts).
+#[derive(Debug)]
+pub struct RetryingFile<F>{
+    inner:F,
+    truncate_attempts:usize,
+    read_attempts:usize
+}
+
+impl<F:FileLike> RetryingFile<F> {
+    /// A `truncate_attempts` of 0 is treated as 1.
+    pub fn new(inner:F,truncate_attempts:usize)->Self{
+        Self { inner, truncate_attempts: truncate_attempts.max(1), read_attempts: 1 }
+    }
+    /// Attempts a read up to `read_attempts` times while it fails with [TimedOut](std::io::ErrorKind::TimedOut). 0 is treated as 1, the default.
+    ///
+    /// Only safe if a timed out read leaves the position where it was, as [TimeoutFile](crate::read::TimeoutFile) does.
+    pub fn with_read_attempts(mut self,read_attempts:usize)->Self{
+        self.read_attempts = read_attempts.max(1);
+        self
+    }
+    pub fn get_ref(&self)->&F{
+        &self.inner
+    }
+    pub fn get_mut(&mut self)->&mut F{
+        &mut self.inner
+    }
+    pub fn into_inner(self)->F{
+        self.inner
+    }
+}
+
+impl<F:FileLike> std::io::Read for RetryingFile<F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut attempts = self.read_attempts;
+        loop {
+            attempts -= 1;
+            match self.inner.read(buf) {
+                Err(e) if e.kind() == std::io::ErrorKind::TimedOut && attempts > 0 => continue,
+                res => return res,
+            }
+        }
+    }
+}
+impl<F:FileLike> std::io::Write for RetryingFile<F> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+impl<F:FileLike> std::io::Seek for RetryingFile<F> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+impl<F:FileLike> FileLike for RetryingFile<F> {
+    fn truncate(&mut self, len: u64)->std::io::Result<()>{
+        let mut attempts = self.truncate_attempts;
+        loop {
+            attempts -= 1;
+            match self.inner.truncate(len) {
+                Err(_) if attempts > 0 => continue,
+                res => return res,
+            }
+        }
+    }
+
+    fn len(&self)->std::io::Result<u64> {
+        self.inner.len()
+    }
+
+    fn sync(&mut self)->std::io::Result<()> {
+        self.inner.sync()
+    }
+}
+
+enum WriterMsg<T:AsRef<[u8]>,C>{
+    Op(Operation<T,C>),
+    Shutdown,
+}
+/// A handle to the writer thread started by [spawn_writer].
+pub struct WriterHandle<T:AsRef<[u8]>,B:BlockInputs>{
+    sender:std::sync::mpsc::Sender<WriterMsg<T,B::CompLevel>>,
+    thread:std::thread::JoinHandle<Result<TailState<B>,Vec<ReadWriteError>>>,
+}
+/// Moves `file` to a new thread that runs each [Operation] sent to it through [perform_file_op], in the order they were sent.
+///
+/// `file` must be positioned at its end, and `tail` must be its [TailState] (e.g. from [open_and_verify](crate::open_and_verify)).
+/// The thread stops at the first Operation that fails all `write_attempts`, and its errors are returned by [WriterHandle::shutdown].
+/// Dropping the handle stops the thread after the queued Operations, leaving an open B block for recovery.
+pub fn spawn_writer<F,T,B>(file:F,tail:TailState<B>,write_attempts:usize)->WriterHandle<T,B>
+where
+    F: FileLike + Send + 'static,
+    T: AsRef<[u8]> + Debug + Send + 'static,
+    B: BlockInputs + Debug + Send + 'static,
+    B::CompLevel: Send + 'static,
+{
+    spawn_writer_with_policy(file, tail, write_attempts, FlushPolicy::Never)
+}
+/// Same as [spawn_writer], but each Operation goes through [perform_file_op_with_policy] with the given [FlushPolicy].
+pub fn spawn_writer_with_policy<F,T,B>(mut file:F,mut tail:TailState<B>,write_attempts:usize,flush_policy:FlushPolicy)->WriterHandle<T,B>
+where
+    F: FileLike + Send + 'static,
+    T: AsRef<[u8]> + Debug + Send + 'static,
+    B: BlockInputs + Debug + Send + 'static,
+    B::CompLevel: Send + 'static,
+{
+    let (sender,receiver) = std::sync::mpsc::channel::<WriterMsg<T,B::CompLevel>>();
+    let thread = std::thread::spawn(move ||{
+        for msg in receiver {
+            match msg {
+                WriterMsg::Op(oper) => tail = perform_file_op_with_policy(&mut file, tail, oper, write_attempts, flush_policy)?,
+                WriterMsg::Shutdown => {
+                    let close = Operation{ op: Op::<T>::CloseBlock, timestamp: None, end_timestamp: None, calc_ecc: false, compress: None };
+                    tail = perform_file_op(&mut file, tail, close, write_attempts)?;
+                    file.sync().map_err(|e|vec![e.into()])?;
+                    break
+                },
+            }
+        }
+        Ok(tail)
+    });
+    WriterHandle { sender, thread }
+}
+impl<T:AsRef<[u8]>,B:BlockInputs> WriterHandle<T,B> {
+    /// Queues an Operation for the writer thread.
+    ///
+    /// Returns the Operation if the thread has stopped (an Operation failed), call [shutdown](Self::shutdown) for the errors.
+    pub fn send(&self,oper:Operation<T,B::CompLevel>)->Result<(),Operation<T,B::CompLevel>>{
+        self.sender.send(WriterMsg::Op(oper)).map_err(|e|match e.0 {
+            WriterMsg::Op(oper) => oper,
+            WriterMsg::Shutdown => unreachable!("sent an Op"),
+        })
+    }
+    /// Lets the writer finish the queued Operations, closes the open B block (if any), syncs the file and joins the thread.
+    ///
+    /// Returns the final [TailState], which is never open on success, so the file needs no recovery.
+    /// If an Operation failed, its errors are returned instead and the tail is left as that Operation left it.
+    pub fn shutdown(self)->Result<TailState<B>,Vec<ReadWriteError>>{
+        //if the thread already stopped it has its errors to return
+        let _ = self.sender.send(WriterMsg::Shutdown);
+        match self.thread.join() {
+            Ok(res) => res,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test_super {
     use super::*;
@@ -356,11 +549,11 @@ mod test_super {
         init_file(&mut cursor).unwrap();
 
         let ops = [
-            Operation{ op:Op::ContentWrite(B_CONTENT.to_vec(),None), timestamp: Some(DummyInput::current_timestamp()), calc_ecc: false , compress:None},
-            Operation{ op:Op::ContentWrite(B_CONTENT.to_vec(),None), timestamp: Some(DummyInput::current_timestamp()), calc_ecc: true , compress:None},
-            Operation{ op:Op::ContentWrite(B_CONTENT.to_vec(),None), timestamp: Some(DummyInput::current_timestamp()), calc_ecc: false, compress:None },
-            Operation{ op:Op::AtomicWrite(A_CONTENT.to_vec()), timestamp: Some(DummyInput::current_timestamp()), calc_ecc: false, compress:None },
-            Operation{ op:Op::AtomicWrite(A_CONTENT.to_vec()), timestamp: Some(DummyInput::current_timestamp()), calc_ecc: true , compress:None},
+            Operation{ op:Op::ContentWrite(B_CONTENT.to_vec(),None), timestamp: Some(DummyInput::current_timestamp()), end_timestamp: None, calc_ecc: false , compress:None},
+            Operation{ op:Op::ContentWrite(B_CONTENT.to_vec(),None), timestamp: Some(DummyInput::current_timestamp()), end_timestamp: None, calc_ecc: true , compress:None},
+            Operation{ op:Op::ContentWrite(B_CONTENT.to_vec(),None), timestamp: Some(DummyInput::current_timestamp()), end_timestamp: None, calc_ecc: false, compress:None },
+            Operation{ op:Op::AtomicWrite(A_CONTENT.to_vec()), timestamp: Some(DummyInput::current_timestamp()), end_timestamp: None, calc_ecc: false, compress:None },
+            Operation{ op:Op::AtomicWrite(A_CONTENT.to_vec()), timestamp: Some(DummyInput::current_timestamp()), end_timestamp: None, calc_ecc: true , compress:None},
         ];
         let mut tail_state: TailState<DummyInput> = TailState::ClosedBlock;
         for oper in ops {
@@ -372,6 +565,87 @@ mod test_super {
 
 
 
+    #[test]
+    fn test_resume_from_closed_block() {
+        use crate::{recovery::try_read_block, integrity::integrity_check_file};
+        use std::io::Seek;
+        let mut cursor = generate_test_file();
+        let block_start = 268;
+        cursor.set_position(block_start);
+        let bs = try_read_block::<_,DummyInput>(&mut cursor, false, false).unwrap();
+        let tail = bs.to_tail_state::<_,DummyInput>(&mut cursor, block_start).unwrap().unwrap();
+        assert!(tail.is_closed());
+        cursor.seek(std::io::SeekFrom::End(0)).unwrap();
+        let oper = Operation{ op:Op::AtomicWrite(A_CONTENT.to_vec()), timestamp: None, end_timestamp: None, calc_ecc: true , compress:None};
+        let tail = perform_file_op(&mut cursor, tail, oper, 1).unwrap();
+        assert!(tail.is_closed());
+        cursor.set_position(0);
+        let ok = integrity_check_file::<_,DummyInput>(&mut cursor).unwrap();
+        assert!(ok.last_block_state.unwrap().is_closed());
+    }
+    #[test]
+    fn test_resume_from_open_b_block() {
+        use crate::{recovery::try_read_block, integrity::integrity_check_file, core::{Block, BlockState}};
+        let mut cursor = Cursor::new(Vec::new());
+        init_file(&mut cursor).unwrap();
+        let mut tail: TailState<DummyInput> = TailState::ClosedBlock;
+        for calc_ecc in [false,true] {
+            let oper = Operation{ op:Op::ContentWrite(B_CONTENT.to_vec(),None), timestamp: None, end_timestamp: None, calc_ecc, compress:None};
+            tail = perform_file_op(&mut cursor, tail, oper, 1).unwrap();
+        }
+        //torn third component
+        let mut v = cursor.into_inner();
+        v.extend_from_slice(&[HeaderTag::CComponent as u8,1,2]);
+        let mut cursor = Cursor::new(v);
+        let block_start = (FILE_HEADER_LEN as usize + MN_ECC_LEN) as u64;
+        cursor.set_position(block_start);
+        let bs = try_read_block::<_,DummyInput>(&mut cursor, false, false).unwrap();
+        let BlockState::OpenBBlock { truncate_at, .. } = bs else {panic!("expected open B block, got {:?}",bs)};
+        let tail = bs.to_tail_state::<_,DummyInput>(&mut cursor, block_start).unwrap().unwrap();
+        assert!(tail.is_open());
+        cursor.get_mut().truncate(truncate_at as usize);
+        cursor.set_position(truncate_at);
+        let oper = Operation{ op:Op::ContentWrite(B_CONTENT.to_vec(),None), timestamp: None, end_timestamp: None, calc_ecc: false, compress:None};
+        let tail = perform_file_op(&mut cursor, tail, oper, 1).unwrap();
+        let oper = Operation{ op:Op::<Vec<u8>>::CloseBlock, timestamp: None, end_timestamp: None, calc_ecc: false, compress:None};
+        let tail = perform_file_op(&mut cursor, tail, oper, 1).unwrap();
+        assert!(tail.is_closed());
+        cursor.set_position(0);
+        let ok = integrity_check_file::<_,DummyInput>(&mut cursor).unwrap();
+        let Some(BlockState::Closed(summary)) = ok.last_block_state else {panic!("expected closed block")};
+        let Block::B { middle, .. } = summary.block else {panic!("expected B block")};
+        assert_eq!(middle.len(),3);
+    }
+    #[test]
+    fn test_resume_from_chained_open_b_block() {
+        use crate::{recovery::try_read_block, integrity::integrity_check_file};
+        let mut cursor = Cursor::new(Vec::new());
+        init_file(&mut cursor).unwrap();
+        write_magic_number(&mut cursor).unwrap();
+        let prev = write_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, false, None, None).unwrap();
+        write_magic_number(&mut cursor).unwrap();
+        let block_start = cursor.position();
+        let mut hasher = chained_hasher::<DummyInput>(Some(&prev.hash));
+        write_header(&mut cursor, &ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8 | CHAINED, [0;8], None)).unwrap();
+        write_content_component(&mut cursor, true, None, None, B_CONTENT, &mut hasher).unwrap();
+        let truncate_at = cursor.position();
+        cursor.get_mut().extend_from_slice(&[HeaderTag::CComponent as u8,1,2]);
+        cursor.set_position(block_start);
+        let bs = try_read_block::<_,DummyInput>(&mut cursor, false, false).unwrap();
+        assert!(bs.is_open_b());
+        let tail = bs.to_tail_state::<_,DummyInput>(&mut cursor, block_start).unwrap().unwrap();
+        cursor.get_mut().truncate(truncate_at as usize);
+        cursor.set_position(truncate_at);
+        let oper = Operation{ op:Op::ContentWrite(B_CONTENT.to_vec(),None), timestamp: None, end_timestamp: None, calc_ecc: false, compress:None};
+        let tail = perform_file_op(&mut cursor, tail, oper, 1).unwrap();
+        let oper = Operation{ op:Op::<Vec<u8>>::CloseBlock, timestamp: None, end_timestamp: None, calc_ecc: false, compress:None};
+        assert!(perform_file_op(&mut cursor, tail, oper, 1).unwrap().is_closed());
+        cursor.set_position(0);
+        let ok = integrity_check_file::<_,DummyInput>(&mut cursor).unwrap();
+        assert_eq!(ok.num_blocks,2);
+        assert!(ok.corrupted_segments.is_empty());
+        assert!(ok.last_block_state.unwrap().is_closed());
+    }
     #[test]
     fn compare_test_files() {
         let orig = generate_test_file().into_inner();
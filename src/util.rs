@@ -0,0 +1,110 @@
+//! Small helpers that don't belong to a single part of the format.
+
+/// Lookup table for the standard (IEEE 802.3, reflected) CRC32 polynomial.
+pub const CRC32:[u32;256] = crc32_table();
+
+const fn crc32_table()->[u32;256]{
+    let mut table = [0u32;256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 == 1 {0xEDB8_8320 ^ (c >> 1)}else{c >> 1};
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+/// CRC32 (IEEE) of the given bytes, using the [CRC32] table.
+pub const fn crc32(data:&[u8])->u32{
+    let mut crc = 0xFFFF_FFFFu32;
+    let mut i = 0;
+    while i < data.len() {
+        crc = CRC32[((crc ^ data[i] as u32) & 0xFF) as usize] ^ (crc >> 8);
+        i += 1;
+    }
+    !crc
+}
+
+/// The current time as microseconds since the Unix epoch, the timestamp convention used by [ComponentHeader::timestamp_systemtime](crate::core::ComponentHeader::timestamp_systemtime).
+///
+/// A clock before the epoch returns 0. Suitable for [BlockInputs::current_timestamp](crate::core::BlockInputs::current_timestamp).
+pub fn microseconds_since_epoch()->u64{
+    systemtime_to_micros(std::time::SystemTime::now()).unwrap_or(0)
+}
+
+/// `time` as microseconds since the Unix epoch, truncating anything finer. None if it is before the epoch or does not fit a u64.
+pub fn systemtime_to_micros(time:std::time::SystemTime)->Option<u64>{
+    time.duration_since(std::time::UNIX_EPOCH).ok()?.as_micros().try_into().ok()
+}
+
+/// The inverse of [systemtime_to_micros]. None if the platform's SystemTime can not represent it.
+pub fn micros_to_systemtime(micros:u64)->Option<std::time::SystemTime>{
+    std::time::UNIX_EPOCH.checked_add(std::time::Duration::from_micros(micros))
+}
+
+/// Issues strictly increasing microsecond timestamps (see [microseconds_since_epoch]), for use as ids or header timestamps.
+///
+/// If the clock steps backward (or two ids are asked for in the same microsecond), the next id is one past the last one instead,
+/// so ids never repeat or go back. They catch up with the clock once it passes the last id again.
+#[derive(Copy,Clone,Debug,Default,PartialEq,Eq)]
+pub struct DocIdSequence{
+    last:Option<u64>
+}
+impl DocIdSequence {
+    pub fn new()->Self{
+        Self { last: None }
+    }
+    /// A sequence whose ids all come after `last`, e.g. the newest timestamp already in a file.
+    pub fn starting_after(last:u64)->Self{
+        Self { last: Some(last) }
+    }
+    /// The last id issued (or the one given to [starting_after](Self::starting_after)).
+    pub fn last(&self)->Option<u64>{
+        self.last
+    }
+    /// Returns a new id from the current time, greater than every id before it.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self)->u64{
+        self.next_from(microseconds_since_epoch())
+    }
+    /// Same as [next](Self::next), with `now` as the current time.
+    pub fn next_from(&mut self,now:u64)->u64{
+        let id = match self.last {
+            Some(last) if now <= last => last + 1,
+            _ => now,
+        };
+        self.last = Some(id);
+        id
+    }
+}
+
+#[cfg(test)]
+mod test_super {
+    use super::*;
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"),0xCBF43926);
+        assert_eq!(crc32(&[]),0);
+    }
+    #[test]
+    fn test_doc_id_sequence() {
+        let mut seq = DocIdSequence::new();
+        assert_eq!(seq.next_from(100),100);
+        //same microsecond, then the clock steps back
+        assert_eq!(seq.next_from(100),101);
+        assert_eq!(seq.next_from(50),102);
+        assert_eq!(seq.next_from(90),103);
+        //the clock catches up
+        assert_eq!(seq.next_from(200),200);
+        assert_eq!(seq.last(),Some(200));
+
+        let mut seq = DocIdSequence::starting_after(microseconds_since_epoch() + 1_000_000);
+        let ids:Vec<u64> = (0..100).map(|_|seq.next()).collect();
+        assert!(ids.windows(2).all(|w|w[0] < w[1]));
+    }
+}
@@ -10,6 +10,13 @@ The format for a Docufort file is simple, consisting of three distinct message t
 | 10..11 | ECC_LEN value (Reed-Solomon encoding value) |
 | 11 onwards | First block starts |
 
+All integers have a fixed byte order, regardless of the host that wrote the file:
+- Timestamps are big endian.
+- The header data field (content length) is little endian.
+- The original length prefixed to compressed content is big endian.
+
+See [read_u32](crate::core::read_u32) and friends for the helpers that encode/decode these.
+
 ## Block Structure
 
 Each block is structured into three components: BlockStart, Content, and BlockEnd.
@@ -35,6 +42,11 @@ There is nothing more to a BlockStart than the Header. Their might be different
     - This header is followed directly by the ECC Data for the content, then the content bytes.
     - Since ECC is fixed for the life of the file, we can deduce the length of the ECC Data, given the content len (header data u32)
 - A FLAG_TAG of b'B' is a Best Effort Block. A series of 'Content' components follow this header.
+    - The header data field is 0, or the number of bytes between this header and the BlockEnd if the block was closed with [write_block_end_with_body_len].
+- Any of the above may have the [CHAINED] bit set. The block hash then starts with the hash of the block before it (see [write_chained_atomic_block]).
+- b'Q' may have the [TRAILING_ECC] bit set. Then each chunk of content is followed by its ECC, instead of all the ECC coming first (see [write_streamed_atomic_block]).
+- Any of the above may have the [COMMITTED] bit set, marking a commit point for [rollback_uncommitted](crate::recovery::RecoveryPolicy::rollback_uncommitted) recovery.
+- Any of the above may have the [HAS_BATCH] bit set. Then an 8 byte [BatchMarker] and its ECC directly follow the header, and are part of the block hash.
 
 ### 2. Content
 
@@ -43,12 +55,18 @@ Is a Header followed by the same pattern as an Atomic Block, but with different
     - The header data field represents the number of bytes for the atomic write. These directly follow the header.
 - A FLAG_TAG of b'P' is a Content Block with error correction on the contents of the block.
     - This header is followed directly by the ECC Data for the content, then the content bytes.
+- Either may have the [HAS_TYPE] bit set. Then a 2 byte (big endian) content type and its ECC sit between the header and the rest.
+- Compressed content may have the [HAS_LEVEL] bit set. Then a 1 byte compression level and its ECC follow (after the content type, if any).
+- Either may have the [HAS_HASH] bit set. Then a 20 byte hash of just the stored content and its ECC follow (after the content type and compression level, if any).
+- Compressed content may have the [SEEKABLE] bit set. Then the content is a frame table followed by independently compressed frames (see [write_seekable_content_component]).
 
 ### 3. BlockEnd
 
 Is a Header followed by a 160bit hash of the block contents (all bytes after the BlockStart Header to the start of this header).
 The Tag for a BlockEnd is b'D'.
 The hash is also ECC'd to ensure integrity to avoid unnecessary error correction decoding during recovery.
+- It may have the [LOGICAL_HASH] bit set (A blocks only). Then the hash covers the decompressed content instead (see [write_logical_hash_atomic_block]).
+- It may have the [OFFSET_BOUND] bit set. Then the block's start offset is hashed last, so the block only verifies where it was written (see [write_offset_bound_atomic_block]).
 
 | Byte Range | Field | Type | Description |
 | --- | --- | --- | --- |
@@ -86,7 +104,7 @@ It is recommended to use a cryptographic hash.
 use std::{borrow::Cow, io::Seek};
 
 
-use crate::{core::{BlockInputs, ComponentHeader}, ecc::{calculate_ecc_chunk, calculate_ecc_for_chunks}, HashAdapter, HeaderTag, ReadWriteError, ECC_LEN, HASH_LEN, HAS_ECC, IS_COMP, MAGIC_NUMBER, MN_ECC};
+use crate::{recovery::{recover_tail, TailRecoverySummary}, retry_writer::{perform_file_op, Op, Operation, TailState}, FileLike, core::{BlockInputs, BlockCoords, ComponentHeader, BatchMarker, ContentDirEntry, ExternalRef, write_comp_len}, ecc::{calculate_ecc_chunk, calculate_ecc_for_chunks, calc_ecc_data_len}, HashAdapter, HeaderTag, ReadWriteError, ECC_LEN, HASH_LEN, HEADER_LEN, HASH_AND_ECC_LEN, MN_ECC_LEN, FILE_HEADER_LEN, FILE_ECC_LEN_BYTE, HAS_ECC, IS_COMP, CHAINED, COMMITTED, HAS_TYPE, HAS_LEVEL, HAS_HASH, HAS_BATCH, TRAILING_ECC, LOGICAL_HASH, OFFSET_BOUND, SEEKABLE, SEEK_TABLE_HEADER_LEN, CONTENT_DIRECTORY_TYPE, GENERATION_TYPE, EXTERNAL_REF_TYPE, DATA_SIZE, MAGIC_NUMBER, MN_ECC, MAX_ALIGNMENT, MAX_COMPONENTS_PER_BLOCK};
 
 
 /// Initializes a new DocuFort file at the specified path.
@@ -96,7 +114,7 @@ use crate::{core::{BlockInputs, ComponentHeader}, ecc::{calculate_ecc_chunk, cal
 pub fn init_file<W:std::io::Write>(file: &mut W) -> std::io::Result<()> {
     file.write_all(&MAGIC_NUMBER)?;
     file.write_all(&[b'V',b'1'])?;
-    file.write_all(&[ECC_LEN as u8])?;
+    file.write_all(&[FILE_ECC_LEN_BYTE])?;
     Ok(())
 }
 
@@ -108,6 +126,21 @@ pub fn write_magic_number<W: std::io::Write>(writer: &mut W)->std::io::Result<()
     writer.write_all(&MN_ECC)?;
     Ok(())
 }
+///Same as [write_magic_number], but first writes zero bytes until the writer is at a multiple of `alignment`, so the block's magic number starts aligned (e.g. for direct IO).
+///
+///None (or an already aligned writer) writes no padding. `alignment` must be a power of two no larger than [MAX_ALIGNMENT], otherwise this errors with InvalidInput.
+///Readers skip the padding, and recovery truncates a torn one with the rest of the incomplete magic number.
+pub fn write_aligned_magic_number<W: std::io::Write+Seek>(writer: &mut W,alignment:Option<u64>)->Result<(),ReadWriteError>{
+    if let Some(alignment) = alignment {
+        if !alignment.is_power_of_two() || alignment > MAX_ALIGNMENT {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "alignment must be a power of two no larger than MAX_ALIGNMENT").into())
+        }
+        let pos = writer.stream_position()?;
+        let pad = (alignment - pos % alignment) % alignment;
+        std::io::copy(&mut std::io::Read::take(std::io::repeat(0), pad), writer)?;
+    }
+    Ok(write_magic_number(writer)?)
+}
 
 ///Calculates ECC and Writes the header to the given writer.
 pub fn write_header<W: std::io::Write>(writer: &mut W,header:&ComponentHeader)->Result<(),ReadWriteError>{
@@ -117,35 +150,141 @@ pub fn write_header<W: std::io::Write>(writer: &mut W,header:&ComponentHeader)->
 }
 ///Calculates ECC and Writes the header to the given writer.
 pub fn write_content_header<W: std::io::Write, B:BlockInputs>(writer: &mut W,data_len:u32,has_ecc:bool,is_compressed:bool,time_stamp: Option<u64>,hasher:&mut B)->Result<(),ReadWriteError>{
+    write_typed_content_header(writer, data_len, has_ecc, is_compressed, None, time_stamp, hasher)
+}
+///Same as [write_content_header], but if there is a content_type the [HAS_TYPE] flag is set and the type (+ its ECC) is written after the header.
+pub fn write_typed_content_header<W: std::io::Write, B:BlockInputs>(writer: &mut W,data_len:u32,has_ecc:bool,is_compressed:bool,content_type:Option<u16>,time_stamp: Option<u64>,hasher:&mut B)->Result<(),ReadWriteError>{
+    write_content_header_fields(writer, data_len, has_ecc, is_compressed, false, content_type, None, None, time_stamp, hasher)
+}
+///Writes the Content header and the optional content type, compression level and component hash fields (each + ECC) that follow it.
+#[allow(clippy::too_many_arguments)]
+fn write_content_header_fields<W: std::io::Write, B:BlockInputs>(writer: &mut W,data_len:u32,has_ecc:bool,is_compressed:bool,seekable:bool,content_type:Option<u16>,comp_level:Option<u8>,component_hash:Option<[u8;HASH_LEN]>,time_stamp: Option<u64>,hasher:&mut B)->Result<(),ReadWriteError>{
     let mut tag = HeaderTag::CComponent as u8;
     if has_ecc {tag |= HAS_ECC}
     if is_compressed {tag |= IS_COMP}
+    if seekable {tag |= SEEKABLE}
+    if content_type.is_some() {tag |= HAS_TYPE}
+    if comp_level.is_some() {tag |= HAS_LEVEL}
+    if component_hash.is_some() {tag |= HAS_HASH}
     let time_stamp = if let Some(ts) = time_stamp {ts.to_be_bytes()}else{B::current_timestamp().to_be_bytes()};
     let content_header = ComponentHeader::new_from_parts(tag, time_stamp, Some(data_len));
     let mut ha = HashAdapter::new(writer, hasher);
     use std::io::Write;
     ha.write_all(content_header.as_slice())?;
     calculate_ecc_chunk(content_header.as_slice(), &mut ha)?;
+    if let Some(ct) = content_type {
+        let ct = ct.to_be_bytes();
+        ha.write_all(&ct)?;
+        calculate_ecc_chunk(&ct, &mut ha)?;
+    }
+    if let Some(level) = comp_level {
+        ha.write_all(&[level])?;
+        calculate_ecc_chunk(&[level], &mut ha)?;
+    }
+    if let Some(hash) = component_hash {
+        ha.write_all(&hash)?;
+        calculate_ecc_chunk(&hash, &mut ha)?;
+    }
     Ok(())
 }
 
 ///Only use with Atomic Block. Does **NOT** write the header, Does **NOT** Compress.
+///
+///Everything goes through one [HashAdapter], so each byte written (the ECC, then the content) is hashed exactly once, the same range the reader hashes.
 pub fn write_content<W: std::io::Write,B:BlockInputs>(writer: &mut W,content:&[u8],calc_ecc:bool,hasher:&mut B)->Result<(),ReadWriteError>{
+    use std::io::Write;
+    let mut hw = HashAdapter::new(writer, hasher);
     if calc_ecc {
-        let mut hw = HashAdapter::new(writer, hasher);
         calculate_ecc_for_chunks(content, &mut hw)?;
     }
-    hasher.update(content);
-    writer.write_all(content)?;
+    hw.write_all(content)?;
     Ok(())
 }
 /// Writer represents the append only file, with the writer position at the end of the file.
+///
+/// The header, hash and their ECC go out in a single write, so a crash leaves at most one torn BlockEnd for recovery to drop.
 pub fn write_block_end<W: std::io::Write>(writer: &mut W,header:&ComponentHeader,hash:&[u8;HASH_LEN])->Result<(),ReadWriteError>{
-    write_header(writer, header)?;
-    write_block_hash(writer, hash)?;
+    let mut buf = Vec::with_capacity(HEADER_LEN + ECC_LEN + HASH_AND_ECC_LEN);
+    write_header(&mut buf, header)?;
+    write_block_hash(&mut buf, hash)?;
+    writer.write_all(&buf)?;
     Ok(())
 }
 
+/// Same as [write_block_end] for a B block, but first back-patches its BlockStart header with the number of bytes between that header and the BlockEnd.
+///
+/// Readers can then jump straight to the BlockEnd, see [recorded_block_end](crate::read::recorded_block_end).
+/// `block_start` is where `start` was written (right after the magic number). The BlockStart header is not part of the block hash.
+/// The patch is written before the BlockEnd, so a crash in between leaves an open block that recovery closes right where the length points.
+/// A body longer than u32::MAX is left unrecorded.
+pub fn write_block_end_with_body_len<W: std::io::Write + Seek>(writer: &mut W,block_start:u64,start:&ComponentHeader,header:&ComponentHeader,hash:&[u8;HASH_LEN])->Result<(),ReadWriteError>{
+    let end_start = writer.stream_position()?;
+    let body_len = end_start.checked_sub(BlockCoords::new(block_start).body_start())
+        .ok_or_else(||std::io::Error::new(std::io::ErrorKind::InvalidInput, "Writer is before the end of the BlockStart header"))?;
+    if let Ok(body_len) = u32::try_from(body_len) {
+        let patched = ComponentHeader::new_from_parts(start.as_slice()[0], start.time_stamp(), Some(body_len));
+        writer.seek(std::io::SeekFrom::Start(block_start))?;
+        write_header(writer, &patched)?;
+        writer.seek(std::io::SeekFrom::Start(end_start))?;
+    }
+    write_block_end(writer, header, hash)
+}
+
+/// Closes a B block with a BlockEnd flagged [OFFSET_BOUND], like [write_offset_bound_atomic_block] does for A blocks.
+///
+/// `block_start` is where the StartBBlock header was written (right after the magic number), and is fed to `hasher` last.
+/// Pass the hasher used for the block's content components. Returns the hash written.
+pub fn write_offset_bound_block_end<W: std::io::Write,B:BlockInputs>(writer: &mut W,block_start:u64,time_stamp: Option<u64>,mut hasher:B)->Result<[u8;HASH_LEN],ReadWriteError>{
+    hasher.update(&block_start.to_be_bytes());
+    let hash = hasher.finalize();
+    let time_stamp = time_stamp.unwrap_or_else(B::current_timestamp).to_be_bytes();
+    let header = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8 | OFFSET_BOUND, time_stamp, None);
+    write_block_end(writer, &header, &hash)?;
+    Ok(hash)
+}
+
+/// Runs `write`, which must write exactly one whole block (magic number included) at the writer position, then reads it back to catch silent write errors.
+///
+/// After a flush the block is re-read with no error correction and re-hashed (nothing is written while checking).
+/// Returns [ReadWriteError::HashMismatch] if the readback is not a closed block matching its hash, ending where `write` left the writer.
+/// The bad block is left in place, so the caller can truncate it (at the position the writer was at) and retry.
+/// The writer is left at the end of the block.
+pub fn write_verified<RW:std::io::Read + std::io::Write + Seek,B:BlockInputs,T>(file:&mut RW,write:impl FnOnce(&mut RW)->Result<T,ReadWriteError>)->Result<T,ReadWriteError>{
+    let mn_start = file.stream_position()?;
+    let res = write(file)?;
+    file.flush()?;
+    let end = file.stream_position()?;
+    let block_start = mn_start + MN_ECC_LEN as u64;
+    let mut mn = [0u8;MN_ECC_LEN];
+    file.seek(std::io::SeekFrom::Start(mn_start))?;
+    std::io::Read::read_exact(file, &mut mn)?;
+    let mut reader = crate::read::DeferredPatches::new(&mut *file);
+    let verified = mn[..MAGIC_NUMBER.len()] == MAGIC_NUMBER && mn[MAGIC_NUMBER.len()..] == MN_ECC && match crate::recovery::try_read_block::<_,B>(&mut reader, false, false)? {
+        crate::core::BlockState::Closed(brs) => brs.block.take_end().hash.hash() == &brs.hash_as_read[..] && reader.stream_position()? == end,
+        _ => false,
+    };
+    file.seek(std::io::SeekFrom::Start(end))?;
+    if !verified {return Err(ReadWriteError::HashMismatch { block_start })}
+    Ok(res)
+}
+
+/// Runs `write`, which must write exactly one whole block (magic number included) at the writer position, then caches the block hash for [verify_block_hash](crate::read::verify_block_hash).
+///
+/// Only the hash at the end of the block is read back, the block is not re-hashed. The writer is left at the end of the block.
+pub fn write_cached<RW:std::io::Read + std::io::Write + Seek,T>(file:&mut RW,cache:&mut crate::read::BlockHashCache,write:impl FnOnce(&mut RW)->Result<T,ReadWriteError>)->Result<T,ReadWriteError>{
+    let mn_start = file.stream_position()?;
+    let res = write(file)?;
+    let end = file.stream_position()?;
+    let hash_start = end.checked_sub(HASH_AND_ECC_LEN as u64).filter(|h|*h >= mn_start)
+        .ok_or_else(||std::io::Error::new(std::io::ErrorKind::InvalidInput, "No block was written"))?;
+    let mut hash = [0u8;HASH_LEN];
+    file.seek(std::io::SeekFrom::Start(hash_start))?;
+    std::io::Read::read_exact(file, &mut hash)?;
+    file.seek(std::io::SeekFrom::Start(end))?;
+    cache.insert(mn_start + MN_ECC_LEN as u64, hash);
+    Ok(res)
+}
+
 /// Writer represents the append only file, with the writer position at the end of the file.
 pub fn write_block_hash<W: std::io::Write>(writer: &mut W,hash:&[u8;HASH_LEN])->Result<(),ReadWriteError>{
     writer.write_all(hash)?;
@@ -153,8 +292,81 @@ pub fn write_block_hash<W: std::io::Write>(writer: &mut W,hash:&[u8;HASH_LEN])->
     Ok(())
 }
 
+/// Compresses content into a buffer no larger than the original content.
+///
+/// Returns Ok(Some(original_len + compressed bytes)) if compression shrank the data.
+/// Returns Ok(None) if it did not (the compressor ran out of room, or the output was not smaller).
+/// Any other error from the compressor is returned.
+fn try_compress<B:BlockInputs>(content:&[u8],comp_level:&B::CompLevel)->Result<Option<Vec<u8>>,ReadWriteError>{
+    let data_len = content.len();
+    let mut v = vec![0u8;data_len+4];//we need to allocate given the nature of needing to do ECC yet. TODO: Figure out how not to
+    let mut crsr = std::io::Cursor::new(&mut v[4..]);
+    match B::compress(content, &mut crsr, comp_level) {
+        Ok(_) if crsr.position() < data_len as u64 => {
+            let n = crsr.position() as usize;
+            v.truncate(n+4);
+            v[0..4].copy_from_slice(&write_comp_len(data_len as u32));
+            Ok(Some(v))
+        },
+        Ok(_) => Ok(None),
+        //our buffer is full, so compression would not have helped
+        Err(e) if e.kind() == std::io::ErrorKind::WriteZero => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+///Compresses each `frame_len` slice of `content` on its own and lays them out for [SEEKABLE] content:
+///the original length, `frame_len` and the number of frames (u32 each), a u32 compressed length per frame, then the frames. All big endian.
+///None if the result would not be smaller than `content`.
+fn try_compress_seekable<B:BlockInputs>(content:&[u8],comp_level:&B::CompLevel,frame_len:u32)->Result<Option<Vec<u8>>,ReadWriteError>{
+    let frames = content.chunks(frame_len as usize);
+    let table_len = SEEK_TABLE_HEADER_LEN + frames.len()*4;
+    let mut v = Vec::with_capacity(content.len());
+    v.extend_from_slice(&write_comp_len(content.len() as u32));
+    v.extend_from_slice(&frame_len.to_be_bytes());
+    v.extend_from_slice(&(frames.len() as u32).to_be_bytes());
+    v.resize(table_len, 0);
+    for (i,frame) in frames.enumerate() {
+        let start = v.len();
+        B::compress(frame, &mut v, comp_level)?;
+        let (entry,stored) = (SEEK_TABLE_HEADER_LEN + i*4,(v.len() - start) as u32);
+        v[entry..entry+4].copy_from_slice(&stored.to_be_bytes());
+        if v.len() >= content.len() {return Ok(None)}
+    }
+    Ok(Some(v))
+}
+
 ///Writes Header + Content Component, optionally computes ECC
 pub fn write_content_component<W: std::io::Write+Seek,B:BlockInputs>(writer: &mut W,calc_ecc:bool,compress:Option<&B::CompLevel>,time_stamp: Option<u64>,content:&[u8],hasher:&mut B)->Result<(usize,bool),ReadWriteError>{
+    write_typed_content_component(writer, calc_ecc, compress, time_stamp, None, content, hasher)
+}
+///Same as [write_content_component], but tags the content with a user defined type.
+///The type is ECC protected like the header, and returned in [Content::content_type](crate::core::Content::content_type) when read.
+#[allow(clippy::too_many_arguments)]
+pub fn write_typed_content_component<W: std::io::Write+Seek,B:BlockInputs>(writer: &mut W,calc_ecc:bool,compress:Option<&B::CompLevel>,time_stamp: Option<u64>,content_type:Option<u16>,content:&[u8],hasher:&mut B)->Result<(usize,bool),ReadWriteError>{
+    content_component(writer, calc_ecc, compress, None, time_stamp, content_type, false, content, hasher)
+}
+///Same as [write_typed_content_component], but also stores a hash of just this component's content (see [HAS_HASH](crate::HAS_HASH)).
+///
+///The component can then be checked on its own with [verify_component](crate::read::verify_component). It is still part of the block hash as well.
+#[allow(clippy::too_many_arguments)]
+pub fn write_hashed_content_component<W: std::io::Write+Seek,B:BlockInputs>(writer: &mut W,calc_ecc:bool,compress:Option<&B::CompLevel>,time_stamp: Option<u64>,content_type:Option<u16>,content:&[u8],hasher:&mut B)->Result<(usize,bool),ReadWriteError>{
+    content_component(writer, calc_ecc, compress, None, time_stamp, content_type, true, content, hasher)
+}
+///Same as [write_typed_content_component], but the content is compressed as independent frames of `frame_len` bytes each (the last may be shorter), behind a frame table (see [SEEKABLE]).
+///
+///Reading from an offset then only decompresses the frames it touches, see [SeekableContentReader](crate::read::SeekableContentReader).
+///Each frame costs a table entry and its own compression overhead, so pick a `frame_len` well above the reads you expect.
+///As with [write_content_component], the content is stored uncompressed if that is not larger. Errors with InvalidInput if `frame_len` is 0.
+#[allow(clippy::too_many_arguments)]
+pub fn write_seekable_content_component<W: std::io::Write+Seek,B:BlockInputs>(writer: &mut W,calc_ecc:bool,compress:&B::CompLevel,frame_len:u32,time_stamp: Option<u64>,content_type:Option<u16>,content:&[u8],hasher:&mut B)->Result<(usize,bool),ReadWriteError>{
+    if frame_len == 0 {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "frame_len must be at least 1").into())
+    }
+    content_component(writer, calc_ecc, Some(compress), Some(frame_len), time_stamp, content_type, false, content, hasher)
+}
+#[allow(clippy::too_many_arguments)]
+fn content_component<W: std::io::Write+Seek,B:BlockInputs>(writer: &mut W,calc_ecc:bool,compress:Option<&B::CompLevel>,frame_len:Option<u32>,time_stamp: Option<u64>,content_type:Option<u16>,with_hash:bool,content:&[u8],hasher:&mut B)->Result<(usize,bool),ReadWriteError>{
     //TODO: figure out a more elegant way to do this to avoid allocating the vec.
     //challenge: current helper fn's hash the data, so we can only call each fn once.
     //for now we just allocate a vec of size data_len+4
@@ -163,69 +375,570 @@ pub fn write_content_component<W: std::io::Write+Seek,B:BlockInputs>(writer: &mu
     //Either way, we would need to hash things in the right order, and this would mean we also need to add the Read bound.
     //So we end up with lots of bounds to avoid an allocation.
     //For now we just let this ride, as my first use cases don't use the compression routines here.
-    let (content_to_write,is_compressed) = if let Some(cl) = compress {
-        let data_len = content.len();
-        let mut v = vec![0u8;data_len+4];//we need to allocate given the nature of needing to do ECC yet. TODO: Figure out how not to
-        let mut crsr = std::io::Cursor::new(&mut v[4..]);
-        match B::compress(content, &mut crsr, cl) {
-            Ok(n) if crsr.position() < data_len as u64 => {
-                v.truncate(n+4);
-                use std::io::Write;
-                (&mut v[0..4]).write_all(&(data_len as u32).to_be_bytes()).unwrap();
-                (Cow::Owned(v),true)
-            },
-            _ => (Cow::Borrowed(content),false),
-        }
-    }else{(Cow::Borrowed(content),false)};
-    write_content_header(writer, content_to_write.len() as u32,calc_ecc,is_compressed,time_stamp,hasher)?;
+    let compressed = match frame_len {
+        Some(frame_len) => compress.map(|cl|try_compress_seekable::<B>(content, cl, frame_len)).transpose()?.flatten(),
+        None => compress.map(|cl|try_compress::<B>(content, cl)).transpose()?.flatten(),
+    };
+    let (content_to_write,is_compressed) = match compressed {
+        Some(v) => (Cow::Owned(v),true),
+        None => (Cow::Borrowed(content),false),
+    };
+    let seekable = is_compressed && frame_len.is_some();
+    //only compressed content records the level it was compressed at
+    let comp_level = if is_compressed {compress.and_then(B::comp_level_byte)}else{None};
+    let component_hash = with_hash.then(||{
+        let mut h = B::new();
+        h.update(&content_to_write);
+        h.finalize()
+    });
+    write_content_header_fields(writer, content_to_write.len() as u32,calc_ecc,is_compressed,seekable,content_type,comp_level,component_hash,time_stamp,hasher)?;
     write_content(writer, content_to_write.as_ref(), calc_ecc, hasher)?;
     Ok((content_to_write.len(),is_compressed))
 }
 
+/// Writes a content directory as the last content component of a B block, listing `entries` so readers can seek straight to a component.
+///
+/// It is an ordinary content component with ECC and type [CONTENT_DIRECTORY_TYPE], so it is hashed with the block and older readers see plain content.
+/// Record an entry for each component as it is written: its Content header position minus `block_start`, and the stored length [write_content_component] returns.
+/// Write the BlockEnd directly after. See [BlockReadSummary::directory](crate::recovery::BlockReadSummary::directory).
+pub fn write_content_directory<W: std::io::Write+Seek,B:BlockInputs>(writer: &mut W,entries:&[ContentDirEntry],time_stamp: Option<u64>,hasher:&mut B)->Result<(),ReadWriteError>{
+    let bytes:Vec<u8> = entries.iter().flat_map(|e|e.to_bytes()).collect();
+    write_typed_content_component(writer, true, None, time_stamp, Some(CONTENT_DIRECTORY_TYPE), &bytes, hasher)?;
+    Ok(())
+}
+
+/// A content component that was already hashed (and had its ECC computed), ready for [write_content_precomputed_hash].
+#[derive(Clone, Debug)]
+pub struct PrehashedContent<B>{
+    ///Header + ECC, content type + ECC (if any), then the content ECC data.
+    head:Vec<u8>,
+    content_len:usize,
+    ///The hasher after the whole component.
+    hasher:B
+}
+impl<B> PrehashedContent<B> {
+    pub fn content_len(&self)->usize{
+        self.content_len
+    }
+}
+
+/// Does all the hashing and ECC work of [write_typed_content_component] without writing, so it can run on another thread.
+///
+/// `hasher` must be a clone of the block hasher as it will be when this component is written.
+/// The content is not copied, pass the same bytes to [write_content_precomputed_hash]. Does **NOT** Compress.
+pub fn prehash_content_component<B:BlockInputs>(mut hasher:B,calc_ecc:bool,time_stamp: Option<u64>,content_type:Option<u16>,content:&[u8])->Result<PrehashedContent<B>,ReadWriteError>{
+    let mut head = Vec::with_capacity(HEADER_LEN+ECC_LEN+if calc_ecc {calc_ecc_data_len(content.len())}else{0});
+    write_typed_content_header(&mut head, content.len() as u32, calc_ecc, false, content_type, time_stamp, &mut hasher)?;
+    if calc_ecc {
+        let mut hw = HashAdapter::new(&mut head, &mut hasher);
+        calculate_ecc_for_chunks(content, &mut hw)?;
+    }
+    hasher.update(content);
+    Ok(PrehashedContent { head, content_len: content.len(), hasher })
+}
+
+/// Writes a component from [prehash_content_component] without hashing it again, then sets `hasher` to the state after it.
+///
+/// Errors with InvalidInput if `content` is not the length it was hashed with. Components must be written in the order their hashers were chained.
+pub fn write_content_precomputed_hash<W: std::io::Write,B:BlockInputs>(writer: &mut W,content:&[u8],prehashed:PrehashedContent<B>,hasher:&mut B)->Result<(),ReadWriteError>{
+    if content.len() != prehashed.content_len {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "content does not match the prehashed component").into())
+    }
+    writer.write_all(&prehashed.head)?;
+    writer.write_all(content)?;
+    *hasher = prehashed.hasher;
+    Ok(())
+}
+
+/// Time spent in each phase of [timed_write_content_component], in nanoseconds.
+#[cfg(feature = "bench")]
+#[derive(Copy,Clone,Debug,Default,PartialEq,Eq)]
+pub struct WriteTimings{
+    pub compress_ns:u64,
+    pub ecc_ns:u64,
+    ///Writing (and hashing) the header, ECC data and content.
+    pub io_ns:u64,
+}
+
+/// Same as [write_content_component], but also returns how long each phase took.
+///
+/// The content ECC is computed into a buffer first so it can be timed apart from the writes.
+#[cfg(feature = "bench")]
+pub fn timed_write_content_component<W: std::io::Write+Seek,B:BlockInputs>(writer: &mut W,calc_ecc:bool,compress:Option<&B::CompLevel>,time_stamp: Option<u64>,content:&[u8],hasher:&mut B)->Result<((usize,bool),WriteTimings),ReadWriteError>{
+    use std::time::Instant;
+    let start = Instant::now();
+    let (content_to_write,is_compressed) = match compress.map(|cl|try_compress::<B>(content, cl)).transpose()?.flatten() {
+        Some(v) => (Cow::Owned(v),true),
+        None => (Cow::Borrowed(content),false),
+    };
+    let compress_ns = start.elapsed().as_nanos() as u64;
+
+    let start = Instant::now();
+    let mut ecc_data = Vec::new();
+    if calc_ecc {calculate_ecc_for_chunks(content_to_write.as_ref(), &mut ecc_data)?}
+    let ecc_ns = start.elapsed().as_nanos() as u64;
+
+    let start = Instant::now();
+    let comp_level = if is_compressed {compress.and_then(B::comp_level_byte)}else{None};
+    write_content_header_fields(writer, content_to_write.len() as u32,calc_ecc,is_compressed,false,None,comp_level,None,time_stamp,hasher)?;
+    hasher.update(&ecc_data);
+    writer.write_all(&ecc_data)?;
+    hasher.update(content_to_write.as_ref());
+    writer.write_all(content_to_write.as_ref())?;
+    let io_ns = start.elapsed().as_nanos() as u64;
+    Ok(((content_to_write.len(),is_compressed),WriteTimings { compress_ns, ecc_ns, io_ns }))
+}
+
+/// Appends a B block holding only a generation record, one higher than [file_generation](crate::read::file_generation), and returns the new generation.
+///
+/// Call it each time the file is opened for appending (after [recover_tail](crate::recovery::recover_tail)), so copies of a file can be ordered:
+/// the higher generation is newer, and for equal generations the one with more blocks is.
+/// The file must end in a closed block, or just the file header.
+pub fn bump_generation<RW:std::io::Read + std::io::Write + Seek,B:BlockInputs>(file:&mut RW)->Result<u64,ReadWriteError>{
+    let generation = crate::read::file_generation::<_,B>(file)? + 1;
+    file.seek(std::io::SeekFrom::End(0))?;
+    write_magic_number(file)?;
+    let mut hasher = B::new();
+    write_header(file, &ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, B::current_timestamp().to_be_bytes(), None))?;
+    write_typed_content_component(file, true, None, None, Some(GENERATION_TYPE), &generation.to_be_bytes(), &mut hasher)?;
+    let end = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, B::current_timestamp().to_be_bytes(), None);
+    write_block_end(file, &end, &hasher.finalize())?;
+    Ok(generation)
+}
+/// Writes a thin block: a B block whose only content is the [ExternalRef] to data stored elsewhere, typed [EXTERNAL_REF_TYPE] and with ECC.
+///
+/// Like [write_atomic_block], the caller must write the magic number first. Read it back with [read_thin_block](crate::read::read_thin_block).
+pub fn write_thin_block<W: std::io::Write+Seek,B:BlockInputs>(writer: &mut W,reference:&ExternalRef,start_time_stamp: Option<u64>)->Result<WrittenBlock,ReadWriteError>{
+    let mut hasher = B::new();
+    let start_offset = writer.stream_position()?;
+    let time_stamp = start_time_stamp.unwrap_or_else(||B::current_timestamp());
+    write_header(writer, &ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, time_stamp.to_be_bytes(), None))?;
+    write_typed_content_component(writer, true, None, None, Some(EXTERNAL_REF_TYPE), &reference.to_bytes(), &mut hasher)?;
+    let hash = hasher.finalize();
+    let end = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, B::current_timestamp().to_be_bytes(), None);
+    write_block_end(writer, &end, &hash)?;
+    Ok(WrittenBlock { start_offset, end_offset: writer.stream_position()?, hash, timestamp: time_stamp })
+}
+/// Writes `content` as one B block, split into as many content components as needed so none holds more than `max_component_size` bytes.
+///
+/// This lifts the u32 length limit of a single component. Each piece is compressed (if it shrinks) and ECC'd on its own, like [write_content_component].
+/// Like [write_atomic_block], the caller must write the magic number first. Read it back with [read_large_content](crate::read::read_large_content).
+/// Nothing is flushed, see [write_large_content_with_policy].
+/// Errors with InvalidInput, before writing anything, if `max_component_size` is 0 or the content would need more than
+/// [MAX_COMPONENTS_PER_BLOCK] components, as readers give up on a block with that many.
+pub fn write_large_content<W: std::io::Write+Seek,B:BlockInputs>(writer: &mut W,content:&[u8],max_component_size:u32,calc_ecc:bool,compress:Option<&B::CompLevel>)->Result<WrittenBlock,ReadWriteError>{
+    large_content(writer, content, max_component_size, calc_ecc, compress, |_,_|Ok(()))
+}
+/// Same as [write_large_content], but flushes or syncs the file after each component and the BlockEnd, as the [FlushPolicy] says.
+pub fn write_large_content_with_policy<W: FileLike,B:BlockInputs>(writer: &mut W,content:&[u8],max_component_size:u32,calc_ecc:bool,compress:Option<&B::CompLevel>,flush_policy:FlushPolicy)->Result<WrittenBlock,ReadWriteError>{
+    large_content(writer, content, max_component_size, calc_ecc, compress, |w,block_end|flush_policy.apply(w, block_end))
+}
+///`flush` is called after each content component (false) and the BlockEnd (true).
+fn large_content<W: std::io::Write+Seek,B:BlockInputs>(writer: &mut W,content:&[u8],max_component_size:u32,calc_ecc:bool,compress:Option<&B::CompLevel>,mut flush:impl FnMut(&mut W,bool)->std::io::Result<()>)->Result<WrittenBlock,ReadWriteError>{
+    if max_component_size == 0 {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "max_component_size must be greater than 0").into())
+    }
+    if content.len().div_ceil(max_component_size as usize) > MAX_COMPONENTS_PER_BLOCK {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "content needs more than MAX_COMPONENTS_PER_BLOCK components").into())
+    }
+    let mut hasher = B::new();
+    let start_offset = writer.stream_position()?;
+    let time_stamp = B::current_timestamp();
+    write_header(writer, &ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, time_stamp.to_be_bytes(), None))?;
+    if content.is_empty() {
+        write_content_component(writer, calc_ecc, None, None, content, &mut hasher)?;
+        flush(writer,false)?;
+    }
+    for piece in content.chunks(max_component_size as usize) {
+        write_content_component(writer, calc_ecc, compress, None, piece, &mut hasher)?;
+        flush(writer,false)?;
+    }
+    let hash = hasher.finalize();
+    let end = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, B::current_timestamp().to_be_bytes(), None);
+    write_block_end(writer, &end, &hash)?;
+    flush(writer,true)?;
+    Ok(WrittenBlock { start_offset, end_offset: writer.stream_position()?, hash, timestamp: time_stamp })
+}
+/// When a B block writer ([write_large_content_with_policy], [perform_file_op_with_policy](crate::retry_writer::perform_file_op_with_policy))
+/// flushes or syncs the file.
+///
+/// A flush only hands buffered bytes to the OS. For them to survive a power loss use a `Sync` policy, which calls [sync](crate::FileLike::sync) (`sync_all` for a File) instead.
+#[derive(Copy,Debug,Clone,PartialEq,Eq)]
+pub enum FlushPolicy{
+    ///Flush after every content component, and after the BlockEnd.
+    ///After a crash, [recover_tail](crate::recovery::recover_tail) can keep every component that was flushed (the open block is closed on recovery),
+    ///at the cost of one flush (and usually a small write) per component.
+    PerComponent,
+    ///Flush once, after the BlockEnd. The block is written in as few large writes as the writer's buffer allows,
+    ///but a crash before the flush can lose the whole block.
+    PerBlock,
+    ///Same as [PerComponent](Self::PerComponent), but syncs. Every component is durable once written, at the cost of an fsync each.
+    SyncPerComponent,
+    ///Same as [PerBlock](Self::PerBlock), but syncs, so the block is durable once written.
+    SyncPerBlock,
+    ///Never flush, the caller decides when (e.g. once after several blocks). Fastest, and nothing is durable until the caller flushes.
+    Never,
+}
+impl FlushPolicy {
+    ///Flushes or syncs `file` if this policy does so after a content component, or after a BlockEnd if `block_end`.
+    pub(crate) fn apply<F:FileLike>(self,file:&mut F,block_end:bool)->std::io::Result<()>{
+        match self {
+            FlushPolicy::PerComponent => file.flush(),
+            FlushPolicy::SyncPerComponent => file.sync(),
+            FlushPolicy::PerBlock if block_end => file.flush(),
+            FlushPolicy::SyncPerBlock if block_end => file.sync(),
+            _ => Ok(()),
+        }
+    }
+}
+/// Where a block landed and what it hashed to, as returned by the atomic block writers, so an external index needs no re-read.
+#[derive(Copy,Debug,Clone,PartialEq,Eq)]
+pub struct WrittenBlock{
+    ///Position of the BlockStart header (just after the magic number), like `block_start` elsewhere.
+    pub start_offset:u64,
+    ///Position just past the BlockEnd hash and its ECC.
+    pub end_offset:u64,
+    ///The hash stored in the BlockEnd.
+    pub hash:[u8;HASH_LEN],
+    ///Timestamp of the BlockStart header.
+    pub timestamp:u64,
+}
+/// The ECC overhead of storing content of a given length with ECC, see [validate_content_ecc].
+#[derive(Copy,Debug,Clone,PartialEq,Eq)]
+pub struct EccPlan{
+    ///Number of DATA_SIZE chunks the content is split into, each getting ECC_LEN bytes of ECC.
+    pub num_chunks:usize,
+    ///Same as [calc_ecc_data_len].
+    pub total_ecc_bytes:usize,
+    ///The content plus its ECC, headers not included.
+    pub total_on_disk:u64,
+}
+/// Returns the [EccPlan] for `data_len` bytes of (stored, so compressed if it will be) content written with ECC, to check the overhead before writing.
+///
+/// Errors with InvalidInput if `data_len` does not fit the u32 length a content header records, as no single component can hold it.
+pub fn validate_content_ecc(data_len:usize)->Result<EccPlan,ReadWriteError>{
+    if u32::try_from(data_len).is_err() {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "content is too long for one component").into())
+    }
+    let num_chunks = data_len.div_ceil(DATA_SIZE);
+    let total_ecc_bytes = calc_ecc_data_len(data_len);
+    Ok(EccPlan { num_chunks, total_ecc_bytes, total_on_disk: data_len as u64 + total_ecc_bytes as u64 })
+}
 ///Writes Header + Content Component, optionally computes ECC
-pub fn write_atomic_block<W: std::io::Write,B:BlockInputs>(writer: &mut W,start_time_stamp: Option<u64>,content:&[u8],calc_ecc:bool,compress:Option<&B::CompLevel>,end_block:Option<&ComponentHeader>)->Result<(),ReadWriteError>{
+///
+///Returns the [WrittenBlock], with offsets taken from the writer's position.
+///Pass `end_block` (an EndBlock header) to pin the end timestamp too, with `start_time_stamp` that makes the block byte for byte reproducible.
+///If None the BlockEnd gets [BlockInputs::current_timestamp].
+pub fn write_atomic_block<W: std::io::Write+Seek,B:BlockInputs>(writer: &mut W,start_time_stamp: Option<u64>,content:&[u8],calc_ecc:bool,compress:Option<&B::CompLevel>,end_block:Option<&ComponentHeader>)->Result<WrittenBlock,ReadWriteError>{
+    atomic_block(writer, B::new(), 0, None, 0, start_time_stamp, content, calc_ecc, compress, end_block)
+}
+/// Same as [write_atomic_block], but the block is flagged [COMMITTED], so [rollback_uncommitted](crate::recovery::RecoveryPolicy::rollback_uncommitted) recovery keeps it and everything before it.
+///
+/// To commit a B block, OR [COMMITTED] into the StartBBlock tag.
+pub fn write_committed_atomic_block<W: std::io::Write+Seek,B:BlockInputs>(writer: &mut W,start_time_stamp: Option<u64>,content:&[u8],calc_ecc:bool,compress:Option<&B::CompLevel>,end_block:Option<&ComponentHeader>)->Result<WrittenBlock,ReadWriteError>{
+    atomic_block(writer, B::new(), COMMITTED, None, 0, start_time_stamp, content, calc_ecc, compress, end_block)
+}
+/// Same as [write_atomic_block], but the block carries the given [BatchMarker] so recovery can report how far a batch got.
+#[allow(clippy::too_many_arguments)]
+pub fn write_batch_atomic_block<W: std::io::Write+Seek,B:BlockInputs>(writer: &mut W,marker:&BatchMarker,start_time_stamp: Option<u64>,content:&[u8],calc_ecc:bool,compress:Option<&B::CompLevel>,end_block:Option<&ComponentHeader>)->Result<WrittenBlock,ReadWriteError>{
+    atomic_block(writer, B::new(), 0, Some(marker), 0, start_time_stamp, content, calc_ecc, compress, end_block)
+}
+/// Writes a StartBBlock header with [HAS_BATCH] set, followed by the [BatchMarker].
+///
+/// The marker is hashed, so pass the same hasher on to the content components of this block.
+pub fn write_batch_block_start<W: std::io::Write,B:BlockInputs>(writer: &mut W,marker:&BatchMarker,time_stamp: Option<u64>,hasher:&mut B)->Result<(),ReadWriteError>{
+    let time_stamp = time_stamp.unwrap_or_else(B::current_timestamp).to_be_bytes();
+    let header = ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8 | HAS_BATCH, time_stamp, None);
+    write_header(writer, &header)?;
+    write_batch_marker(writer, marker, hasher)
+}
+/// Writes the [BatchMarker] and its ECC through the hasher. Must directly follow a BlockStart header with [HAS_BATCH] set.
+pub fn write_batch_marker<W: std::io::Write,B:BlockInputs>(writer: &mut W,marker:&BatchMarker,hasher:&mut B)->Result<(),ReadWriteError>{
+    let mut ha = HashAdapter::new(writer, hasher);
+    use std::io::Write;
+    let bytes = marker.to_bytes();
+    ha.write_all(&bytes)?;
+    calculate_ecc_chunk(&bytes, &mut ha)?;
+    Ok(())
+}
+
+/// Same as [write_atomic_block], but the block hash covers the content as given (before compression), and the BlockEnd is flagged [LOGICAL_HASH].
+///
+/// The hash then only depends on the content, so the same content compressed at any level (or not at all) has the same block hash, e.g. for dedup or recompression.
+/// Readers decompress the content to check the hash. The stored bytes and content ECC are not hashed, they are only checked by the ECC and by decompressing.
+pub fn write_logical_hash_atomic_block<W: std::io::Write+Seek,B:BlockInputs>(writer: &mut W,start_time_stamp: Option<u64>,content:&[u8],calc_ecc:bool,compress:Option<&B::CompLevel>,end_block:Option<&ComponentHeader>)->Result<WrittenBlock,ReadWriteError>{
+    atomic_block(writer, B::new(), 0, None, LOGICAL_HASH, start_time_stamp, content, calc_ecc, compress, end_block)
+}
+
+/// Same as [write_atomic_block], but the BlockEnd is flagged [OFFSET_BOUND] and the hash also covers the block's start offset.
+///
+/// Each block verifies on its own, so two blocks of the same length could be swapped (or a block copied over another) without any hash failing.
+/// Binding the hash to the offset makes an integrity check catch that. The block can then not be moved, e.g. by compaction, without rewriting its BlockEnd.
+pub fn write_offset_bound_atomic_block<W: std::io::Write+Seek,B:BlockInputs>(writer: &mut W,start_time_stamp: Option<u64>,content:&[u8],calc_ecc:bool,compress:Option<&B::CompLevel>,end_block:Option<&ComponentHeader>)->Result<WrittenBlock,ReadWriteError>{
+    atomic_block(writer, B::new(), 0, None, OFFSET_BOUND, start_time_stamp, content, calc_ecc, compress, end_block)
+}
+
+/// Returns a hasher for a [CHAINED] block, already fed the previous block's hash.
+///
+/// `prev_hash` is the hash stored in the BlockEnd directly before this block's magic number, and must be None only for the first block in the file.
+/// To write a chained B block, OR [CHAINED] into the StartBBlock tag and use this hasher for its content components.
+pub fn chained_hasher<B:BlockInputs>(prev_hash:Option<&[u8;HASH_LEN]>)->B{
     let mut h = B::new();
-    let (content,is_compressed) = if let Some(cl) = compress {
-        let data_len = content.len();
-        let mut v = vec![0u8;data_len+4];//we need to allocate given the nature of needing to do ECC yet. TODO: Figure out how not to
-        let mut crsr = std::io::Cursor::new(&mut v[4..]);
-        match B::compress(content, &mut crsr, &cl) {
-            Ok(n) if n < data_len => {
-                v.truncate(n+4);
-                use std::io::Write;
-                (&mut v[0..3]).write_all(&(data_len as u32).to_be_bytes()).unwrap();
-                (Cow::Owned(v),true)
-            },
-            _ => (Cow::Borrowed(content),false),
-        }
-    }else{(Cow::Borrowed(content),false)};
-    let mut tag = HeaderTag::StartABlock as u8;
+    if let Some(hash) = prev_hash {h.update(hash)}
+    h
+}
+
+/// Same as [write_atomic_block], but the block is flagged [CHAINED] and its hash covers the previous block's hash.
+///
+/// This makes the hash of every chained block depend on all the blocks before it, so a forged block that is consistent with itself
+/// will still fail the hash check of the block after it. See [chained_hasher] for `prev_hash`.
+#[allow(clippy::too_many_arguments)]
+pub fn write_chained_atomic_block<W: std::io::Write+Seek,B:BlockInputs>(writer: &mut W,prev_hash:Option<&[u8;HASH_LEN]>,start_time_stamp: Option<u64>,content:&[u8],calc_ecc:bool,compress:Option<&B::CompLevel>,end_block:Option<&ComponentHeader>)->Result<WrittenBlock,ReadWriteError>{
+    atomic_block(writer, chained_hasher::<B>(prev_hash), CHAINED, None, 0, start_time_stamp, content, calc_ecc, compress, end_block)
+}
+
+#[allow(clippy::too_many_arguments)]
+///`flags` are OR'd into the BlockStart tag ([CHAINED], [COMMITTED]), and `end_flags` into the BlockEnd tag.
+///With [LOGICAL_HASH] `content` is hashed instead of what is stored, with [OFFSET_BOUND] the block start is hashed last.
+fn atomic_block<W: std::io::Write+Seek,B:BlockInputs>(writer: &mut W,mut h:B,flags:u8,batch:Option<&BatchMarker>,end_flags:u8,start_time_stamp: Option<u64>,content:&[u8],calc_ecc:bool,compress:Option<&B::CompLevel>,end_block:Option<&ComponentHeader>)->Result<WrittenBlock,ReadWriteError>{
+    let (stored,is_compressed) = match compress.map(|cl|try_compress::<B>(content, cl)).transpose()?.flatten() {
+        Some(v) => (Cow::Owned(v),true),
+        None => (Cow::Borrowed(content),false),
+    };
+    let mut tag = HeaderTag::StartABlock as u8 | flags;
     if calc_ecc {tag |= HAS_ECC}
     if is_compressed {tag |= IS_COMP}
-    let data = content.len() as u32;
+    if batch.is_some() {tag |= HAS_BATCH}
+    let data = stored.len() as u32;
     let time_stamp = start_time_stamp.unwrap_or_else(||B::current_timestamp()).to_be_bytes();
     let header = ComponentHeader::new_from_parts(tag as u8,time_stamp , Some(data));
+    let start_offset = writer.stream_position()?;
     write_header(writer, &header)?;
-    write_content(writer, content.as_ref(), calc_ecc, &mut h)?;
+    if let Some(marker) = batch {write_batch_marker(writer, marker, &mut h)?}
+    if end_flags & LOGICAL_HASH == LOGICAL_HASH {
+        write_content(writer, stored.as_ref(), calc_ecc, &mut B::new())?;
+        h.update(content);
+    }else{
+        write_content(writer, stored.as_ref(), calc_ecc, &mut h)?;
+    }
+    if end_flags & OFFSET_BOUND == OFFSET_BOUND {h.update(&start_offset.to_be_bytes())}
     let hash = h.finalize();
     if let Some(header) = end_block {
         assert_eq!(header.tag(),HeaderTag::EndBlock);
-        write_block_end(writer, header, &hash)?;
+        let header = ComponentHeader::new_from_parts(header.as_slice()[0] | end_flags, header.time_stamp(), None);
+        write_block_end(writer, &header, &hash)?;
     }else{
-        let tag = HeaderTag::EndBlock;
+        let tag = HeaderTag::EndBlock as u8 | end_flags;
         let data = None;
         let time_stamp = B::current_timestamp().to_be_bytes();
-        let header = ComponentHeader::new_from_parts(tag as u8,time_stamp , data);
+        let header = ComponentHeader::new_from_parts(tag,time_stamp , data);
         write_block_end(writer, &header, &hash)?;
     }
-    Ok(())
+    Ok(WrittenBlock { start_offset, end_offset: writer.stream_position()?, hash, timestamp: u64::from_be_bytes(time_stamp) })
+}
+
+/// Writes an Atomic Block with ECC from a stream, holding only one ECC chunk in memory however large the content is.
+///
+/// The block is flagged [TRAILING_ECC]: each `DATA_SIZE` chunk of data is written directly followed by its ECC.
+/// `content_len` goes in the header up front, so `content` must yield at least that many bytes (an early EOF is an error).
+/// Like [write_atomic_block], the caller must write the magic number first, and `end_block` pins the BlockEnd. Does **NOT** Compress.
+pub fn write_streamed_atomic_block<W: std::io::Write+Seek,R: std::io::Read,B:BlockInputs>(writer: &mut W,content:&mut R,content_len:u32,start_time_stamp: Option<u64>,end_block:Option<&ComponentHeader>)->Result<WrittenBlock,ReadWriteError>{
+    let tag = HeaderTag::StartAEBlock as u8 | TRAILING_ECC;
+    let time_stamp = start_time_stamp.unwrap_or_else(B::current_timestamp).to_be_bytes();
+    let start_offset = writer.stream_position()?;
+    write_header(writer, &ComponentHeader::new_from_parts(tag, time_stamp, Some(content_len)))?;
+    let mut h = B::new();
+    let mut chunk = [0u8;DATA_SIZE];
+    let mut remaining = content_len as usize;
+    {
+        let mut ha = HashAdapter::new(&mut *writer, &mut h);
+        use std::io::Write;
+        while remaining > 0 {
+            let n = remaining.min(DATA_SIZE);
+            content.read_exact(&mut chunk[..n])?;
+            ha.write_all(&chunk[..n])?;
+            calculate_ecc_chunk(&chunk[..n], &mut ha)?;
+            remaining -= n;
+        }
+    }
+    let hash = h.finalize();
+    if let Some(header) = end_block {
+        assert_eq!(header.tag(),HeaderTag::EndBlock);
+        write_block_end(writer, header, &hash)?;
+    }else{
+        let header = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, B::current_timestamp().to_be_bytes(), None);
+        write_block_end(writer, &header, &hash)?;
+    }
+    Ok(WrittenBlock { start_offset, end_offset: writer.stream_position()?, hash, timestamp: u64::from_be_bytes(time_stamp) })
+}
+
+/// Writes an Atomic Block from a stream of unknown length, holding only one `DATA_SIZE` chunk in memory.
+///
+/// The BlockStart is written with a placeholder length of `u32::MAX`, and patched (seeking back) once `source` hits EOF.
+/// If the write is interrupted before the patch, the placeholder runs past the end of the file, so recovery sees an incomplete block and truncates it.
+/// With `calc_ecc` the block is flagged [TRAILING_ECC] as in [write_streamed_atomic_block], since the length isn't known before the data.
+/// Errors with InvalidInput if `source` yields more than `u32::MAX - 1` bytes. Like [write_atomic_block], the caller must write the magic number first. Does **NOT** Compress.
+pub fn write_atomic_block_streaming<W: std::io::Write+Seek,R: std::io::Read,B:BlockInputs>(writer: &mut W,source:&mut R,calc_ecc:bool)->Result<WrittenBlock,ReadWriteError>{
+    let tag = if calc_ecc {HeaderTag::StartAEBlock as u8 | TRAILING_ECC}else{HeaderTag::StartABlock as u8};
+    let time_stamp = B::current_timestamp().to_be_bytes();
+    let start_offset = writer.stream_position()?;
+    write_header(writer, &ComponentHeader::new_from_parts(tag, time_stamp, Some(u32::MAX)))?;
+    let mut h = B::new();
+    let mut chunk = [0u8;DATA_SIZE];
+    let mut content_len = 0u64;
+    {
+        let mut ha = HashAdapter::new(&mut *writer, &mut h);
+        use std::io::Write;
+        loop {
+            let mut n = 0;
+            while n < DATA_SIZE {
+                match source.read(&mut chunk[n..]) {
+                    Ok(0) => break,
+                    Ok(m) => n += m,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            if n == 0 {break}
+            content_len += n as u64;
+            if content_len >= u32::MAX as u64 {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "streamed content is too long for one block").into())
+            }
+            ha.write_all(&chunk[..n])?;
+            if calc_ecc {calculate_ecc_chunk(&chunk[..n], &mut ha)?}
+            if n < DATA_SIZE {break}
+        }
+    }
+    let hash = h.finalize();
+    let content_end = writer.stream_position()?;
+    writer.seek(std::io::SeekFrom::Start(start_offset))?;
+    write_header(writer, &ComponentHeader::new_from_parts(tag, time_stamp, Some(content_len as u32)))?;
+    writer.seek(std::io::SeekFrom::Start(content_end))?;
+    let header = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, B::current_timestamp().to_be_bytes(), None);
+    write_block_end(writer, &header, &hash)?;
+    Ok(WrittenBlock { start_offset, end_offset: writer.stream_position()?, hash, timestamp: u64::from_be_bytes(time_stamp) })
 }
 
+/// Writes an empty Atomic Block that records a timestamp, to mark 'still alive at time T' without any content.
+///
+/// Like [write_atomic_block], the caller must write the magic number first.
+/// Both the BlockStart and BlockEnd headers carry the given timestamp.
+/// Overhead is 2 headers + hash: `2*(HEADER_LEN+ECC_LEN) + HASH_AND_ECC_LEN` bytes (58 with ECC_LEN = 4), plus the magic number and its ECC (12 more).
+pub fn write_heartbeat<W: std::io::Write+Seek,B:BlockInputs>(writer: &mut W,time_stamp:u64)->Result<WrittenBlock,ReadWriteError>{
+    let end_header = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, time_stamp.to_be_bytes(), None);
+    write_atomic_block::<_,B>(writer, Some(time_stamp), &[], false, None, Some(&end_header))
+}
+/// The shape of a block for [estimate_file_size].
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct BlockSpec{
+    ///Length of each content as stored (after any compression, including the 4 byte length prefix if compressed).
+    ///An atomic block stores these as a single content of the summed length.
+    pub content_lens:Vec<usize>,
+    pub calc_ecc:bool,
+    pub atomic:bool,
+}
 
+/// Bytes on disk for a content component: header + ECC, the content ECC data (if any), and the content.
+pub fn component_disk_size(content_len:usize,calc_ecc:bool)->u64{
+    let ecc_data = if calc_ecc {calc_ecc_data_len(content_len)}else{0};
+    (HEADER_LEN + ECC_LEN + ecc_data + content_len) as u64
+}
+
+/// Exact file size for a new file holding the given blocks, including the file header and each block's magic number, headers and hash.
+pub fn estimate_file_size(blocks:&[BlockSpec])->u64{
+    let block_end = (HEADER_LEN + ECC_LEN + HASH_AND_ECC_LEN) as u64;
+    blocks.iter().fold(FILE_HEADER_LEN as u64, |total,BlockSpec { content_lens, calc_ecc, atomic }|{
+        let middle = if *atomic {
+            //the start header *is* the content header
+            component_disk_size(content_lens.iter().sum(), *calc_ecc)
+        }else{
+            (HEADER_LEN + ECC_LEN) as u64 + content_lens.iter().map(|len|component_disk_size(*len, *calc_ecc)).sum::<u64>()
+        };
+        total + MN_ECC_LEN as u64 + middle + block_end
+    })
+}
+
+/// Spreads writes over several docufort files (shards), each with its own [TailState], routing by a caller supplied hash of the content or its key.
+///
+/// Every shard is an ordinary docufort file built with [perform_file_op], so each can be checked, recovered and read on its own.
+/// A failed write leaves that shard's tail unknown: it refuses further writes until [recover_all](Self::recover_all) runs.
+pub struct ShardedWriter<F,B>{
+    shards:Vec<(F,Option<TailState<B>>)>,
+    write_attempts:usize
+}
+impl<F:FileLike,B:BlockInputs+std::fmt::Debug> ShardedWriter<F,B> {
+    /// Takes the shard files in order (the order must not change between runs, or keys move). Empty files are initialized with [init_file].
+    ///
+    /// Runs [recover_all](Self::recover_all), so all shards start from a closed block. Errors with InvalidInput if `files` is empty.
+    /// `write_attempts` is passed to [perform_file_op] for each write (0 is treated as 1).
+    pub fn open(files:Vec<F>,write_attempts:usize)->Result<Self,ReadWriteError>{
+        if files.is_empty() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "a ShardedWriter needs at least one file").into())
+        }
+        let mut writer = Self { shards: files.into_iter().map(|f|(f,None)).collect(), write_attempts: write_attempts.max(1) };
+        for (file,_) in writer.shards.iter_mut() {
+            if file.len()? == 0 {init_file(file)?}
+        }
+        writer.recover_all()?;
+        Ok(writer)
+    }
+    /// Runs [recover_tail] on every shard, in order, leaving each positioned at its end with a closed tail.
+    pub fn recover_all(&mut self)->Result<Vec<TailRecoverySummary>,ReadWriteError>{
+        let mut summaries = Vec::with_capacity(self.shards.len());
+        for (file,tail) in self.shards.iter_mut() {
+            *tail = None;
+            summaries.push(recover_tail::<_,B>(file)?);
+            file.seek(std::io::SeekFrom::End(0))?;
+            *tail = Some(TailState::ClosedBlock);
+        }
+        Ok(summaries)
+    }
+    pub fn num_shards(&self)->usize{
+        self.shards.len()
+    }
+    /// The shard `key_hash` routes to.
+    pub fn shard_for(&self,key_hash:u64)->usize{
+        (key_hash % self.shards.len() as u64) as usize
+    }
+    /// Performs `oper` on the shard `key_hash` routes to, and returns that shard's index.
+    pub fn write<T:AsRef<[u8]>+std::fmt::Debug>(&mut self,key_hash:u64,oper:Operation<T,B::CompLevel>)->Result<usize,Vec<ReadWriteError>>{
+        let shard = self.shard_for(key_hash);
+        self.write_to(shard, oper)?;
+        Ok(shard)
+    }
+    fn write_to<T:AsRef<[u8]>+std::fmt::Debug>(&mut self,shard:usize,oper:Operation<T,B::CompLevel>)->Result<(),Vec<ReadWriteError>>{
+        let (file,tail) = &mut self.shards[shard];
+        let Some(state) = tail.take() else {
+            return Err(vec![std::io::Error::other(format!("Shard {} needs recovery after a failed write",shard)).into()])
+        };
+        *tail = Some(perform_file_op(file, state, oper, self.write_attempts)?);
+        Ok(())
+    }
+    /// Closes the open B block of every shard that has one.
+    pub fn close_all(&mut self)->Result<(),Vec<ReadWriteError>>{
+        for shard in 0..self.shards.len() {
+            if self.shards[shard].1.as_ref().is_some_and(|t|t.is_open()) {
+                let close = Operation::<&[u8],B::CompLevel>{ op: Op::CloseBlock, timestamp: None, end_timestamp: None, calc_ecc: false, compress: None };
+                self.write_to(shard, close)?;
+            }
+        }
+        Ok(())
+    }
+    /// None if the shard needs [recover_all](Self::recover_all).
+    pub fn tail_state(&self,shard:usize)->Option<&TailState<B>>{
+        self.shards[shard].1.as_ref()
+    }
+    pub fn get_mut(&mut self,shard:usize)->&mut F{
+        &mut self.shards[shard].0
+    }
+    pub fn into_inner(self)->Vec<F>{
+        self.shards.into_iter().map(|(f,_)|f).collect()
+    }
+}
 
 #[cfg(test)]
 mod test_super {
-    use crate::{HeaderTag, HEADER_LEN, core::Content, read::read_content};
+    use crate::{HeaderTag, HEADER_LEN, core::{Content, StoredLen, OriginalLen}, read::read_content};
     use super::*;
     use std::io::Cursor;
 
@@ -428,13 +1141,145 @@ mod test_super {
         assert_eq!(&inner[1..9],[1u8;8]);
         assert_eq!(&inner[9..13],[0,0,0,0]);
         assert_eq!(inner[HEADER_LEN+ECC_LEN],HeaderTag::CECComponent as u8);
-        let content = Content{ data_len: content_len as u32, data_start:( (HEADER_LEN+ECC_LEN)*2+ECC_LEN) as u64, ecc: true, compressed: Some(50) };
+        let content = Content{ data_len: StoredLen(content_len as u32), data_start:( (HEADER_LEN+ECC_LEN)*2+ECC_LEN) as u64, ecc: true, compressed: Some(OriginalLen(50)), content_type: None, comp_level: None, component_hash: None, trailing_ecc: false, seekable: false };
         let mut crsr = Cursor::new(inner);
         let mut out = Vec::new();
         read_content::<_,_,DummyHasher>(&mut crsr, &mut out, &content).unwrap();
         assert_eq!(&data[..],&out);
 
     }
+    #[derive(Clone, Debug)]
+    struct FailingCompressor(DummyHasher);
+    impl BlockInputs for FailingCompressor {
+        type CompLevel = i32;
+        fn new() -> Self {Self(DummyHasher::new())}
+        fn update(&mut self, data: &[u8]) {self.0.update(data)}
+        fn finalize(&self) -> [u8; HASH_LEN] {self.0.finalize()}
+        fn current_timestamp() -> u64 {0}
+        fn compress<W:std::io::Write>(_data: &[u8], _writer: &mut W, _comp_level: &Self::CompLevel) -> std::io::Result<usize> {
+            Err(std::io::Error::other("compressor failed"))
+        }
+        fn decompress<R:std::io::Read,W:std::io::Write>(_compressed: &mut R, _sink: &mut W, _output_size:u32) -> std::io::Result<usize> {
+            unimplemented!()
+        }
+    }
+    #[test]
+    fn test_compressor_error_propagates() {
+        let mut writer = Cursor::new(Vec::new());
+        let mut h = FailingCompressor::new();
+        let result = write_content_component(&mut writer, true,Some(&22),Some(0),&[3u8;50],&mut h);
+        assert!(matches!(result, Err(ReadWriteError::Io(ref e)) if e.kind() == std::io::ErrorKind::Other), "{:?}", result);
+        let result = write_atomic_block::<_,FailingCompressor>(&mut writer, Some(0), &[3u8;50], true, Some(&22),None);
+        assert!(matches!(result, Err(ReadWriteError::Io(ref e)) if e.kind() == std::io::ErrorKind::Other), "{:?}", result);
+        assert!(writer.into_inner().is_empty());
+    }
+    #[test]
+    fn test_compression_expands() {
+        //random-ish data will not compress, zstd runs out of room in the buffer
+        let mut x = 0x2545F4914F6CDD1Du64;
+        let data:Vec<u8> = (0..64).map(|_|{x ^= x << 13; x ^= x >> 7; x ^= x << 17; x as u8}).collect();
+        let mut writer = Cursor::new(Vec::new());
+        let mut h = DummyHasher::new();
+        let (content_len,is_comp) = write_content_component(&mut writer, false,Some(&22),Some(0),&data,&mut h).unwrap();
+        assert!(!is_comp);
+        assert_eq!(content_len,data.len());
+        assert_eq!(&writer.into_inner()[HEADER_LEN+ECC_LEN..],&data[..]);
+    }
+    ///Same as [DummyHasher], but records the compression level.
+    #[cfg(feature = "bench")]
+    #[derive(Clone, Debug)]
+    struct LevelHasher(DummyHasher);
+    #[cfg(feature = "bench")]
+    impl BlockInputs for LevelHasher {
+        type CompLevel = i32;
+        fn new() -> Self {Self(DummyHasher::new())}
+        fn update(&mut self, data: &[u8]) {self.0.update(data)}
+        fn finalize(&self) -> [u8; HASH_LEN] {self.0.finalize()}
+        fn current_timestamp() -> u64 {0}
+        fn comp_level_byte(comp_level: &Self::CompLevel) -> Option<u8> {u8::try_from(*comp_level).ok()}
+        fn compress<W:std::io::Write>(data: &[u8], writer: &mut W, comp_level: &Self::CompLevel) -> std::io::Result<usize> {
+            DummyHasher::compress(data, writer, comp_level)
+        }
+        fn decompress<R:std::io::Read,W:std::io::Write>(compressed: &mut R, sink: &mut W, output_size:u32) -> std::io::Result<usize> {
+            DummyHasher::decompress(compressed, sink, output_size)
+        }
+    }
+    #[cfg(feature = "bench")]
+    #[test]
+    fn test_timed_write_content_component() {
+        let data = [3u8;2000];
+        let mut plain = Cursor::new(Vec::new());
+        let mut h1 = LevelHasher::new();
+        let plain_res = write_content_component(&mut plain, true,Some(&3),Some(0),&data,&mut h1).unwrap();
+
+        let mut timed = Cursor::new(Vec::new());
+        let mut h2 = LevelHasher::new();
+        let start = std::time::Instant::now();
+        let (timed_res,timings) = timed_write_content_component(&mut timed, true,Some(&3),Some(0),&data,&mut h2).unwrap();
+        let total = start.elapsed().as_nanos() as u64;
+
+        assert_eq!(plain_res,timed_res);
+        assert!(timed_res.1);
+        let timed = timed.into_inner();
+        assert_ne!(timed[0] & HAS_LEVEL,0);
+        assert_eq!(plain.into_inner(),timed);
+        assert_eq!(h1.finalize(),h2.finalize());
+        assert!(timings.compress_ns > 0 && timings.ecc_ns > 0 && timings.io_ns > 0, "{:?}", timings);
+        assert!(timings.compress_ns + timings.ecc_ns + timings.io_ns <= total);
+    }
+    #[test]
+    fn test_fixed_endianness() {
+        assert_eq!(crate::core::write_u32(0x01020304),[4,3,2,1]);
+        assert_eq!(crate::core::read_u32([4,3,2,1]),0x01020304);
+        assert_eq!(write_comp_len(0x01020304),[1,2,3,4]);
+        assert_eq!(crate::core::read_comp_len([1,2,3,4]),0x01020304);
 
-}
+        let mut writer = Cursor::new(Vec::new());
+        let data = [3u8;300];
+        let mut h = DummyHasher::new();
+        let (content_len,is_comp) = write_content_component(&mut writer, false,Some(&22),Some(0),&data,&mut h).unwrap();
+        assert!(is_comp);
+        let inner = writer.into_inner();
+        //header data field is little endian, compressed length prefix is big endian
+        assert_eq!(&inner[9..13],&(content_len as u32).to_le_bytes());
+        assert_eq!(&inner[HEADER_LEN+ECC_LEN..HEADER_LEN+ECC_LEN+4],&[0,0,1,44]);
+    }
+
+    #[test]
+    fn test_pipelined_hash_matches_inline() {
+        let contents:Vec<Vec<u8>> = vec![vec![1u8;10],vec![2u8;600],vec![3u8;0],vec![4u8;255]];
+        let mut inline = Cursor::new(Vec::new());
+        let mut inline_h = DummyHasher::new();
+        for (i,c) in contents.iter().enumerate() {
+            let ct = if i == 1 {Some(7)}else{None};
+            write_typed_content_component(&mut inline, i % 2 == 1, None, Some(i as u64), ct, c, &mut inline_h).unwrap();
+        }
+
+        //hash on a worker thread, only write on this one
+        let mut piped = Cursor::new(Vec::new());
+        let mut piped_h = DummyHasher::new();
+        std::thread::scope(|s|{
+            let (tx,rx) = std::sync::mpsc::channel();
+            let contents = &contents;
+            s.spawn(move ||{
+                let mut h = DummyHasher::new();
+                for (i,c) in contents.iter().enumerate() {
+                    let ct = if i == 1 {Some(7)}else{None};
+                    let pre = prehash_content_component(h.clone(), i % 2 == 1, Some(i as u64), ct, c).unwrap();
+                    h = pre.hasher.clone();
+                    tx.send(pre).unwrap();
+                }
+            });
+            for (c,pre) in contents.iter().zip(rx.iter()) {
+                write_content_precomputed_hash(&mut piped, c, pre, &mut piped_h).unwrap();
+            }
+        });
+        assert_eq!(piped_h.finalize(),inline_h.finalize());
+        assert_eq!(piped.into_inner(),inline.into_inner());
 
+        let pre = prehash_content_component(DummyHasher::new(), false, Some(0), None, &[1,2,3]).unwrap();
+        let res = write_content_precomputed_hash(&mut Cursor::new(Vec::new()), &[1,2], pre, &mut piped_h);
+        assert!(matches!(res,Err(ReadWriteError::Io(e)) if e.kind() == std::io::ErrorKind::InvalidInput));
+    }
+
+}
@@ -26,15 +26,20 @@ impl BlockInputs for DummyInput {
         u64::from_be_bytes([7, 6, 5, 4, 3, 2, 1, 0])
     }
     type CompLevel = i32;
+    const COMPRESSED_MAGIC: &'static [u8] = &[0x28, 0xB5, 0x2F, 0xFD];
+
+    fn comp_level_byte(comp_level: &Self::CompLevel) -> Option<u8> {
+        u8::try_from(*comp_level).ok()
+    }
 
     fn compress<W:std::io::Write>(data: &[u8], writer: &mut W, comp_level: &Self::CompLevel) -> std::io::Result<usize> {
         let mut encoder = zstd::Encoder::new(writer, *comp_level)?;
         encoder.set_pledged_src_size(Some(data.len() as u64))?;
         encoder.include_contentsize(true)?;
         use std::io::Write;
-        let written = encoder.write(data)?;
+        encoder.write_all(data)?;
         encoder.finish()?;
-        Ok(written)
+        Ok(data.len())
     }
 
     fn decompress<R:std::io::Read,W:std::io::Write>(compressed: &mut R, sink: &mut W,output_size:u32) -> std::io::Result<usize> {
@@ -103,4 +108,31 @@ pub fn generate_test_file() -> Cursor<Vec<u8>> {
 }
 pub const NULL_HASH:[u8;HASH_LEN] = [175, 19, 73, 185, 245, 249, 161, 166, 160, 64, 77, 234, 54, 220, 201, 73, 155, 203, 37, 201];
 pub const BLOCK_1_HASH:[u8;HASH_LEN] = [33, 215, 215, 192, 27, 1, 94, 58, 192, 97, 207, 38, 108, 77, 159, 4, 65, 107, 184, 244];
-pub const BLOCK_3_HASH:[u8;HASH_LEN] = [59, 64, 117, 102, 139, 248, 203, 101, 132, 81, 227, 62, 79, 23, 156, 103, 106, 46, 127, 152];
\ No newline at end of file
+pub const BLOCK_3_HASH:[u8;HASH_LEN] = [59, 64, 117, 102, 139, 248, 203, 101, 132, 81, 227, 62, 79, 23, 156, 103, 106, 46, 127, 152];
+/// Truncates a copy of `bytes` at every possible offset, runs [recover_tail](docufort::recovery::recover_tail) and asserts the result integrity checks.
+///
+/// Offsets inside the file header can not be recovered and must return an error.
+/// Every other offset must recover to a file that integrity checks completely, with only closed blocks.
+pub fn assert_recoverable_at_every_truncation(bytes: &[u8]) {
+    use docufort::recovery::recover_tail;
+    use docufort::integrity::integrity_check_file;
+    for offset in 0..=bytes.len() {
+        let mut cursor = Cursor::new(bytes[..offset].to_vec());
+        let summary = recover_tail::<_, DummyInput>(&mut cursor);
+        if offset < FILE_HEADER_LEN as usize {
+            assert!(summary.is_err(), "Offset {}: recovered a file with an incomplete file header", offset);
+            continue;
+        }
+        let summary = summary.unwrap_or_else(|e| panic!("Offset {}: recover_tail failed: {}", offset, e));
+        assert_eq!(summary.recovered_file_len, cursor.get_ref().len() as u64, "Offset {}: recovered_file_len does not match the file", offset);
+        cursor.set_position(0);
+        let check = integrity_check_file::<_, DummyInput>(&mut cursor).unwrap_or_else(|e| panic!("Offset {}: integrity check failed: {}", offset, e));
+        assert!(check.corrupted_segments.is_empty(), "Offset {}: {:?}", offset, check.corrupted_segments);
+        if summary.has_blocks {
+            assert_eq!(check.file_len_checked, summary.recovered_file_len, "Offset {}: integrity check stopped early", offset);
+            assert!(check.last_block_state.map(|s| s.is_closed()).unwrap_or(false), "Offset {}: last block is not closed", offset);
+        } else {
+            assert_eq!(check.num_blocks, 0, "Offset {}: found blocks in a file recovered as empty", offset);
+        }
+    }
+}
@@ -4,6 +4,10 @@ mod common;
 use common::*;
 use docufort::*;
 use docufort::content_reader::find_content;
+use docufort::read::{catalog, content_fingerprint, read_content, replay};
+use docufort::core::{BlockInputs, BlockState, Block, ComponentHeader};
+use docufort::recovery::{try_read_block, BlockReadSummary};
+use docufort::write::{init_file, write_magic_number, write_header, write_content_component, write_block_end};
 
 use std::io::Cursor;
 
@@ -116,4 +120,1540 @@ fn test_find_content_open_2_corrupt_1() {
     let mut cursor = Cursor::new(file_contents);
     let summary = find_content::<_,DummyInput,_>(&mut cursor,None,Some(u64::from_be_bytes([7, 6, 5, 4, 3, 2, 1, 0])..)).unwrap();
     assert_eq!(summary.len(),3);
-}
\ No newline at end of file
+}
+#[test]
+fn test_catalog() {
+    let mut cursor = generate_test_file();
+    let entries = catalog(&mut cursor).unwrap();
+    assert_eq!(entries.len(),3);
+    assert_eq!(entries.iter().map(|e|e.offset).collect::<Vec<_>>(),vec![23,184,268]);
+    assert_eq!(entries.iter().map(|e|e.is_atomic).collect::<Vec<_>>(),vec![false,true,true]);
+    assert_eq!(entries[0].total_content_bytes,B_CONTENT.len() as u64 * 3);
+    assert_eq!(entries[1].total_content_bytes,A_CONTENT.len() as u64);
+    assert_eq!(entries[2].total_content_bytes,A_CONTENT.len() as u64);
+    for e in entries {
+        assert_eq!(e.start_ts,DummyInput::current_timestamp());
+        assert_eq!(e.end_ts,DummyInput::current_timestamp());
+    }
+}
+#[test]
+fn test_catalog_open_tail() {
+    let cursor = generate_test_file();
+    let mut file_content = cursor.into_inner();
+    file_content.truncate(268+HEADER_LEN+ECC_LEN+4);
+    let mut cursor = Cursor::new(file_content);
+    let entries = catalog(&mut cursor).unwrap();
+    assert_eq!(entries.len(),2);
+}
+#[test]
+fn test_content_fingerprint() {
+    let mut cursor = generate_test_file();
+    let summary = find_content::<_,DummyInput,std::ops::RangeFull>(&mut cursor,None,None).unwrap();
+    assert_eq!(summary.len(),5);
+    let prints:Vec<_> = summary.iter().map(|(_,c)|content_fingerprint::<_,DummyInput>(&mut cursor,c).unwrap()).collect();
+    //3 identical B components (with and without ECC) then 2 identical A contents
+    assert_eq!(prints[0],prints[1]);
+    assert_eq!(prints[0],prints[2]);
+    assert_eq!(prints[3],prints[4]);
+    assert_ne!(prints[0],prints[3]);
+
+    //changing a byte changes the fingerprint
+    let (_,c) = summary[0];
+    let mut v = cursor.into_inner();
+    v[c.data_start as usize] ^= 0xFF;
+    let changed = content_fingerprint::<_,DummyInput>(&mut Cursor::new(v),&c).unwrap();
+    assert_ne!(changed,prints[0]);
+}
+#[test]
+fn test_replay_strings() {
+    let mut cursor = generate_test_file();
+    let events = replay::<_,DummyInput,_>(&mut cursor, |bytes|{
+        String::from_utf8(bytes.to_vec()).map_err(std::io::Error::other)
+    }).unwrap();
+    assert_eq!(events,vec!["Some content","Some content","Some content","Atomic content","Atomic content"]);
+}
+#[test]
+fn test_replay_hash_mismatch() {
+    let mut v = generate_test_file().into_inner();
+    v[184+HEADER_LEN+ECC_LEN] ^= 1;//A block without ECC
+    let res = replay::<_,DummyInput,_>(&mut Cursor::new(v), |bytes|Ok(bytes.len()));
+    assert!(matches!(res,Err(ReadWriteError::HashMismatch { block_start: 184 })),"{:?}",res);
+}
+fn typed_b_block() -> Cursor<Vec<u8>> {
+    use docufort::write::write_typed_content_component;
+    let mut cursor = Cursor::new(Vec::new());
+    let mut hasher = DummyInput::new();
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let b_block_header = ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_header(&mut cursor, &b_block_header).unwrap();
+    write_typed_content_component(&mut cursor, false, None, None, Some(1), b"first", &mut hasher).unwrap();
+    write_typed_content_component(&mut cursor, true, None, None, Some(0xBEEF), b"second", &mut hasher).unwrap();
+    write_content_component(&mut cursor, false, None, None, b"untyped", &mut hasher).unwrap();
+    let block_end_header = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_block_end(&mut cursor, &block_end_header, &hasher.finalize()).unwrap();
+    cursor
+}
+#[test]
+fn test_typed_content_round_trip() {
+    let mut cursor = typed_b_block();
+    let summary = find_content::<_,DummyInput,std::ops::RangeFull>(&mut cursor,None,None).unwrap();
+    let types:Vec<_> = summary.iter().map(|(_,c)|c.content_type).collect();
+    assert_eq!(types,vec![Some(1),Some(0xBEEF),None]);
+    let data:Vec<_> = summary.iter().map(|(_,c)|{
+        let mut sink = Vec::new();
+        read_content::<_,_,DummyInput>(&mut cursor,&mut sink,c).unwrap();
+        sink
+    }).collect();
+    assert_eq!(data,vec![b"first".to_vec(),b"second".to_vec(),b"untyped".to_vec()]);
+    assert_recoverable_at_every_truncation(cursor.get_ref());
+}
+#[test]
+fn test_typed_content_ecc() {
+    let mut v = typed_b_block().into_inner();
+    //first type byte sits right after the first content header
+    let type_start = FILE_HEADER_LEN as usize+MN_ECC_LEN+HEADER_LEN+ECC_LEN+HEADER_LEN+ECC_LEN;
+    v[type_start] ^= 0xFF;
+    let mut cursor = Cursor::new(v);
+    cursor.set_position((FILE_HEADER_LEN as usize+MN_ECC_LEN) as u64);
+    let BlockState::Closed(BlockReadSummary{ errors_corrected, block:Block::B { middle, .. }, .. }) = try_read_block::<_,DummyInput>(&mut cursor,true,true).unwrap() else {panic!("expected closed B block")};
+    assert!(errors_corrected > 0);
+    assert_eq!(middle[0].1.content_type,Some(1));
+    assert_eq!(cursor.get_ref()[type_start],0);//corrected on disk
+}
+#[test]
+fn test_locate_offset() {
+    use docufort::read::{locate_offset, OffsetLocation};
+    let mut cursor = generate_test_file();
+    //block 3 is an A block with ECC: header at 268, then 4 bytes of content ECC, content at 289
+    let loc = locate_offset(&mut cursor, 295).unwrap();
+    assert_eq!(loc,OffsetLocation{ block_start: 268, component_tag: ComponentTag::StartHeader, within_content: true, content_relative_offset: 6 });
+    let loc = locate_offset(&mut cursor, 287).unwrap();
+    assert_eq!((loc.block_start,loc.within_content),(268,false));
+    let loc = locate_offset(&mut cursor, 260).unwrap();//its magic number
+    assert_eq!((loc.block_start,loc.component_tag),(268,ComponentTag::StartHeader));
+    let loc = locate_offset(&mut cursor, 343).unwrap();
+    assert_eq!((loc.block_start,loc.component_tag),(268,ComponentTag::Hash));
+    //first content component of the B block, without ECC
+    let loc = locate_offset(&mut cursor, 23+2*(HEADER_LEN+ECC_LEN) as u64+3).unwrap();
+    assert_eq!(loc,OffsetLocation{ block_start: 23, component_tag: ComponentTag::ContentHeader, within_content: true, content_relative_offset: 3 });
+    let loc = locate_offset(&mut cursor, 45).unwrap();
+    assert_eq!(loc,OffsetLocation{ block_start: 23, component_tag: ComponentTag::ContentHeader, within_content: false, content_relative_offset: 0 });
+    let loc = locate_offset(&mut cursor, 250).unwrap();
+    assert_eq!((loc.block_start,loc.component_tag),(184,ComponentTag::Hash));
+
+    assert!(matches!(locate_offset(&mut cursor, 3),Err(ReadWriteError::Io(_))));
+    assert!(matches!(locate_offset(&mut cursor, 344),Err(ReadWriteError::EndOfFile)));
+}
+#[test]
+fn test_copy_block_raw() {
+    use docufort::read::copy_block_raw;
+    use docufort::recovery::recover_tail;
+    let mut cursor = generate_test_file();
+    let mut replica = Cursor::new(Vec::new());
+    init_file(&mut replica).unwrap();
+    let copied = copy_block_raw::<_,_,DummyInput>(&mut cursor, 23, &mut replica, true).unwrap();
+    //block 2's magic number starts at 172
+    assert_eq!(copied,172-(FILE_HEADER_LEN as u64));
+    assert_eq!(&replica.get_ref()[..],&cursor.get_ref()[..172]);
+
+    let summary = recover_tail::<_,DummyInput>(&mut replica).unwrap();
+    assert_eq!(summary.recovered_file_len,172);
+    let (_,state) = summary.file_ops.last().unwrap();
+    let BlockState::Closed(BlockReadSummary{ block:Block::B { middle, end, .. }, hash_as_read, .. }) = state else {panic!("expected closed B block")};
+    assert_eq!(middle.len(),3);
+    assert_eq!(end.hash.hash(),&hash_as_read[..]);
+
+    //a tampered block is not copied when verifying, and is copied as is when not
+    let mut v = cursor.into_inner();
+    v[184+HEADER_LEN+ECC_LEN] ^= 0xFF;
+    let mut tampered = Cursor::new(v);
+    let mut dest = Vec::new();
+    let res = copy_block_raw::<_,_,DummyInput>(&mut tampered, 184, &mut dest, true);
+    assert!(matches!(res,Err(ReadWriteError::HashMismatch { block_start: 184 })),"{:?}",res);
+    assert!(dest.is_empty());
+    //an error the content ECC corrects still verifies, but the bytes are copied as they are
+    tampered.get_mut()[295] ^= 0xFF;
+    let raw = tampered.get_ref()[256..344].to_vec();
+    let mut dest = Vec::new();
+    assert_eq!(copy_block_raw::<_,_,DummyInput>(&mut tampered, 268, &mut dest, true).unwrap(),344-256);
+    assert_eq!(dest,raw);
+    assert_eq!(tampered.get_ref()[256..344],raw[..]);
+    let mut dest = Vec::new();
+    assert_eq!(copy_block_raw::<_,_,DummyInput>(&mut tampered, 184, &mut dest, false).unwrap(),256-172);
+    assert_eq!(&dest[..],&tampered.get_ref()[172..256]);
+}
+#[test]
+fn test_read_ahead_same_results() {
+    use docufort::read::ReadAheadReader;
+    use docufort::integrity::integrity_check_file;
+    let expected_events = replay::<_,DummyInput,_>(&mut generate_test_file(), |bytes|Ok(bytes.to_vec())).unwrap();
+    let expected_found = find_content::<_,DummyInput,_>(&mut generate_test_file(),None,Some(0..)).unwrap();
+    let mut corrupted = generate_test_file().into_inner();
+    corrupted[289] ^= 0xFF;//A block with ECC content
+    let mut plain = Cursor::new(corrupted.clone());
+    let expected_check = integrity_check_file::<_,DummyInput>(&mut plain).unwrap();
+    for window in [1,7,64,4096] {
+        let mut reader = ReadAheadReader::new(generate_test_file(), window).unwrap();
+        let events = replay::<_,DummyInput,_>(&mut reader, |bytes|Ok(bytes.to_vec())).unwrap();
+        assert_eq!(events,expected_events,"window {}",window);
+        let mut reader = ReadAheadReader::new(generate_test_file(), window).unwrap();
+        let found = find_content::<_,DummyInput,_>(&mut reader,None,Some(0..)).unwrap();
+        assert_eq!(found,expected_found,"window {}",window);
+        let mut reader = ReadAheadReader::new(Cursor::new(corrupted.clone()), window).unwrap();
+        let check = integrity_check_file::<_,DummyInput>(&mut reader).unwrap();
+        assert_eq!(check.errors_corrected,expected_check.errors_corrected,"window {}",window);
+        assert_eq!(check.block_times,expected_check.block_times,"window {}",window);
+        assert_eq!(reader.into_inner().into_inner(),plain.get_ref().clone(),"window {}",window);
+    }
+}
+#[test]
+fn test_source_slice_and_file() {
+    use docufort::read::{Source, locate_offset};
+    use docufort::recovery::find_block_start;
+    use std::io::{Seek, SeekFrom};
+    let bytes = generate_test_file().into_inner();
+    let path = std::env::temp_dir().join(format!("docufort_source_{}",std::process::id()));
+    std::fs::write(&path, &bytes).unwrap();
+    let file = std::fs::File::open(&path).unwrap();
+    let mut from_slice = Source::from_slice(&bytes);
+    let mut from_file = Source::from_file(&file);
+
+    let expected = catalog(&mut Cursor::new(bytes.clone())).unwrap();
+    assert_eq!(catalog(&mut from_slice).unwrap(),expected);
+    assert_eq!(catalog(&mut from_file).unwrap(),expected);
+    for source in [&mut from_slice,&mut from_file] {
+        source.seek(SeekFrom::End(0)).unwrap();
+        assert_eq!(find_block_start(source).unwrap(),268);
+        source.seek(SeekFrom::Start(268 - MN_ECC_LEN as u64)).unwrap();
+        assert_eq!(find_block_start(source).unwrap(),184);
+        assert_eq!(locate_offset(source,300).unwrap(),locate_offset(&mut Cursor::new(bytes.clone()),300).unwrap());
+    }
+    //positioned reads don't move the source
+    let mut buf = [0u8;14];
+    from_file.seek(SeekFrom::Start(5)).unwrap();
+    assert_eq!(from_file.read_at(289, &mut buf).unwrap(),14);
+    assert_eq!(&buf,b"Atomic content");
+    assert_eq!(from_file.stream_position().unwrap(),5);
+    assert_eq!(from_slice.read_at(bytes.len() as u64 + 1, &mut buf).unwrap(),0);
+    drop(file);
+    std::fs::remove_file(&path).unwrap();
+}
+#[test]
+fn test_recorded_block_end() {
+    use docufort::read::{recorded_block_end, read_header, read_hash, hash_range};
+    use docufort::write::write_block_end_with_body_len;
+    use docufort::integrity::integrity_check_file;
+    assert_eq!(recorded_block_end(&mut generate_test_file(), 23).unwrap(),None);
+    assert_eq!(recorded_block_end(&mut generate_test_file(), 184).unwrap(),None);
+
+    let mut cursor = Cursor::new(Vec::new());
+    let mut hasher = DummyInput::new();
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let block_start = cursor.position();
+    let start = ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_header(&mut cursor, &start).unwrap();
+    for i in 0..5 {write_content_component(&mut cursor, i % 2 == 0, None, None, &vec![i;300], &mut hasher).unwrap();}
+    let end_start = cursor.position();
+    let end = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_block_end_with_body_len(&mut cursor, block_start, &start, &end, &hasher.finalize()).unwrap();
+    let file_len = cursor.position();
+    assert_eq!(file_len,cursor.get_ref().len() as u64);
+
+    //jump to the end and verify the hash without reading any content headers
+    assert_eq!(recorded_block_end(&mut cursor, block_start).unwrap(),Some(end_start));
+    cursor.set_position(end_start);
+    read_header(&mut cursor, true).unwrap();
+    let (_,hash) = read_hash(&mut cursor, true).unwrap();
+    let mut rehash = DummyInput::new();
+    hash_range(&mut cursor, block_start + (HEADER_LEN + ECC_LEN) as u64, end_start, &mut rehash).unwrap();
+    assert_eq!(&rehash.finalize()[..],hash.hash());
+
+    cursor.set_position(0);
+    let check = integrity_check_file::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(check.num_blocks,1);
+    assert_eq!(check.file_len_checked,file_len);
+    assert_eq!(check.errors_corrected,0);
+}
+#[test]
+#[cfg(all(feature = "ecc_len_4", not(feature = "crc_only")))]
+fn test_transcode_content_ecc() {
+    use docufort::read::transcode_content_ecc;
+    use docufort::ecc::{apply_ecc_with_len, calc_ecc_data_len_for};
+    use docufort::write::{write_atomic_block, write_streamed_atomic_block};
+    let data:Vec<u8> = (0..1000u32).map(|i|(i * 7 % 251) as u8).collect();
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    write_atomic_block::<_,DummyInput>(&mut cursor, None, &data, true, None, None).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    write_streamed_atomic_block::<_,_,DummyInput>(&mut cursor, &mut &data[..], data.len() as u32, None, None).unwrap();
+    let contents = find_content::<_,DummyInput,std::ops::RangeFull>(&mut cursor,None,None).unwrap();
+    assert_eq!(contents.len(),2);
+    //two errors in the first chunk of the prepended ECC content
+    let mut v = cursor.into_inner();
+    let prepended = contents[0].1;
+    v[prepended.data_start as usize] ^= 0xFF;
+    v[prepended.data_start as usize + 10] ^= 0xFF;
+    let mut cursor = Cursor::new(v);
+
+    let new_ecc_len = 8;
+    let ecc_len = calc_ecc_data_len_for(data.len(), new_ecc_len);
+    let mut outputs = Vec::new();
+    for (_,content) in contents.iter() {
+        let mut out = Vec::new();
+        let (errors,written) = transcode_content_ecc(&mut cursor, &mut out, content, ECC_LEN, new_ecc_len).unwrap();
+        assert_eq!(errors,if content.trailing_ecc {0}else{2});
+        assert_eq!(written,out.len());
+        assert_eq!(out.len(),ecc_len + data.len());
+        outputs.push(out);
+    }
+    //both layouts transcode to the same prepended bytes, and nothing was written back
+    assert_eq!(outputs[0],outputs[1]);
+    assert_ne!(cursor.get_ref()[prepended.data_start as usize],data[0]);
+
+    //the output reads clean with the new ECC length, and corrects what the old length could not
+    let mut out = outputs.pop().unwrap();
+    let (ecc,msg) = out.split_at_mut(ecc_len);
+    for (i,(chunk,chunk_ecc)) in msg.chunks(255 - new_ecc_len).zip(ecc.chunks(new_ecc_len)).enumerate() {
+        let mut buf = [chunk,chunk_ecc].concat();
+        assert_eq!(apply_ecc_with_len(&mut buf, new_ecc_len).unwrap(),0,"chunk {}",i);
+        for j in [0,3,9,buf.len()-1] {buf[j] ^= 0x5A;}
+        assert_eq!(apply_ecc_with_len(&mut buf, new_ecc_len).unwrap(),4,"chunk {}",i);
+        assert_eq!(&buf[..chunk.len()],chunk);
+    }
+    assert_eq!(msg,&data[..]);
+}
+#[test]
+fn test_pinned_timestamps_reproducible() {
+    use docufort::write::{write_atomic_block, write_streamed_atomic_block, write_typed_content_component};
+    use docufort::retry_writer::{Op, Operation, TailState, perform_file_op};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static CLOCK:AtomicU64 = AtomicU64::new(1);
+    ///A clock that never returns the same time twice, so any timestamp that isn't pinned changes the file.
+    #[derive(Clone, Debug)]
+    struct Ticking(DummyInput);
+    impl BlockInputs for Ticking {
+        fn new() -> Self {Ticking(DummyInput::new())}
+        fn update(&mut self, data: &[u8]) {self.0.update(data)}
+        fn finalize(&self) -> [u8; HASH_LEN] {self.0.finalize()}
+        fn current_timestamp() -> u64 {CLOCK.fetch_add(1, Ordering::Relaxed)}
+        type CompLevel = i32;
+        fn compress<W:std::io::Write>(data: &[u8], writer: &mut W, comp_level: &i32) -> std::io::Result<usize> {DummyInput::compress(data, writer, comp_level)}
+        fn decompress<R:std::io::Read,W:std::io::Write>(compressed: &mut R, sink: &mut W,output_size:u32) -> std::io::Result<usize> {DummyInput::decompress(compressed, sink, output_size)}
+    }
+    let ts = |i:u64|Some(1_000 + i);
+    let end = |i:u64|ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, (2_000 + i).to_be_bytes(), None);
+    let build = |pin:bool|{
+        let ts = |i|if pin {ts(i)}else{None};
+        let mut cursor = Cursor::new(Vec::new());
+        init_file(&mut cursor).unwrap();
+        write_magic_number(&mut cursor).unwrap();
+        write_atomic_block::<_,Ticking>(&mut cursor, ts(0), A_CONTENT, true, Some(&3), pin.then(||end(0)).as_ref()).unwrap();
+        write_magic_number(&mut cursor).unwrap();
+        write_streamed_atomic_block::<_,_,Ticking>(&mut cursor, &mut &A_CONTENT[..], A_CONTENT.len() as u32, ts(1), pin.then(||end(1)).as_ref()).unwrap();
+        write_magic_number(&mut cursor).unwrap();
+        let mut hasher = Ticking::new();
+        let start_ts = ts(2).unwrap_or_else(Ticking::current_timestamp);
+        write_header(&mut cursor, &ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, start_ts.to_be_bytes(), None)).unwrap();
+        write_typed_content_component(&mut cursor, true, None, ts(3), Some(7), B_CONTENT, &mut hasher).unwrap();
+        let end_header = if pin {end(2)}else{ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, Ticking::current_timestamp().to_be_bytes(), None)};
+        write_block_end(&mut cursor, &end_header, &hasher.finalize()).unwrap();
+        let ops = [
+            Operation{ op:Op::ContentWrite(B_CONTENT.to_vec(),ts(4)), timestamp: ts(5), end_timestamp: ts(6), calc_ecc: true, compress:None },
+            Operation{ op:Op::AtomicWrite(A_CONTENT.to_vec()), timestamp: ts(7), end_timestamp: ts(8), calc_ecc: false, compress:None },
+            Operation{ op:Op::ContentWrite(B_CONTENT.to_vec(),ts(9)), timestamp: ts(10), end_timestamp: ts(11), calc_ecc: false, compress:None },
+            Operation{ op:Op::CloseBlock, timestamp: ts(12), end_timestamp: ts(13), calc_ecc: false, compress:None },
+        ];
+        let mut tail_state: TailState<Ticking> = TailState::ClosedBlock;
+        for oper in ops {
+            tail_state = perform_file_op(&mut cursor, tail_state, oper, 1).unwrap();
+        }
+        assert!(tail_state.is_closed());
+        cursor.into_inner()
+    };
+    let first = build(true);
+    assert_eq!(first,build(true));
+    assert_ne!(build(false),build(false));
+    let mut cursor = Cursor::new(first);
+    let check = docufort::integrity::integrity_check_file::<_,Ticking>(&mut cursor).unwrap();
+    assert_eq!(check.num_blocks,6);
+    assert!(check.corrupted_segments.is_empty());
+}
+#[test]
+fn test_compression_level_round_trip() {
+    use docufort::write::write_typed_content_component;
+    let data = vec![9u8;500];
+    let mut cursor = Cursor::new(Vec::new());
+    let mut hasher = DummyInput::new();
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let b_block_header = ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_header(&mut cursor, &b_block_header).unwrap();
+    write_typed_content_component(&mut cursor, true, Some(&5), None, Some(3), &data, &mut hasher).unwrap();
+    write_content_component(&mut cursor, false, Some(&5), None, &data, &mut hasher).unwrap();
+    write_content_component(&mut cursor, false, None, None, &data, &mut hasher).unwrap();
+    let block_end_header = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_block_end(&mut cursor, &block_end_header, &hasher.finalize()).unwrap();
+
+    cursor.set_position(23);
+    let BlockState::Closed(BlockReadSummary { block: Block::B { middle, .. }, .. }) = try_read_block::<_,DummyInput>(&mut cursor, true, true).unwrap() else {panic!()};
+    let levels:Vec<_> = middle.iter().map(|(_,c)|c.compression_level()).collect();
+    assert_eq!(levels,vec![Some(5),Some(5),None]);
+    assert_eq!(middle[0].1.content_type,Some(3));
+    for (_,c) in middle.iter() {
+        let mut out = Vec::new();
+        read_content::<_,_,DummyInput>(&mut cursor, &mut out, c).unwrap();
+        assert_eq!(out,data);
+    }
+
+    //the level field is ECC protected, it sits after the header and the content type
+    let level_pos = 23 + (HEADER_LEN + ECC_LEN) * 2 + CONTENT_TYPE_LEN + ECC_LEN;
+    cursor.get_mut()[level_pos] ^= 0xFF;
+    cursor.set_position(23);
+    let BlockState::Closed(BlockReadSummary { block: Block::B { middle, .. }, errors_corrected, .. }) = try_read_block::<_,DummyInput>(&mut cursor, true, true).unwrap() else {panic!()};
+    assert_eq!(middle[0].1.compression_level(),Some(5));
+    assert_eq!(errors_corrected,1);
+}
+/// Yields `len` bytes of a repeating pattern, tracking the largest read asked of it.
+struct PatternSource{pos:usize,len:usize,max_read:usize}
+impl std::io::Read for PatternSource {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.max_read = self.max_read.max(buf.len());
+        let n = buf.len().min(self.len - self.pos);
+        for (i,b) in buf[..n].iter_mut().enumerate() {*b = ((self.pos + i) % 251) as u8}
+        self.pos += n;
+        Ok(n)
+    }
+}
+/// Tracks the largest single write.
+struct MaxWrite{inner:Cursor<Vec<u8>>,max_write:usize}
+impl std::io::Write for MaxWrite {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.max_write = self.max_write.max(buf.len());
+        std::io::Write::write(&mut self.inner, buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+impl std::io::Seek for MaxWrite {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+#[test]
+fn test_streamed_atomic_block() {
+    use docufort::write::write_streamed_atomic_block;
+    use docufort::read::locate_offset;
+    const LEN:usize = 1024*1024;
+    let mut source = PatternSource{pos:0,len:LEN,max_read:0};
+    let mut out = MaxWrite{inner:Cursor::new(Vec::new()),max_write:0};
+    init_file(&mut out).unwrap();
+    write_magic_number(&mut out).unwrap();
+    write_streamed_atomic_block::<_,_,DummyInput>(&mut out, &mut source, LEN as u32, None, None).unwrap();
+    assert!(source.max_read <= DATA_SIZE, "{}", source.max_read);
+    assert!(out.max_write <= DATA_SIZE, "{}", out.max_write);
+    let mut cursor = out.inner;
+    let expected:Vec<u8> = (0..LEN).map(|i|(i % 251) as u8).collect();
+
+    //the ECC of chunk 1 directly follows its data
+    let data_start = 23 + HEADER_LEN + ECC_LEN;
+    let loc = locate_offset(&mut cursor, (data_start + DATA_SIZE) as u64).unwrap();
+    assert!(!loc.within_content);
+    let loc = locate_offset(&mut cursor, (data_start + DATA_SIZE + ECC_LEN) as u64).unwrap();
+    assert_eq!((loc.within_content,loc.content_relative_offset),(true,DATA_SIZE as u64));
+    assert_eq!(catalog(&mut cursor).unwrap()[0].total_content_bytes,LEN as u64);
+
+    //an error in the middle of the content is corrected in place
+    let corrupt_at = data_start + 1000 * (DATA_SIZE + ECC_LEN) + 7;
+    cursor.get_mut()[corrupt_at] ^= 0xFF;
+    cursor.set_position(23);
+    let BlockState::Closed(BlockReadSummary { block: Block::A { middle, .. }, errors_corrected, corrupted_content_blocks, .. }) = try_read_block::<_,DummyInput>(&mut cursor, true, true).unwrap() else {panic!()};
+    assert_eq!(errors_corrected,1);
+    assert!(corrupted_content_blocks.is_empty());
+    assert!(middle.trailing_ecc);
+    let mut data = Vec::new();
+    read_content::<_,_,DummyInput>(&mut cursor, &mut data, &middle).unwrap();
+    assert_eq!(data,expected);
+    let mut hasher = DummyInput::new();
+    hasher.update(&expected);
+    assert_eq!(content_fingerprint::<_,DummyInput>(&mut cursor, &middle).unwrap(),hasher.finalize());
+}
+#[test]
+fn test_atomic_block_streaming() {
+    use docufort::write::write_atomic_block_streaming;
+    use docufort::recovery::recover_tail;
+    for (len,calc_ecc) in [(3*DATA_SIZE + 17,true),(2*DATA_SIZE,true),(3*DATA_SIZE + 17,false),(0,false)] {
+        //the length is only known to the source
+        let mut source = PatternSource{pos:0,len,max_read:0};
+        let mut cursor = Cursor::new(Vec::new());
+        init_file(&mut cursor).unwrap();
+        write_magic_number(&mut cursor).unwrap();
+        let written = write_atomic_block_streaming::<_,_,DummyInput>(&mut cursor, &mut source, calc_ecc).unwrap();
+        assert!(source.max_read <= DATA_SIZE, "{}", source.max_read);
+        assert_eq!((written.start_offset,written.end_offset),(23,cursor.get_ref().len() as u64));
+
+        cursor.set_position(23);
+        let BlockState::Closed(BlockReadSummary { block: Block::A { middle, .. }, hash_as_read, .. }) = try_read_block::<_,DummyInput>(&mut cursor, true, true).unwrap() else {panic!("len {len}")};
+        assert_eq!(hash_as_read,written.hash);
+        assert_eq!((middle.data_len.0 as usize,middle.ecc,middle.trailing_ecc),(len,calc_ecc,calc_ecc));
+        let mut data = Vec::new();
+        assert_eq!(read_content::<_,_,DummyInput>(&mut cursor, &mut data, &middle).unwrap(),len);
+        assert!(data.iter().enumerate().all(|(i,b)|*b == (i % 251) as u8));
+    }
+
+    //interrupted before the length is patched: the block runs past EOF and recovery removes it
+    struct Failing(PatternSource);
+    impl std::io::Read for Failing {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.0.pos >= DATA_SIZE*2 {return Err(std::io::ErrorKind::BrokenPipe.into())}
+            self.0.read(buf)
+        }
+    }
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let mut source = Failing(PatternSource{pos:0,len:DATA_SIZE*4,max_read:0});
+    assert!(write_atomic_block_streaming::<_,_,DummyInput>(&mut cursor, &mut source, true).is_err());
+    recover_tail::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(cursor.get_ref().len(),FILE_HEADER_LEN as usize);
+}
+#[test]
+fn test_verify_block_start_slice() {
+    use docufort::read::verify_block_start_slice;
+    let v = generate_test_file().into_inner();
+    //B block, A block without ECC, A block with ECC
+    for start in [23,184,268] {
+        assert_eq!(verify_block_start_slice(&v[start..]),Some(true),"{}",start);
+    }
+    let mut corrupted = v.clone();
+    corrupted[184+3] ^= 0xFF;
+    assert_eq!(verify_block_start_slice(&corrupted[184..]),Some(false));
+    //a content header decodes, but is not a BlockStart
+    assert_eq!(verify_block_start_slice(&v[40..]),None);
+    //noise
+    let noise:Vec<u8> = (0..64u32).map(|i|(i.wrapping_mul(2654435761) >> 13) as u8).collect();
+    assert_eq!(verify_block_start_slice(&noise),None);
+    //too short
+    assert_eq!(verify_block_start_slice(&v[23..23+HEADER_LEN]),None);
+}
+#[test]
+fn test_read_content_slice() {
+    use docufort::read::read_content_slice;
+    use docufort::write::write_streamed_atomic_block;
+    //a Vec stands in for a writable mmap of the file, read_content_slice only sees the &mut [u8]
+    let clean = generate_test_file().into_inner();
+    let mut map = clean.clone();
+    map[289+3] ^= 0xFF;//A block with ECC content
+    let content = {
+        let mut cursor = Cursor::new(map.clone());
+        cursor.set_position(268);
+        let BlockState::Closed(BlockReadSummary { block: Block::A { middle, .. }, .. }) = try_read_block::<_,DummyInput>(&mut cursor, true, false).unwrap() else {panic!()};
+        middle
+    };
+    let mut out = Vec::new();
+    let (errors,written) = read_content_slice::<_,DummyInput>(&mut map, &mut out, &content).unwrap();
+    assert_eq!((errors,written),(1,A_CONTENT.len()));
+    assert_eq!(out,A_CONTENT);
+    assert_eq!(map,clean,"corrected in place");
+
+    //trailing ECC layout
+    let data:Vec<u8> = (0..2000u32).map(|i|(i % 7) as u8).collect();
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    write_streamed_atomic_block::<_,_,DummyInput>(&mut cursor, &mut &data[..], data.len() as u32, None, None).unwrap();
+    let clean = cursor.into_inner();
+    let mut map = clean.clone();
+    let data_start = 23 + HEADER_LEN + ECC_LEN;
+    map[data_start + 2*(DATA_SIZE+ECC_LEN) + 10] ^= 0x55;
+    let mut cursor = Cursor::new(map.clone());
+    cursor.set_position(23);
+    let BlockState::Closed(BlockReadSummary { block: Block::A { middle:content, .. }, .. }) = try_read_block::<_,DummyInput>(&mut cursor, true, false).unwrap() else {panic!()};
+    let mut out = Vec::new();
+    assert_eq!(read_content_slice::<_,DummyInput>(&mut map, &mut out, &content).unwrap(),(1,data.len()));
+    assert_eq!(out,data);
+    assert_eq!(map,clean);
+
+    //too many errors in one chunk (more noise can land near another codeword and "correct")
+    for i in [0,100,200] {map[data_start + i] ^= 0xFF}
+    let res = read_content_slice::<_,DummyInput>(&mut map, &mut Vec::new(), &content);
+    assert!(matches!(res,Err(ReadWriteError::EccTooManyErrors)),"{:?}",res);
+}
+#[test]
+fn test_content_cow() {
+    use std::borrow::Cow;
+    use docufort::read::content_cow;
+    use docufort::write::write_atomic_block;
+    let data = b"docufort ".repeat(100);
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let plain = write_atomic_block::<_,DummyInput>(&mut cursor, None, &data, false, None, None).unwrap().start_offset;
+    write_magic_number(&mut cursor).unwrap();
+    let packed = write_atomic_block::<_,DummyInput>(&mut cursor, None, &data, false, Some(&3), None).unwrap().start_offset;
+    let file = cursor.get_ref().clone();
+    let content_at = |cursor:&mut Cursor<Vec<u8>>,start|{
+        cursor.set_position(start);
+        let BlockState::Closed(BlockReadSummary { block: Block::A { middle, .. }, .. }) = try_read_block::<_,DummyInput>(cursor, false, false).unwrap() else {panic!()};
+        middle
+    };
+    let content = content_at(&mut cursor, plain);
+    let Cow::Borrowed(bytes) = content_cow::<DummyInput>(&file, &content).unwrap() else {panic!("uncompressed content was copied")};
+    assert_eq!(bytes,data.as_slice());
+    assert!(matches!(content_cow::<DummyInput>(&file[..content.data_start as usize + 10], &content),Err(ReadWriteError::EndOfFile)));
+    let content = content_at(&mut cursor, packed);
+    assert!(content.compressed.is_some());
+    let Cow::Owned(bytes) = content_cow::<DummyInput>(&file, &content).unwrap() else {panic!("compressed content was borrowed")};
+    assert_eq!(bytes,data);
+}
+#[test]
+fn test_logical_size() {
+    use docufort::read::logical_size;
+    use docufort::integrity::integrity_check_file;
+    use docufort::write::write_atomic_block;
+    let mut cursor = generate_test_file();
+    cursor.set_position(0);
+    let check = integrity_check_file::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(logical_size(&mut cursor).unwrap(),check.data_contents);
+
+    //compressed content counts its original length
+    let data = vec![1u8;1000];
+    let mut cursor = Cursor::new(Vec::new());
+    let mut hasher = DummyInput::new();
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let b_block_header = ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_header(&mut cursor, &b_block_header).unwrap();
+    write_content_component(&mut cursor, true, Some(&3), None, &data, &mut hasher).unwrap();
+    write_content_component(&mut cursor, false, None, None, B_CONTENT, &mut hasher).unwrap();
+    let block_end_header = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_block_end(&mut cursor, &block_end_header, &hasher.finalize()).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    write_atomic_block::<_,DummyInput>(&mut cursor, None, &data, false, Some(&3), None).unwrap();
+    cursor.set_position(0);
+    let check = integrity_check_file::<_,DummyInput>(&mut cursor).unwrap();
+    assert!(check.data_size_on_disk < check.data_contents);
+    assert_eq!(check.data_contents,(2*data.len() + B_CONTENT.len()) as u64);
+    assert_eq!(logical_size(&mut cursor).unwrap(),check.data_contents);
+}
+#[test]
+fn test_compressed_flag_mismatch() {
+    use docufort::core::OriginalLen;
+    use docufort::write::write_streamed_atomic_block;
+    //a tag corrupted into a valid header with IS_COMP set, over plain content
+    let mut cursor = generate_test_file();
+    cursor.set_position(268);
+    let BlockState::Closed(BlockReadSummary { block: Block::A { middle:mut content, .. }, .. }) = try_read_block::<_,DummyInput>(&mut cursor, true, false).unwrap() else {panic!()};
+    content.compressed = Some(OriginalLen(A_CONTENT.len() as u32));
+    let res = read_content::<_,_,DummyInput>(&mut cursor, &mut Vec::new(), &content);
+    assert!(matches!(res,Err(ReadWriteError::DataCorruption { data_start }) if data_start == content.data_start),"{:?}",res);
+
+    //trailing ECC layout
+    let data:Vec<u8> = (0..2000u32).map(|i|(i % 7) as u8).collect();
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    write_streamed_atomic_block::<_,_,DummyInput>(&mut cursor, &mut &data[..], data.len() as u32, None, None).unwrap();
+    cursor.set_position(23);
+    let BlockState::Closed(BlockReadSummary { block: Block::A { middle:mut content, .. }, .. }) = try_read_block::<_,DummyInput>(&mut cursor, true, false).unwrap() else {panic!()};
+    content.compressed = Some(OriginalLen(data.len() as u32));
+    let res = read_content::<_,_,DummyInput>(&mut cursor, &mut Vec::new(), &content);
+    assert!(matches!(res,Err(ReadWriteError::DataCorruption { .. })),"{:?}",res);
+}
+#[test]
+fn test_verify_component_in_isolation() {
+    use docufort::read::{read_content_header, check_read_content, verify_component};
+    use docufort::write::write_hashed_content_component;
+    let mut cursor = Cursor::new(Vec::new());
+    let mut hasher = DummyInput::new();
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let b_block_header = ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_header(&mut cursor, &b_block_header).unwrap();
+    let mut starts = Vec::new();
+    for (i,data) in [b"first".as_slice(),b"second",b"third"].into_iter().enumerate() {
+        starts.push(cursor.position());
+        write_hashed_content_component(&mut cursor, i == 1, None, None, None, data, &mut hasher).unwrap();
+    }
+    let block_end_header = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_block_end(&mut cursor, &block_end_header, &hasher.finalize()).unwrap();
+    let summary = find_content::<_,DummyInput,std::ops::RangeFull>(&mut cursor,None,None).unwrap();
+    assert!(summary.iter().all(|(_,c)|c.component_hash.is_some()));
+    let last = summary[2].1;
+    cursor.get_mut()[last.data_start as usize] ^= 0xFF;
+
+    let mut read_at = |start:u64|{
+        cursor.set_position(start);
+        let mut hasher = DummyInput::new();
+        let (_,header) = read_content_header(&mut cursor, false, &mut hasher).unwrap();
+        let (_,_,_,content) = check_read_content(&mut cursor, &header.as_content(), false, &mut hasher).unwrap();
+        verify_component::<_,DummyInput>(&mut cursor, &content).unwrap()
+    };
+    assert!(read_at(starts[1]));
+    assert!(read_at(starts[0]));
+    assert!(!read_at(starts[2]));
+}
+#[test]
+fn test_read_content_prefetched() {
+    use docufort::read::read_content_prefetched;
+    use docufort::write::write_streamed_atomic_block;
+    //compressible, but not so much that it fits in a single chunk
+    let mut x = 0x2545F491u32;
+    let data:Vec<u8> = (0..20_000).map(|i|{x ^= x << 13; x ^= x >> 17; x ^= x << 5; if i % 3 == 0 {(x % 16) as u8}else{b'a'}}).collect();
+    let mut cursor = Cursor::new(Vec::new());
+    let mut hasher = DummyInput::new();
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let b_block_header = ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_header(&mut cursor, &b_block_header).unwrap();
+    write_content_component(&mut cursor, true, Some(&3), None, &data, &mut hasher).unwrap();
+    write_content_component(&mut cursor, false, None, None, &data, &mut hasher).unwrap();
+    let block_end_header = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_block_end(&mut cursor, &block_end_header, &hasher.finalize()).unwrap();
+    //trailing ECC layout
+    write_magic_number(&mut cursor).unwrap();
+    write_streamed_atomic_block::<_,_,DummyInput>(&mut cursor, &mut &data[..], data.len() as u32, None, None).unwrap();
+
+    let summary = find_content::<_,DummyInput,std::ops::RangeFull>(&mut cursor,None,None).unwrap();
+    assert_eq!(summary.len(),3);
+    let compressed = &summary[0].1;
+    assert!(compressed.compressed.is_some() && compressed.data_len.0 as usize > 3*DATA_SIZE);
+    for (_,c) in summary.iter() {
+        let mut expected = Vec::new();
+        read_content::<_,_,DummyInput>(&mut cursor, &mut expected, c).unwrap();
+        assert_eq!(expected,data);
+        for chunk in [0,1,7,DATA_SIZE,4096,1 << 20] {
+            let mut out = Vec::new();
+            let written = read_content_prefetched::<_,_,DummyInput>(&mut cursor, &mut out, c, chunk).unwrap();
+            assert_eq!(written,data.len());
+            assert_eq!(out,expected,"chunk {}",chunk);
+        }
+    }
+}
+#[test]
+fn test_block_hash_cache() {
+    use docufort::read::{BlockHashCache, verify_block_hash};
+    use docufort::write::{write_cached, write_atomic_block};
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    let mut cache = BlockHashCache::new(2);
+    let mut starts = Vec::new();
+    for content in [b"one".as_slice(),b"two",b"three"] {
+        starts.push(cursor.position() + MN_ECC_LEN as u64);
+        write_cached(&mut cursor, &mut cache, |f|{
+            write_magic_number(f)?;
+            write_atomic_block::<_,DummyInput>(f, None, content, false, None, None)
+        }).unwrap();
+    }
+    assert_eq!(cache.len(),2);
+    let stored_hash = |cursor:&mut Cursor<Vec<u8>>,start:u64|{
+        cursor.set_position(start);
+        let BlockState::Closed(BlockReadSummary { hash_as_read, .. }) = try_read_block::<_,DummyInput>(cursor, false, false).unwrap() else {panic!()};
+        hash_as_read
+    };
+    let expected:Vec<_> = starts.iter().map(|s|stored_hash(&mut cursor,*s)).collect();
+
+    //the last block is cached, so tampering with it goes unnoticed unless re-reading
+    let third_data = starts[2] as usize + HEADER_LEN + ECC_LEN;
+    cursor.get_mut()[third_data] ^= 0xFF;
+    assert_eq!(verify_block_hash::<_,DummyInput>(&mut cursor, starts[2], &mut cache).unwrap(),Some(expected[2]));
+    cache.reread_on_hit = true;
+    assert_eq!(verify_block_hash::<_,DummyInput>(&mut cursor, starts[2], &mut cache).unwrap(),None);
+    cache.reread_on_hit = false;
+
+    //the first was evicted, a miss reads it and caches it
+    assert_eq!(cache.get(starts[0]),None);
+    assert_eq!(verify_block_hash::<_,DummyInput>(&mut cursor, starts[0], &mut cache).unwrap(),Some(expected[0]));
+    assert_eq!(cache.get(starts[0]),Some(expected[0]));
+}
+#[test]
+fn test_content_chunks() {
+    use docufort::read::content_chunks;
+    use docufort::write::{write_atomic_block, write_streamed_atomic_block};
+    let data:Vec<u8> = (0..3000u32).map(|i|(i % 251) as u8).collect();
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    write_atomic_block::<_,DummyInput>(&mut cursor, None, &data, true, None, None).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    write_atomic_block::<_,DummyInput>(&mut cursor, None, &data, false, Some(&3), None).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    write_streamed_atomic_block::<_,_,DummyInput>(&mut cursor, &mut &data[..], data.len() as u32, None, None).unwrap();
+
+    let summary = find_content::<_,DummyInput,std::ops::RangeFull>(&mut cursor,None,None).unwrap();
+    assert_eq!(summary.len(),3);
+    assert!(summary[1].1.compressed.is_some() && summary[2].1.trailing_ecc);
+    for (_,c) in summary.iter() {
+        let chunks:Vec<_> = content_chunks::<_,DummyInput>(&mut cursor, c, 1000 - 1).collect::<Result<_,_>>().unwrap();
+        assert_eq!(chunks.iter().map(|(o,_)|*o).collect::<Vec<_>>(),vec![0,999,1998,2997]);
+        assert_eq!(chunks.last().unwrap().1.len(),3);
+        let joined:Vec<u8> = chunks.into_iter().flat_map(|(_,c)|c).collect();
+        assert_eq!(joined,data);
+    }
+}
+#[test]
+fn test_all_content() {
+    let mut cursor = generate_test_file();
+    let all = docufort::read::all_content::<_,DummyInput>(&mut cursor).unwrap();
+    //3 in the B block, then 1 in each A block
+    assert_eq!(all.len(), 5);
+    let coords:Vec<(usize,usize,u64)> = all.iter().map(|g|(g.block_index,g.component_index,g.content.data_start)).collect();
+    assert_eq!(coords, vec![(0,0,57),(0,1,90),(0,2,119),(1,0,201),(2,0,289)]);
+    for (g,expected) in all.iter().zip([&B_CONTENT[..],B_CONTENT,B_CONTENT,A_CONTENT,A_CONTENT]) {
+        assert!(g.timestamp > 0);
+        let mut buf = Vec::new();
+        read_content::<_,_,DummyInput>(&mut cursor, &mut buf, &g.content).unwrap();
+        assert_eq!(buf, expected);
+    }
+}
+#[test]
+fn test_decompress_policy() {
+    use docufort::read::{read_content_with_policy, DecompressPolicy, DecodedContent};
+    use docufort::write::write_atomic_block;
+    let data:Vec<u8> = (0..4000u32).map(|i|(i % 13) as u8).collect();
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    write_atomic_block::<_,DummyInput>(&mut cursor, None, &data, false, Some(&3), None).unwrap();
+    cursor.set_position(23);
+    let BlockState::Closed(BlockReadSummary { block: Block::A { middle:content, .. }, .. }) = try_read_block::<_,DummyInput>(&mut cursor, false, false).unwrap() else {panic!()};
+    assert!(content.compressed.is_some());
+    let mut sink = Vec::new();
+    assert_eq!(read_content_with_policy::<_,_,DummyInput>(&mut cursor, &mut sink, &content, DecompressPolicy::Raw).unwrap(), DecodedContent::Complete(data.len()));
+    assert_eq!(sink, data);
+    //no ECC, so nothing stands between the corruption and the decompressor. Skip the length prefix, zstd magic and frame header.
+    for b in &mut cursor.get_mut()[content.data_start as usize + 12..][..4] {*b ^= 0xFF}
+    let corrupt = cursor.get_ref()[content.data_start as usize..][..content.data_len.0 as usize].to_vec();
+
+    let res = read_content::<_,_,DummyInput>(&mut cursor, &mut Vec::new(), &content);
+    assert!(matches!(res,Err(ReadWriteError::DecompressionFailed { data_start }) if data_start == content.data_start),"{:?}",res);
+    let res = read_content_with_policy::<_,_,DummyInput>(&mut cursor, &mut Vec::new(), &content, DecompressPolicy::default());
+    assert!(matches!(res,Err(ReadWriteError::DecompressionFailed { .. })),"{:?}",res);
+
+    let mut sink = Vec::new();
+    let res = read_content_with_policy::<_,_,DummyInput>(&mut cursor, &mut sink, &content, DecompressPolicy::Raw).unwrap();
+    assert_eq!(res, DecodedContent::Raw(corrupt.len()));
+    assert_eq!(sink, corrupt);
+
+    let mut sink = Vec::new();
+    let res = read_content_with_policy::<_,_,DummyInput>(&mut cursor, &mut sink, &content, DecompressPolicy::Partial).unwrap();
+    assert!(matches!(res,DecodedContent::Partial(n) if n == sink.len() && n < data.len()),"{:?}",res);
+    assert_eq!(&sink[..], &data[..sink.len()]);
+}
+#[test]
+fn test_content_directory() {
+    use docufort::core::{ContentDirEntry, StoredLen};
+    use docufort::read::{read_content_header, check_read_content};
+    use docufort::write::{write_typed_content_component, write_content_directory};
+    use std::io::Seek;
+    let payloads:[&[u8];3] = [b"first", b"the second one", b"third"];
+    let mut cursor = Cursor::new(Vec::new());
+    let mut hasher = DummyInput::new();
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let block_start = cursor.position();
+    write_header(&mut cursor, &ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None)).unwrap();
+    let mut entries = Vec::new();
+    for (i,p) in payloads.iter().enumerate() {
+        let offset = cursor.position() - block_start;
+        let (len,_) = write_typed_content_component(&mut cursor, i == 1, None, None, Some(i as u16), p, &mut hasher).unwrap();
+        entries.push(ContentDirEntry{ offset, len: StoredLen(len as u32), content_type: Some(i as u16) });
+    }
+    write_content_directory(&mut cursor, &entries, None, &mut hasher).unwrap();
+    let end = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_block_end(&mut cursor, &end, &hasher.finalize()).unwrap();
+
+    cursor.set_position(block_start);
+    let BlockState::Closed(brs) = try_read_block::<_,DummyInput>(&mut cursor, true, true).unwrap() else {panic!("expected a closed block")};
+    assert!(brs.corrupted_content_blocks.is_empty());
+    let dir = brs.directory.clone().unwrap();
+    assert_eq!(dir, entries);
+    //the directory is the last component of the block
+    let Block::B { middle, .. } = &brs.block else {panic!()};
+    assert_eq!(middle.len(), 4);
+    assert_eq!(middle[3].1.content_type, Some(CONTENT_DIRECTORY_TYPE));
+
+    //jump straight to the second component
+    cursor.seek(std::io::SeekFrom::Start(block_start + dir[1].offset)).unwrap();
+    let (_,header) = read_content_header(&mut cursor, true, &mut DummyInput::new()).unwrap();
+    let (_,_,_,content) = check_read_content(&mut cursor, &header.as_content(), true, &mut DummyInput::new()).unwrap();
+    assert_eq!(content.data_len, dir[1].len);
+    let mut buf = Vec::new();
+    read_content::<_,_,DummyInput>(&mut cursor, &mut buf, &content).unwrap();
+    assert_eq!(buf, payloads[1]);
+
+    //blocks without one have none
+    let mut cursor = generate_test_file();
+    cursor.set_position(23);
+    let BlockState::Closed(brs) = try_read_block::<_,DummyInput>(&mut cursor, false, false).unwrap() else {panic!()};
+    assert_eq!(brs.directory, None);
+}
+#[test]
+fn test_file_generation() {
+    use docufort::read::file_generation;
+    use docufort::write::{bump_generation, write_atomic_block};
+    use docufort::integrity::integrity_check_file;
+    let mut cursor = generate_test_file();
+    assert_eq!(file_generation::<_,DummyInput>(&mut cursor).unwrap(), 0);
+    assert_eq!(bump_generation::<_,DummyInput>(&mut cursor).unwrap(), 1);
+    let mut stale = cursor.clone();
+    //append more, then reopen
+    write_magic_number(&mut cursor).unwrap();
+    write_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, false, None, None).unwrap();
+    assert_eq!(bump_generation::<_,DummyInput>(&mut cursor).unwrap(), 2);
+    assert_eq!(file_generation::<_,DummyInput>(&mut cursor).unwrap(), 2);
+    assert_eq!(file_generation::<_,DummyInput>(&mut stale).unwrap(), 1);
+    cursor.set_position(0);
+    let check = integrity_check_file::<_,DummyInput>(&mut cursor).unwrap();
+    assert!(check.corrupted_segments.is_empty());
+    assert_eq!(check.file_len_checked, cursor.get_ref().len() as u64);
+}
+#[test]
+fn test_read_content_tail() {
+    use docufort::read::read_content_tail;
+    use docufort::write::{write_atomic_block, write_streamed_atomic_block};
+    let read_a = |cursor:&mut Cursor<Vec<u8>>,start:u64|{
+        cursor.set_position(start);
+        let BlockState::Closed(BlockReadSummary { block: Block::A { middle, .. }, .. }) = try_read_block::<_,DummyInput>(cursor, false, false).unwrap() else {panic!()};
+        middle
+    };
+    //ECC'd atomic block of the test file
+    let mut cursor = generate_test_file();
+    let content = read_a(&mut cursor, 268);
+    assert!(content.ecc);
+    let mut tail = Vec::new();
+    assert_eq!(read_content_tail::<_,_,DummyInput>(&mut cursor, &mut tail, &content, 4).unwrap(), 4);
+    assert_eq!(tail, &A_CONTENT[A_CONTENT.len()-4..]);
+    //more than there is, and no ECC
+    let content = read_a(&mut cursor, 184);
+    let mut tail = Vec::new();
+    assert_eq!(read_content_tail::<_,_,DummyInput>(&mut cursor, &mut tail, &content, 100).unwrap(), A_CONTENT.len());
+    assert_eq!(tail, A_CONTENT);
+
+    //spanning two ECC chunks, with an error in the last, in both ECC layouts
+    let data:Vec<u8> = (0..1000u32).map(|i|(i % 251) as u8).collect();
+    for streamed in [false,true] {
+        let mut cursor = Cursor::new(Vec::new());
+        init_file(&mut cursor).unwrap();
+        write_magic_number(&mut cursor).unwrap();
+        if streamed {
+            write_streamed_atomic_block::<_,_,DummyInput>(&mut cursor, &mut &data[..], data.len() as u32, None, None).unwrap();
+        }else{
+            write_atomic_block::<_,DummyInput>(&mut cursor, None, &data, true, None, None).unwrap();
+        }
+        let content = read_a(&mut cursor, 23);
+        let last_byte = if streamed {content.data_start as usize + data.len() + 3*ECC_LEN - 1}else{content.data_start as usize + data.len() - 1};
+        cursor.get_mut()[last_byte] ^= 0xFF;
+        let mut tail = Vec::new();
+        assert_eq!(read_content_tail::<_,_,DummyInput>(&mut cursor, &mut tail, &content, 300).unwrap(), 300);
+        assert_eq!(tail, &data[700..], "streamed: {}", streamed);
+        //corrected in memory only
+        assert_ne!(cursor.get_ref()[last_byte], data[999]);
+    }
+    //streamed content with more ECC bytes than the file has in front of the data
+    let data:Vec<u8> = (0..40_000u32).map(|i|(i % 251) as u8).collect();
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    write_streamed_atomic_block::<_,_,DummyInput>(&mut cursor, &mut &data[..], data.len() as u32, None, None).unwrap();
+    let content = read_a(&mut cursor, 23);
+    assert!(content.trailing_ecc && docufort::ecc::calc_ecc_data_len(data.len()) as u64 > content.data_start);
+    let mut tail = Vec::new();
+    assert_eq!(read_content_tail::<_,_,DummyInput>(&mut cursor, &mut tail, &content, 300).unwrap(), 300);
+    assert_eq!(tail, &data[data.len()-300..]);
+
+    //compressed content is refused
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    write_atomic_block::<_,DummyInput>(&mut cursor, None, &data, false, Some(&3), None).unwrap();
+    let content = read_a(&mut cursor, 23);
+    let res = read_content_tail::<_,_,DummyInput>(&mut cursor, &mut Vec::new(), &content, 4);
+    assert!(matches!(res,Err(ReadWriteError::Io(ref e)) if e.kind() == std::io::ErrorKind::InvalidInput),"{:?}",res);
+}
+#[test]
+fn test_block_hash_matches_read_back() {
+    use docufort::read::hash_range;
+    use docufort::write::{write_typed_content_component, write_hashed_content_component};
+    let mut cursor = Cursor::new(Vec::new());
+    let mut hasher = DummyInput::new();
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let block_start = cursor.position();
+    let start = ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_header(&mut cursor, &start).unwrap();
+    //multi chunk ECC, a partial last chunk, no ECC, and the optional header fields
+    write_content_component(&mut cursor, true, None, None, &vec![1;DATA_SIZE*2+7], &mut hasher).unwrap();
+    write_content_component(&mut cursor, false, None, None, &vec![2;300], &mut hasher).unwrap();
+    write_typed_content_component(&mut cursor, true, None, Some(3), Some(9), &[3;40], &mut hasher).unwrap();
+    write_hashed_content_component(&mut cursor, true, None, None, None, &[4;DATA_SIZE], &mut hasher).unwrap();
+    let end_start = cursor.position();
+    let written = hasher.finalize();
+    let end = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_block_end(&mut cursor, &end, &written).unwrap();
+
+    let mut rehash = DummyInput::new();
+    hash_range(&mut cursor, block_start + (HEADER_LEN + ECC_LEN) as u64, end_start, &mut rehash).unwrap();
+    assert_eq!(rehash.finalize(),written);
+    for error_correct in [false,true] {
+        cursor.set_position(block_start);
+        let BlockState::Closed(BlockReadSummary { block: Block::B { middle, end, .. }, hash_as_read, .. }) = try_read_block::<_,DummyInput>(&mut cursor, error_correct, false).unwrap() else {panic!("expected a closed block")};
+        assert_eq!(middle.len(),4);
+        assert_eq!(hash_as_read,written);
+        assert_eq!(end.hash.hash(),&written[..]);
+    }
+}
+#[test]
+fn test_seekable_content_reader() {
+    use docufort::read::SeekableContentReader;
+    use docufort::write::write_seekable_content_component;
+    use std::io::{Read, Seek, SeekFrom};
+    let data:Vec<u8> = (0..10_000u32).map(|i|(i % 251) as u8).collect();
+    let mut cursor = Cursor::new(Vec::new());
+    let mut hasher = DummyInput::new();
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let block_start = cursor.position();
+    let start = ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_header(&mut cursor, &start).unwrap();
+    let (stored,compressed) = write_seekable_content_component(&mut cursor, true, &3, 1024, None, None, &data, &mut hasher).unwrap();
+    assert!(compressed && stored < data.len());
+    write_content_component(&mut cursor, false, None, None, B_CONTENT, &mut hasher).unwrap();
+    let end = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_block_end(&mut cursor, &end, &hasher.finalize()).unwrap();
+
+    cursor.set_position(block_start);
+    let BlockState::Closed(BlockReadSummary { block: Block::B { middle, .. }, .. }) = try_read_block::<_,DummyInput>(&mut cursor, true, false).unwrap() else {panic!("expected a closed block")};
+    let (seekable,plain) = (middle[0].1,middle[1].1);
+    assert!(seekable.seekable && seekable.compressed.is_some());
+    let mut whole = Vec::new();
+    assert_eq!(read_content::<_,_,DummyInput>(&mut cursor, &mut whole, &seekable).unwrap(), data.len());
+    assert_eq!(whole, data);
+
+    let mut reader = SeekableContentReader::<_,DummyInput>::new(&mut cursor, &seekable).unwrap();
+    assert_eq!(reader.len(), data.len() as u64);
+    //straddles the boundary between the 5th and 6th frames
+    reader.seek(SeekFrom::Start(5000)).unwrap();
+    let mut slice = vec![0u8;300];
+    reader.read_exact(&mut slice).unwrap();
+    assert_eq!(slice, &data[5000..5300]);
+    assert_eq!(reader.seek(SeekFrom::Current(-300)).unwrap(), 5000);
+    reader.read_exact(&mut slice).unwrap();
+    assert_eq!(slice, &data[5000..5300]);
+    reader.seek(SeekFrom::End(-10)).unwrap();
+    let mut tail = Vec::new();
+    reader.read_to_end(&mut tail).unwrap();
+    assert_eq!(tail, &data[data.len()-10..]);
+    assert!(reader.seek(SeekFrom::Current(-20_000)).is_err());
+
+    let res = SeekableContentReader::<_,DummyInput>::new(&mut cursor, &plain);
+    assert!(matches!(res,Err(ReadWriteError::Io(ref e)) if e.kind() == std::io::ErrorKind::InvalidInput));
+}
+#[test]
+fn test_load_content_decoded() {
+    use docufort::read::load_content_decoded;
+    //compressible, but not so much that it fits in a single ECC chunk
+    let mut seed = 0x2545_f491u32;
+    let data:Vec<u8> = (0..20_000).map(|i|if i % 4 == 0 {seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345); (seed >> 24) as u8}else{b'a'}).collect();
+    let mut cursor = Cursor::new(Vec::new());
+    let mut hasher = DummyInput::new();
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let block_start = cursor.position();
+    let start = ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_header(&mut cursor, &start).unwrap();
+    let (stored,compressed) = write_content_component(&mut cursor, true, Some(&3), None, &data, &mut hasher).unwrap();
+    assert!(compressed && stored < data.len() && stored > 2*DATA_SIZE);
+    let end = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_block_end(&mut cursor, &end, &hasher.finalize()).unwrap();
+
+    cursor.set_position(block_start);
+    let BlockState::Closed(BlockReadSummary { block: Block::B { middle, .. }, .. }) = try_read_block::<_,DummyInput>(&mut cursor, false, false).unwrap() else {panic!("expected a closed block")};
+    let content = middle[0].1;
+    assert!(content.ecc && content.compressed.is_some());
+
+    //one error in the second chunk of the compressed bytes
+    let pos = content.data_start as usize + DATA_SIZE + 7;
+    cursor.get_mut()[pos] ^= 0xFF;
+    let corrupted = cursor.get_ref()[pos];
+    let mut out = Vec::new();
+    assert_eq!(load_content_decoded::<_,_,DummyInput>(&mut cursor, &mut out, &content).unwrap(), (1, data.len()));
+    assert_eq!(out, data);
+    //corrected in memory only
+    assert_eq!(cursor.get_ref()[pos], corrupted);
+    let mut out = Vec::new();
+    assert_eq!(load_content_decoded::<_,_,DummyInput>(&mut cursor, &mut out, &content).unwrap(), (1, data.len()));
+
+    //too many errors in one chunk (more noise can land near another codeword and "correct")
+    let chunk_start = content.data_start as usize;
+    for i in [0,100,200] {cursor.get_mut()[chunk_start + i] ^= 0xFF}
+    let res = load_content_decoded::<_,_,DummyInput>(&mut cursor, &mut Vec::new(), &content);
+    assert!(matches!(res,Err(ReadWriteError::EccTooManyErrors)),"{:?}",res);
+}
+#[test]
+fn test_lazy_content_reader() {
+    use docufort::read::LazyContentReader;
+    use docufort::write::write_atomic_block;
+    use docufort::core::OriginalLen;
+    use std::io::Read;
+    let data:Vec<u8> = (0..DATA_SIZE*4 + 9).map(|i|(i % 251) as u8).collect();
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let written = write_atomic_block::<_,DummyInput>(&mut cursor, None, &data, true, None, None).unwrap();
+    cursor.set_position(written.start_offset);
+    let BlockState::Closed(BlockReadSummary { block: Block::A { middle, .. }, .. }) = try_read_block::<_,DummyInput>(&mut cursor, false, false).unwrap() else {panic!("expected a closed block")};
+    //an error in the first and the last chunk
+    cursor.get_mut()[middle.data_start as usize + 3] ^= 0xFF;
+    cursor.get_mut()[middle.data_start as usize + data.len() - 1] ^= 0xFF;
+
+    //stopping after the first chunk never touches the others
+    let mut reader = LazyContentReader::<_,DummyInput>::new(&mut cursor, &middle).unwrap();
+    let mut head = [0u8;DATA_SIZE];
+    reader.read_exact(&mut head).unwrap();
+    assert_eq!(head[..],data[..DATA_SIZE]);
+    assert_eq!((reader.chunks_checked(),reader.errors_corrected()),(1,1));
+
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest[..],data[DATA_SIZE..]);
+    assert_eq!((reader.chunks_checked(),reader.errors_corrected()),(5,2));
+
+    //compressed content has to be decompressed as a whole
+    let mut compressed = middle;
+    compressed.compressed = Some(OriginalLen(1));
+    assert!(LazyContentReader::<_,DummyInput>::new(&mut cursor, &compressed).is_err());
+}
+#[test]
+fn test_ecc_chunk_boundaries() {
+    for len in [DATA_SIZE, 2*DATA_SIZE, 2*DATA_SIZE+1] {
+        let data:Vec<u8> = (0..len).map(|i|(i % 253) as u8).collect();
+        let mut cursor = Cursor::new(Vec::new());
+        let mut hasher = DummyInput::new();
+        init_file(&mut cursor).unwrap();
+        write_magic_number(&mut cursor).unwrap();
+        let block_start = cursor.position();
+        let start = ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+        write_header(&mut cursor, &start).unwrap();
+        assert_eq!(write_content_component(&mut cursor, true, None, None, &data, &mut hasher).unwrap(), (len,false));
+        let end = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+        write_block_end(&mut cursor, &end, &hasher.finalize()).unwrap();
+
+        cursor.set_position(block_start);
+        let BlockState::Closed(BlockReadSummary { block: Block::B { middle, .. }, errors_corrected, corrupted_content_blocks, .. }) = try_read_block::<_,DummyInput>(&mut cursor, true, true).unwrap() else {panic!("expected a closed block")};
+        assert_eq!((errors_corrected,corrupted_content_blocks.len()),(0,0),"len {len}");
+        let content = middle[0].1;
+        //the last byte of the last chunk
+        let last = (content.data_start + len as u64 - 1) as usize;
+        cursor.get_mut()[last] ^= 0xFF;
+        cursor.set_position(block_start);
+        let BlockState::Closed(BlockReadSummary { errors_corrected, corrupted_content_blocks, .. }) = try_read_block::<_,DummyInput>(&mut cursor, true, true).unwrap() else {panic!("expected a closed block")};
+        assert_eq!((errors_corrected,corrupted_content_blocks.len()),(1,0),"len {len}");
+        let mut out = Vec::new();
+        assert_eq!(read_content::<_,_,DummyInput>(&mut cursor, &mut out, &content).unwrap(), len);
+        assert_eq!(out, data, "len {len}");
+    }
+}
+#[test]
+fn test_logical_hash_ignores_compression_level() {
+    use docufort::write::write_logical_hash_atomic_block;
+    //repetitive, but not so simple that every level finds the same matches
+    let mut x = 1u32;
+    let data:Vec<u8> = (0..DATA_SIZE*64).map(|_|{x = x.wrapping_mul(1103515245).wrapping_add(12345);b"docufort "[(x >> 16) as usize % 9]}).collect();
+    let mut stored = Vec::new();
+    let mut hashes = Vec::new();
+    for level in [1,19] {
+        let mut cursor = Cursor::new(Vec::new());
+        init_file(&mut cursor).unwrap();
+        write_magic_number(&mut cursor).unwrap();
+        let written = write_logical_hash_atomic_block::<_,DummyInput>(&mut cursor, Some(1), &data, true, Some(&level), None).unwrap();
+        cursor.set_position(written.start_offset);
+        let BlockState::Closed(BlockReadSummary { block: Block::A { middle, end, .. }, hash_as_read, .. }) = try_read_block::<_,DummyInput>(&mut cursor, true, true).unwrap() else {panic!("level {level}")};
+        assert!(middle.compressed.is_some() && end.header.is_logical_hash());
+        assert_eq!(hash_as_read,written.hash);
+        let mut content = Vec::new();
+        read_content::<_,_,DummyInput>(&mut cursor, &mut content, &middle).unwrap();
+        assert_eq!(content,data);
+        stored.push(content_fingerprint::<_,DummyInput>(&mut cursor, &middle).unwrap());
+        hashes.push(written.hash);
+    }
+    assert_ne!(stored[0],stored[1]);
+    assert_eq!(hashes[0],hashes[1]);
+    let mut hasher = DummyInput::new();
+    hasher.update(&data);
+    assert_eq!(hashes[0],hasher.finalize());
+
+    //the hash is still checked, against the decompressed content
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let written = write_logical_hash_atomic_block::<_,DummyInput>(&mut cursor, None, &data, false, Some(&3), None).unwrap();
+    let end = cursor.get_ref().len() - (HEADER_LEN + ECC_LEN + HASH_AND_ECC_LEN) - 1;
+    cursor.get_mut()[end] ^= 0xFF;
+    cursor.set_position(written.start_offset);
+    let BlockState::Closed(BlockReadSummary { hash_as_read, .. }) = try_read_block::<_,DummyInput>(&mut cursor, true, false).unwrap() else {panic!("expected a closed block")};
+    assert_ne!(hash_as_read,written.hash);
+}
+#[test]
+fn test_logical_hash_block_end_is_a_block_end() {
+    use docufort::read::{locate_offset, ends_with_block_end, read_prev_hash, recorded_block_end};
+    use docufort::write::{write_logical_hash_atomic_block, write_atomic_block, write_aligned_magic_number, write_block_end_with_body_len};
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let first = write_logical_hash_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, true, None, None).unwrap();
+    assert!(ends_with_block_end(&mut cursor).unwrap());
+    write_magic_number(&mut cursor).unwrap();
+    let second = write_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, false, None, None).unwrap();
+    //walks past the LOGICAL_HASH BlockEnd into the next block
+    let loc = locate_offset(&mut cursor, first.end_offset - 1).unwrap();
+    assert_eq!((loc.block_start,loc.component_tag),(first.start_offset,ComponentTag::Hash));
+    let loc = locate_offset(&mut cursor, second.start_offset + (HEADER_LEN + ECC_LEN) as u64 + 2).unwrap();
+    assert_eq!(loc,OffsetLocation{ block_start: second.start_offset, component_tag: ComponentTag::StartHeader, within_content: true, content_relative_offset: 2 });
+
+    //alignment padding after a LOGICAL_HASH BlockEnd is stepped back over to find its hash
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let first = write_logical_hash_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, true, None, None).unwrap();
+    write_aligned_magic_number(&mut cursor, Some(512)).unwrap();
+    let second = write_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, false, None, None).unwrap();
+    assert_eq!(second.start_offset,(512 + MN_ECC_LEN) as u64);
+    assert_eq!(read_prev_hash(&mut cursor, second.start_offset).unwrap(),Some(first.hash));
+
+    //LOGICAL_HASH is only written on A blocks, so check the B only recorded_block_end with the other BlockEnd flag
+    let mut cursor = Cursor::new(Vec::new());
+    let mut hasher = DummyInput::new();
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let block_start = cursor.position();
+    let start = ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_header(&mut cursor, &start).unwrap();
+    write_content_component(&mut cursor, true, None, None, B_CONTENT, &mut hasher).unwrap();
+    let end_start = cursor.position();
+    hasher.update(&block_start.to_be_bytes());
+    let end = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8 | OFFSET_BOUND, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_block_end_with_body_len(&mut cursor, block_start, &start, &end, &hasher.finalize()).unwrap();
+    assert_eq!(recorded_block_end(&mut cursor, block_start).unwrap(),Some(end_start));
+}
+#[test]
+fn test_validate_content_ecc() {
+    use docufort::write::{validate_content_ecc, EccPlan, write_atomic_block};
+    assert_eq!(validate_content_ecc(0).unwrap(),EccPlan{ num_chunks: 0, total_ecc_bytes: 0, total_on_disk: 0 });
+    assert_eq!(validate_content_ecc(1).unwrap(),EccPlan{ num_chunks: 1, total_ecc_bytes: ECC_LEN, total_on_disk: 1 + ECC_LEN as u64 });
+    assert_eq!(validate_content_ecc(DATA_SIZE).unwrap().num_chunks,1);
+    assert_eq!(validate_content_ecc(DATA_SIZE+1).unwrap().num_chunks,2);
+    #[cfg(feature = "ecc_len_2")]
+    assert_eq!(validate_content_ecc(1000).unwrap(),EccPlan{ num_chunks: 4, total_ecc_bytes: 8, total_on_disk: 1008 });
+    #[cfg(feature = "ecc_len_4")]
+    assert_eq!(validate_content_ecc(1000).unwrap(),EccPlan{ num_chunks: 4, total_ecc_bytes: 16, total_on_disk: 1016 });
+    #[cfg(feature = "ecc_len_32")]
+    assert_eq!(validate_content_ecc(1000).unwrap(),EccPlan{ num_chunks: 5, total_ecc_bytes: 160, total_on_disk: 1160 });
+    let big = validate_content_ecc(u32::MAX as usize).unwrap();
+    assert_eq!(big.num_chunks,(u32::MAX as usize).div_ceil(DATA_SIZE));
+    assert_eq!(big.total_on_disk,u32::MAX as u64 + (big.num_chunks * ECC_LEN) as u64);
+    #[cfg(target_pointer_width = "64")]
+    {
+        let Err(ReadWriteError::Io(e)) = validate_content_ecc(u32::MAX as usize + 1) else {panic!("accepted content too long for a header")};
+        assert_eq!(e.kind(),std::io::ErrorKind::InvalidInput);
+    }
+
+    //the plan matches what an A block actually stores
+    for len in [1,DATA_SIZE,5000] {
+        let mut cursor = Cursor::new(Vec::new());
+        init_file(&mut cursor).unwrap();
+        write_magic_number(&mut cursor).unwrap();
+        let with_ecc = write_atomic_block::<_,DummyInput>(&mut cursor, None, &vec![9;len], true, None, None).unwrap();
+        write_magic_number(&mut cursor).unwrap();
+        let without = write_atomic_block::<_,DummyInput>(&mut cursor, None, &vec![9;len], false, None, None).unwrap();
+        let plan = validate_content_ecc(len).unwrap();
+        assert_eq!(with_ecc.end_offset - with_ecc.start_offset,without.end_offset - without.start_offset + plan.total_ecc_bytes as u64);
+    }
+}
+#[test]
+fn test_verify_atomic_block() {
+    use docufort::read::verify_atomic_block;
+    use docufort::HEADER_LEN;
+    let mut cursor = generate_test_file();
+    //the first A block has no content ECC
+    assert!(verify_atomic_block::<_,DummyInput>(&mut cursor, 184).unwrap());
+    assert!(verify_atomic_block::<_,DummyInput>(&mut cursor, 268).unwrap());
+    let Err(ReadWriteError::Io(e)) = verify_atomic_block::<_,DummyInput>(&mut cursor, 23) else {panic!("verified a B block")};
+    assert_eq!(e.kind(),std::io::ErrorKind::InvalidInput);
+
+    let clean = cursor.get_ref().clone();
+    cursor.get_mut()[184 + HEADER_LEN + ECC_LEN + 2] ^= 1;
+    assert!(!verify_atomic_block::<_,DummyInput>(&mut cursor, 184).unwrap());
+    //nothing was written back, or to the later block
+    let mut expected = clean.clone();
+    expected[184 + HEADER_LEN + ECC_LEN + 2] ^= 1;
+    assert_eq!(cursor.get_ref(),&expected);
+    assert!(verify_atomic_block::<_,DummyInput>(&mut cursor, 268).unwrap());
+}
+#[test]
+fn test_hash_adapter_partial_reads() {
+    use std::io::Read;
+    ///Returns at most 3 bytes per read.
+    struct Trickle<'a>(&'a [u8]);
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(3).min(self.0.len());
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+    let data:Vec<u8> = (0..1000u32).map(|i|(i % 251) as u8).collect();
+    let mut one_shot = DummyInput::new();
+    one_shot.update(&data);
+
+    let mut hasher = DummyInput::new();
+    let mut source = Trickle(&data);
+    let mut adapter = HashAdapter::new(&mut source, &mut hasher);
+    let mut out = Vec::new();
+    let mut chunk = [0u8;7];
+    loop {
+        let n = adapter.read(&mut chunk).unwrap();
+        if n == 0 {break}
+        out.extend_from_slice(&chunk[..n]);
+    }
+    assert_eq!(out,data);
+    assert_eq!(hasher.finalize(),one_shot.finalize());
+}
+#[test]
+fn test_extract_block_content() {
+    use docufort::read::extract_block_content;
+    let mut cursor = generate_test_file();
+    //the third block is the last A block
+    let mut out = Vec::new();
+    assert_eq!(extract_block_content::<_,_,DummyInput>(&mut cursor, 2, &mut out).unwrap(),A_CONTENT.len() as u64);
+    assert_eq!(out,A_CONTENT);
+    //a B block's components are concatenated
+    let mut out = Vec::new();
+    extract_block_content::<_,_,DummyInput>(&mut cursor, 0, &mut out).unwrap();
+    assert_eq!(out,[&B_CONTENT[..],B_CONTENT,B_CONTENT].concat());
+    let Err(ReadWriteError::Io(e)) = extract_block_content::<_,_,DummyInput>(&mut cursor, 3, &mut Vec::new()) else {panic!("extracted a missing block")};
+    assert_eq!(e.kind(),std::io::ErrorKind::NotFound);
+
+    //ECC fixes the copy, not the file
+    let clean = cursor.get_ref().clone();
+    cursor.get_mut()[268 + HEADER_LEN + ECC_LEN + ECC_LEN + 1] ^= 0x10;
+    let damaged = cursor.get_ref().clone();
+    let mut out = Vec::new();
+    extract_block_content::<_,_,DummyInput>(&mut cursor, 2, &mut out).unwrap();
+    assert_eq!(out,A_CONTENT);
+    assert_eq!(cursor.get_ref(),&damaged);
+    assert_ne!(&damaged,&clean);
+    //the first A block has no ECC, so damage there is a mismatch
+    cursor.get_mut()[184 + HEADER_LEN + ECC_LEN] ^= 0x10;
+    assert!(matches!(extract_block_content::<_,_,DummyInput>(&mut cursor, 1, &mut Vec::new()),Err(ReadWriteError::HashMismatch { block_start: 184 })));
+}
+#[test]
+fn test_timestamp_systemtime() {
+    use docufort::util::{systemtime_to_micros, micros_to_systemtime, microseconds_since_epoch};
+    use docufort::write::write_atomic_block;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    let when = UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456);
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let written = write_atomic_block::<_,DummyInput>(&mut cursor, systemtime_to_micros(when), A_CONTENT, false, None, None).unwrap();
+    cursor.set_position(written.start_offset);
+    let BlockState::Closed(BlockReadSummary { block, .. }) = try_read_block::<_,DummyInput>(&mut cursor, false, false).unwrap() else {panic!()};
+    assert_eq!(block.take_start().timestamp_systemtime(),Some(when));
+
+    //sub microsecond precision is dropped
+    assert_eq!(systemtime_to_micros(when + Duration::from_nanos(999)),systemtime_to_micros(when));
+    assert_eq!(systemtime_to_micros(UNIX_EPOCH - Duration::from_secs(1)),None);
+    assert_eq!(micros_to_systemtime(0),Some(UNIX_EPOCH));
+    let now = microseconds_since_epoch();
+    assert!(micros_to_systemtime(now).unwrap() <= SystemTime::now());
+}
+#[test]
+fn test_write_large_content() {
+    use docufort::write::write_large_content;
+    use docufort::read::read_large_content;
+    const MB:usize = 1024 * 1024;
+    let content:Vec<u8> = (0..10 * MB).map(|i|(i as u32).wrapping_mul(2654435761).to_be_bytes()[i % 3]).collect();
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let written = write_large_content::<_,DummyInput>(&mut cursor, &content, MB as u32, true, Some(&3)).unwrap();
+    assert_eq!(written.end_offset,cursor.get_ref().len() as u64);
+
+    cursor.set_position(written.start_offset);
+    let BlockState::Closed(BlockReadSummary { block: Block::B { middle, .. }, .. }) = try_read_block::<_,DummyInput>(&mut cursor, false, false).unwrap() else {panic!("not a closed B block")};
+    assert_eq!(middle.len(),10);
+    assert!(middle.iter().all(|(_,c)|c.ecc && c.data_len.0 as usize <= MB));
+
+    let mut out = Vec::new();
+    assert_eq!(read_large_content::<_,_,DummyInput>(&mut cursor, written.start_offset, &mut out).unwrap(),content.len() as u64);
+    assert!(out == content);
+
+    //empty content still makes a readable block
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let written = write_large_content::<_,DummyInput>(&mut cursor, &[], MB as u32, false, None).unwrap();
+    let mut out = Vec::new();
+    assert_eq!(read_large_content::<_,_,DummyInput>(&mut cursor, written.start_offset, &mut out).unwrap(),0);
+    assert!(write_large_content::<_,DummyInput>(&mut cursor, A_CONTENT, 0, false, None).is_err());
+
+    //more components than a reader accepts is refused up front
+    let len = cursor.get_ref().len();
+    let res = write_large_content::<_,DummyInput>(&mut cursor, &vec![0u8;MAX_COMPONENTS_PER_BLOCK + 1], 1, false, None);
+    assert!(matches!(res,Err(ReadWriteError::Io(ref e)) if e.kind() == std::io::ErrorKind::InvalidInput),"{:?}",res);
+    assert_eq!(cursor.get_ref().len(),len);
+}
+///Counts flushes and syncs, to check when a [FlushPolicy](docufort::write::FlushPolicy) hands bytes to the OS.
+struct FlushCounter{inner:Cursor<Vec<u8>>,flushes:usize,syncs:usize}
+impl std::io::Read for FlushCounter {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {self.inner.read(buf)}
+}
+impl std::io::Write for FlushCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {self.inner.write(buf)}
+    fn flush(&mut self) -> std::io::Result<()> {self.flushes += 1; Ok(())}
+}
+impl std::io::Seek for FlushCounter {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {self.inner.seek(pos)}
+}
+impl FileLike for FlushCounter {
+    fn truncate(&mut self, len: u64)->std::io::Result<()> {self.inner.truncate(len)}
+    fn len(&self)->std::io::Result<u64> {self.inner.len()}
+    fn sync(&mut self)->std::io::Result<()> {self.syncs += 1; Ok(())}
+}
+const FLUSH_POLICIES:[(docufort::write::FlushPolicy,usize,usize);5] = {
+    use docufort::write::FlushPolicy;
+    //(policy, flushes, syncs) for 3 components and the BlockEnd
+    [(FlushPolicy::PerComponent,4,0),(FlushPolicy::PerBlock,1,0),(FlushPolicy::SyncPerComponent,0,4),(FlushPolicy::SyncPerBlock,0,1),(FlushPolicy::Never,0,0)]
+};
+#[test]
+fn test_write_large_content_flush_policy() {
+    use docufort::write::write_large_content_with_policy;
+    for (policy,flushes,syncs) in FLUSH_POLICIES {
+        let mut writer = FlushCounter{inner:Cursor::new(Vec::new()),flushes:0,syncs:0};
+        init_file(&mut writer.inner).unwrap();
+        write_magic_number(&mut writer.inner).unwrap();
+        write_large_content_with_policy::<_,DummyInput>(&mut writer, &[7u8;30], 10, false, None, policy).unwrap();
+        assert_eq!((writer.flushes,writer.syncs),(flushes,syncs),"{:?}",policy);
+    }
+}
+#[test]
+fn test_perform_file_op_flush_policy() {
+    use docufort::retry_writer::{Op, Operation, TailState, perform_file_op_with_policy};
+    for (policy,flushes,syncs) in FLUSH_POLICIES {
+        let mut writer = FlushCounter{inner:Cursor::new(Vec::new()),flushes:0,syncs:0};
+        init_file(&mut writer.inner).unwrap();
+        let mut tail = TailState::<DummyInput>::ClosedBlock;
+        for _ in 0..3 {
+            let oper = Operation{ op:Op::ContentWrite(B_CONTENT.to_vec(),None), timestamp: None, end_timestamp: None, calc_ecc: false, compress:None};
+            tail = perform_file_op_with_policy(&mut writer, tail, oper, 1, policy).unwrap();
+        }
+        let close = Operation{ op:Op::<Vec<u8>>::CloseBlock, timestamp: None, end_timestamp: None, calc_ecc: false, compress:None};
+        tail = perform_file_op_with_policy(&mut writer, tail, close, 1, policy).unwrap();
+        assert!(tail.is_closed());
+        assert_eq!((writer.flushes,writer.syncs),(flushes,syncs),"{:?}",policy);
+    }
+}
+#[test]
+fn test_byte_classification() {
+    use docufort::read::{byte_classification, ByteClass};
+    use docufort::write::{write_aligned_magic_number, write_atomic_block};
+    let mut cursor = generate_test_file();
+    let file_len = cursor.get_ref().len() as u64;
+    let ranges = byte_classification::<_,DummyInput>(&mut cursor).unwrap();
+    //no gaps or overlaps
+    assert_eq!(ranges.first().unwrap().0.start,0);
+    assert_eq!(ranges.last().unwrap().0.end,file_len);
+    assert!(ranges.windows(2).all(|w|w[0].0.end == w[1].0.start));
+    assert!(ranges.iter().all(|(r,_)|!r.is_empty()));
+
+    let total = |class:ByteClass|ranges.iter().filter(|(_,c)|*c == class).map(|(r,_)|r.end - r.start).sum::<u64>();
+    assert_eq!(total(ByteClass::Content),(3 * B_CONTENT.len() + 2 * A_CONTENT.len()) as u64);
+    assert_eq!(total(ByteClass::Magic),(MAGIC_NUMBER.len() + 3 * MN_ECC_LEN) as u64);
+    assert_eq!(total(ByteClass::Hash),3 * HASH_LEN as u64);
+    assert_eq!(total(ByteClass::ContentEcc),2 * ECC_LEN as u64);
+    assert_eq!(total(ByteClass::Padding),0);
+    assert!(ranges.contains(&(184..184 + HEADER_LEN as u64,ByteClass::Header)));
+
+    //alignment padding, and an open tail is left out
+    write_aligned_magic_number(&mut cursor, Some(512)).unwrap();
+    write_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, false, None, None).unwrap();
+    let aligned_len = cursor.get_ref().len() as u64;
+    cursor.get_mut().extend_from_slice(&MAGIC_NUMBER);
+    let ranges = byte_classification::<_,DummyInput>(&mut cursor).unwrap();
+    assert!(ranges.contains(&(file_len..512,ByteClass::Padding)));
+    assert_eq!(ranges.last().unwrap().0.end,aligned_len);
+}
+#[test]
+fn test_read_content_ecc_only() {
+    use docufort::read::{read_block_middle, read_content_ecc_only, BlockMiddleState};
+    //open B block: no BlockEnd, so there is no hash to check the content against
+    let mut cursor = Cursor::new(Vec::new());
+    let mut hasher = DummyInput::new();
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let b_block_header = ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_header(&mut cursor, &b_block_header).unwrap();
+    let middle_start = cursor.position();
+    write_content_component(&mut cursor, false, None, None, B_CONTENT, &mut hasher).unwrap();
+    write_content_component(&mut cursor, true, None, None, A_CONTENT, &mut hasher).unwrap();
+
+    cursor.set_position(middle_start);
+    let BlockMiddleState::UnexpectedEof { content, .. } = read_block_middle::<_,DummyInput>(&mut cursor, false, false).unwrap() else {panic!("not an open block")};
+    let (_, last) = content.last().unwrap();
+    cursor.get_mut()[last.data_start as usize] ^= 0x01;
+    let mut out = Vec::new();
+    assert_eq!(read_content_ecc_only::<_,_,DummyInput>(&mut cursor, &mut out, last).unwrap(),(1,A_CONTENT.len()));
+    assert_eq!(out,A_CONTENT);
+    //nothing is written back
+    assert_ne!(&cursor.get_ref()[last.data_start as usize..][..A_CONTENT.len()],A_CONTENT);
+
+    //the first component has no ECC to check it with
+    let (_, first) = &content[0];
+    let res = read_content_ecc_only::<_,_,DummyInput>(&mut cursor, &mut Vec::new(), first);
+    assert!(matches!(res,Err(ReadWriteError::Io(e)) if e.kind() == std::io::ErrorKind::InvalidInput));
+}
+#[test]
+fn test_read_content_verified() {
+    use docufort::read::read_content_verified;
+    use docufort::write::write_atomic_block;
+    let checksum = |data:&[u8]|data.iter().fold(0u8,|a,b|a.wrapping_add(*b));
+    let verify = |data:&[u8]|data.split_last().is_some_and(|(sum,rest)|*sum == checksum(rest));
+    let mut record = A_CONTENT.to_vec();
+    record.push(checksum(A_CONTENT));
+
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let written = write_atomic_block::<_,DummyInput>(&mut cursor, None, &record, false, None, None).unwrap();
+    cursor.set_position(written.start_offset);
+    let BlockState::Closed(BlockReadSummary { block: Block::A { middle, .. }, .. }) = try_read_block::<_,DummyInput>(&mut cursor, false, false).unwrap() else {panic!("not a closed A block")};
+    let mut out = Vec::new();
+    assert_eq!(read_content_verified::<_,_,DummyInput>(&mut cursor, &mut out, &middle, verify).unwrap(),record.len());
+    assert_eq!(out,record);
+
+    //a record whose checksum does not match is not written out
+    cursor.get_mut()[middle.data_start as usize] ^= 0x01;
+    let mut out = Vec::new();
+    let res = read_content_verified::<_,_,DummyInput>(&mut cursor, &mut out, &middle, verify);
+    assert!(matches!(res,Err(ReadWriteError::ContentVerificationFailed { data_start }) if data_start == middle.data_start));
+    assert!(out.is_empty());
+}
+#[test]
+fn test_read_prev_hash_too_close_to_header() {
+    use docufort::read::read_prev_hash;
+    let mut cursor = generate_test_file();
+    let first_block = (FILE_HEADER_LEN as usize + MN_ECC_LEN) as u64;
+    assert_eq!(read_prev_hash(&mut cursor, first_block).unwrap(),None);
+    cursor.set_position(7);
+    //a corrupt position with no room for a hash before it is an error, not an underflow
+    for block_start in first_block + 1..first_block + HASH_AND_ECC_LEN as u64 {
+        assert!(matches!(read_prev_hash(&mut cursor, block_start),Err(ReadWriteError::EccTooManyErrors)));
+        assert_eq!(cursor.position(),7);
+    }
+    assert_eq!(read_prev_hash(&mut cursor, 184).unwrap().unwrap(),BLOCK_1_HASH);
+}
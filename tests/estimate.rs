@@ -0,0 +1,28 @@
+mod common;
+
+use common::*;
+use docufort::*;
+use docufort::write::{estimate_file_size, component_disk_size, BlockSpec};
+
+#[test]
+fn test_estimate_matches_test_file() {
+    let file_len = generate_test_file().into_inner().len() as u64;
+    let blocks = [
+        BlockSpec { content_lens: vec![B_CONTENT.len();3], calc_ecc: false, atomic: false },
+        BlockSpec { content_lens: vec![A_CONTENT.len()], calc_ecc: false, atomic: true },
+        BlockSpec { content_lens: vec![A_CONTENT.len()], calc_ecc: true, atomic: true },
+    ];
+    //the middle component of the B block has ECC
+    let b_ecc = component_disk_size(B_CONTENT.len(), true) - component_disk_size(B_CONTENT.len(), false);
+    assert_eq!(estimate_file_size(&blocks) + b_ecc, file_len);
+}
+#[test]
+fn test_estimate_per_block() {
+    //block starts from generate_test_file, each preceded by a magic number
+    let file_len = generate_test_file().into_inner().len() as u64;
+    let a_block = estimate_file_size(&[BlockSpec { content_lens: vec![A_CONTENT.len()], calc_ecc: false, atomic: true }]) - FILE_HEADER_LEN as u64;
+    let a_ecc_block = estimate_file_size(&[BlockSpec { content_lens: vec![A_CONTENT.len()], calc_ecc: true, atomic: true }]) - FILE_HEADER_LEN as u64;
+    assert_eq!(a_block, 268 - 184);
+    assert_eq!(a_ecc_block, file_len - 268 + MN_ECC_LEN as u64);
+    assert_eq!(estimate_file_size(&[]), FILE_HEADER_LEN as u64);
+}
@@ -244,4 +244,536 @@ fn test_integrity_test_recovery_open_2_corrupt_1() {
     let cc2 = CorruptDataSegment::MaybeCorrupt { data_start: content_start3 as u64, data_len: B_CONTENT.len() as u32 };
     assert_eq!(corrupted_segments[0], cc1);
     assert_eq!(corrupted_segments[1], cc2);
-}
\ No newline at end of file
+}
+///Writes 3 atomic blocks (no ECC on content), chained or not.
+///Returns the file and (content start, hash start) for each block.
+fn atomic_file(chained:bool)->(Cursor<Vec<u8>>,Vec<(usize,usize)>){
+    use docufort::write::*;
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    let mut prev_hash = None;
+    let mut positions = Vec::new();
+    for _ in 0..3 {
+        write_magic_number(&mut cursor).unwrap();
+        let content_start = cursor.position() as usize + HEADER_LEN + ECC_LEN;
+        if chained {
+            write_chained_atomic_block::<_,DummyInput>(&mut cursor, prev_hash.as_ref(), None, A_CONTENT, false, None, None).unwrap();
+        }else{
+            write_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, false, None, None).unwrap();
+        }
+        let hash_start = cursor.position() as usize - HASH_AND_ECC_LEN;
+        prev_hash = Some(cursor.get_ref()[hash_start..hash_start+HASH_LEN].try_into().unwrap());
+        positions.push((content_start,hash_start));
+    }
+    (cursor,positions)
+}
+///Replaces the middle block's content and rewrites its hash so the block is consistent with itself.
+fn forge_middle_block(v:&mut [u8],positions:&[(usize,usize)],chained:bool){
+    let (content_start,hash_start) = positions[1];
+    let forged = b"Forged content";
+    v[content_start..content_start+forged.len()].copy_from_slice(forged);
+    let prev_hash:[u8;HASH_LEN] = v[positions[0].1..positions[0].1+HASH_LEN].try_into().unwrap();
+    let mut h:DummyInput = docufort::write::chained_hasher(if chained {Some(&prev_hash)}else{None});
+    h.update(forged);
+    let hash = h.finalize();
+    v[hash_start..hash_start+HASH_LEN].copy_from_slice(&hash);
+    let mut ecc = Vec::new();
+    docufort::ecc::calculate_ecc_chunk(&hash, &mut ecc).unwrap();
+    v[hash_start+HASH_LEN..hash_start+HASH_AND_ECC_LEN].copy_from_slice(&ecc);
+}
+#[test]
+fn test_integrity_chained_clean() {
+    let (cursor,_) = atomic_file(true);
+    let mut cursor = Cursor::new(cursor.into_inner());
+    let summary = integrity_check_file::<_, DummyInput>(&mut cursor).unwrap();
+    assert_eq!(summary.num_blocks, 3);
+    assert!(summary.corrupted_segments.is_empty());
+}
+#[test]
+fn test_integrity_chained_forgery() {
+    //per block hashing can't tell
+    let (cursor,positions) = atomic_file(false);
+    let mut v = cursor.into_inner();
+    forge_middle_block(&mut v, &positions, false);
+    let summary = integrity_check_file::<_, DummyInput>(&mut Cursor::new(v)).unwrap();
+    assert_eq!(summary.num_blocks, 3);
+    assert!(summary.corrupted_segments.is_empty());
+
+    //the chain breaks at the block after the forgery
+    let (cursor,positions) = atomic_file(true);
+    let mut v = cursor.into_inner();
+    forge_middle_block(&mut v, &positions, true);
+    let summary = integrity_check_file::<_, DummyInput>(&mut Cursor::new(v)).unwrap();
+    assert_eq!(summary.num_blocks, 3);
+    assert_eq!(summary.corrupted_segments,vec![CorruptDataSegment::Corrupt { data_start: positions[2].0 as u64, data_len: A_CONTENT.len() as u32 }]);
+}
+/// A file of `prefix`, then `zeros` zero bytes, then `suffix`, without holding the zeros in memory.
+/// Records the largest single read, any write is a failure.
+struct ZeroBlockFile{prefix:Vec<u8>,zeros:u64,suffix:Vec<u8>,pos:u64,max_read:usize}
+impl ZeroBlockFile {
+    fn byte_len(&self)->u64{self.prefix.len() as u64 + self.zeros + self.suffix.len() as u64}
+}
+impl std::io::Read for ZeroBlockFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.max_read = self.max_read.max(buf.len());
+        let (p_len,z_end) = (self.prefix.len() as u64,self.prefix.len() as u64 + self.zeros);
+        let n = if self.pos < p_len {
+            let n = buf.len().min((p_len - self.pos) as usize);
+            buf[..n].copy_from_slice(&self.prefix[self.pos as usize..self.pos as usize + n]);
+            n
+        }else if self.pos < z_end {
+            let n = buf.len().min((z_end - self.pos) as usize);
+            buf[..n].fill(0);
+            n
+        }else if self.pos < self.byte_len() {
+            let s = (self.pos - z_end) as usize;
+            let n = buf.len().min(self.suffix.len() - s);
+            buf[..n].copy_from_slice(&self.suffix[s..s+n]);
+            n
+        }else{0};
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+impl std::io::Write for ZeroBlockFile {
+    fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {panic!("nothing should be corrected")}
+    fn flush(&mut self) -> std::io::Result<()> {Ok(())}
+}
+impl std::io::Seek for ZeroBlockFile {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.pos = match pos {
+            std::io::SeekFrom::Start(p) => p,
+            std::io::SeekFrom::End(p) => (self.byte_len() as i64 + p) as u64,
+            std::io::SeekFrom::Current(p) => (self.pos as i64 + p) as u64,
+        };
+        Ok(self.pos)
+    }
+}
+impl FileLike for ZeroBlockFile {
+    fn truncate(&mut self, _len: u64)->std::io::Result<()> {panic!("nothing should be truncated")}
+    fn len(&self)->std::io::Result<u64> {Ok(self.byte_len())}
+}
+#[test]
+fn test_integrity_large_ecc_block_bounded_reads() {
+    use docufort::write::{init_file, write_magic_number, write_header, write_block_end};
+    use docufort::ecc::calc_ecc_data_len;
+    let data_len = 100*1024*1024;
+    let mut prefix = Cursor::new(Vec::new());
+    init_file(&mut prefix).unwrap();
+    write_magic_number(&mut prefix).unwrap();
+    let start = ComponentHeader::new_from_parts(HeaderTag::StartAEBlock as u8, DummyInput::current_timestamp().to_be_bytes(), Some(data_len as u32));
+    write_header(&mut prefix, &start).unwrap();
+    //the ECC of all zero data is all zeros
+    let zeros = (calc_ecc_data_len(data_len) + data_len) as u64;
+    let mut hasher = DummyInput::new();
+    let buf = vec![0u8;1024*1024];
+    let mut left = zeros as usize;
+    while left > 0 {
+        let n = left.min(buf.len());
+        hasher.update(&buf[..n]);
+        left -= n;
+    }
+    let mut suffix = Cursor::new(Vec::new());
+    let end = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_block_end(&mut suffix, &end, &hasher.finalize()).unwrap();
+
+    let mut file = ZeroBlockFile{ prefix: prefix.into_inner(), zeros, suffix: suffix.into_inner(), pos: 0, max_read: 0 };
+    let IntegrityCheckOk { errors_corrected, data_contents, num_blocks, corrupted_segments, .. } = integrity_check_file::<_,DummyInput>(&mut file).unwrap();
+    assert_eq!(errors_corrected,0);
+    assert_eq!(num_blocks,1);
+    assert_eq!(data_contents,data_len as u64);
+    assert!(corrupted_segments.is_empty());
+    //every read is at most the hashing buffer, not the whole ECC data (~1.6 MB)
+    assert!(file.max_read <= 4096,"{}",file.max_read);
+}
+fn manifest_of(v:&[u8]) -> Vec<[u8;HASH_LEN]> {
+    let mut cursor = Cursor::new(v.to_vec());
+    docufort::read::TailingBlockIter::<_,DummyInput>::new(&mut cursor).map(|brs|brs.unwrap().hash_as_read).collect()
+}
+#[test]
+fn test_verify_against_manifest() {
+    use docufort::integrity::{verify_against_manifest, BlockVerifyResult};
+    let v = generate_test_file().into_inner();
+    let manifest = manifest_of(&v);
+    assert_eq!(manifest.len(),3);
+    let results = verify_against_manifest::<_,DummyInput>(&mut Cursor::new(v.clone()), &manifest).unwrap();
+    assert_eq!(results,vec![
+        BlockVerifyResult{ offset: 23, matched: true, manifest_index: Some(0) },
+        BlockVerifyResult{ offset: 184, matched: true, manifest_index: Some(1) },
+        BlockVerifyResult{ offset: 268, matched: true, manifest_index: Some(2) },
+    ]);
+
+    //a correctable header error still matches, and is not written back
+    let mut flipped = v.clone();
+    flipped[184+1] ^= 0xFF;
+    let mut cursor = Cursor::new(flipped.clone());
+    assert!(verify_against_manifest::<_,DummyInput>(&mut cursor, &manifest).unwrap().iter().all(|r|r.matched));
+    assert_eq!(cursor.into_inner(),flipped);
+
+    //tamper with the content of block 2 (no ECC)
+    let mut tampered = v.clone();
+    tampered[184+HEADER_LEN+ECC_LEN] ^= 0xFF;
+    let results = verify_against_manifest::<_,DummyInput>(&mut Cursor::new(tampered), &manifest).unwrap();
+    let bad:Vec<_> = results.iter().filter(|r|!r.matched).collect();
+    assert_eq!(bad,vec![&BlockVerifyResult{ offset: 184, matched: false, manifest_index: None }]);
+
+    //a block missing from the file
+    let mut longer = manifest.clone();
+    longer.insert(1,[0u8;HASH_LEN]);
+    let results = verify_against_manifest::<_,DummyInput>(&mut Cursor::new(v), &longer).unwrap();
+    assert_ne!(results.len(),longer.len());
+    assert_eq!(results.iter().map(|r|r.manifest_index).collect::<Vec<_>>(),vec![Some(0),Some(2),Some(3)]);
+    assert!(results[0].matched && !results[1].matched && !results[2].matched);
+}
+
+///Counts the seeks that reach the file.
+struct SeekCounter{inner:Cursor<Vec<u8>>,seeks:usize}
+impl std::io::Read for SeekCounter {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {self.inner.read(buf)}
+}
+impl std::io::Write for SeekCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {self.inner.write(buf)}
+    fn flush(&mut self) -> std::io::Result<()> {Ok(())}
+}
+impl std::io::Seek for SeekCounter {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.seeks += 1;
+        self.inner.seek(pos)
+    }
+}
+impl FileLike for SeekCounter {
+    fn truncate(&mut self, len: u64)->std::io::Result<()> {self.inner.truncate(len)}
+    fn len(&self)->std::io::Result<u64> {self.inner.len()}
+}
+fn scan_blocks<RW:FileLike>(file:&mut RW)->Vec<u64>{
+    use docufort::read::read_magic_number;
+    use docufort::recovery::{try_read_block, BlockReadSummary};
+    file.seek(std::io::SeekFrom::Start(FILE_HEADER_LEN as u64)).unwrap();
+    let mut starts = Vec::new();
+    while read_magic_number(file, true).is_ok() {
+        let BlockState::Closed(BlockReadSummary { block_start, .. }) = try_read_block::<_,DummyInput>(file, true, true).unwrap() else {panic!()};
+        starts.push(block_start);
+    }
+    starts
+}
+#[test]
+fn test_positioned_file_fewer_seeks() {
+    use docufort::read::PositionedFile;
+    let mut raw = SeekCounter { inner: generate_test_file(), seeks: 0 };
+    let expected = scan_blocks(&mut raw);
+    assert_eq!(expected,vec![23,184,268]);
+
+    let mut cached = PositionedFile::new(SeekCounter { inner: generate_test_file(), seeks: 0 });
+    assert_eq!(scan_blocks(&mut cached),expected);
+    let cached = cached.into_inner();
+    assert!(cached.seeks * 10 <= raw.seeks,"{} vs {}",cached.seeks,raw.seeks);
+
+    let mut checked = SeekCounter { inner: generate_test_file(), seeks: 0 };
+    checked.inner.set_position(0);
+    let IntegrityCheckOk { num_blocks, .. } = integrity_check_file::<_,DummyInput>(&mut checked).unwrap();
+    assert_eq!(num_blocks,3);
+    assert!(checked.seeks * 10 <= raw.seeks,"{} vs {}",checked.seeks,raw.seeks);
+}
+#[test]
+fn test_sharded_writer() {
+    use docufort::write::ShardedWriter;
+    use docufort::retry_writer::{Op, Operation};
+    use docufort::read::all_content;
+    let files = (0..4).map(|_|Cursor::new(Vec::new())).collect();
+    let mut writer = ShardedWriter::<_,DummyInput>::open(files, 3).unwrap();
+    let mut per_shard = [0usize;4];
+    for i in 0..100u64 {
+        let item = format!("item {}",i).into_bytes();
+        let op = if i % 3 == 0 {Op::AtomicWrite(item)}else{Op::ContentWrite(item,None)};
+        let key_hash = i.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32;
+        let shard = writer.write(key_hash, Operation { op, timestamp: None, end_timestamp: None, calc_ecc: i % 2 == 0, compress: None }).unwrap();
+        assert_eq!(shard, writer.shard_for(key_hash));
+        per_shard[shard] += 1;
+    }
+    assert!(per_shard.iter().all(|n|*n > 0),"{:?}",per_shard);
+    writer.close_all().unwrap();
+    assert!((0..4).all(|s|writer.tail_state(s).unwrap().is_closed()));
+    //nothing to recover after a clean close
+    for summary in writer.recover_all().unwrap() {
+        assert_eq!(summary.original_file_len, summary.recovered_file_len);
+    }
+    for (shard,mut file) in writer.into_inner().into_iter().enumerate() {
+        file.set_position(0);
+        let check = integrity_check_file::<_,DummyInput>(&mut file).unwrap();
+        assert_eq!(check.errors_corrected, 0);
+        assert_eq!(check.file_len_checked, file.get_ref().len() as u64);
+        assert_eq!(all_content::<_,DummyInput>(&mut file).unwrap().len(), per_shard[shard]);
+    }
+}
+#[test]
+fn test_corrupt_magic_number_between_blocks() {
+    use docufort::integrity::IntegrityErr;
+    use docufort::read::TailingBlockIter;
+    //the magic number in front of the first A block
+    let mn_start = 184 - MN_ECC_LEN;
+    let clean = generate_test_file().into_inner();
+
+    //correctable: fixed in place by the forward check
+    let mut cursor = Cursor::new(clean.clone());
+    cursor.get_mut()[mn_start] ^= 0xFF;
+    cursor.get_mut()[mn_start + 3] ^= 0x0F;
+    //read only walks check it too, but leave the file alone
+    let blocks = TailingBlockIter::<_,DummyInput>::new(&mut cursor).collect::<Result<Vec<_>,_>>().unwrap();
+    assert_eq!(blocks.len(), 3);
+    assert_ne!(cursor.get_ref(), &clean);
+    cursor.set_position(0);
+    let check = integrity_check_file::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(check.num_blocks, 3);
+    assert_eq!(check.errors_corrected, 2);
+    assert_eq!(cursor.get_ref(), &clean);
+
+    //beyond the ECC: reported, not stepped over
+    for b in &mut cursor.get_mut()[mn_start..mn_start + MAGIC_NUMBER.len()] {*b ^= 0xFF}
+    cursor.set_position(0);
+    let res = integrity_check_file::<_,DummyInput>(&mut cursor);
+    assert!(matches!(res,Err(IntegrityErr::Corruption(pos,ComponentTag::MagicNumber)) if pos == mn_start as u64),"{:?}",res);
+    let res = TailingBlockIter::<_,DummyInput>::new(&mut cursor).collect::<Result<Vec<_>,_>>();
+    assert!(matches!(res,Err(ReadWriteError::EccTooManyErrors)),"{:?}",res);
+}
+#[test]
+fn test_integrity_check_resume() {
+    use docufort::integrity::integrity_check_file_resume;
+    let mut cursor = Cursor::new(generate_test_file().into_inner());
+    let full = integrity_check_file::<_, DummyInput>(&mut cursor).unwrap();
+    assert_eq!(full.resume_at,None);
+
+    let mut split = integrity_check_file_resume::<_, DummyInput>(&mut cursor, None, Some(2)).unwrap();
+    assert_eq!(split.num_blocks,2);
+    let resume_at = split.resume_at.unwrap();
+    assert_eq!(split.file_len_checked,resume_at);
+    let rest = integrity_check_file_resume::<_, DummyInput>(&mut cursor, Some(resume_at), Some(2)).unwrap();
+    assert_eq!(rest.num_blocks,1);
+    assert_eq!(rest.resume_at,None);
+    split.append(rest);
+
+    assert_eq!(split.num_blocks,full.num_blocks);
+    assert_eq!(split.errors_corrected,full.errors_corrected);
+    assert_eq!(split.data_contents,full.data_contents);
+    assert_eq!(split.data_size_on_disk,full.data_size_on_disk);
+    assert_eq!(split.protected_content_bytes,full.protected_content_bytes);
+    assert_eq!(split.unprotected_content_bytes,full.unprotected_content_bytes);
+    assert_eq!(split.file_len_checked,full.file_len_checked);
+    assert_eq!(split.block_times,full.block_times);
+    assert_eq!(split.corrupted_segments,full.corrupted_segments);
+    assert_eq!(split.last_block_state,full.last_block_state);
+    assert_eq!(split.resume_at,None);
+
+    //a limit that is not reached runs to the end
+    let once = integrity_check_file_resume::<_, DummyInput>(&mut cursor, None, Some(3)).unwrap();
+    assert_eq!((once.num_blocks,once.resume_at),(3,None));
+}
+#[test]
+fn test_integrity_protected_content_bytes() {
+    //B block only: [no ecc, ecc, no ecc]
+    let mut file_content = generate_test_file().into_inner();
+    file_content.truncate(184 - MN_ECC_LEN);
+    let mut cursor = Cursor::new(file_content);
+    let summary = integrity_check_file::<_, DummyInput>(&mut cursor).unwrap();
+    assert_eq!(summary.num_blocks,1);
+    assert_eq!(summary.protected_content_bytes,B_CONTENT.len() as u64);
+    assert_eq!(summary.unprotected_content_bytes,2 * B_CONTENT.len() as u64);
+
+    //Full file adds an A block without ecc and one with ecc
+    let mut cursor = generate_test_file();
+    let summary = integrity_check_file::<_, DummyInput>(&mut cursor).unwrap();
+    assert_eq!(summary.protected_content_bytes,(B_CONTENT.len() + A_CONTENT.len()) as u64);
+    assert_eq!(summary.unprotected_content_bytes,(2 * B_CONTENT.len() + A_CONTENT.len()) as u64);
+    assert_eq!(summary.protected_content_bytes + summary.unprotected_content_bytes,summary.data_size_on_disk);
+}
+#[test]
+fn test_generate_manifest() {
+    use docufort::integrity::{generate_manifest, verify_against_manifest};
+    use docufort::recovery::try_read_block;
+    let mut cursor = generate_test_file();
+    let manifest = generate_manifest::<_, DummyInput>(&mut cursor).unwrap();
+    assert_eq!(manifest.iter().map(|e|e.offset).collect::<Vec<_>>(),vec![23,184,268]);
+    for entry in manifest.iter() {
+        cursor.set_position(entry.offset);
+        let BlockState::Closed(brs) = try_read_block::<_, DummyInput>(&mut cursor, false, false).unwrap() else {panic!("not a closed block")};
+        assert_eq!(entry.timestamp,brs.block_start_timestamp);
+        assert_eq!(&entry.hash[..],brs.block.take_end().hash.hash());
+    }
+    let hashes:Vec<_> = manifest.iter().map(|e|e.hash).collect();
+    assert!(verify_against_manifest::<_, DummyInput>(&mut cursor, &hashes).unwrap().iter().all(|r|r.matched));
+
+    //chained blocks hash in the block before them
+    let (mut chained,_) = atomic_file(true);
+    let manifest = generate_manifest::<_, DummyInput>(&mut chained).unwrap();
+    let hashes:Vec<_> = manifest.iter().map(|e|e.hash).collect();
+    assert_eq!(hashes.len(),3);
+    assert!(verify_against_manifest::<_, DummyInput>(&mut chained, &hashes).unwrap().iter().all(|r|r.matched));
+
+    //a logical hash covers the decompressed content
+    let mut cursor = generate_test_file();
+    cursor.set_position(cursor.get_ref().len() as u64);
+    docufort::write::write_magic_number(&mut cursor).unwrap();
+    let written = docufort::write::write_logical_hash_atomic_block::<_,DummyInput>(&mut cursor, None, &[7u8;1000], true, Some(&3), None).unwrap();
+    let manifest = generate_manifest::<_, DummyInput>(&mut cursor).unwrap();
+    assert_eq!(manifest.len(),4);
+    assert_eq!((manifest[3].offset,manifest[3].hash),(written.start_offset,written.hash));
+
+    //a damaged (no ECC) block is reported
+    let mut v = generate_test_file().into_inner();
+    v[184 + HEADER_LEN + ECC_LEN] ^= 0x01;
+    assert!(matches!(generate_manifest::<_, DummyInput>(&mut Cursor::new(v)),Err(ReadWriteError::HashMismatch { block_start: 184 })));
+}
+#[test]
+fn test_integrity_check_merge() {
+    use docufort::integrity::MergedCheck;
+    let mut file_content = generate_test_file().into_inner();
+    file_content.truncate(184 - MN_ECC_LEN);
+    let first = integrity_check_file::<_, DummyInput>(&mut Cursor::new(file_content)).unwrap();
+    let second = integrity_check_file::<_, DummyInput>(&mut generate_test_file()).unwrap();
+    let (first_len, second_len) = (first.file_len_checked, second.file_len_checked);
+    let (first_times, second_times) = (first.block_times.clone(), second.block_times.clone());
+
+    let merged = first.merge(second);
+    assert_eq!(merged.file_len_checked, first_len + second_len);
+    assert_eq!(merged.num_blocks, 4);
+    assert_eq!(merged.protected_content_bytes, (2 * B_CONTENT.len() + A_CONTENT.len()) as u64);
+    assert_eq!(merged.unprotected_content_bytes, (4 * B_CONTENT.len() + A_CONTENT.len()) as u64);
+    assert_eq!(merged.block_times[..1], first_times[..]);
+    assert_eq!(merged.block_times[1..], second_times[..]);
+    assert!(merged.corrupted_segments.is_empty());
+    assert_eq!(merged.merged_files, vec![
+        MergedCheck { file_len_checked: first_len, num_blocks: 1, corrupted_segments: 0, block_times: 1, stray_block_ends: 0, unchecked_external_refs: 0 },
+        MergedCheck { file_len_checked: second_len, num_blocks: 3, corrupted_segments: 0, block_times: 3, stray_block_ends: 0, unchecked_external_refs: 0 },
+    ]);
+}
+#[test]
+fn test_offset_bound_detects_swapped_blocks() {
+    use docufort::write::{init_file, write_magic_number, write_atomic_block, write_offset_bound_atomic_block};
+    for offset_bound in [false,true] {
+        let mut cursor = Cursor::new(Vec::new());
+        init_file(&mut cursor).unwrap();
+        let mut blocks = Vec::new();
+        for content in [b"first block!", b"second block"] {
+            write_magic_number(&mut cursor).unwrap();
+            let written = if offset_bound {
+                write_offset_bound_atomic_block::<_, DummyInput>(&mut cursor, None, content, false, None, None).unwrap()
+            }else{
+                write_atomic_block::<_, DummyInput>(&mut cursor, None, content, false, None, None).unwrap()
+            };
+            blocks.push(written);
+        }
+        let ok = integrity_check_file::<_, DummyInput>(&mut cursor.clone()).unwrap();
+        assert!(ok.corrupted_segments.is_empty());
+
+        //same length blocks, so swapping them leaves a well formed file
+        let (first,second) = (blocks[0].start_offset as usize..blocks[0].end_offset as usize,blocks[1].start_offset as usize..blocks[1].end_offset as usize);
+        let mut bytes = cursor.into_inner();
+        let first_bytes = bytes[first.clone()].to_vec();
+        let second_bytes = bytes[second.clone()].to_vec();
+        bytes[first.start..first.start + second_bytes.len()].copy_from_slice(&second_bytes);
+        bytes[second.end - first_bytes.len()..second.end].copy_from_slice(&first_bytes);
+        let ok = integrity_check_file::<_, DummyInput>(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(ok.num_blocks,2);
+        assert_eq!(ok.corrupted_segments.len(),if offset_bound {2}else{0},"offset_bound: {}",offset_bound);
+    }
+}
+#[test]
+fn test_offset_bound_b_block_is_listed() {
+    use docufort::write::{init_file, write_magic_number, write_header, write_content_component, write_offset_bound_block_end, write_atomic_block};
+    use docufort::read::{catalog, locate_offset};
+    use docufort::integrity::generate_manifest;
+    let mut cursor = Cursor::new(Vec::new());
+    let mut hasher = DummyInput::new();
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let block_start = cursor.position();
+    write_header(&mut cursor, &ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None)).unwrap();
+    write_content_component(&mut cursor, true, None, None, B_CONTENT, &mut hasher).unwrap();
+    let hash = write_offset_bound_block_end(&mut cursor, block_start, None, hasher).unwrap();
+    let b_end = cursor.position();
+    write_magic_number(&mut cursor).unwrap();
+    let a_block = write_atomic_block::<_, DummyInput>(&mut cursor, None, A_CONTENT, false, None, None).unwrap();
+
+    let entries = catalog(&mut cursor).unwrap();
+    assert_eq!(entries.iter().map(|e|(e.offset,e.is_atomic)).collect::<Vec<_>>(),vec![(block_start,false),(a_block.start_offset,true)]);
+    assert_eq!(entries[0].total_content_bytes,B_CONTENT.len() as u64);
+
+    let manifest = generate_manifest::<_, DummyInput>(&mut cursor).unwrap();
+    assert_eq!(manifest.iter().map(|e|(e.offset,e.hash)).collect::<Vec<_>>(),vec![(block_start,hash),(a_block.start_offset,a_block.hash)]);
+
+    let loc = locate_offset(&mut cursor, b_end - 1).unwrap();
+    assert_eq!((loc.block_start,loc.component_tag),(block_start,ComponentTag::Hash));
+    let loc = locate_offset(&mut cursor, a_block.end_offset - 1).unwrap();
+    assert_eq!((loc.block_start,loc.component_tag),(a_block.start_offset,ComponentTag::Hash));
+}
+#[test]
+fn test_verify_stream() {
+    use docufort::integrity::verify_stream;
+    ///Hands out a few bytes per read, and can not seek.
+    struct Trickle<'a>(&'a [u8]);
+    impl std::io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(7);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+    let bytes = generate_test_file().into_inner();
+    let expected = integrity_check_file::<_, DummyInput>(&mut Cursor::new(bytes.clone())).unwrap();
+    let ok = verify_stream::<_, DummyInput>(Trickle(&bytes)).unwrap();
+    assert_eq!(ok.num_blocks,3);
+    assert_eq!(ok.file_len_checked,bytes.len() as u64);
+    assert_eq!(ok.block_times,expected.block_times);
+    assert_eq!(ok.errors_corrected,0);
+    assert!(ok.corrupted_segments.is_empty());
+
+    //first content byte of the A block with ECC, corrected in memory
+    let mut flipped = bytes.clone();
+    flipped[268 + HEADER_LEN + ECC_LEN * 2] ^= 0x01;
+    let ok = verify_stream::<_, DummyInput>(Trickle(&flipped)).unwrap();
+    assert_eq!(ok.num_blocks,3);
+    assert_eq!(ok.errors_corrected,1);
+    assert!(ok.corrupted_segments.is_empty());
+
+    //an open tail stops the check at the last closed block
+    let ok = verify_stream::<_, DummyInput>(Trickle(&bytes[..300])).unwrap();
+    assert_eq!(ok.num_blocks,2);
+    assert_eq!(ok.file_len_checked,268 - MN_ECC_LEN as u64);
+
+    assert!(matches!(verify_stream::<_, DummyInput>(Trickle(&[0u8;4])),Err(_)));
+}
+#[test]
+fn test_version_policy() {
+    use docufort::integrity::{integrity_check_file_with_policy, integrity_check_file_resume_with_policy, verify_stream, verify_stream_with_policy, generate_manifest, generate_manifest_with_policy, verify_against_manifest, verify_against_manifest_with_policy, IntegrityErr};
+    use docufort::read::{check_configs, ConfigMatch, VersionPolicy};
+    let mut bytes = generate_test_file().into_inner();
+    let ok = integrity_check_file_with_policy::<_, DummyInput>(&mut Cursor::new(bytes.clone()), VersionPolicy::AllowNewer).unwrap();
+    assert_eq!(ok.newer_version,None);
+
+    //a V2 file that only holds V1 blocks
+    bytes[MAGIC_NUMBER.len() + 1] = b'2';
+    assert_eq!(check_configs(&mut Cursor::new(&bytes), VersionPolicy::Strict).unwrap(),ConfigMatch::Mismatch);
+    assert_eq!(check_configs(&mut Cursor::new(&bytes), VersionPolicy::AllowNewer).unwrap(),ConfigMatch::Newer(2));
+    assert!(matches!(integrity_check_file::<_, DummyInput>(&mut Cursor::new(bytes.clone())),Err(IntegrityErr::FileConfigMisMatch)));
+    assert!(matches!(integrity_check_file_with_policy::<_, DummyInput>(&mut Cursor::new(bytes.clone()), VersionPolicy::Strict),Err(IntegrityErr::FileConfigMisMatch)));
+    let ok = integrity_check_file_with_policy::<_, DummyInput>(&mut Cursor::new(bytes.clone()), VersionPolicy::AllowNewer).unwrap();
+    assert_eq!(ok.newer_version,Some(2));
+    assert_eq!(ok.num_blocks,3);
+    assert!(ok.corrupted_segments.is_empty());
+    let ok = integrity_check_file_resume_with_policy::<_, DummyInput>(&mut Cursor::new(bytes.clone()), None, Some(1), VersionPolicy::AllowNewer).unwrap();
+    assert_eq!((ok.num_blocks,ok.newer_version),(1,Some(2)));
+    assert!(matches!(verify_stream::<_, DummyInput>(&bytes[..]),Err(IntegrityErr::FileConfigMisMatch)));
+    let ok = verify_stream_with_policy::<_, DummyInput>(&bytes[..], VersionPolicy::AllowNewer).unwrap();
+    assert_eq!((ok.num_blocks,ok.newer_version),(3,Some(2)));
+    assert!(matches!(generate_manifest::<_, DummyInput>(&mut Cursor::new(&bytes)),Err(ReadWriteError::NotADocuFortFile)));
+    let manifest = generate_manifest_with_policy::<_, DummyInput>(&mut Cursor::new(&bytes), VersionPolicy::AllowNewer).unwrap();
+    assert_eq!(manifest.len(),3);
+    let hashes:Vec<_> = manifest.iter().map(|e|e.hash).collect();
+    assert!(matches!(verify_against_manifest::<_, DummyInput>(&mut Cursor::new(&bytes), &hashes),Err(IntegrityErr::FileConfigMisMatch)));
+    let results = verify_against_manifest_with_policy::<_, DummyInput>(&mut Cursor::new(&bytes), &hashes, VersionPolicy::AllowNewer).unwrap();
+    assert!(results.len() == 3 && results.iter().all(|r|r.matched));
+
+    //an older or unknown version is never accepted
+    bytes[MAGIC_NUMBER.len() + 1] = b'0';
+    assert_eq!(check_configs(&mut Cursor::new(&bytes), VersionPolicy::AllowNewer).unwrap(),ConfigMatch::Mismatch);
+}
@@ -0,0 +1,29 @@
+mod common;
+
+use common::{DummyInput, A_CONTENT, B_CONTENT};
+use docufort::prelude::*;
+
+use std::io::Cursor;
+
+#[test]
+fn test_prelude_write_and_recover() {
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    let ops = [
+        Operation{ op:Op::ContentWrite(B_CONTENT.to_vec(),None), timestamp: None, end_timestamp: None, calc_ecc: true, compress:None },
+        Operation{ op:Op::AtomicWrite(A_CONTENT.to_vec()), timestamp: None, end_timestamp: None, calc_ecc: false, compress:None },
+        Operation{ op:Op::ContentWrite(B_CONTENT.to_vec(),None), timestamp: None, end_timestamp: None, calc_ecc: false, compress:None },
+    ];
+    let mut tail_state: TailState<DummyInput> = TailState::ClosedBlock;
+    for oper in ops {
+        tail_state = perform_file_op(&mut cursor, tail_state, oper, 1).unwrap();
+    }
+    assert!(tail_state.is_open());
+    let TailRecoverySummary { has_blocks, file_ops, .. } = recover_tail::<_,DummyInput>(&mut cursor).unwrap();
+    assert!(has_blocks);
+    assert!(matches!(file_ops[0].1, BlockState::OpenBBlock { .. }));
+    cursor.set_position(0);
+    let IntegrityCheckOk { num_blocks, corrupted_segments, .. } = integrity_check_file::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(num_blocks,3);
+    assert!(corrupted_segments.is_empty());
+}
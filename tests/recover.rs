@@ -35,6 +35,116 @@ fn test_find_block_start_after_truncation() {
     }
 }
 #[test]
+fn test_find_block_start_foreign_file() {
+    ///Counts the bytes read.
+    struct ReadCounter{inner:Cursor<Vec<u8>>,read:usize}
+    impl std::io::Read for ReadCounter {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.read += n;
+            Ok(n)
+        }
+    }
+    impl std::io::Seek for ReadCounter {
+        fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {self.inner.seek(pos)}
+    }
+    //16 MiB of xorshift noise
+    let mut x = 0x2545F4914F6CDD1Du64;
+    let noise:Vec<u8> = (0..16 << 20).map(|_|{x ^= x << 13; x ^= x >> 7; x ^= x << 17; x as u8}).collect();
+    let mut file = ReadCounter { inner: Cursor::new(noise.clone()), read: 0 };
+    file.seek(std::io::SeekFrom::End(0)).unwrap();
+    let err = find_block_start(&mut file).unwrap_err();
+    assert!(err.get_ref().is_some_and(|e|e.is::<NotADocuFortFile>()),"{:?}",err);
+    assert!(file.read <= (FOREIGN_FILE_SCAN_WINDOW as usize + 1) * MN_ECC_LEN + FILE_HEADER_LEN as usize,"read {} bytes",file.read);
+
+    let mut cursor = Cursor::new(noise.clone());
+    let res = recover_tail::<_,DummyInput>(&mut cursor);
+    assert!(matches!(res,Err(ReadWriteError::NotADocuFortFile)),"{:?}",res);
+    assert_eq!(cursor.into_inner(),noise);
+}
+#[test]
+fn test_find_block_start_damaged_file_header() {
+    //the first magic number is further back than a foreign file is scanned
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    write_atomic_block::<_,DummyInput>(&mut cursor, None, &vec![7u8;FOREIGN_FILE_SCAN_WINDOW as usize], false, None, None).unwrap();
+    let clean = cursor.into_inner();
+    let first_block = (FILE_HEADER_LEN as usize + MN_ECC_LEN) as u64;
+    //a flipped version byte, ECC_LEN byte, or magic number byte
+    for at in [MAGIC_NUMBER.len(),FILE_HEADER_LEN as usize - 1,0] {
+        let mut v = clean.clone();
+        v[at] ^= 0x10;
+        let mut cursor = Cursor::new(v);
+        cursor.seek(std::io::SeekFrom::End(0)).unwrap();
+        assert_eq!(find_block_start(&mut cursor).unwrap(),first_block,"damaged at {}",at);
+    }
+}
+#[test]
+fn test_header_ecc_bytes() {
+    use docufort::read::read_header;
+    use docufort::ecc::calculate_ecc_chunk;
+    let mut cursor = generate_test_file();
+    for start in [23,184,268] {
+        cursor.set_position(start);
+        let (_,header) = read_header(&mut cursor, false).unwrap();
+        let mut fresh = Vec::new();
+        calculate_ecc_chunk(header.as_slice(), &mut fresh).unwrap();
+        assert_eq!(&header.ecc_bytes()[..],&fresh[..]);
+        assert_eq!(&header.ecc_bytes()[..],&cursor.get_ref()[start as usize + HEADER_LEN..start as usize + HEADER_LEN + ECC_LEN]);
+        assert!(header.verify_self());
+    }
+    let built = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    assert!(built.verify_self());
+
+    //a damaged ECC byte is kept as read, unless corrected
+    let ecc_pos = 268 + HEADER_LEN;
+    cursor.get_mut()[ecc_pos] ^= 0xFF;
+    cursor.set_position(268);
+    let (_,damaged) = read_header(&mut cursor, false).unwrap();
+    assert!(!damaged.verify_self());
+    cursor.set_position(268);
+    let (errors,corrected) = read_header(&mut cursor, true).unwrap();
+    assert_eq!(errors,1);
+    assert!(corrected.verify_self());
+    assert_eq!(corrected.as_slice(),damaged.as_slice());
+    //the ECC bytes take no part in comparisons
+    assert_eq!(corrected,damaged);
+    assert_eq!(corrected.cmp(&damaged),std::cmp::Ordering::Equal);
+    let mut changed = corrected;
+    changed.as_mut_slice()[0] ^= COMMITTED;
+    assert!(!changed.verify_self());
+}
+#[test]
+fn test_block_coords() {
+    let mut cursor = generate_test_file();
+    let file = cursor.get_ref().clone();
+    let mut rel_data_starts = Vec::new();
+    for block_start in [23,184,268] {
+        let coords = BlockCoords::new(block_start);
+        cursor.set_position(block_start);
+        let BlockState::Closed(BlockReadSummary { block, block_start:read_start, .. }) = try_read_block::<_,DummyInput>(&mut cursor, true,true).unwrap() else {panic!()};
+        assert_eq!(read_start,block_start);
+        let block_end = cursor.position();
+        assert_eq!(coords.to_absolute(coords.to_relative(block_end).unwrap()),block_end);
+        assert_eq!(coords.to_relative(block_start - 1),None);
+        let mn = coords.magic_number_start() as usize;
+        assert_eq!(&file[mn..mn+MAGIC_NUMBER.len()],&MAGIC_NUMBER);
+        match block {
+            Block::A { middle, .. } => rel_data_starts.push(coords.to_relative(middle.data_start).unwrap()),
+            Block::B { middle, .. } => {
+                assert_eq!(middle[0].0.start_pos(),coords.body_start());
+                rel_data_starts.extend(middle.iter().map(|(_,c)|coords.to_relative(c.data_start).unwrap()));
+            },
+        }
+    }
+    //B contents (no ECC, ECC, no ECC), then an A block without and with ECC
+    let ecc = ECC_LEN as u64;
+    let h = (HEADER_LEN + ECC_LEN) as u64;
+    let b = B_CONTENT.len() as u64;
+    assert_eq!(rel_data_starts,vec![2*h, 3*h+b+ecc, 4*h+2*b+ecc, h, h+ecc]);
+}
+#[test]
 fn test_try_read_block_3_clean() {
     let mut cursor = generate_test_file();
     cursor.set_position(268);
@@ -85,11 +195,11 @@ fn test_try_read_block_3_data_recovery() {
             assert_eq!(&hash_as_read[..],block.clone().take_end().hash.hash());
             assert_eq!(errors_corrected,2);
             if let Block::A { middle, .. } = block {
-                let Content{ data_len, data_start, ecc, compressed } = middle;
+                let Content{ data_len, data_start, ecc, compressed, .. } = middle;
                 assert!(ecc);
                 assert!(compressed.is_none());
                 cursor.set_position(data_start);
-                let mut data = vec![0u8;data_len as usize];
+                let mut data = vec![0u8;data_len.0 as usize];
                 cursor.read_exact(&mut data).unwrap();
                 assert_eq!(data.as_slice(),A_CONTENT.as_slice());
             }
@@ -288,8 +398,26 @@ fn test_try_read_block_1_truncate_in_header() {
         a => panic!("Invalid Read: {:?}",a),
     }
 }
+#[test]
+fn test_verify_after_patch() {
+    let cursor = generate_test_file();
+    let block_start = 184;
+    let content_start = block_start + HEADER_LEN + ECC_LEN;
+    let mut v = cursor.into_inner();
+    let orig = v[content_start];
+    v[content_start] ^= 0xFF;
+    let mut cursor = Cursor::new(v);
+    assert!(!verify_after_patch::<_,DummyInput>(&mut cursor, block_start as u64).unwrap());
+    //patch the byte back, as a user would after fixing a CorruptDataSegment::Corrupt
+    cursor.set_position(content_start as u64);
+    cursor.write_all(&[orig]).unwrap();
+    assert!(verify_after_patch::<_,DummyInput>(&mut cursor, block_start as u64).unwrap());
+    cursor.set_position(content_start as u64);
+    cursor.write_all(&[orig ^ 0x01]).unwrap();
+    assert!(!verify_after_patch::<_,DummyInput>(&mut cursor, block_start as u64).unwrap());
+}
 
-use std::io::{Cursor, Read, Seek};
+use std::io::{Cursor, Read, Seek, Write};
 
 #[test]
 fn test_empty_file_recovery() {
@@ -334,7 +462,12 @@ fn test_tail_recovery_trailing_truncate() {
         tot_errors_corrected,
         file_ops,
         corrupted_content_blocks,
+        last_batch_marker,
+        tail_block_unverified,
+        ..
     } = summary;
+    assert_eq!(last_batch_marker, None);
+    assert!(!tail_block_unverified);
     assert_eq!(tot_errors_corrected, 0);
     assert_eq!(file_ops.len(), 1);
     assert_eq!(original_file_len - MAGIC_NUMBER.len() as u64, recovered_file_len);
@@ -357,7 +490,12 @@ fn test_tail_recovery_open_a_data() {
         tot_errors_corrected,
         file_ops,
         corrupted_content_blocks,
+        last_batch_marker,
+        tail_block_unverified,
+        ..
     } = summary;
+    assert_eq!(last_batch_marker, None);
+    assert!(!tail_block_unverified);
     assert_eq!(tot_errors_corrected, 0);
     assert_eq!(original_file_len as usize, new_len);
     assert_eq!(recovered_file_len as usize, block_start - MN_ECC_LEN);
@@ -379,8 +517,13 @@ fn test_tail_recovery_open_a_header() {
         has_blocks,
         tot_errors_corrected,
         file_ops,
-        corrupted_content_blocks
+        corrupted_content_blocks,
+        last_batch_marker,
+        tail_block_unverified,
+        ..
     } = summary;
+    assert_eq!(last_batch_marker, None);
+    assert!(!tail_block_unverified);
     assert_eq!(tot_errors_corrected, 0);
     assert_eq!(original_file_len as usize, new_len);
     assert_eq!(recovered_file_len as usize, block_start - MN_ECC_LEN);
@@ -404,7 +547,12 @@ fn test_tail_recovery_open_b() {
         tot_errors_corrected,
         file_ops,
         corrupted_content_blocks,
+        last_batch_marker,
+        tail_block_unverified,
+        ..
     } = summary;
+    assert_eq!(last_batch_marker, None);
+    assert!(!tail_block_unverified);
     assert_eq!(tot_errors_corrected, 0);
     assert_eq!(original_file_len as usize, new_len);
     assert_eq!(recovered_file_len as usize, 81);
@@ -429,7 +577,12 @@ fn test_tail_test_recovery_ecc_block_3_data() {
         tot_errors_corrected,
         file_ops,
         corrupted_content_blocks,
+        last_batch_marker,
+        tail_block_unverified,
+        ..
     } = summary;
+    assert_eq!(last_batch_marker, None);
+    assert!(!tail_block_unverified);
     assert_eq!(tot_errors_corrected, 2);
     assert_eq!(original_file_len, recovered_file_len);
     assert!(has_blocks);
@@ -453,7 +606,12 @@ fn test_tail_test_recovery_ecc_block_3_header() {
         tot_errors_corrected,
         file_ops,
         corrupted_content_blocks,
+        last_batch_marker,
+        tail_block_unverified,
+        ..
     } = summary;
+    assert_eq!(last_batch_marker, None);
+    assert!(!tail_block_unverified);
     assert_eq!(tot_errors_corrected, 2);
     assert_eq!(original_file_len, recovered_file_len);
     assert!(has_blocks);
@@ -481,7 +639,12 @@ fn test_tail_test_recovery_open_3_corrupt_2() {
         tot_errors_corrected,
         file_ops,
         corrupted_content_blocks,
+        last_batch_marker,
+        tail_block_unverified,
+        ..
     } = summary;
+    assert_eq!(last_batch_marker, None);
+    assert!(tail_block_unverified);
     assert_eq!(tot_errors_corrected, 0);
     assert_eq!(recovered_file_len as usize, 256);
     assert_eq!(original_file_len, new_len as u64);
@@ -510,6 +673,26 @@ fn test_tail_test_recovery_open_3_corrupt_2() {
     }
 }
 #[test]
+fn test_tail_recovery_render_steps() {
+    let mut file_contents = generate_test_file().into_inner();
+    file_contents.truncate(268 + HEADER_LEN + ECC_LEN + 4);
+    let content_start = 184 + HEADER_LEN + ECC_LEN;
+    file_contents[content_start] ^= file_contents[content_start];
+    file_contents[content_start + 2] ^= file_contents[content_start + 2];
+    let summary = recover_tail::<_, DummyInput>(&mut Cursor::new(file_contents)).unwrap();
+    let steps = summary.steps();
+    assert_eq!(steps.iter().map(|s|s.action).collect::<Vec<_>>(),vec![
+        RecoveryAction::Truncate { truncate_at: 256 },
+        RecoveryAction::RetryWithEcc,
+        RecoveryAction::Keep { end: 256 },
+    ]);
+    assert!(steps[0].block_summary.is_none() && steps[2].block_summary.is_some());
+    assert_eq!(summary.render_steps(),"\
+read block at 268 → OpenABlock → truncate to 256
+read block at 184 → Closed → retry with ECC
+read block at 184 → Closed → keep, file ends at 256");
+}
+#[test]
 fn test_tail_test_recovery_open_2_corrupt_1() {
     let block_start = 184;
     let mut file_contents = generate_test_file().into_inner();
@@ -529,7 +712,12 @@ fn test_tail_test_recovery_open_2_corrupt_1() {
         tot_errors_corrected,
         file_ops,
         corrupted_content_blocks,
+        last_batch_marker,
+        tail_block_unverified,
+        ..
     } = summary;
+    assert_eq!(last_batch_marker, None);
+    assert!(tail_block_unverified);
     assert_eq!(tot_errors_corrected, 0);
     assert_eq!(recovered_file_len as usize, 172);
     assert_eq!(original_file_len, new_len as u64);
@@ -558,4 +746,1569 @@ fn test_tail_test_recovery_open_2_corrupt_1() {
             _ => panic!("Too many ops!")
         }
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_recoverable_at_every_truncation() {
+    let file_content = generate_test_file().into_inner();
+    assert_recoverable_at_every_truncation(&file_content);
+}
+
+#[test]
+fn test_try_read_block_3_corrupt_length() {
+    let cursor = generate_test_file();
+    let block_start = 268;
+    let mut v = cursor.into_inner();
+    //a length that passes header ECC, but runs past the end of the file
+    v[block_start+9..block_start+13].copy_from_slice(&1000u32.to_le_bytes());
+    let mut ecc = Vec::new();
+    docufort::ecc::calculate_ecc_chunk(&v[block_start..block_start+HEADER_LEN], &mut ecc).unwrap();
+    v[block_start+HEADER_LEN..block_start+HEADER_LEN+ECC_LEN].copy_from_slice(&ecc);
+    let mut cursor = Cursor::new(v);
+    cursor.set_position(block_start as u64);
+    match try_read_block::<_,DummyInput>(&mut cursor, true,true).unwrap() {
+        BlockState::CorruptContentLength { component_start, is_b_block } => {
+            assert_eq!(component_start,block_start as u64);
+            assert!(!is_b_block);
+        },
+        a => panic!("Invalid Read: {:?}",a),
+    }
+    let summary = recover_tail::<_, DummyInput>(&mut cursor).unwrap();
+    assert_eq!(summary.recovered_file_len, (block_start - MN_ECC_LEN) as u64);
+}
+#[test]
+fn test_try_read_block_1_corrupt_component_length() {
+    let mut v = generate_test_file().into_inner();
+    let component_start = 23 + HEADER_LEN + ECC_LEN;
+    //past the end of the file, the file still ends in block 3's BlockEnd
+    v[component_start+9..component_start+13].copy_from_slice(&100_000u32.to_le_bytes());
+    let mut ecc = Vec::new();
+    docufort::ecc::calculate_ecc_chunk(&v[component_start..component_start+HEADER_LEN], &mut ecc).unwrap();
+    v[component_start+HEADER_LEN..component_start+HEADER_LEN+ECC_LEN].copy_from_slice(&ecc);
+    let mut cursor = Cursor::new(v.clone());
+    cursor.set_position(23);
+    let state = try_read_block::<_,DummyInput>(&mut cursor, true,true).unwrap();
+    assert!(matches!(state,BlockState::CorruptContentLength { component_start:c, is_b_block:true } if c == component_start as u64),"{:?}",state);
+
+    //a length that fits in the file is not reported as corrupt, even though the file ends in a BlockEnd
+    v[component_start+9..component_start+13].copy_from_slice(&200u32.to_le_bytes());
+    let mut ecc = Vec::new();
+    docufort::ecc::calculate_ecc_chunk(&v[component_start..component_start+HEADER_LEN], &mut ecc).unwrap();
+    v[component_start+HEADER_LEN..component_start+HEADER_LEN+ECC_LEN].copy_from_slice(&ecc);
+    let mut cursor = Cursor::new(v);
+    cursor.set_position(23);
+    let state = try_read_block::<_,DummyInput>(&mut cursor, true,true).unwrap();
+    assert!(!matches!(state,BlockState::CorruptContentLength { .. }),"{:?}",state);
+}
+#[test]
+fn test_try_read_block_3_truncated_length() {
+    let cursor = generate_test_file();
+    let block_start = 268;
+    let mut v = cursor.into_inner();
+    v.truncate(block_start+HEADER_LEN+ECC_LEN*2+A_CONTENT.len()-1);//last data byte missing
+    let mut cursor = Cursor::new(v);
+    cursor.set_position(block_start as u64);
+    match try_read_block::<_,DummyInput>(&mut cursor, true,true).unwrap() {
+        BlockState::OpenABlock { truncate_at } => {
+            assert_eq!(truncate_at,(block_start-MN_ECC_LEN) as u64);
+        },
+        a => panic!("Invalid Read: {:?}",a),
+    }
+}
+#[test]
+fn test_try_read_block_3_correction_headroom() {
+    use docufort::ecc::MAX_CORRECTABLE_ERRORS;
+    let block_start = 268;
+    let content_start = block_start + HEADER_LEN + ECC_LEN;
+    for corrupt in 0..=MAX_CORRECTABLE_ERRORS {
+        let mut v = generate_test_file().into_inner();
+        for i in 0..corrupt {
+            v[content_start+i*2] ^= 0xFF;
+        }
+        let mut cursor = Cursor::new(v);
+        cursor.set_position(block_start as u64);
+        match try_read_block::<_,DummyInput>(&mut cursor, true,true).unwrap() {
+            BlockState::Closed(BlockReadSummary { errors_corrected, min_correction_headroom, .. }) => {
+                assert_eq!(errors_corrected,corrupt);
+                assert_eq!(min_correction_headroom,MAX_CORRECTABLE_ERRORS-corrupt);
+            },
+            a => panic!("Invalid Read: {:?}",a),
+        }
+    }
+}
+#[test]
+fn test_heartbeat_recovers_closed() {
+    let mut cursor = generate_test_file();
+    cursor.seek(std::io::SeekFrom::End(0)).unwrap();
+    let block_start = cursor.position() + MN_ECC_LEN as u64;
+    write_magic_number(&mut cursor).unwrap();
+    write_heartbeat::<_,DummyInput>(&mut cursor, 42).unwrap();
+    let file_len = cursor.get_ref().len() as u64;
+    assert_eq!(file_len - block_start,(2*(HEADER_LEN+ECC_LEN)+HASH_AND_ECC_LEN) as u64);
+
+    let summary = recover_tail::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(summary.recovered_file_len,file_len);
+    match summary.file_ops.last().unwrap() {
+        (_,BlockState::Closed(BlockReadSummary { block_start:start, block_start_timestamp, block:Block::A { middle, end, .. }, .. })) => {
+            assert_eq!(*start,block_start);
+            assert_eq!(*block_start_timestamp,42);
+            assert_eq!(middle.data_len,StoredLen(0));
+            assert_eq!(u64::from_be_bytes(end.header.time_stamp()),42);
+        },
+        x => panic!("expected a closed atomic block, got {:?}",x),
+    }
+}
+
+///Fails the first truncate call, then behaves like the inner cursor.
+struct FailTruncateOnce(Cursor<Vec<u8>>,bool);
+impl std::io::Read for FailTruncateOnce {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {self.0.read(buf)}
+}
+impl std::io::Write for FailTruncateOnce {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {self.0.write(buf)}
+    fn flush(&mut self) -> std::io::Result<()> {self.0.flush()}
+}
+impl std::io::Seek for FailTruncateOnce {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {self.0.seek(pos)}
+}
+impl FileLike for FailTruncateOnce {
+    fn truncate(&mut self, len: u64)->std::io::Result<()>{
+        if !self.1 {
+            self.1 = true;
+            return Err(std::io::Error::new(std::io::ErrorKind::Interrupted, "transient"))
+        }
+        FileLike::truncate(&mut self.0, len)
+    }
+    fn len(&self)->std::io::Result<u64> {self.0.len()}
+}
+#[test]
+fn test_recover_tail_retries_truncate() {
+    use docufort::retry_writer::RetryingFile;
+    let mut file_content = generate_test_file().into_inner();
+    file_content.truncate(290);//part way through block 3
+    let expected = recover_tail::<_,DummyInput>(&mut Cursor::new(file_content.clone())).unwrap();
+    assert!(expected.recovered_file_len < 290);
+
+    let mut bare = FailTruncateOnce(Cursor::new(file_content.clone()),false);
+    assert!(matches!(recover_tail::<_,DummyInput>(&mut bare),Err(ReadWriteError::Io(_))));
+
+    let mut file = RetryingFile::new(FailTruncateOnce(Cursor::new(file_content),false),3);
+    let summary = recover_tail::<_,DummyInput>(&mut file).unwrap();
+    assert_eq!(summary.recovered_file_len,expected.recovered_file_len);
+    assert!(file.get_ref().1);
+    assert_eq!(file.into_inner().0.into_inner().len() as u64,expected.recovered_file_len);
+}
+#[test]
+fn test_deferred_patches_match_inline() {
+    use docufort::read::{DeferredPatches, apply_patches};
+    let block_start = 268;
+    let mut v = generate_test_file().into_inner();
+    let orig = v.clone();
+    v[block_start+1] ^= 0xFF;//start header
+    v[block_start+HEADER_LEN+ECC_LEN*2] ^= 0xFF;//content
+    v[orig.len()-3] ^= 0xFF;//hash ecc
+
+    let mut inline = Cursor::new(v.clone());
+    inline.set_position(block_start as u64);
+    let inline_state = try_read_block::<_,DummyInput>(&mut inline, true,true).unwrap();
+
+    let mut deferred = Cursor::new(v.clone());
+    deferred.set_position(block_start as u64);
+    let mut patcher = DeferredPatches::new(&mut deferred);
+    let deferred_state = try_read_block::<_,DummyInput>(&mut patcher, true,true).unwrap();
+    let patches = patcher.into_patches();
+    let num_patches = patches.len();
+    assert!(num_patches >= 3);
+    assert_eq!(deferred.get_ref(),&v,"nothing written until applied");
+    assert_eq!(apply_patches(&mut deferred, patches).unwrap(),num_patches);
+
+    assert_eq!(inline_state,deferred_state);
+    assert_eq!(inline.into_inner(),orig);
+    assert_eq!(deferred.into_inner(),orig);
+}
+#[test]
+fn test_tailing_block_iter() {
+    use docufort::read::TailingBlockIter;
+    let full = generate_test_file().into_inner();
+    let mut cursor = Cursor::new(full[..290].to_vec());//part way through block 3
+    let mut iter = TailingBlockIter::<_,DummyInput>::new(&mut cursor);
+    let first_pass:Vec<_> = iter.by_ref().map(|b|b.unwrap().block_start).collect();
+    assert_eq!(first_pass,vec![23,184]);
+    assert!(iter.next().is_none());
+    assert_eq!(iter.next_block(),268-MN_ECC_LEN as u64);
+
+    //writer finishes the block
+    let file = iter.get_mut();
+    file.seek(std::io::SeekFrom::End(0)).unwrap();
+    file.write_all(&full[290..]).unwrap();
+
+    iter.refresh();
+    let second_pass:Vec<_> = iter.by_ref().map(|b|b.unwrap().block_start).collect();
+    assert_eq!(second_pass,vec![268]);
+    assert_eq!(iter.next_block(),full.len() as u64);
+}
+#[cfg(feature = "crc_only")]
+#[test]
+fn test_crc_only_uncorrectable_end_header() {
+    let mut v = generate_test_file().into_inner();
+    assert_eq!(v[10],0);//file header stores an ECC_LEN of 0
+    let end_header = v.len() - HASH_AND_ECC_LEN - HEADER_LEN - ECC_LEN;
+    v[end_header+2] ^= 1;
+    let mut cursor = Cursor::new(v);
+    let summary = recover_tail::<_,DummyInput>(&mut cursor).unwrap();
+    //a single flipped bit can't be corrected, so the whole last (atomic) block is dropped.
+    assert_eq!(summary.recovered_file_len,268-MN_ECC_LEN as u64);
+    assert_eq!(summary.tot_errors_corrected,0);
+    assert!(matches!(summary.file_ops[0].1,BlockState::DataCorruption { component_tag:ComponentTag::EndHeader, .. }));
+}
+#[cfg(feature = "recovery_lock")]
+fn temp_test_file(name:&str)->std::path::PathBuf{
+    let path = std::env::temp_dir().join(format!("docufort_{}_{}",name,std::process::id()));
+    let mut v = generate_test_file().into_inner();
+    v.truncate(290);//needs recovery
+    std::fs::write(&path, v).unwrap();
+    path
+}
+#[cfg(feature = "recovery_lock")]
+#[test]
+fn test_recovery_lock_held() {
+    let path = temp_test_file("lock_held");
+    let open = ||std::fs::OpenOptions::new().read(true).write(true).open(&path).unwrap();
+    let mut first = open();
+    let inner = with_recovery_lock(&mut first, |_|{
+        let mut second = open();
+        Ok(with_recovery_lock(&mut second, recover_tail::<_,DummyInput>))
+    }).unwrap();
+    assert!(matches!(inner,Err(ReadWriteError::Locked)));
+    //released once the closure returns
+    let summary = with_recovery_lock(&mut open(), recover_tail::<_,DummyInput>).unwrap();
+    assert_eq!(summary.recovered_file_len,268-MN_ECC_LEN as u64);
+    std::fs::remove_file(&path).unwrap();
+}
+#[cfg(feature = "recovery_lock")]
+#[test]
+fn test_recovery_lock_threads() {
+    let path = temp_test_file("lock_threads");
+    let barrier = std::sync::Arc::new(std::sync::Barrier::new(2));
+    let handles:Vec<_> = (0..2).map(|_|{
+        let (path,barrier) = (path.clone(),barrier.clone());
+        std::thread::spawn(move ||{
+            let mut file = std::fs::OpenOptions::new().read(true).write(true).open(&path).unwrap();
+            barrier.wait();
+            with_recovery_lock(&mut file, |f|{
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                recover_tail::<_,DummyInput>(f)
+            })
+        })
+    }).collect();
+    let results:Vec<_> = handles.into_iter().map(|h|h.join().unwrap()).collect();
+    //either one was locked out, or they ran one after the other
+    for r in results.iter() {
+        match r {
+            Ok(summary) => assert_eq!(summary.recovered_file_len,268-MN_ECC_LEN as u64),
+            Err(ReadWriteError::Locked) => (),
+            Err(e) => panic!("{:?}",e),
+        }
+    }
+    assert!(results.iter().any(|r|r.is_ok()));
+    assert_eq!(std::fs::metadata(&path).unwrap().len(),268-MN_ECC_LEN as u64);
+    std::fs::remove_file(&path).unwrap();
+}
+#[test]
+fn test_recover_chained_open_b_block() {
+    use docufort::integrity::integrity_check_file;
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let prev = write_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, true, None, None).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let mut hasher = chained_hasher::<DummyInput>(Some(&prev.hash));
+    write_header(&mut cursor, &ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8 | CHAINED, [0;8], None)).unwrap();
+    for calc_ecc in [false,true] {
+        write_content_component(&mut cursor, calc_ecc, None, None, B_CONTENT, &mut hasher).unwrap();
+    }
+    let good_len = cursor.position();
+    //crashed while writing the third component
+    cursor.get_mut().extend_from_slice(&[HeaderTag::CComponent as u8,1,2]);
+    let summary = recover_tail::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(summary.recovered_file_len,good_len + (HEADER_LEN + ECC_LEN + HASH_AND_ECC_LEN) as u64);
+    let hash_start = summary.recovered_file_len as usize - HASH_AND_ECC_LEN;
+    assert_eq!(&cursor.get_ref()[hash_start..hash_start + HASH_LEN],&hasher.finalize());
+
+    //it verifies, and so does the next block chained to it
+    write_magic_number(&mut cursor).unwrap();
+    write_chained_atomic_block::<_,DummyInput>(&mut cursor, Some(&hasher.finalize()), None, A_CONTENT, true, None, None).unwrap();
+    let check = integrity_check_file::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(check.num_blocks,3);
+    assert!(check.corrupted_segments.is_empty());
+}
+#[test]
+fn test_recover_unreadable_first_magic_number() {
+    let mut v = generate_test_file().into_inner();
+    //the whole first magic number is destroyed, the rest of the file is intact but can not be found
+    v[FILE_HEADER_LEN as usize..FILE_HEADER_LEN as usize + MN_ECC_LEN].fill(0xAA);
+    v.truncate(184 - MN_ECC_LEN);
+    let len = v.len();
+    let mut cursor = Cursor::new(v);
+    let res = recover_tail::<_,DummyInput>(&mut cursor);
+    assert!(matches!(res,Err(ReadWriteError::DataCorruption { data_start }) if data_start == FILE_HEADER_LEN as u64),"{:?}",res);
+    assert_eq!(cursor.get_ref().len(),len);
+}
+#[test]
+fn test_recover_partial_first_magic_number() {
+    let mut v = generate_test_file().into_inner();
+    v.truncate(FILE_HEADER_LEN as usize + 3);
+    let mut cursor = Cursor::new(v);
+    let summary = recover_tail::<_,DummyInput>(&mut cursor).unwrap();
+    assert!(!summary.has_blocks);
+    assert_eq!(summary.original_file_len,FILE_HEADER_LEN as u64 + 3);
+    assert_eq!(summary.recovered_file_len,FILE_HEADER_LEN as u64);
+    assert_eq!(cursor.get_ref().len(),FILE_HEADER_LEN as usize);
+    //and a fresh block can be appended right after the header
+    cursor.seek(std::io::SeekFrom::End(0)).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    write_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, false, None,None).unwrap();
+    cursor.set_position(0);
+    let check = docufort::integrity::integrity_check_file::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(check.num_blocks,1);
+}
+#[test]
+fn test_recover_long_trailing_garbage() {
+    let mut v = generate_test_file().into_inner();
+    let len = v.len();
+    //too long to be a torn magic number behind alignment padding
+    v.extend(std::iter::repeat(0xAB).take(MN_ECC_LEN + MAX_ALIGNMENT as usize));
+    let garbage_len = v.len();
+    let mut cursor = Cursor::new(v);
+    let res = recover_tail::<_,DummyInput>(&mut cursor);
+    assert!(matches!(res,Err(ReadWriteError::DataCorruption { data_start }) if data_start == len as u64),"{:?}",res);
+    assert_eq!(cursor.get_ref().len(),garbage_len);
+    //one byte less is still treated as a torn write
+    cursor.get_mut().truncate(garbage_len - 1);
+    let summary = recover_tail::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(summary.recovered_file_len,len as u64);
+}
+#[test]
+fn test_recover_closes_large_b_block() {
+    use docufort::read::hash_range;
+    let mut cursor = Cursor::new(Vec::new());
+    let mut hasher = DummyInput::new();
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let block_start = cursor.position();
+    let b_block_header = ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_header(&mut cursor, &b_block_header).unwrap();
+    let big:Vec<u8> = (0..1024*1024).map(|i|(i % 251) as u8).collect();
+    for calc_ecc in [false,true,false] {
+        write_content_component(&mut cursor, calc_ecc, None, None, &big, &mut hasher).unwrap();
+    }
+    let good_end = cursor.position();
+    //torn fourth component
+    write_content_component(&mut cursor, false, None, None, &big, &mut hasher).unwrap();
+    let mut v = cursor.into_inner();
+    v.truncate(good_end as usize + 1000);
+
+    let mut cursor = Cursor::new(v);
+    let summary = recover_tail::<_,DummyInput>(&mut cursor).unwrap();
+    assert!(summary.recovered_file_len > 3*1024*1024);
+    let mut expected = DummyInput::new();
+    hash_range(&mut cursor, block_start + (HEADER_LEN + ECC_LEN) as u64, good_end, &mut expected).unwrap();
+
+    cursor.set_position(block_start);
+    match try_read_block::<_,DummyInput>(&mut cursor, true,true).unwrap() {
+        BlockState::Closed(BlockReadSummary { block:Block::B { middle, end, .. }, hash_as_read, .. }) => {
+            assert_eq!(middle.len(),3);
+            assert_eq!(end.hash.hash(),&expected.finalize()[..]);
+            assert_eq!(end.hash.hash(),&hash_as_read[..]);
+        },
+        a => panic!("Invalid Read: {:?}",a),
+    }
+}
+fn batch_file() -> (Vec<u8>,Vec<u64>) {
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    let mut block_starts = Vec::new();
+    for index in 0..5 {
+        let marker = BatchMarker{ seq: 7, index };
+        write_magic_number(&mut cursor).unwrap();
+        block_starts.push(cursor.position());
+        if index == 1 {
+            let mut hasher = DummyInput::new();
+            write_batch_block_start(&mut cursor, &marker, None, &mut hasher).unwrap();
+            write_content_component(&mut cursor, true, None, None, B_CONTENT, &mut hasher).unwrap();
+            let end = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+            write_block_end(&mut cursor, &end, &hasher.finalize()).unwrap();
+        }else{
+            write_batch_atomic_block::<_,DummyInput>(&mut cursor, &marker, None, A_CONTENT, index % 2 == 0, None, None).unwrap();
+        }
+    }
+    (cursor.into_inner(),block_starts)
+}
+#[test]
+fn test_recover_batch_marker() {
+    let (v,block_starts) = batch_file();
+    let mut cursor = Cursor::new(v.clone());
+    let summary = recover_tail::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(summary.last_batch_marker,Some(BatchMarker{ seq: 7, index: 4 }));
+    assert_eq!(docufort::read::catalog(&mut Cursor::new(v.clone())).unwrap().len(),5);
+    assert_recoverable_at_every_truncation(&v);
+
+    //crash part way through the third block
+    let mut torn = v;
+    torn.truncate(block_starts[2] as usize + HEADER_LEN + 3);
+    let mut cursor = Cursor::new(torn);
+    let summary = recover_tail::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(summary.recovered_file_len,block_starts[2] - MN_ECC_LEN as u64);
+    let marker = summary.last_batch_marker.unwrap();
+    assert_eq!(marker,BatchMarker{ seq: 7, index: 1 });
+    //resume the batch
+    for index in marker.index+1..5 {
+        write_magic_number(&mut cursor).unwrap();
+        write_batch_atomic_block::<_,DummyInput>(&mut cursor, &BatchMarker{ seq: 7, index }, None, A_CONTENT, false, None, None).unwrap();
+    }
+    let summary = recover_tail::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(summary.last_batch_marker,Some(BatchMarker{ seq: 7, index: 4 }));
+
+    //blocks without a marker after the batch, and a torn one that is dropped, still report the last batch block
+    write_magic_number(&mut cursor).unwrap();
+    write_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, false, None, None).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let closed_len = cursor.position();
+    write_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, false, None, None).unwrap();
+    let mut torn = cursor.into_inner();
+    torn.truncate(closed_len as usize + HEADER_LEN);
+    let mut cursor = Cursor::new(torn);
+    let summary = recover_tail::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(summary.recovered_file_len,closed_len - MN_ECC_LEN as u64);
+    assert_eq!(summary.last_batch_marker,Some(BatchMarker{ seq: 7, index: 4 }));
+    assert_eq!(cursor.position(),summary.recovered_file_len);
+}
+#[test]
+fn test_batch_marker_ecc() {
+    let (mut v,block_starts) = batch_file();
+    let marker_start = block_starts[0] as usize + HEADER_LEN + ECC_LEN;
+    v[marker_start+7] ^= 0xFF;
+    let mut cursor = Cursor::new(v);
+    cursor.set_position(block_starts[0]);
+    match try_read_block::<_,DummyInput>(&mut cursor, true,true).unwrap() {
+        BlockState::Closed(BlockReadSummary { errors_corrected, batch_marker, block:Block::A { end, .. }, hash_as_read, .. }) => {
+            assert_eq!(errors_corrected,1);
+            assert_eq!(batch_marker,Some(BatchMarker{ seq: 7, index: 0 }));
+            assert_eq!(end.hash.hash(),&hash_as_read[..]);
+        },
+        a => panic!("Invalid Read: {:?}",a),
+    }
+}
+#[test]
+fn test_estimate_recovery() {
+    let v = generate_test_file().into_inner();
+    let estimate = estimate_recovery::<_,DummyInput>(&mut Cursor::new(v.clone())).unwrap();
+    assert_eq!(estimate,RecoveryEstimate{ file_len: 344, last_block_start: Some(268), tail_bytes: 344-256, tail_open: false });
+
+    let mut torn = v;
+    torn.truncate(290);//part way through block 3
+    torn[268+1] ^= 0xFF;//a correctable header error that must not be written back
+    let orig = torn.clone();
+    let mut cursor = Cursor::new(torn);
+    let estimate = estimate_recovery::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(estimate,RecoveryEstimate{ file_len: 290, last_block_start: Some(268), tail_bytes: 290-256, tail_open: true });
+    assert_eq!(cursor.into_inner(),orig);
+    let summary = recover_tail::<_,DummyInput>(&mut Cursor::new(orig)).unwrap();
+    assert_eq!(summary.original_file_len - summary.recovered_file_len,estimate.tail_bytes);
+
+    let estimate = estimate_recovery::<_,DummyInput>(&mut Cursor::new(generate_test_file().into_inner()[..FILE_HEADER_LEN as usize + 5].to_vec())).unwrap();
+    assert_eq!(estimate,RecoveryEstimate{ file_len: FILE_HEADER_LEN as u64 + 5, last_block_start: None, tail_bytes: 5, tail_open: true });
+}
+///Block 3 with more errors in its only content chunk than ECC can correct.
+fn uncorrectable_tail() -> Vec<u8> {
+    let mut v = generate_test_file().into_inner();
+    let data_start = 268+HEADER_LEN+ECC_LEN+ECC_LEN;
+    for i in 0..ECC_LEN {
+        v[data_start+i] ^= 0xFF;
+    }
+    v
+}
+#[test]
+fn test_recovery_policy_keep() {
+    let mut cursor = Cursor::new(uncorrectable_tail());
+    let summary = recover_tail_with_policy::<_,DummyInput>(&mut cursor, RecoveryPolicy{ uncorrectable_content: UncorrectableContent::Keep, ..Default::default() }).unwrap();
+    assert_eq!(summary.recovered_file_len,344);
+    assert!(summary.tail_block_unverified);
+    assert!(!summary.corrupted_content_blocks.is_empty());
+    assert_eq!(summary,recover_tail::<_,DummyInput>(&mut Cursor::new(uncorrectable_tail())).unwrap());
+    assert!(!recover_tail::<_,DummyInput>(&mut generate_test_file()).unwrap().tail_block_unverified);
+}
+#[test]
+fn test_recovery_policy_truncate_block() {
+    let mut cursor = Cursor::new(uncorrectable_tail());
+    let summary = recover_tail_with_policy::<_,DummyInput>(&mut cursor, RecoveryPolicy{ uncorrectable_content: UncorrectableContent::TruncateBlock, ..Default::default() }).unwrap();
+    assert_eq!(summary.recovered_file_len,256);
+    assert_eq!(cursor.get_ref().len(),256);
+    assert!(summary.corrupted_content_blocks.is_empty());
+    assert!(!summary.tail_block_unverified);
+    assert!(matches!(summary.file_ops.last(),Some((184,BlockState::Closed(_)))));
+}
+#[test]
+fn test_recovery_policy_fail() {
+    let orig = uncorrectable_tail();
+    let mut cursor = Cursor::new(orig.clone());
+    let res = recover_tail_with_policy::<_,DummyInput>(&mut cursor, RecoveryPolicy{ uncorrectable_content: UncorrectableContent::Fail, ..Default::default() });
+    assert!(matches!(res,Err(ReadWriteError::HashMismatch { block_start: 268 })),"{:?}",res);
+    assert_eq!(cursor.into_inner().len(),orig.len());
+}
+#[test]
+fn test_recovery_policy_drop_duplicate_tail() {
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    write_atomic_block::<_,DummyInput>(&mut cursor, None, B_CONTENT, true, None, None).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    write_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, true, None, None).unwrap();
+    let first_end = cursor.position();
+    //the retry wrote the same block again (with a later timestamp)
+    write_magic_number(&mut cursor).unwrap();
+    write_atomic_block::<_,DummyInput>(&mut cursor, Some(DummyInput::current_timestamp() + 1), A_CONTENT, true, None, None).unwrap();
+    let orig = cursor.into_inner();
+
+    //by default both are kept
+    let summary = recover_tail::<_,DummyInput>(&mut Cursor::new(orig.clone())).unwrap();
+    assert_eq!(summary.recovered_file_len,orig.len() as u64);
+
+    let policy = RecoveryPolicy{ drop_duplicate_tail: true, ..Default::default() };
+    let mut cursor = Cursor::new(orig.clone());
+    let summary = recover_tail_with_policy::<_,DummyInput>(&mut cursor, policy).unwrap();
+    assert_eq!(summary.recovered_file_len,first_end);
+    assert_eq!(cursor.get_ref()[..],orig[..first_end as usize]);
+    assert!(matches!(summary.steps()[0].action,RecoveryAction::DropBlock { .. }));
+
+    //with a batch marker as the key, the content of the retry may differ
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    let marker = BatchMarker{ seq: 7, index: 3 };
+    write_magic_number(&mut cursor).unwrap();
+    write_batch_atomic_block::<_,DummyInput>(&mut cursor, &BatchMarker{ seq: 7, index: 2 }, None, A_CONTENT, true, None, None).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    write_batch_atomic_block::<_,DummyInput>(&mut cursor, &marker, None, A_CONTENT, true, None, None).unwrap();
+    let first_end = cursor.position();
+    write_magic_number(&mut cursor).unwrap();
+    write_batch_atomic_block::<_,DummyInput>(&mut cursor, &marker, None, B_CONTENT, true, None, None).unwrap();
+    let summary = recover_tail_with_policy::<_,DummyInput>(&mut cursor, policy).unwrap();
+    assert_eq!(summary.recovered_file_len,first_end);
+    assert_eq!(summary.last_batch_marker,Some(marker));
+}
+#[test]
+fn test_recovery_policy_rollback_uncommitted() {
+    let build = |commit_first:bool|{
+        let mut cursor = Cursor::new(Vec::new());
+        init_file(&mut cursor).unwrap();
+        write_magic_number(&mut cursor).unwrap();
+        if commit_first {
+            write_committed_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, true, None, None).unwrap();
+        }else{
+            write_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, true, None, None).unwrap();
+        }
+        let committed_end = cursor.position();
+        write_magic_number(&mut cursor).unwrap();
+        write_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, false, None, None).unwrap();
+        //a well formed B block, just never committed
+        let mut hasher = DummyInput::new();
+        write_magic_number(&mut cursor).unwrap();
+        write_header(&mut cursor, &ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None)).unwrap();
+        write_content_component(&mut cursor, true, None, None, B_CONTENT, &mut hasher).unwrap();
+        write_block_end(&mut cursor, &ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None), &hasher.finalize()).unwrap();
+        (cursor.into_inner(),committed_end)
+    };
+    let (orig,committed_end) = build(true);
+
+    //by default all three complete blocks are kept
+    let summary = recover_tail::<_,DummyInput>(&mut Cursor::new(orig.clone())).unwrap();
+    assert_eq!(summary.recovered_file_len,orig.len() as u64);
+
+    let policy = RecoveryPolicy{ rollback_uncommitted: true, ..Default::default() };
+    let mut cursor = Cursor::new(orig.clone());
+    let summary = recover_tail_with_policy::<_,DummyInput>(&mut cursor, policy).unwrap();
+    assert_eq!(summary.recovered_file_len,committed_end);
+    assert_eq!(cursor.get_ref()[..],orig[..committed_end as usize]);
+    let Some((_,BlockState::Closed(BlockReadSummary { block, .. }))) = summary.file_ops.last() else {panic!("{:?}",summary.file_ops)};
+    assert!(block.clone().take_start().is_committed());
+
+    //with no commit point everything is rolled back
+    let mut cursor = Cursor::new(build(false).0);
+    let summary = recover_tail_with_policy::<_,DummyInput>(&mut cursor, policy).unwrap();
+    assert!(!summary.has_blocks);
+    assert_eq!(cursor.get_ref().len(),FILE_HEADER_LEN as usize);
+}
+#[test]
+fn test_recovery_policy_max_corrections() {
+    //correctable errors in the header and content of the last A block
+    let mut orig = generate_test_file().into_inner();
+    for i in [270,289,291] {orig[i] ^= 0x55;}
+    let unlimited = recover_tail::<_,DummyInput>(&mut Cursor::new(orig.clone())).unwrap();
+    assert_eq!(unlimited.tot_errors_corrected,3);
+    assert_eq!(unlimited.recovered_file_len,orig.len() as u64);
+
+    let res = recover_tail_with_policy::<_,DummyInput>(&mut Cursor::new(orig.clone()), RecoveryPolicy{ max_corrections: Some(2), ..Default::default() });
+    assert!(matches!(res,Err(ReadWriteError::TooMuchCorruption(3))),"{:?}",res);
+    let summary = recover_tail_with_policy::<_,DummyInput>(&mut Cursor::new(orig.clone()), RecoveryPolicy{ max_corrections: Some(3), ..Default::default() }).unwrap();
+    assert_eq!(summary,unlimited);
+}
+
+#[test]
+fn test_tail_recovery_open_b_at_component_boundary() {
+    let start_end = 23 + HEADER_LEN + ECC_LEN;
+    let first_data = start_end + HEADER_LEN + ECC_LEN;
+    let first_end = first_data + B_CONTENT.len();
+    //second component has ECC
+    let second_end = first_end + HEADER_LEN + ECC_LEN + B_CONTENT.len() + docufort::ecc::calc_ecc_data_len(B_CONTENT.len());
+    for (boundary, components) in [(start_end, 0), (first_end, 1), (second_end, 2)] {
+        let mut file_content = generate_test_file().into_inner();
+        file_content.truncate(boundary);
+        let mut cursor = Cursor::new(file_content);
+        let summary = recover_tail::<_, DummyInput>(&mut cursor).unwrap();
+        assert_eq!(summary.original_file_len as usize, boundary);
+        assert_eq!(summary.recovered_file_len as usize, boundary + HEADER_LEN + ECC_LEN + HASH_AND_ECC_LEN);
+        let mut hasher = DummyInput::new();
+        let mut scratch = Cursor::new(Vec::new());
+        for ecc in [false, true].into_iter().take(components) {
+            write_content_component(&mut scratch, ecc, None, None, B_CONTENT, &mut hasher).unwrap();
+        }
+        let expected_hash = hasher.finalize();
+        cursor.set_position(23);
+        let BlockState::Closed(BlockReadSummary { hash_as_read, block: Block::B { middle, .. }, .. }) = try_read_block::<_, DummyInput>(&mut cursor, false, false).unwrap() else {panic!("not closed at {}", boundary)};
+        assert_eq!(middle.len(), components);
+        assert_eq!(hash_as_read, expected_hash);
+        assert_eq!(cursor.get_ref().len(), boundary + HEADER_LEN + ECC_LEN + HASH_AND_ECC_LEN);
+    }
+}
+
+/// Fails every mutation once `budget` is spent. A truncate costs 1, a write costs 1 per byte and may be torn.
+struct CrashingFile{
+    inner:Cursor<Vec<u8>>,
+    budget:usize
+}
+impl std::io::Read for CrashingFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+impl std::io::Seek for CrashingFile {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+impl std::io::Write for CrashingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.budget == 0 {return Err(std::io::Error::other("crash"))}
+        let n = buf.len().min(self.budget);
+        self.budget -= n;
+        self.inner.write(&buf[..n])
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+impl FileLike for CrashingFile {
+    fn truncate(&mut self, len: u64) -> std::io::Result<()> {
+        if self.budget == 0 {return Err(std::io::Error::other("crash"))}
+        self.budget -= 1;
+        self.inner.truncate(len)
+    }
+    fn len(&self) -> std::io::Result<u64> {
+        self.inner.len()
+    }
+}
+
+#[test]
+fn test_tail_recovery_survives_crash() {
+    let full = generate_test_file().into_inner();
+    //in the last B content, in the first A block, and in the last A block
+    for cut in [23 + 70, 184 + 20, 268 + 30] {
+        let mut expected = Cursor::new(full[..cut].to_vec());
+        recover_tail::<_, DummyInput>(&mut expected).unwrap();
+        let expected = expected.into_inner();
+        let mut budget = 0;
+        loop {
+            let mut crashing = CrashingFile { inner: Cursor::new(full[..cut].to_vec()), budget };
+            let crashed = recover_tail::<_, DummyInput>(&mut crashing).is_err();
+            let mut rerun = crashing.inner;
+            rerun.set_position(0);
+            recover_tail::<_, DummyInput>(&mut rerun).unwrap();
+            assert_eq!(rerun.into_inner(), expected, "cut {} budget {}", cut, budget);
+            if !crashed {break}
+            budget += 1;
+        }
+        assert!(budget > 0, "cut {} did not mutate the file", cut);
+    }
+}
+
+#[test]
+fn test_tail_recovery_open_b_policy() {
+    let block_start = 23;
+    let mut file_content = generate_test_file().into_inner();
+    let new_len = block_start + HEADER_LEN * 2 + ECC_LEN * 2 + 4;
+    file_content.truncate(new_len);
+
+    let mut closed = Cursor::new(file_content.clone());
+    let policy = RecoveryPolicy::default();
+    assert!(policy.close_open_b_blocks);
+    let summary = recover_tail_with_policy::<_, DummyInput>(&mut closed, policy).unwrap();
+    assert_eq!(summary.recovered_file_len, 81);
+    assert!(summary.has_blocks);
+
+    let mut dropped = Cursor::new(file_content);
+    let summary = recover_tail_with_policy::<_, DummyInput>(&mut dropped, RecoveryPolicy{ close_open_b_blocks: false, ..Default::default() }).unwrap();
+    //the only block was open, so nothing is left after the file header
+    assert_eq!(summary.recovered_file_len, FILE_HEADER_LEN as u64);
+    assert!(!summary.has_blocks);
+    assert_eq!(dropped.into_inner().len(), FILE_HEADER_LEN as usize);
+}
+
+#[test]
+fn test_atomic_replace() {
+    use docufort::read::{catalog, copy_block_raw};
+    use docufort::integrity::integrity_check_file;
+    let path = std::env::temp_dir().join(format!("docufort_atomic_replace_{}",std::process::id()));
+    std::fs::write(&path, generate_test_file().into_inner()).unwrap();
+
+    //compact: keep only the A blocks
+    let mut original = std::fs::File::open(&path).unwrap();
+    let keep:Vec<_> = catalog(&mut original).unwrap().into_iter().filter(|e|e.is_atomic).map(|e|e.offset).collect();
+    assert_eq!(keep,vec![184,268]);
+    atomic_replace::<DummyInput>(&path, |new|{
+        init_file(new)?;
+        for offset in keep.iter() {copy_block_raw::<_,_,DummyInput>(&mut original, *offset, new, true)?;}
+        Ok(())
+    }).unwrap();
+    drop(original);
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    assert!(!std::path::Path::new(&tmp_path).exists());
+    let mut replaced = std::fs::OpenOptions::new().read(true).write(true).open(&path).unwrap();
+    let check = integrity_check_file::<_,DummyInput>(&mut replaced).unwrap();
+    assert_eq!(check.num_blocks,2);
+    assert_eq!(check.block_times.iter().map(|(o,_)|*o).collect::<Vec<_>>(),vec![23,23+84]);
+
+    //an invalid replacement leaves the file as it was
+    let before = std::fs::read(&path).unwrap();
+    let res = atomic_replace::<DummyInput>(&path, |new|{
+        init_file(new)?;
+        write_magic_number(new)?;
+        Ok(())
+    });
+    assert!(matches!(&res,Err(ReadWriteError::Io(e)) if e.kind() == std::io::ErrorKind::InvalidData),"{:?}",res);
+    assert_eq!(std::fs::read(&path).unwrap(),before);
+    assert!(!std::path::Path::new(&tmp_path).exists());
+    std::fs::remove_file(&path).unwrap();
+}
+#[test]
+fn test_stray_block_end() {
+    use docufort::integrity::integrity_check_file;
+    let stray = {
+        let mut cursor = Cursor::new(Vec::new());
+        write_magic_number(&mut cursor).unwrap();
+        let header = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+        write_block_end(&mut cursor, &header, &[7u8;HASH_LEN]).unwrap();
+        cursor.into_inner()
+    };
+    //between the second and third block
+    let mut v = generate_test_file().into_inner();
+    let clean_len = v.len();
+    v.splice(256..256, stray.iter().copied());
+    let mut cursor = Cursor::new(v);
+    cursor.set_position(256 + MN_ECC_LEN as u64);
+    let res = try_read_block::<_,DummyInput>(&mut cursor, true, true).unwrap();
+    assert_eq!(res,BlockState::StrayBlockEnd { block_start: 268, skip_to: (256 + stray.len()) as u64 });
+    cursor.set_position(0);
+    let check = integrity_check_file::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(check.num_blocks,3);
+    assert_eq!(check.stray_block_ends,vec![268]);
+    assert_eq!(check.file_len_checked,(clean_len + stray.len()) as u64);
+    assert_eq!(check.block_times.iter().map(|(s,_)|*s).collect::<Vec<_>>(),vec![23,184,268 + stray.len() as u64]);
+
+    //at the tail, it is dropped and the block before it kept
+    let mut v = generate_test_file().into_inner();
+    v.extend_from_slice(&stray);
+    let mut cursor = Cursor::new(v);
+    let summary = recover_tail::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(summary.recovered_file_len,clean_len as u64);
+    assert_eq!(cursor.into_inner(),generate_test_file().into_inner());
+}
+#[test]
+fn test_needs_recovery_read_only() {
+    let check = |name:&str,v:Vec<u8>|{
+        let path = std::env::temp_dir().join(format!("docufort_needs_recovery_{}_{}",name,std::process::id()));
+        std::fs::write(&path, v).unwrap();
+        let mut perms = std::fs::metadata(&path).unwrap().permissions();
+        perms.set_readonly(true);
+        std::fs::set_permissions(&path, perms.clone()).unwrap();
+        let res = needs_recovery::<DummyInput>(&path).unwrap();
+        #[allow(clippy::permissions_set_readonly_false)]
+        perms.set_readonly(false);
+        std::fs::set_permissions(&path, perms).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        res
+    };
+    let clean = generate_test_file().into_inner();
+    assert!(!check("clean",clean.clone()));
+    assert!(check("truncated",clean[..290].to_vec()));
+}
+///Reports every write as complete, but never stores the byte that would land at `drop_at`.
+struct DroppingWriter{inner:Cursor<Vec<u8>>,drop_at:u64}
+impl std::io::Read for DroppingWriter {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {self.inner.read(buf)}
+}
+impl std::io::Write for DroppingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let pos = self.inner.position();
+        match self.drop_at.checked_sub(pos).map(|i|i as usize).filter(|i|*i < buf.len()) {
+            Some(i) => {
+                self.inner.write_all(&buf[..i])?;
+                self.inner.write_all(&buf[i+1..])?;
+            },
+            None => self.inner.write_all(buf)?,
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {Ok(())}
+}
+impl std::io::Seek for DroppingWriter {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {self.inner.seek(pos)}
+}
+impl FileLike for DroppingWriter {
+    fn truncate(&mut self, len: u64)->std::io::Result<()> {self.inner.truncate(len)}
+    fn len(&self)->std::io::Result<u64> {self.inner.len()}
+}
+#[test]
+fn test_write_verified() {
+    let write_block = |file:&mut DroppingWriter|{
+        write_magic_number(file)?;
+        write_atomic_block::<_,DummyInput>(file, None, A_CONTENT, true, None, None)
+    };
+    let mut file = DroppingWriter { inner: Cursor::new(Vec::new()), drop_at: u64::MAX };
+    init_file(&mut file).unwrap();
+    write_verified::<_,DummyInput,_>(&mut file, write_block).unwrap();
+    let good_end = file.inner.position();
+    assert_eq!(good_end,file.len().unwrap());
+    write_verified::<_,DummyInput,_>(&mut file, write_block).unwrap();
+
+    //a byte of the second block's content goes missing
+    let mut file = DroppingWriter { inner: Cursor::new(Vec::new()), drop_at: good_end + 40 };
+    init_file(&mut file).unwrap();
+    write_verified::<_,DummyInput,_>(&mut file, write_block).unwrap();
+    let res = write_verified::<_,DummyInput,_>(&mut file, write_block);
+    assert!(matches!(res,Err(ReadWriteError::HashMismatch { block_start }) if block_start == good_end + MN_ECC_LEN as u64),"{:?}",res);
+}
+#[test]
+fn test_magic_number_ecc() {
+    use docufort::ecc::magic_number_ecc;
+    assert_eq!(magic_number_ecc(&MAGIC_NUMBER),MN_ECC);
+    assert_ne!(magic_number_ecc(b"mylogfmt"),MN_ECC);
+}
+#[test]
+fn test_read_magic_number_error_count() {
+    use docufort::read::read_magic_number;
+    let clean = generate_test_file().into_inner();
+    let mut v = clean.clone();
+    v[FILE_HEADER_LEN as usize + 2] ^= 0xFF;
+    let damaged = v.clone();
+    let mut cursor = Cursor::new(v);
+    cursor.set_position(FILE_HEADER_LEN as u64);
+    //counted, but left on disk
+    assert_eq!(read_magic_number(&mut cursor, false).unwrap(),1);
+    assert_eq!(cursor.position(),23);
+    assert_eq!(cursor.get_ref(),&damaged);
+    cursor.set_position(FILE_HEADER_LEN as u64);
+    assert_eq!(read_magic_number(&mut cursor, true).unwrap(),1);
+    assert_eq!(cursor.get_ref(),&clean);
+}
+#[test]
+fn test_recovery_policy_verify_via() {
+    let clean = generate_test_file().into_inner();
+    let recover = |v:&[u8],verify_via:VerifyStrategy|{
+        let mut cursor = Cursor::new(v.to_vec());
+        let policy = RecoveryPolicy { verify_via, uncorrectable_content: UncorrectableContent::Fail, ..Default::default() };
+        recover_tail_with_policy::<_,DummyInput>(&mut cursor, policy).map(|s|(s,cursor.into_inner()))
+    };
+    //correctable: EccOnly fixes it in one read
+    let mut v = clean.clone();
+    v[289] ^= 0xFF;
+    let (summary,fixed) = recover(&v,VerifyStrategy::HashThenEcc).unwrap();
+    assert_eq!((summary.file_ops.len(),summary.tot_errors_corrected,&fixed),(2,1,&clean));
+    let (summary,fixed) = recover(&v,VerifyStrategy::EccOnly).unwrap();
+    assert_eq!((summary.file_ops.len(),summary.tot_errors_corrected,&fixed),(1,1,&clean));
+    assert!(matches!(recover(&v,VerifyStrategy::HashOnly),Err(ReadWriteError::HashMismatch { block_start: 268 })));
+
+    //a different (but intact) stored hash is never consulted by EccOnly
+    let mut cursor = Cursor::new(clean.clone());
+    cursor.set_position((clean.len() - HASH_AND_ECC_LEN) as u64);
+    write_block_hash(&mut cursor, &[7u8;HASH_LEN]).unwrap();
+    let v = cursor.into_inner();
+    assert!(matches!(recover(&v,VerifyStrategy::HashThenEcc),Err(ReadWriteError::HashMismatch { block_start: 268 })));
+    assert!(recover(&v,VerifyStrategy::EccOnly).is_ok());
+
+    //uncorrectable content is caught by ECC alone
+    let mut v = clean.clone();
+    for i in [289,291,293] {v[i] ^= 0xFF}
+    assert!(matches!(recover(&v,VerifyStrategy::EccOnly),Err(ReadWriteError::HashMismatch { block_start: 268 })));
+    let mut cursor = Cursor::new(v);
+    cursor.set_position(268);
+    let BlockState::Closed(brs) = try_read_block_verified::<_,DummyInput>(&mut cursor, true, VerifyStrategy::EccOnly).unwrap() else {panic!()};
+    assert_eq!(brs.hash_as_read,[0u8;HASH_LEN]);
+    assert!(matches!(brs.corrupted_content_blocks[..],[CorruptDataSegment::EccChunk { .. }]));
+}
+#[test]
+fn test_repair_header_config() {
+    let clean = generate_test_file().into_inner();
+    let mut corrupt = clean.clone();
+    corrupt[FILE_HEADER_LEN as usize - 1] ^= 0x10;
+    let mut file = Cursor::new(corrupt);
+    assert!(!docufort::read::verify_configs(&mut file).unwrap());
+    assert_eq!(repair_header_config(&mut file).unwrap(), Some(FILE_ECC_LEN_BYTE));
+    file.set_position(0);
+    assert!(docufort::read::verify_configs(&mut file).unwrap());
+    assert_eq!(file.get_ref(), &clean);
+    //nothing to infer it from without a block
+    let mut header_only = Cursor::new(clean[..FILE_HEADER_LEN as usize].to_vec());
+    assert_eq!(repair_header_config(&mut header_only).unwrap(), None);
+}
+///Sleeps for `delay` in each of its first `slow_reads` reads.
+struct SlowFile{inner:Cursor<Vec<u8>>,delay:std::time::Duration,slow_reads:usize}
+impl std::io::Read for SlowFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.slow_reads > 0 {
+            self.slow_reads -= 1;
+            std::thread::sleep(self.delay);
+        }
+        self.inner.read(buf)
+    }
+}
+impl std::io::Write for SlowFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {self.inner.write(buf)}
+    fn flush(&mut self) -> std::io::Result<()> {self.inner.flush()}
+}
+impl std::io::Seek for SlowFile {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {self.inner.seek(pos)}
+}
+impl FileLike for SlowFile {
+    fn truncate(&mut self, len: u64)->std::io::Result<()> {self.inner.truncate(len)}
+    fn len(&self)->std::io::Result<u64> {self.inner.len()}
+}
+#[test]
+fn test_read_write_error_is_transient() {
+    //EAGAIN
+    let err = ReadWriteError::from(std::io::Error::from(std::io::ErrorKind::WouldBlock));
+    assert!(matches!(err,ReadWriteError::Io(_)));
+    assert!(err.is_transient());
+    assert!(ReadWriteError::from(std::io::Error::from(std::io::ErrorKind::Interrupted)).is_transient());
+    assert!(ReadWriteError::Timeout.is_transient());
+    assert!(ReadWriteError::Locked.is_transient());
+    assert!(!ReadWriteError::from(std::io::Error::from(std::io::ErrorKind::PermissionDenied)).is_transient());
+    assert!(!ReadWriteError::EccTooManyErrors.is_transient());
+    assert!(!ReadWriteError::NotADocuFortFile.is_transient());
+}
+#[test]
+fn test_timeout_file() {
+    use docufort::read::TimeoutFile;
+    let clean = generate_test_file().into_inner();
+    let slow = |slow_reads| SlowFile{inner:Cursor::new(clean.clone()),delay:std::time::Duration::from_millis(200),slow_reads};
+    let mut file = TimeoutFile::new(slow(1), Some(std::time::Duration::from_millis(20)));
+    let mut buf = [0u8;FILE_HEADER_LEN as usize];
+    let err = file.read_exact(&mut buf).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    assert!(matches!(ReadWriteError::from(err), ReadWriteError::Timeout));
+    //the stale read is discarded and the position did not move
+    assert_eq!(file.stream_position().unwrap(), 0);
+    file.read_timeout = None;
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf[..], &clean[..FILE_HEADER_LEN as usize]);
+
+    //retried through RetryingFile, recovery sees a clean file once the stalled read returns
+    let file = TimeoutFile::new(slow(1), Some(std::time::Duration::from_millis(50)));
+    let mut file = docufort::retry_writer::RetryingFile::new(file, 1).with_read_attempts(10);
+    let summary = recover_tail::<_,DummyInput>(&mut file).unwrap();
+    assert_eq!(summary.recovered_file_len, clean.len() as u64);
+}
+#[test]
+fn test_written_block_matches_read_back() {
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let first = write_atomic_block::<_,DummyInput>(&mut cursor, Some(7), A_CONTENT, false, None, None).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let second = write_streamed_atomic_block::<_,_,DummyInput>(&mut cursor, &mut &B_CONTENT[..], B_CONTENT.len() as u32, Some(9), None).unwrap();
+    assert_eq!(first.start_offset, (FILE_HEADER_LEN as usize + MN_ECC_LEN) as u64);
+    assert_eq!(second.start_offset, first.end_offset + MN_ECC_LEN as u64);
+    assert_eq!((first.timestamp,second.timestamp), (7,9));
+
+    let summary = recover_tail::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(summary.recovered_file_len, second.end_offset);
+    for written in [first,second] {
+        cursor.set_position(written.start_offset);
+        let BlockState::Closed(brs) = try_read_block::<_,DummyInput>(&mut cursor, false, false).unwrap() else {panic!("expected a closed block")};
+        assert_eq!(brs.block_start, written.start_offset);
+        assert_eq!(brs.hash_as_read, written.hash);
+        assert_eq!(brs.block_start_timestamp, written.timestamp);
+        assert_eq!(cursor.position(), written.end_offset);
+    }
+}
+///Appends `append` to the file on its first read, like another process writing during recovery.
+struct GrowingFile{inner:Cursor<Vec<u8>>,append:Option<Vec<u8>>}
+impl std::io::Read for GrowingFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if let Some(a) = self.append.take() {self.inner.get_mut().extend_from_slice(&a)}
+        self.inner.read(buf)
+    }
+}
+impl std::io::Write for GrowingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {self.inner.write(buf)}
+    fn flush(&mut self) -> std::io::Result<()> {self.inner.flush()}
+}
+impl std::io::Seek for GrowingFile {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {self.inner.seek(pos)}
+}
+impl FileLike for GrowingFile {
+    fn truncate(&mut self, len: u64)->std::io::Result<()> {self.inner.truncate(len)}
+    fn len(&self)->std::io::Result<u64> {self.inner.len()}
+}
+#[test]
+fn test_recovery_detects_length_change() {
+    let clean = generate_test_file().into_inner();
+    for bytes in [&clean[..], &clean[..290]] {
+        let mut file = GrowingFile{inner:Cursor::new(bytes.to_vec()),append:Some(vec![0xAB;40])};
+        let res = recover_tail::<_,DummyInput>(&mut file);
+        //caught before recovery truncates anything
+        let expected = bytes.len() as u64;
+        assert!(matches!(res,Err(ReadWriteError::LengthChanged { expected:e, found }) if e == expected && found == expected + 40),"{:?}",res);
+        assert_eq!(file.inner.get_ref().len() as u64, expected + 40);
+    }
+    //without a concurrent writer the same files recover
+    let mut file = GrowingFile{inner:Cursor::new(clean[..290].to_vec()),append:None};
+    assert_eq!(recover_tail::<_,DummyInput>(&mut file).unwrap().recovered_file_len, 268 - MN_ECC_LEN as u64);
+}
+#[test]
+fn test_tail_recovery_partial_component_data() {
+    let start_end = 23 + HEADER_LEN + ECC_LEN;
+    let first_end = start_end + HEADER_LEN + ECC_LEN + B_CONTENT.len();
+    let second_data = first_end + HEADER_LEN + ECC_LEN;
+    let second_end = second_data + B_CONTENT.len() + docufort::ecc::calc_ecc_data_len(B_CONTENT.len());
+    //the second header is complete, but only some of its ECC and data made it
+    for cut in second_data..second_end {
+        let mut file_content = generate_test_file().into_inner();
+        file_content.truncate(cut);
+        let mut cursor = Cursor::new(file_content);
+        cursor.set_position(23);
+        let bs = try_read_block::<_, DummyInput>(&mut cursor, false, false).unwrap();
+        let BlockState::OpenBBlock { truncate_at, content, .. } = bs else {panic!("cut at {}: {:?}", cut, bs)};
+        assert_eq!(truncate_at as usize, first_end, "cut at {}", cut);
+        assert_eq!(content.len(), 1, "cut at {}", cut);
+
+        let summary = recover_tail::<_, DummyInput>(&mut cursor).unwrap();
+        assert_eq!(summary.recovered_file_len as usize, first_end + HEADER_LEN + ECC_LEN + HASH_AND_ECC_LEN, "cut at {}", cut);
+        cursor.set_position(23);
+        let BlockState::Closed(BlockReadSummary { block: Block::B { middle, end, .. }, hash_as_read, .. }) = try_read_block::<_, DummyInput>(&mut cursor, false, false).unwrap() else {panic!("not closed at {}", cut)};
+        assert_eq!(middle.len(), 1, "cut at {}", cut);
+        assert_eq!(end.hash.hash(), hash_as_read, "cut at {}", cut);
+    }
+}
+#[test]
+fn test_component_limit() {
+    let mut cursor = Cursor::new(Vec::new());
+    let mut hasher = DummyInput::new();
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let block_start = cursor.position();
+    let start = ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_header(&mut cursor, &start).unwrap();
+    let mut component_starts = Vec::new();
+    for i in 0..50u8 {
+        component_starts.push(cursor.position());
+        write_content_component(&mut cursor, false, None, None, &[i], &mut hasher).unwrap();
+    }
+    let end = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_block_end(&mut cursor, &end, &hasher.finalize()).unwrap();
+
+    cursor.set_position(block_start);
+    let state = try_read_block_with_limit::<_,DummyInput>(&mut cursor, true, true, 10).unwrap();
+    assert!(matches!(state,BlockState::DataCorruption { component_start, is_b_block: true, component_tag: ComponentTag::ContentHeader } if component_start == component_starts[10]),"{:?}",state);
+    //exactly at the limit still closes
+    cursor.set_position(block_start);
+    assert!(try_read_block_with_limit::<_,DummyInput>(&mut cursor, true, true, 50).unwrap().is_closed());
+    cursor.set_position(block_start);
+    assert!(try_read_block::<_,DummyInput>(&mut cursor, true, true).unwrap().is_closed());
+}
+#[test]
+fn test_merge_b_block_components() {
+    use docufort::integrity::integrity_check_file;
+    use docufort::read::read_content;
+    let mut cursor = generate_test_file();
+    let old_len = cursor.get_ref().len();
+    let written = merge_b_block_components::<_,DummyInput>(&mut cursor, 23).unwrap();
+    assert!(cursor.get_ref().len() < old_len);
+    assert_eq!(cursor.position(),written.end_offset);
+
+    cursor.set_position(23);
+    let BlockState::Closed(BlockReadSummary { block: Block::B { middle, end, .. }, hash_as_read, .. }) = try_read_block::<_,DummyInput>(&mut cursor, false, false).unwrap() else {panic!("expected a closed B block")};
+    assert_eq!(middle.len(),1);
+    assert!(middle[0].1.ecc);
+    assert_eq!(end.hash.hash(),&hash_as_read[..]);
+    assert_eq!(hash_as_read,written.hash);
+    let mut data = Vec::new();
+    read_content::<_,_,DummyInput>(&mut cursor, &mut data, &middle[0].1).unwrap();
+    assert_eq!(data,[&B_CONTENT[..],B_CONTENT,B_CONTENT].concat());
+    //the A blocks after it moved down with it
+    cursor.set_position(0);
+    let check = integrity_check_file::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!((check.num_blocks,check.file_len_checked),(3,cursor.get_ref().len() as u64));
+    assert!(check.corrupted_segments.is_empty());
+
+    //adding ECC to a large component grows the block, so the blocks after it move up
+    let mut cursor = Cursor::new(Vec::new());
+    let mut hasher = DummyInput::new();
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let start = ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_header(&mut cursor, &start).unwrap();
+    write_content_component(&mut cursor, false, None, None, &[7;DATA_SIZE*10], &mut hasher).unwrap();
+    write_content_component(&mut cursor, true, None, None, B_CONTENT, &mut hasher).unwrap();
+    let end = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_block_end(&mut cursor, &end, &hasher.finalize()).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let a_block = write_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, true, None, None).unwrap().start_offset;
+    let Err(ReadWriteError::Io(e)) = merge_b_block_components::<_,DummyInput>(&mut cursor, a_block) else {panic!("merged an A block")};
+    assert_eq!(e.kind(),std::io::ErrorKind::InvalidInput);
+    let old_len = cursor.get_ref().len();
+    merge_b_block_components::<_,DummyInput>(&mut cursor, 23).unwrap();
+    assert!(cursor.get_ref().len() > old_len);
+    cursor.set_position(0);
+    let check = integrity_check_file::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!((check.num_blocks,check.file_len_checked),(2,cursor.get_ref().len() as u64));
+
+    //a block that fails its hash is left alone (the first component has no ECC)
+    let mut cursor = generate_test_file();
+    cursor.get_mut()[23 + 17 + 17 + 2] ^= 0xFF;
+    let before = cursor.get_ref().clone();
+    assert!(matches!(merge_b_block_components::<_,DummyInput>(&mut cursor, 23),Err(ReadWriteError::HashMismatch { block_start: 23 })));
+    assert_eq!(cursor.get_ref(),&before);
+
+    //an offset bound block is rehashed with its (unchanged) start
+    let mut cursor = Cursor::new(Vec::new());
+    let mut hasher = DummyInput::new();
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    write_header(&mut cursor, &start).unwrap();
+    for calc_ecc in [false,true] {
+        write_content_component(&mut cursor, calc_ecc, None, None, B_CONTENT, &mut hasher).unwrap();
+    }
+    write_offset_bound_block_end(&mut cursor, 23, None, hasher).unwrap();
+    merge_b_block_components::<_,DummyInput>(&mut cursor, 23).unwrap();
+    cursor.set_position(0);
+    let check = integrity_check_file::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(check.num_blocks,1);
+    assert!(check.corrupted_segments.is_empty());
+
+    //an offset bound block after it can not move
+    let mut cursor = generate_test_file();
+    cursor.seek(std::io::SeekFrom::End(0)).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    write_offset_bound_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, true, None, None).unwrap();
+    let before = cursor.get_ref().clone();
+    let Err(ReadWriteError::Io(e)) = merge_b_block_components::<_,DummyInput>(&mut cursor, 23) else {panic!("moved an offset bound block")};
+    assert_eq!(e.kind(),std::io::ErrorKind::InvalidInput);
+    assert_eq!(cursor.get_ref(),&before);
+}
+#[test]
+fn test_load_content_with_report() {
+    let mut cursor = Cursor::new(Vec::new());
+    let mut hasher = DummyInput::new();
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let start = ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_header(&mut cursor, &start).unwrap();
+    write_content_component(&mut cursor, true, None, None, A_CONTENT, &mut hasher).unwrap();
+    write_content_component(&mut cursor, true, None, None, B_CONTENT, &mut hasher).unwrap();
+    let end = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_block_end(&mut cursor, &end, &hasher.finalize()).unwrap();
+
+    cursor.set_position(23);
+    let BlockState::Closed(BlockReadSummary { block: Block::B { middle, .. }, .. }) = try_read_block::<_,DummyInput>(&mut cursor, false, false).unwrap() else {panic!("expected a closed B block")};
+    let data_start = middle[1].1.data_start as usize;
+    //more errors than the ECC can correct in the second component
+    for b in &mut cursor.get_mut()[data_start..data_start+8] {
+        *b ^= 0xFF;
+    }
+    cursor.set_position(23);
+    let BlockState::Closed(brs) = try_read_block::<_,DummyInput>(&mut cursor, true, true).unwrap() else {panic!("expected a closed B block")};
+    let report = brs.load_content_with_report::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(report.len(),2);
+    assert_eq!(report[0],(A_CONTENT.to_vec(),None));
+    assert_ne!(report[1].0.as_slice(),B_CONTENT);
+    assert!(matches!(report[1].1,Some(CorruptDataSegment::EccChunk { data_start:ds, .. }) if ds == data_start as u64));
+}
+#[test]
+fn test_open_and_verify() {
+    let path = std::env::temp_dir().join(format!("docufort_open_and_verify_{}",std::process::id()));
+    let clean = generate_test_file().into_inner();
+
+    std::fs::write(&path, &clean).unwrap();
+    let res = open_and_verify::<DummyInput>(&path, true).unwrap();
+    assert_eq!(res.recovered_len,clean.len() as u64);
+    assert!(res.tail_state.is_closed());
+    assert!(res.integrity.is_some_and(|c|c.num_blocks == 3 && c.corrupted_segments.is_empty()));
+    assert_eq!(std::fs::read(&path).unwrap(),clean);
+
+    //a torn last block is recovered away
+    std::fs::write(&path, &clean[..clean.len()-10]).unwrap();
+    let res = open_and_verify::<DummyInput>(&path, false).unwrap();
+    assert_eq!(res.recovered_len,268 - MN_ECC_LEN as u64);
+    assert!(res.integrity.is_none());
+    assert_eq!(std::fs::read(&path).unwrap(),&clean[..268 - MN_ECC_LEN]);
+
+    //a BlockStart corrupted past its ECC stops the integrity check
+    let mut corrupt = clean.clone();
+    for b in &mut corrupt[23..23+HEADER_LEN] {*b ^= 0xFF}
+    std::fs::write(&path, &corrupt).unwrap();
+    let Err(ReadWriteError::Io(e)) = open_and_verify::<DummyInput>(&path, true) else {panic!("corruption not reported")};
+    assert_eq!(e.kind(),std::io::ErrorKind::InvalidData);
+    assert!(matches!(e.get_ref().and_then(|e|e.downcast_ref::<docufort::integrity::IntegrityErr>()),Some(docufort::integrity::IntegrityErr::Corruption(..))));
+
+    //not a DocuFort file, left as it was
+    std::fs::write(&path, b"just some text, long enough to not be a header").unwrap();
+    assert!(matches!(open_and_verify::<DummyInput>(&path, false),Err(ReadWriteError::NotADocuFortFile)));
+    assert_eq!(std::fs::read(&path).unwrap(),b"just some text, long enough to not be a header");
+    std::fs::remove_file(&path).unwrap();
+}
+#[test]
+fn test_newer_version_recovery() {
+    use docufort::read::VersionPolicy;
+    //a V2 file, its only magic number further back than a foreign file is scanned, then a torn block
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    write_atomic_block::<_,DummyInput>(&mut cursor, None, &vec![7u8;FOREIGN_FILE_SCAN_WINDOW as usize], false, None, None).unwrap();
+    let closed_len = cursor.position();
+    write_magic_number(&mut cursor).unwrap();
+    write_header(&mut cursor, &ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None)).unwrap();
+    let mut v = cursor.into_inner();
+    v[MAGIC_NUMBER.len() + 1] = b'2';
+
+    assert!(recover_tail::<_,DummyInput>(&mut Cursor::new(v.clone())).is_err());
+
+    let mut cursor = Cursor::new(v.clone());
+    let summary = recover_tail_with_policy::<_,DummyInput>(&mut cursor, RecoveryPolicy{ version_policy: VersionPolicy::AllowNewer, ..Default::default() }).unwrap();
+    assert_eq!(summary.recovered_file_len,closed_len);
+    assert_eq!(cursor.get_ref()[..],v[..closed_len as usize]);
+
+    let path = std::env::temp_dir().join(format!("docufort_open_newer_version_{}",std::process::id()));
+    std::fs::write(&path, &v).unwrap();
+    assert!(matches!(open_and_verify::<DummyInput>(&path, true),Err(ReadWriteError::NotADocuFortFile)));
+    assert_eq!(std::fs::read(&path).unwrap(),v);
+    let res = open_and_verify_with_policy::<DummyInput>(&path, true, VersionPolicy::AllowNewer).unwrap();
+    assert_eq!(res.recovered_len,closed_len);
+    assert!(res.tail_state.is_closed());
+    assert!(res.integrity.is_some_and(|c|c.num_blocks == 1 && c.newer_version == Some(2)));
+    assert_eq!(std::fs::read(&path).unwrap(),&v[..closed_len as usize]);
+    std::fs::remove_file(&path).unwrap();
+}
+#[test]
+fn test_aligned_blocks() {
+    use docufort::integrity::integrity_check_file;
+    use docufort::read::{catalog, TailingBlockIter};
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    let mut starts = Vec::new();
+    let mut prev = None;
+    for i in 0..4usize {
+        write_aligned_magic_number(&mut cursor, Some(4096)).unwrap();
+        let written = write_chained_atomic_block::<_,DummyInput>(&mut cursor, prev.as_ref(), None, &vec![i as u8 + 1;100 + i*1000], i % 2 == 0, None, None).unwrap();
+        prev = Some(written.hash);
+        starts.push(written.start_offset);
+    }
+    let clean = cursor.get_ref().clone();
+    assert!(starts.iter().all(|s|BlockCoords::new(*s).magic_number_start() % 4096 == 0),"{:?}",starts);
+
+    assert_eq!(catalog(&mut cursor).unwrap().iter().map(|e|e.offset).collect::<Vec<_>>(),starts);
+    let check = integrity_check_file::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!((check.num_blocks,check.file_len_checked),(4,clean.len() as u64));
+    assert!(check.corrupted_segments.is_empty());
+    //chained hashes reach back over the padding
+    for s in &starts {
+        cursor.set_position(*s);
+        let BlockState::Closed(brs) = try_read_block::<_,DummyInput>(&mut cursor, false, false).unwrap() else {panic!()};
+        assert_eq!(&brs.hash_as_read[..],brs.block.take_end().hash.hash());
+    }
+    assert_eq!(TailingBlockIter::<_,DummyInput>::new(&mut cursor).count(),4);
+    assert!(write_aligned_magic_number(&mut Cursor::new(Vec::new()), Some(1000)).is_err());
+
+    //recovery finds the blocks behind the padding
+    let summary = recover_tail::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(summary.recovered_file_len,clean.len() as u64);
+    //a torn last block
+    cursor.get_mut().truncate(clean.len() - 50);
+    let summary = recover_tail::<_,DummyInput>(&mut cursor).unwrap();
+    let third_end = BlockCoords::new(starts[3]).magic_number_start();
+    assert!(summary.recovered_file_len < third_end);
+    assert_eq!(&cursor.get_ref()[..],&clean[..summary.recovered_file_len as usize]);
+    //torn padding
+    let end = cursor.get_ref().len() as u64;
+    write_aligned_magic_number(&mut cursor, Some(4096)).unwrap();
+    cursor.get_mut().truncate((third_end + 3) as usize);
+    assert!(cursor.get_ref().len() as u64 - end > MN_ECC_LEN as u64);
+    let summary = recover_tail::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(summary.recovered_file_len,end);
+    //a torn first block: part of its padding, all of it, then with part of its magic number
+    let padding = 4096 - FILE_HEADER_LEN as usize;
+    for torn_len in [100,padding,padding + 5] {
+        let mut cursor = Cursor::new(clean[..FILE_HEADER_LEN as usize + torn_len].to_vec());
+        let summary = recover_tail::<_,DummyInput>(&mut cursor).unwrap();
+        assert_eq!((summary.recovered_file_len,summary.has_blocks),(FILE_HEADER_LEN as u64,false),"{}",torn_len);
+        assert_eq!(&cursor.get_ref()[..],&clean[..FILE_HEADER_LEN as usize]);
+    }
+}
+#[test]
+fn test_thin_block() {
+    use docufort::integrity::integrity_check_file;
+    use docufort::read::read_thin_block;
+    let reference = ExternalRef{ location: "s3://bucket/blobs/0001".to_string(), offset: 4096, len: 1 << 30, hash: DummyInput::new().finalize() };
+    let mut cursor = Cursor::new(Vec::new());
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let a_block = write_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, true, None, None).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let thin = write_thin_block::<_,DummyInput>(&mut cursor, &reference, None).unwrap();
+    assert_eq!(thin.end_offset,cursor.get_ref().len() as u64);
+    assert_eq!(read_thin_block::<_,DummyInput>(&mut cursor, thin.start_offset).unwrap(),Some(reference.clone()));
+    assert_eq!(read_thin_block::<_,DummyInput>(&mut cursor, a_block.start_offset).unwrap(),None);
+
+    let check = integrity_check_file::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(check.num_blocks,2);
+    assert_eq!(check.unchecked_external_refs,vec![thin.start_offset]);
+
+    //a torn block after it is dropped, the thin block is kept
+    let clean = cursor.get_ref().clone();
+    write_magic_number(&mut cursor).unwrap();
+    write_atomic_block::<_,DummyInput>(&mut cursor, None, A_CONTENT, false, None, None).unwrap();
+    let torn = cursor.get_ref().len() - 5;
+    cursor.get_mut().truncate(torn);
+    let summary = recover_tail::<_,DummyInput>(&mut cursor).unwrap();
+    assert_eq!(summary.recovered_file_len,clean.len() as u64);
+    assert_eq!(cursor.get_ref(),&clean);
+    assert_eq!(read_thin_block::<_,DummyInput>(&mut cursor, thin.start_offset).unwrap(),Some(reference));
+
+    //a damaged reference is corrected in memory, the file is left as is
+    cursor.set_position(thin.start_offset);
+    let BlockState::Closed(BlockReadSummary { block: Block::B { middle, .. }, .. }) = try_read_block::<_,DummyInput>(&mut cursor, false, false).unwrap() else {panic!()};
+    let data_start = middle[0].1.data_start;
+    cursor.get_mut()[data_start as usize + 20] ^= 0xFF;
+    let damaged = cursor.get_ref().clone();
+    assert_eq!(read_thin_block::<_,DummyInput>(&mut cursor, thin.start_offset).unwrap(),Some(reference.clone()));
+    assert_eq!(cursor.get_ref(),&damaged);
+    //past what ECC can fix it does not read back
+    for i in 0..ECC_LEN {cursor.get_mut()[data_start as usize + i] ^= 0xFF}
+    assert!(matches!(read_thin_block::<_,DummyInput>(&mut cursor, thin.start_offset),Err(ReadWriteError::HashMismatch { block_start }) if block_start == thin.start_offset));
+
+    //a typed block too short to hold a reference is an error, not None
+    cursor.get_mut().truncate(clean.len());
+    cursor.seek(std::io::SeekFrom::End(0)).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let bad_start = cursor.position();
+    let mut hasher = DummyInput::new();
+    write_header(&mut cursor, &ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, [0;8], None)).unwrap();
+    write_typed_content_component::<_,DummyInput>(&mut cursor, true, None, None, Some(docufort::EXTERNAL_REF_TYPE), b"short", &mut hasher).unwrap();
+    write_block_end(&mut cursor, &ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8, [0;8], None), &hasher.finalize()).unwrap();
+    assert!(matches!(read_thin_block::<_,DummyInput>(&mut cursor, bad_start),Err(ReadWriteError::DataCorruption { .. })));
+}
+#[test]
+fn test_writer_shutdown() {
+    use docufort::retry_writer::{spawn_writer, Op, Operation, TailState};
+    use docufort::integrity::integrity_check_file;
+    let path = std::env::temp_dir().join(format!("docufort_writer_shutdown_{}",std::process::id()));
+    let mut file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+    init_file(&mut file).unwrap();
+    let writer = spawn_writer::<_,Vec<u8>,DummyInput>(file, TailState::ClosedBlock, 1);
+    for calc_ecc in [false,true] {
+        assert!(writer.send(Operation{ op:Op::ContentWrite(B_CONTENT.to_vec(),None), timestamp: None, end_timestamp: None, calc_ecc, compress:None }).is_ok());
+    }
+    let tail = writer.shutdown().unwrap();
+    assert!(tail.is_closed());
+
+    assert!(!needs_recovery::<DummyInput>(&path).unwrap());
+    let mut file = std::fs::File::open(&path).unwrap();
+    let ok = integrity_check_file::<_,DummyInput>(&mut file).unwrap();
+    let Some(BlockState::Closed(summary)) = ok.last_block_state else {panic!("expected closed block")};
+    let Block::B { middle, .. } = summary.block else {panic!("expected B block")};
+    assert_eq!(middle.len(),2);
+    std::fs::remove_file(&path).unwrap();
+}
+#[test]
+fn test_refresh_block_ecc() {
+    use docufort::read::DeferredPatches;
+    let clean = generate_test_file().into_inner();
+    let corrections = |cursor:&mut Cursor<Vec<u8>>,block_start:u64|{
+        cursor.set_position(block_start);
+        let mut reader = DeferredPatches::new(cursor);
+        let BlockState::Closed(brs) = try_read_block::<_,DummyInput>(&mut reader, true, true).unwrap() else {panic!("not a closed block")};
+        brs.errors_corrected
+    };
+    //rot in the ECC of the header, content and hash of the last A block, and of its magic number
+    let mut v = clean.clone();
+    let ecc_bytes = [268 - 1, 268 + HEADER_LEN, 268 + HEADER_LEN + ECC_LEN, clean.len() - 1];
+    for i in ecc_bytes {v[i] ^= 0x40}
+    let mut cursor = Cursor::new(v);
+    assert_eq!(corrections(&mut cursor,268),3);
+    assert_eq!(refresh_block_ecc::<_,DummyInput>(&mut cursor, 268).unwrap(),4 * ECC_LEN);
+    assert_eq!(cursor.position(),clean.len() as u64);
+    assert_eq!(corrections(&mut cursor,268),0);
+    assert_eq!(cursor.get_ref(),&clean);
+    //nothing left to refresh
+    assert_eq!(refresh_block_ecc::<_,DummyInput>(&mut cursor, 268).unwrap(),0);
+
+    //a B block component's header ECC
+    let mut v = clean.clone();
+    v[23 + HEADER_LEN + ECC_LEN + HEADER_LEN] ^= 0x01;
+    let mut cursor = Cursor::new(v);
+    assert_eq!(refresh_block_ecc::<_,DummyInput>(&mut cursor, 23).unwrap(),ECC_LEN);
+    assert_eq!(cursor.get_ref(),&clean);
+
+    //content that no longer matches the hash is left alone
+    let mut v = clean.clone();
+    v[184 + HEADER_LEN + ECC_LEN] ^= 0x01;
+    let mut cursor = Cursor::new(v.clone());
+    assert!(matches!(refresh_block_ecc::<_,DummyInput>(&mut cursor, 184),Err(ReadWriteError::HashMismatch { block_start: 184 })));
+    assert_eq!(cursor.into_inner(),v);
+}
+#[test]
+fn test_tail_recovery_summary_merge() {
+    let mut first = generate_test_file().into_inner();
+    first.extend_from_slice(&MAGIC_NUMBER);
+    let first = recover_tail::<_, DummyInput>(&mut Cursor::new(first)).unwrap();
+    let mut second = generate_test_file().into_inner();
+    second.truncate(268 + HEADER_LEN + ECC_LEN + 4);
+    let second = recover_tail::<_, DummyInput>(&mut Cursor::new(second)).unwrap();
+
+    let merged = first.clone().merge(second.clone());
+    assert_eq!(merged.original_file_len, first.original_file_len + second.original_file_len);
+    assert_eq!(merged.recovered_file_len, first.recovered_file_len + second.recovered_file_len);
+    assert_eq!(merged.file_ops.len(), 3);
+    assert_eq!(&merged.file_ops[..1], &first.file_ops[..]);
+    assert_eq!(&merged.file_ops[1..], &second.file_ops[..]);
+    assert!(merged.has_blocks);
+    assert_eq!(merged.tot_errors_corrected, 0);
+    assert!(!merged.tail_block_unverified);
+    assert_eq!(merged.merged_files, vec![
+        MergedRecovery { original_file_len: first.original_file_len, recovered_file_len: first.recovered_file_len, file_ops: 1, corrupted_content_blocks: 0 },
+        MergedRecovery { original_file_len: second.original_file_len, recovered_file_len: second.recovered_file_len, file_ops: 2, corrupted_content_blocks: 0 },
+    ]);
+
+    //Merging a merged summary keeps its per file entries
+    let merged = merged.merge(TailRecoverySummary::default());
+    assert_eq!(merged.merged_files.len(), 3);
+    assert_eq!(merged.file_ops.len(), 3);
+}
+#[test]
+fn test_block_into_owned() {
+    let path = std::env::temp_dir().join(format!("docufort_into_owned_{}",std::process::id()));
+    std::fs::write(&path, generate_test_file().into_inner()).unwrap();
+    let (b_block,a_block) = {
+        let mut file = std::fs::File::open(&path).unwrap();
+        let mut owned = Vec::new();
+        for block_start in [23,268] {
+            file.seek(std::io::SeekFrom::Start(block_start)).unwrap();
+            let BlockState::Closed(summary) = try_read_block::<_, DummyInput>(&mut file, false, false).unwrap() else {panic!("not a closed block")};
+            owned.push(summary.into_owned::<_,DummyInput>(&mut file).unwrap());
+        }
+        let a_block = owned.pop().unwrap();
+        (owned.pop().unwrap(),a_block)
+    };
+    //the file is closed and gone, the content is still there
+    std::fs::remove_file(&path).unwrap();
+    let handle = std::thread::spawn(move||{
+        assert!(!b_block.is_atomic());
+        assert_eq!(b_block.block_start,23);
+        assert_eq!(b_block.content.len(),3);
+        assert!(b_block.content.iter().all(|(h,c)|h.is_some() && c == B_CONTENT));
+        assert!(a_block.is_atomic());
+        assert_eq!(a_block.content,vec![(None,A_CONTENT.to_vec())]);
+        assert_eq!(a_block.hash_as_read,BLOCK_3_HASH);
+    });
+    handle.join().unwrap();
+}
+#[test]
+fn test_offset_bound_b_block() {
+    let mut cursor = Cursor::new(Vec::new());
+    let mut hasher = DummyInput::new();
+    init_file(&mut cursor).unwrap();
+    write_magic_number(&mut cursor).unwrap();
+    let block_start = cursor.position();
+    write_header(&mut cursor, &ComponentHeader::new_from_parts(HeaderTag::StartBBlock as u8, DummyInput::current_timestamp().to_be_bytes(), None)).unwrap();
+    write_content_component(&mut cursor, true, None, None, B_CONTENT, &mut hasher).unwrap();
+    hasher.update(&block_start.to_be_bytes());
+    let end = ComponentHeader::new_from_parts(HeaderTag::EndBlock as u8 | OFFSET_BOUND, DummyInput::current_timestamp().to_be_bytes(), None);
+    write_block_end(&mut cursor, &end, &hasher.finalize()).unwrap();
+
+    cursor.set_position(block_start);
+    let BlockState::Closed(brs) = try_read_block::<_, DummyInput>(&mut cursor, false, false).unwrap() else {panic!("not a closed block")};
+    assert!(brs.block.clone().take_end().header.is_offset_bound());
+    assert_eq!(&brs.hash_as_read,brs.block.take_end().hash.hash());
+}
+#[test]
+fn test_truncation_target() {
+    let read_at = |v:Vec<u8>,block_start:u64|{
+        let mut cursor = Cursor::new(v);
+        cursor.set_position(block_start);
+        try_read_block::<_,DummyInput>(&mut cursor, true, true).unwrap()
+    };
+    let policy = RecoveryPolicy::default();
+    let a_start = 268;
+    let a_mn = (a_start - MN_ECC_LEN) as u64;
+
+    let state = read_at(generate_test_file().into_inner(), a_start as u64);
+    assert!(state.is_closed());
+    assert_eq!(state.truncation_target(a_start as u64, &policy),None);
+
+    let mut v = generate_test_file().into_inner();
+    v.truncate(a_start + HEADER_LEN + ECC_LEN + 4);
+    let state = read_at(v, a_start as u64);
+    assert!(state.is_open_a());
+    assert_eq!(state.truncation_target(a_start as u64, &policy),Some(a_mn));
+
+    let mut v = generate_test_file().into_inner();
+    v.truncate(a_start + HEADER_LEN + ECC_LEN - 4);
+    let state = read_at(v, a_start as u64);
+    assert!(matches!(state,BlockState::IncompleteStartHeader { .. }));
+    assert_eq!(state.truncation_target(a_start as u64, &policy),Some(a_mn));
+
+    //an open B block keeps its complete components, here none, so it is closed right after its BlockStart
+    let mut v = generate_test_file().into_inner();
+    v.truncate(23 + HEADER_LEN * 2 + ECC_LEN * 2 + 4);
+    let state = read_at(v, 23);
+    assert!(state.is_open_b());
+    assert_eq!(state.truncation_target(23, &policy),Some((23 + HEADER_LEN + ECC_LEN) as u64));
+    //unless the policy drops open B blocks whole
+    let drop_open_b = RecoveryPolicy { close_open_b_blocks: false, ..Default::default() };
+    assert_eq!(state.truncation_target(23, &drop_open_b),Some((23 - MN_ECC_LEN) as u64));
+
+    let mut v = generate_test_file().into_inner();
+    v[a_start..a_start + 3].fill(0);
+    let state = read_at(v, a_start as u64);
+    assert!(matches!(state,BlockState::ProbablyNotStartHeader { .. }));
+    assert_eq!(state.truncation_target(a_start as u64, &policy),None);
+
+    let mut v = generate_test_file().into_inner();
+    v[a_start+9..a_start+13].copy_from_slice(&1000u32.to_le_bytes());
+    let mut ecc = Vec::new();
+    docufort::ecc::calculate_ecc_chunk(&v[a_start..a_start+HEADER_LEN], &mut ecc).unwrap();
+    v[a_start+HEADER_LEN..a_start+HEADER_LEN+ECC_LEN].copy_from_slice(&ecc);
+    let state = read_at(v, a_start as u64);
+    assert!(matches!(state,BlockState::CorruptContentLength { is_b_block: false, .. }));
+    assert_eq!(state.truncation_target(a_start as u64, &policy),Some(a_mn));
+
+    let state = BlockState::CorruptContentLength { component_start: 57, is_b_block: true };
+    assert_eq!(state.truncation_target(23, &policy),Some(57));
+    //a stray BlockEnd goes from the block_start it carries
+    let state = BlockState::StrayBlockEnd { block_start: a_start as u64, skip_to: 300 };
+    assert_eq!(state.truncation_target(23, &policy),Some(a_mn));
+    let state = BlockState::DataCorruption { component_start: 57, is_b_block: true, component_tag: ComponentTag::Header };
+    assert_eq!(state.truncation_target(23, &policy),Some(57));
+    let state = BlockState::InvalidBlockStructure { end_of_last_good_component: 81, info: String::new() };
+    assert_eq!(state.truncation_target(23, &policy),Some(81));
+}